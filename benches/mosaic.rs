@@ -0,0 +1,187 @@
+//! Timings of the hot paths at 10k, 100k, and 1M tiles.
+//!
+//! To check a change for regressions, save a baseline before it and compare against it
+//! after:
+//!
+//! ```text
+//! cargo bench --bench mosaic -- --save-baseline before
+//! cargo bench --bench mosaic -- --baseline before
+//! ```
+//!
+//! Set `MOSAIC_BENCH_MAX_TILES` to leave out the larger sizes, e.g. `100000` for a quick run.
+
+use std::{hint::black_box, sync::Arc, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use itertools::Itertools;
+use mosaic::{
+    capabilities::{PatternMatchCapability, TraversalCapability},
+    internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices},
+    testing::GraphGenerator,
+};
+
+const SEED: u64 = 0x6d6f_7361_6963;
+
+fn sizes() -> Vec<usize> {
+    let max = std::env::var("MOSAIC_BENCH_MAX_TILES")
+        .ok()
+        .and_then(|max| max.parse().ok())
+        .unwrap_or(usize::MAX);
+    [10_000, 100_000, 1_000_000]
+        .into_iter()
+        .filter(|size| *size <= max)
+        .collect_vec()
+}
+
+/// A square grid of `Node` objects linked by `Edge` arrows, about `tiles` tiles in all.
+fn grid(tiles: usize) -> Arc<Mosaic> {
+    // a grid has about two arrows for every node
+    let side = ((tiles / 3) as f64).sqrt() as usize;
+    GraphGenerator::new(SEED)
+        .with_components(&["Node: i32;", "Edge: unit;"], "Node", "Edge")
+        .grid(side, side)
+}
+
+fn tile_creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tile_creation");
+    group.sample_size(10);
+    for size in sizes() {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, size| {
+            b.iter_batched(
+                Mosaic::new,
+                |mosaic| {
+                    let mut previous = mosaic.new_object("void", void());
+                    for _ in 1..*size / 2 {
+                        let next = mosaic.new_object("void", void());
+                        mosaic.new_arrow(&previous, &next, "void", void());
+                        previous = next;
+                    }
+                    mosaic
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn deletion_cascade(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deletion_cascade");
+    group.sample_size(10);
+    for size in sizes() {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, size| {
+            b.iter_batched(
+                || {
+                    // deleting the hub takes every arrow with it
+                    let mosaic = Mosaic::new();
+                    let hub = mosaic.new_object("void", void());
+                    for _ in 1..*size / 2 {
+                        let leaf = mosaic.new_object("void", void());
+                        mosaic.new_arrow(&hub, &leaf, "void", void());
+                    }
+                    (mosaic, hub)
+                },
+                |(mosaic, hub)| {
+                    mosaic.delete_tile(hub);
+                    mosaic
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn save_and_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save_and_load");
+    group.sample_size(10);
+    for size in sizes() {
+        let mosaic = grid(size);
+        let saved = mosaic.save();
+        group.bench_with_input(BenchmarkId::new("save", size), &mosaic, |b, mosaic| {
+            b.iter(|| black_box(mosaic.save()));
+        });
+        group.bench_with_input(BenchmarkId::new("load", size), &saved, |b, saved| {
+            b.iter_batched(
+                Mosaic::new,
+                |loaded| {
+                    loaded.load(saved).unwrap();
+                    loaded
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn index_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index_queries");
+    for size in sizes() {
+        let mosaic = grid(size);
+        let nodes = mosaic
+            .get_tiles_with_component("Node")
+            .take(1000)
+            .collect_vec();
+        group.bench_with_input(BenchmarkId::new("with_component", size), &mosaic, |b, m| {
+            b.iter(|| black_box(m.get_tiles_with_component("Edge").count()));
+        });
+        group.bench_with_input(BenchmarkId::new("from_1000", size), &mosaic, |b, m| {
+            b.iter(|| {
+                let arrows: usize = nodes.iter().map(|n| m.get_tiles_from(n.id).count()).sum();
+                black_box(arrows)
+            });
+        });
+    }
+    group.finish();
+}
+
+fn traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traversal");
+    group.sample_size(10);
+    for size in sizes() {
+        let mosaic = grid(size);
+        let corner = mosaic.get_tiles_with_component("Node").next().unwrap();
+        group.bench_with_input(BenchmarkId::new("reachable", size), &mosaic, |b, m| {
+            b.iter(|| black_box(m.reachable_from(&corner).count()));
+        });
+        group.bench_with_input(BenchmarkId::new("components", size), &mosaic, |b, m| {
+            b.iter(|| black_box(m.connected_components().len()));
+        });
+    }
+    group.finish();
+}
+
+fn pattern_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pattern_matching");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(10));
+
+    // a corner: a node with one arrow going right and another going down
+    let pattern = Mosaic::new();
+    let corner = pattern.new_object("void", void());
+    let right = pattern.new_object("void", void());
+    let down = pattern.new_object("void", void());
+    pattern.new_arrow(&corner, &right, "void", void());
+    pattern.new_arrow(&corner, &down, "void", void());
+    pattern.match_component(&corner, "Node");
+
+    for size in sizes() {
+        let mosaic = grid(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &mosaic, |b, m| {
+            b.iter(|| black_box(m.pattern_match(&pattern).len()));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    tile_creation,
+    deletion_cascade,
+    save_and_load,
+    index_queries,
+    traversal,
+    pattern_matching
+);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+//! Feeds arbitrary bytes to the loaders, which have to fail on anything malformed rather
+//! than panic. Run with `cargo fuzz run load_mosaic_commands` from the crate root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mosaic::internals::{load_mosaic_commands, Mosaic, MosaicIO};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = load_mosaic_commands(data);
+    let _ = Mosaic::new().load(data);
+});
@@ -0,0 +1,62 @@
+//! Damages the payload of a valid save holding fields of most datatypes, and writes its
+//! checksum anew, so that the damage reaches the field decoding in `load` instead of being
+//! turned away by the header or the checksum. Run with `cargo fuzz run load_mosaic_fields`.
+
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use mosaic::internals::{
+    pars, void, ComponentValuesBuilderSetter, Duration, Mosaic, MosaicBlobs, MosaicCRUD, MosaicIO,
+    MosaicTypelevelCRUD,
+};
+
+fn seed() -> &'static [u8] {
+    static SEED: OnceLock<Vec<u8>> = OnceLock::new();
+    SEED.get_or_init(|| {
+        let mosaic = Mosaic::new();
+        mosaic
+            .new_type(
+                "Rich: { at: datetime, span: duration, pixels: blob, points: [f32; 2], \
+                 tags: [str], to: ref, flag: bool, count: u64, label: s32, name: str };",
+            )
+            .unwrap();
+        mosaic.new_type("Label: s32;").unwrap();
+
+        let pixels = mosaic.write_blob([1u8, 2, 3, 4].as_slice()).unwrap();
+        let a = mosaic.new_object(
+            "Rich",
+            pars()
+                .set("span", Duration::from_micros(1500))
+                .set("pixels", pixels)
+                .set("flag", true)
+                .set("count", 7u64)
+                .set("label", "first")
+                .set("name", "a longer name".to_string())
+                .ok(),
+        );
+        let b = mosaic.new_object("Rich", pars().set("to", a.to_ref()).ok());
+        mosaic.new_arrow(&a, &b, "void", void());
+        mosaic.new_descriptor(&b, "Label", pars().set("self", "second").ok());
+        mosaic.save()
+    })
+}
+
+fuzz_target!(|damage: &[u8]| {
+    let mut data = seed().to_vec();
+    let end = data.len() - 4;
+    for (at, byte) in damage
+        .chunks_exact(3)
+        .map(|c| (c[0] as usize * 256 + c[1] as usize, c[2]))
+    {
+        data[6 + at % (end - 6)] ^= byte;
+    }
+    let checksum = crc32fast::hash(&data[6..end]);
+    data[end..].copy_from_slice(&checksum.to_be_bytes());
+
+    let loaded = Mosaic::new();
+    if loaded.load(&data).is_ok() {
+        Mosaic::new().load(&loaded.save()).unwrap();
+    }
+});
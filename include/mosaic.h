@@ -0,0 +1,149 @@
+/* Generated by cbindgen from src/mosaic_ffi.rs, do not edit by hand. */
+
+#ifndef MOSAIC_H
+#define MOSAIC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Returned in place of a tile id when no tile could be made or found.
+ */
+#define MOSAIC_NO_TILE UINT64_MAX
+
+#define MOSAIC_OBJECT 0
+
+#define MOSAIC_ARROW 1
+
+#define MOSAIC_DESCRIPTOR 2
+
+#define MOSAIC_EXTENSION 3
+
+typedef struct MosaicHandle MosaicHandle;
+
+/**
+ * The message of the last call on this thread that failed, or null. It stays valid until
+ * the next call that fails on this thread.
+ */
+const char *mosaic_last_error(void);
+
+struct MosaicHandle *mosaic_new(void);
+
+void mosaic_free(struct MosaicHandle *handle);
+
+void mosaic_free_string(char *string);
+
+void mosaic_free_bytes(uint8_t *bytes, size_t len);
+
+void mosaic_free_ids(uint64_t *ids, size_t len);
+
+/**
+ * Adds component types, written the same way as for `new_type`.
+ */
+bool mosaic_new_type(const struct MosaicHandle *handle, const char *definition);
+
+/**
+ * Makes an object with every field set to its default.
+ */
+uint64_t mosaic_new_object(const struct MosaicHandle *handle, const char *component_name);
+
+uint64_t mosaic_new_arrow(const struct MosaicHandle *handle,
+                          uint64_t source,
+                          uint64_t target,
+                          const char *component_name);
+
+uint64_t mosaic_new_descriptor(const struct MosaicHandle *handle,
+                               uint64_t subject,
+                               const char *component_name);
+
+/**
+ * Deletes the tile along with everything attached to it.
+ */
+bool mosaic_delete_tile(const struct MosaicHandle *handle, uint64_t id);
+
+bool mosaic_is_tile_valid(const struct MosaicHandle *handle, uint64_t id);
+
+/**
+ * One of `MOSAIC_OBJECT`, `MOSAIC_ARROW`, `MOSAIC_DESCRIPTOR`, or `MOSAIC_EXTENSION`; -1 if
+ * there is no such tile.
+ */
+int32_t mosaic_tile_kind(const struct MosaicHandle *handle, uint64_t id);
+
+/**
+ * The source of an arrow, or the subject of a descriptor or extension; objects are their
+ * own source.
+ */
+uint64_t mosaic_tile_source(const struct MosaicHandle *handle, uint64_t id);
+
+uint64_t mosaic_tile_target(const struct MosaicHandle *handle, uint64_t id);
+
+char *mosaic_tile_component(const struct MosaicHandle *handle, uint64_t id);
+
+/**
+ * Reads an integer or bool field into `out`.
+ */
+bool mosaic_get_int(const struct MosaicHandle *handle,
+                    uint64_t id,
+                    const char *field_name,
+                    int64_t *out);
+
+/**
+ * Reads a float field into `out`.
+ */
+bool mosaic_get_float(const struct MosaicHandle *handle,
+                      uint64_t id,
+                      const char *field_name,
+                      double *out);
+
+/**
+ * Reads an `s32` or `str` field into a new string.
+ */
+char *mosaic_get_string(const struct MosaicHandle *handle, uint64_t id, const char *field_name);
+
+/**
+ * Writes an integer or bool field; fails if `value` doesn't fit.
+ */
+bool mosaic_set_int(const struct MosaicHandle *handle,
+                    uint64_t id,
+                    const char *field_name,
+                    int64_t value);
+
+bool mosaic_set_float(const struct MosaicHandle *handle,
+                      uint64_t id,
+                      const char *field_name,
+                      double value);
+
+bool mosaic_set_string(const struct MosaicHandle *handle,
+                       uint64_t id,
+                       const char *field_name,
+                       const char *value);
+
+/**
+ * Every tile, by id; the number of ids goes into `len`.
+ */
+uint64_t *mosaic_get_all(const struct MosaicHandle *handle, size_t *len);
+
+uint64_t *mosaic_get_tiles_with_component(const struct MosaicHandle *handle,
+                                          const char *component_name,
+                                          size_t *len);
+
+uint64_t *mosaic_get_arrows_from(const struct MosaicHandle *handle, uint64_t id, size_t *len);
+
+uint64_t *mosaic_get_arrows_into(const struct MosaicHandle *handle, uint64_t id, size_t *len);
+
+uint64_t *mosaic_get_descriptors(const struct MosaicHandle *handle, uint64_t id, size_t *len);
+
+/**
+ * Saves the whole mosaic; the number of bytes goes into `len`.
+ */
+uint8_t *mosaic_save(const struct MosaicHandle *handle, size_t *len);
+
+/**
+ * Loads what `mosaic_save` gave out, next to the tiles already there.
+ */
+bool mosaic_load(const struct MosaicHandle *handle, const uint8_t *bytes, size_t len);
+
+#endif /* MOSAIC_H */
@@ -0,0 +1,357 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use atomic_counter::AtomicCounter;
+use bevy::prelude::*;
+
+use crate::internals::{
+    EntityId, Mosaic, MosaicIO, MosaicObservable, MosaicObserver, SubscriptionId, Tile, TileType,
+    Value,
+};
+
+/// Mirrors the objects and arrows of a mosaic into Bevy entities, touching only the tiles
+/// that changed each frame; edits to `MosaicData` are written back into the mosaic.
+pub struct MosaicBridgePlugin {
+    pub mosaic: Arc<Mosaic>,
+}
+
+impl MosaicBridgePlugin {
+    pub fn new(mosaic: &Arc<Mosaic>) -> Self {
+        MosaicBridgePlugin {
+            mosaic: Arc::clone(mosaic),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MosaicResource(pub Arc<Mosaic>);
+
+/// The mapping between mirrored tiles and the Bevy entities that represent them.
+#[derive(Resource, Default)]
+pub struct MosaicEntities(pub HashMap<EntityId, Entity>);
+
+impl MosaicEntities {
+    pub fn get_entity(&self, id: EntityId) -> Option<Entity> {
+        self.0.get(&id).cloned()
+    }
+}
+
+/// Notes the ids of the tiles that changed, for the bridge to pick up on its next sync.
+struct BridgeObserver(Arc<Mutex<HashSet<EntityId>>>);
+
+impl BridgeObserver {
+    fn touch(&self, tile: &Tile) {
+        self.0.lock().unwrap().insert(tile.id);
+    }
+}
+
+impl MosaicObserver for BridgeObserver {
+    fn on_tile_created(&self, tile: &Tile) {
+        self.touch(tile);
+    }
+
+    fn on_tile_deleted(&self, tile: &Tile) {
+        self.touch(tile);
+    }
+
+    fn on_field_changed(&self, tile: &Tile, _field: &str, _before: &Value, _after: &Value) {
+        self.touch(tile);
+    }
+
+    fn on_arrow_reconnected(&self, arrow: &Tile, _before: (EntityId, EntityId)) {
+        self.touch(arrow);
+    }
+
+    fn on_tile_retyped(&self, tile: &Tile, _before: TileType) {
+        self.touch(tile);
+    }
+
+    fn on_type_redefined(&self, _component: &str, migrated: &[Tile]) {
+        migrated.iter().for_each(|tile| self.touch(tile));
+    }
+}
+
+/// The tiles that changed since the last sync, and the subscription that collects them;
+/// the subscription ends when the resource is dropped.
+#[derive(Resource)]
+struct MosaicChanges {
+    mosaic: Arc<Mosaic>,
+    pending: Arc<Mutex<HashSet<EntityId>>>,
+    subscription: SubscriptionId,
+    /// `Mosaic::unobserved_changes` as of the last sync; when it moves, every tile is synced.
+    unobserved: Option<usize>,
+    /// The data last mirrored into each entity; only what differs from it was edited on the
+    /// Bevy side and gets written back.
+    mirrored: HashMap<EntityId, MosaicData>,
+}
+
+impl MosaicChanges {
+    fn subscribe(mosaic: &Arc<Mosaic>) -> MosaicChanges {
+        let pending = Arc::new(Mutex::new(HashSet::new()));
+        let subscription = mosaic.subscribe(Arc::new(BridgeObserver(Arc::clone(&pending))));
+        MosaicChanges {
+            mosaic: Arc::clone(mosaic),
+            pending,
+            subscription,
+            unobserved: None,
+            mirrored: HashMap::new(),
+        }
+    }
+}
+
+impl Drop for MosaicChanges {
+    fn drop(&mut self) {
+        self.mosaic.unsubscribe(self.subscription);
+    }
+}
+
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct MosaicTile {
+    pub id: EntityId,
+    pub component: String,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MosaicObject;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MosaicArrow {
+    pub source: EntityId,
+    pub target: EntityId,
+}
+
+/// The component values of a mirrored tile, keyed by field name.
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct MosaicData {
+    pub fields: BTreeMap<String, Value>,
+}
+
+impl MosaicData {
+    fn from_tile(tile: &Tile) -> MosaicData {
+        MosaicData {
+            fields: tile
+                .data()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields.get(field)
+    }
+}
+
+impl Plugin for MosaicBridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MosaicResource(Arc::clone(&self.mosaic)))
+            .insert_resource(MosaicChanges::subscribe(&self.mosaic))
+            .init_resource::<MosaicEntities>()
+            .add_systems(Update, (sync_bevy_to_mosaic, sync_mosaic_to_bevy).chain());
+    }
+}
+
+fn spawn_tile(commands: &mut Commands, changes: &mut MosaicChanges, tile: &Tile) -> Entity {
+    let marker = MosaicTile {
+        id: tile.id,
+        component: tile.component.to_string(),
+    };
+    let data = MosaicData::from_tile(tile);
+    changes.mirrored.insert(tile.id, data.clone());
+
+    match tile.tile_type {
+        TileType::Arrow { source, target } => commands
+            .spawn((marker, MosaicArrow { source, target }, data))
+            .id(),
+        _ => commands.spawn((marker, MosaicObject, data)).id(),
+    }
+}
+
+fn sync_mosaic_to_bevy(
+    mut commands: Commands,
+    mosaic: Res<MosaicResource>,
+    mut changes: ResMut<MosaicChanges>,
+    mut entities: ResMut<MosaicEntities>,
+    mut mirrored: Query<(&MosaicTile, &mut MosaicData, Option<&mut MosaicArrow>)>,
+) {
+    let mut changed = std::mem::take(&mut *changes.pending.lock().unwrap());
+
+    let unobserved = mosaic.0.unobserved_changes.get();
+    if changes.unobserved != Some(unobserved) {
+        changes.unobserved = Some(unobserved);
+        changed.extend(mosaic.0.get_all().map(|t| t.id));
+        changed.extend(entities.0.keys().cloned());
+    }
+
+    for id in changed {
+        let tile = mosaic.0.get(id).filter(|t| t.is_object() || t.is_arrow());
+        let entity = entities.get_entity(id);
+
+        match (tile, entity) {
+            (Some(tile), Some(entity)) => {
+                let Ok((marker, mut current, arrow)) = mirrored.get_mut(entity) else {
+                    continue;
+                };
+
+                let endpoints = match tile.tile_type {
+                    TileType::Arrow { source, target } => Some(MosaicArrow { source, target }),
+                    _ => None,
+                };
+                let same_kind = endpoints.is_some() == arrow.is_some();
+                if !same_kind || !tile.component.is(&marker.component) {
+                    // turned from an object into an arrow or the other way round, or changed component
+                    commands.entity(entity).despawn();
+                    entities
+                        .0
+                        .insert(id, spawn_tile(&mut commands, &mut changes, &tile));
+                    continue;
+                }
+
+                if let (Some(endpoints), Some(mut arrow)) = (endpoints, arrow) {
+                    if *arrow != endpoints {
+                        *arrow = endpoints;
+                    }
+                }
+
+                // only deref mutably when something changed, to keep change detection meaningful
+                let data = MosaicData::from_tile(&tile);
+                if *current != data {
+                    changes.mirrored.insert(id, data.clone());
+                    *current = data;
+                }
+            }
+            (Some(tile), None) => {
+                entities
+                    .0
+                    .insert(id, spawn_tile(&mut commands, &mut changes, &tile));
+            }
+            (None, Some(entity)) => {
+                entities.0.remove(&id);
+                changes.mirrored.remove(&id);
+                commands.entity(entity).despawn();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn sync_bevy_to_mosaic(
+    mosaic: Res<MosaicResource>,
+    mut changes: ResMut<MosaicChanges>,
+    changed: Query<(&MosaicTile, &MosaicData), Changed<MosaicData>>,
+) {
+    for (marker, data) in changed.iter() {
+        let Some(mirrored) = changes.mirrored.get_mut(&marker.id) else {
+            continue;
+        };
+        if let Some(mut tile) = mosaic.0.get(marker.id) {
+            let current = MosaicData::from_tile(&tile);
+
+            // fields still as they were mirrored may be stale, the mosaic has the say on those
+            for (field, value) in &data.fields {
+                match current.get(field) {
+                    Some(old)
+                        if mirrored.get(field) != Some(value)
+                            && old != value
+                            && old.get_datatype() == value.get_datatype() =>
+                    {
+                        tile.set_field(field, value.clone());
+                        mirrored.fields.insert(field.clone(), value.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod bevy_bridge_tests {
+    use bevy::prelude::*;
+
+    use crate::internals::{
+        par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicRestructure, MosaicTypelevelCRUD,
+        TileFieldSetter, Value,
+    };
+
+    use super::{MosaicArrow, MosaicBridgePlugin, MosaicData, MosaicEntities, MosaicTile};
+
+    #[test]
+    fn test_mirroring_tiles() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Foo: i32;").unwrap();
+        let mut a = mosaic.new_object("Foo", par(7i32));
+        let b = mosaic.new_object("void", void());
+        let ab = mosaic.new_arrow(&a, &b, "void", void());
+
+        let mut app = App::new();
+        app.add_plugins(MosaicBridgePlugin::new(&mosaic));
+        app.update();
+
+        let mut query = app.world.query::<(&MosaicTile, &MosaicData)>();
+        assert_eq!(3, query.iter(&app.world).count());
+
+        let mut arrows = app.world.query::<&MosaicArrow>();
+        let arrow = arrows.single(&app.world);
+        assert_eq!((a.id, b.id), (arrow.source, arrow.target));
+
+        let c = mosaic.new_object("Foo", par(1i32));
+        mosaic.reconnect(&ab, &a, &c).unwrap();
+        a.set("self", 9i32);
+        app.update();
+        assert_eq!(4, query.iter(&app.world).count());
+        assert_eq!((a.id, c.id), {
+            let arrow = arrows.single(&app.world);
+            (arrow.source, arrow.target)
+        });
+        let entity = app
+            .world
+            .resource::<MosaicEntities>()
+            .get_entity(a.id)
+            .unwrap();
+        assert_eq!(
+            Some(&Value::I32(9)),
+            app.world.get::<MosaicData>(entity).unwrap().get("self")
+        );
+
+        mosaic.delete_tile(ab);
+        app.update();
+        assert_eq!(0, arrows.iter(&app.world).count());
+
+        // tiles that come back without observers hearing about it are still picked up
+        let saved = mosaic.save();
+        mosaic.clear();
+        app.update();
+        assert_eq!(0, query.iter(&app.world).count());
+        mosaic.load(&saved).unwrap();
+        app.update();
+        assert_eq!(3, query.iter(&app.world).count());
+    }
+
+    #[test]
+    fn test_writing_back_into_mosaic() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Foo: i32;").unwrap();
+        let a = mosaic.new_object("Foo", par(7i32));
+
+        let mut app = App::new();
+        app.add_plugins(MosaicBridgePlugin::new(&mosaic));
+        app.update();
+
+        let entity = app
+            .world
+            .resource::<MosaicEntities>()
+            .get_entity(a.id)
+            .unwrap();
+        app.world
+            .get_mut::<MosaicData>(entity)
+            .unwrap()
+            .fields
+            .insert("self".to_string(), Value::I32(12));
+        app.update();
+
+        assert_eq!(Value::I32(12), a.get("self"));
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::BTreeSet, fs, path::Path, process::ExitCode, sync::Arc};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use mosaic::{
+    capabilities::MosaicComparison,
+    internals::{
+        format_version, load_mosaic_commands, write_file, Mosaic, MosaicIO, MosaicStatistics, Tile,
+        Value, MOSAIC_FORMAT_VERSION, S32,
+    },
+    mosaic_json::{mosaic_to_json, tile_to_json, value_to_json},
+};
+
+const USAGE: &str = "\
+Usage: mosaic-cli <command> <file> [arguments]
+
+Commands:
+  inspect <file>                  the format version, tile counts, types and components
+  dump <file> [--json | --dot]    every tile, as json (the default) or as a graphviz graph
+  diff <a> <b>                    the types and tiles that differ from a to b, by id
+  validate <file>                 checks that the file loads cleanly, failing if it doesn't
+  migrate <file> [<out>]          rewrites the file, or writes it to out, in the current
+                                  format version; files from before versioning included
+  grep <file> <component>[.<field>] [<text>]
+                                  the tiles holding the component, and if text is given,
+                                  whose field (or any field) holds it";
+
+fn read(path: &str) -> anyhow::Result<(Vec<u8>, Arc<Mosaic>)> {
+    let data = fs::read(path).map_err(|e| anyhow!("Cannot read {}: {}", path, e))?;
+    let mosaic = Mosaic::new();
+    mosaic
+        .load(&data)
+        .map_err(|e| anyhow!("Cannot load {}: {}", path, e))?;
+    Ok((data, mosaic))
+}
+
+fn definitions(mosaic: &Arc<Mosaic>) -> Vec<String> {
+    mosaic
+        .component_registry
+        .component_definitions
+        .read()
+        .unwrap()
+        .clone()
+}
+
+fn tiles(mosaic: &Arc<Mosaic>) -> Vec<Tile> {
+    mosaic.get_all().sorted_by_key(|t| t.id).collect_vec()
+}
+
+fn inspect(path: &str) -> anyhow::Result<String> {
+    let (data, mosaic) = read(path)?;
+    let version = format_version(&data)?;
+    let stats = mosaic.stats();
+
+    let mut lines = vec![
+        format!("file:       {} ({} bytes)", path, data.len()),
+        match version < MOSAIC_FORMAT_VERSION {
+            true => format!(
+                "format:     version {}, current is {}",
+                version, MOSAIC_FORMAT_VERSION
+            ),
+            false => format!("format:     version {}", version),
+        },
+        format!("uuid:       {}", mosaic.uuid()),
+        format!(
+            "tiles:      {} ({} objects, {} arrows, {} descriptors, {} extensions)",
+            stats.tiles(),
+            stats.objects,
+            stats.arrows,
+            stats.descriptors,
+            stats.extensions
+        ),
+        format!("strings:    {}", stats.strings),
+        "types:".to_string(),
+    ];
+    lines.extend(definitions(&mosaic).iter().map(|d| format!("  {}", d)));
+    lines.push("components:".to_string());
+    lines.extend(
+        stats
+            .components
+            .iter()
+            .map(|(component, count)| format!("  {:<32} {}", component, count)),
+    );
+    Ok(lines.join("\n"))
+}
+
+fn dump(path: &str, format: &str) -> anyhow::Result<String> {
+    let (_, mosaic) = read(path)?;
+    match format {
+        "--json" => Ok(serde_json::to_string_pretty(&mosaic_to_json(&mosaic))?),
+        "--dot" => Ok(mosaic.dot("mosaic")),
+        _ => Err(anyhow!("Cannot dump as {}, use --json or --dot", format)),
+    }
+}
+
+/// Lines in the style of a unified diff: `-` for what is only in `a`, `+` for what is only
+/// in `b`, and both for tiles that changed, then a summary.
+fn diff(a_path: &str, b_path: &str) -> anyhow::Result<String> {
+    let (_, a) = read(a_path)?;
+    let (_, b) = read(b_path)?;
+    let mut lines = vec![];
+
+    let (a_types, b_types) = (definitions(&a), definitions(&b));
+    lines.extend(
+        a_types
+            .iter()
+            .filter(|d| !b_types.contains(d))
+            .map(|d| format!("- type {}", d)),
+    );
+    lines.extend(
+        b_types
+            .iter()
+            .filter(|d| !a_types.contains(d))
+            .map(|d| format!("+ type {}", d)),
+    );
+
+    let describe = |mosaic: &Arc<Mosaic>, id| mosaic.get(id).map(|t| tile_to_json(&t).to_string());
+    let ids = a
+        .get_all()
+        .chain(b.get_all())
+        .map(|t| t.id)
+        .collect::<BTreeSet<_>>();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for id in ids {
+        match (describe(&a, id), describe(&b, id)) {
+            (Some(before), Some(after)) if before != after => {
+                changed += 1;
+                lines.push(format!("- {}", before));
+                lines.push(format!("+ {}", after));
+            }
+            (Some(before), None) => {
+                removed += 1;
+                lines.push(format!("- {}", before));
+            }
+            (None, Some(after)) => {
+                added += 1;
+                lines.push(format!("+ {}", after));
+            }
+            _ => {}
+        }
+    }
+
+    lines.push(format!(
+        "{} tiles added, {} removed, {} changed",
+        added, removed, changed
+    ));
+    if added + removed + changed > 0 && a.is_isomorphic_to(&b) {
+        lines.push("The mosaics are isomorphic, only their ids differ".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+fn validate(path: &str) -> anyhow::Result<String> {
+    let data = fs::read(path).map_err(|e| anyhow!("Cannot read {}: {}", path, e))?;
+    let version = format_version(&data)?;
+    load_mosaic_commands(&data)?;
+    let (_, mosaic) = read(path)?;
+
+    let leaks = mosaic.check_leaks();
+    if !leaks.is_empty() {
+        return Err(anyhow!(
+            "{} holds data of tiles that are gone: {:?}",
+            path,
+            leaks
+        ));
+    }
+
+    Ok(format!(
+        "{} is a valid mosaic, format version {}, with {} tiles",
+        path,
+        version,
+        mosaic.stats().tiles()
+    ))
+}
+
+fn migrate(path: &str, out: Option<&str>) -> anyhow::Result<String> {
+    let (data, mosaic) = read(path)?;
+    let version = format_version(&data)?;
+    let out = out.unwrap_or(path);
+    if version == MOSAIC_FORMAT_VERSION && out == path {
+        return Ok(format!("{} is already in format version {}", path, version));
+    }
+
+    // a failed write leaves the file as it was, rather than half rewritten
+    write_file(Path::new(out), &mosaic.save())
+        .map_err(|e| anyhow!("Cannot write {}: {}", out, e))?;
+    Ok(format!(
+        "{} written to {} in format version {}, from {}",
+        path, out, MOSAIC_FORMAT_VERSION, version
+    ))
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::S32(s) => s.to_string(),
+        Value::STR(s) => s.to_string(),
+        value => value_to_json(value).to_string(),
+    }
+}
+
+fn grep(path: &str, pattern: &str, text: Option<&str>) -> anyhow::Result<String> {
+    let (_, mosaic) = read(path)?;
+    let (component, field) = match pattern.split_once('.') {
+        Some((component, field)) => (component, Some(field)),
+        None => (pattern, None),
+    };
+
+    let found = tiles(&mosaic)
+        .into_iter()
+        .filter(|tile| {
+            let Some(values) = tile.get_data(component) else {
+                return false;
+            };
+            values
+                .iter()
+                .filter(|(name, _)| field.is_none_or(|f| *name == S32::truncating(f)))
+                .any(|(_, value)| text.is_none_or(|t| text_of(value).contains(t)))
+        })
+        .map(|tile| tile_to_json(&tile).to_string())
+        .collect_vec();
+    Ok(found.join("\n"))
+}
+
+fn run(args: &[String]) -> anyhow::Result<String> {
+    match args.iter().map(String::as_str).collect_vec().as_slice() {
+        ["help" | "--help" | "-h"] => Ok(USAGE.to_string()),
+        ["inspect", file] => inspect(file),
+        ["dump", file] => dump(file, "--json"),
+        ["dump", file, format] => dump(file, format),
+        ["diff", a, b] => diff(a, b),
+        ["validate", file] => validate(file),
+        ["migrate", file] => migrate(file, None),
+        ["migrate", file, out] => migrate(file, Some(out)),
+        ["grep", file, pattern] => grep(file, pattern, None),
+        ["grep", file, pattern, text] => grep(file, pattern, Some(text)),
+        _ => Err(anyhow!("{}", USAGE)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect_vec();
+    match run(&args) {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use std::{fs, path::PathBuf};
+
+    use mosaic::internals::{
+        pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+        MosaicTypelevelCRUD, TileFieldSetter, Value, MOSAIC_FORMAT_VERSION,
+    };
+
+    use super::run;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mosaic-cli-{}-{}.mos", std::process::id(), name))
+    }
+
+    fn cli(args: &[&str]) -> anyhow::Result<String> {
+        run(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_cli() {
+        let mosaic = Mosaic::new();
+        mosaic
+            .new_type("Node: { name: str, weight?: i32 };")
+            .unwrap();
+        let a = mosaic.new_object("Node", pars().set("name", "first".to_string()).ok());
+        let mut b = mosaic.new_object("Node", pars().set("name", "second".to_string()).ok());
+        mosaic.new_arrow(&a, &b, "void", void());
+
+        let a_path = temp_path("a");
+        let b_path = temp_path("b");
+        let (a_file, b_file) = (a_path.to_str().unwrap(), b_path.to_str().unwrap());
+        fs::write(&a_path, mosaic.save()).unwrap();
+
+        let inspected = cli(&["inspect", a_file]).unwrap();
+        assert!(inspected.contains("3 (2 objects, 1 arrows, 0 descriptors, 0 extensions)"));
+        assert!(inspected.contains("Node: { name: str, weight?: i32 };"));
+
+        let dumped: serde_json::Value =
+            serde_json::from_str(&cli(&["dump", a_file]).unwrap()).unwrap();
+        assert_eq!(3, dumped["tiles"].as_array().unwrap().len());
+        assert!(cli(&["dump", a_file, "--dot"])
+            .unwrap()
+            .starts_with("digraph mosaic"));
+
+        assert_eq!(
+            1,
+            cli(&["grep", a_file, "Node.name", "sec"])
+                .unwrap()
+                .lines()
+                .count()
+        );
+        assert_eq!(2, cli(&["grep", a_file, "Node"]).unwrap().lines().count());
+        assert_eq!("", cli(&["grep", a_file, "Node.weight", "sec"]).unwrap());
+
+        assert!(cli(&["validate", a_file]).is_ok());
+        let mut data = mosaic.save();
+        data.truncate(data.len() / 2);
+        fs::write(&b_path, data).unwrap();
+        assert!(cli(&["validate", b_file]).is_err());
+
+        b.set("weight", 4i32);
+        mosaic.delete_tile(a.id);
+        fs::write(&b_path, mosaic.save()).unwrap();
+        let diff = cli(&["diff", a_file, b_file]).unwrap();
+        assert!(diff.ends_with("0 tiles added, 2 removed, 1 changed"));
+        assert!(diff.contains(r#""weight":4"#));
+        assert!(cli(&["diff", a_file, a_file])
+            .unwrap()
+            .ends_with("0 tiles added, 0 removed, 0 changed"));
+
+        assert!(cli(&["migrate", a_file])
+            .unwrap()
+            .contains("already in format version"));
+        assert!(cli(&["frobnicate", a_file]).is_err());
+
+        fs::remove_file(a_path).unwrap();
+        fs::remove_file(b_path).unwrap();
+    }
+
+    #[test]
+    fn test_cli_migrates_legacy_files() {
+        // from before the header: the type definitions, then the tiles up to the end
+        let mut legacy = vec![];
+        legacy.extend(9u16.to_be_bytes());
+        legacy.extend(b"Foo: i32;");
+        legacy.extend(0u16.to_be_bytes());
+        legacy.extend([0u8; 24]);
+        legacy.extend(3u64.to_be_bytes());
+        legacy.extend(b"Foo");
+        legacy.extend(4u32.to_be_bytes());
+        legacy.extend(101i32.to_be_bytes());
+
+        let path = temp_path("legacy");
+        let file = path.to_str().unwrap();
+        fs::write(&path, &legacy).unwrap();
+        assert!(cli(&["inspect", file])
+            .unwrap()
+            .contains(&format!("version 0, current is {}", MOSAIC_FORMAT_VERSION)));
+        assert!(cli(&["validate", file]).is_ok());
+
+        assert!(cli(&["migrate", file])
+            .unwrap()
+            .ends_with(&format!("format version {}, from 0", MOSAIC_FORMAT_VERSION)));
+        let migrated = Mosaic::new();
+        migrated.load(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(Value::I32(101), migrated.get(0).unwrap().get("self"));
+        assert!(cli(&["migrate", file])
+            .unwrap()
+            .contains("already in format version"));
+
+        // nothing is left next to the file but the file itself
+        let prefix = format!(".{}", path.file_name().unwrap().to_str().unwrap());
+        assert!(!fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .any(|entry| entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&prefix)));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,10 +1,50 @@
 pub mod archetype;
-
+pub mod audit;
+pub mod comparison;
+pub mod grouping;
+pub mod history;
+pub mod labeled_edges;
+pub mod labels;
+pub mod metrics;
+pub mod namespace;
+pub mod operation_log;
+pub mod parenting;
+pub mod pattern_match;
+pub mod plugin;
+// processes run on worker threads, which wasm doesn't have
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pipeline;
+pub mod priority_queue;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod process;
 pub mod queue;
+pub mod rewrite;
 pub mod selection;
+pub mod traversal;
+pub mod versioning;
 
 mod unit_tests;
 
 pub use archetype::*;
+pub use audit::*;
+pub use comparison::*;
+pub use grouping::*;
+pub use history::*;
+pub use labeled_edges::*;
+pub use labels::*;
+pub use metrics::*;
+pub use namespace::*;
+pub use operation_log::*;
+pub use parenting::*;
+pub use pattern_match::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pipeline::*;
+pub use plugin::*;
+pub use priority_queue::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use process::*;
 pub use queue::*;
+pub use rewrite::*;
 pub use selection::*;
+pub use traversal::*;
+pub use versioning::*;
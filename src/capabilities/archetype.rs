@@ -1,10 +1,14 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+    vec::IntoIter,
+};
 
 use array_tool::vec::Uniq;
 use itertools::Itertools;
 
 use crate::{
-    internals::{Mosaic, MosaicCRUD, Tile, Value, S32},
+    internals::{Mosaic, MosaicCRUD, MosaicIndices, Tile, TileGetById, Value, S32},
     iterators::{
         component_selectors::ComponentSelectors, tile_deletion::TileDeletion,
         tile_getters::TileGetters,
@@ -16,6 +20,8 @@ pub trait Archetype {
     fn get_components(&self, target: &Tile, component: &str) -> Vec<Tile>;
     fn add_component(&self, target: &Tile, component: &str, data: Vec<(S32, Value)>) -> Tile;
     fn remove_components(&self, target: &Tile, component: &str);
+    /// All tiles for which `match_archetype` holds, found through the component index.
+    fn get_tiles_with_archetype(&self, components: &[&str]) -> IntoIter<Tile>;
 
     fn match_archetype(&self, target: &Tile, components: &[&str]) -> bool {
         components
@@ -98,7 +104,20 @@ impl Archetype for Arc<Mosaic> {
         result
     }
 
+    /// Components declared `unique` replace the one the target already has, rather than
+    /// being refused.
     fn add_component(&self, target: &Tile, component: &str, data: Vec<(S32, Value)>) -> Tile {
+        if self
+            .component_registry
+            .get_modifiers(component.into())
+            .unique
+        {
+            target
+                .iter()
+                .get_descriptors()
+                .include_component(component)
+                .delete();
+        }
         self.new_descriptor(target, component, data)
     }
 
@@ -109,6 +128,31 @@ impl Archetype for Arc<Mosaic> {
             .include_component(component)
             .delete();
     }
+
+    fn get_tiles_with_archetype(&self, components: &[&str]) -> IntoIter<Tile> {
+        // a tile carries a component if it is of that component or depends on a tile that is
+        let holders = components
+            .iter()
+            .map(|c| {
+                self.get_tiles_with_component(c)
+                    .flat_map(|t| [t.id, t.source_id(), t.target_id()])
+                    .collect::<BTreeSet<_>>()
+            })
+            .sorted_by_key(|ids| ids.len())
+            .collect_vec();
+
+        let Some((smallest, rest)) = holders.split_first() else {
+            return vec![].into_iter();
+        };
+
+        let ids = smallest
+            .iter()
+            .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+            .copied()
+            .collect_vec();
+
+        self.get_tiles(ids)
+    }
 }
 
 impl ArchetypeSubject for Tile {
@@ -0,0 +1,156 @@
+use std::{collections::HashSet, sync::Arc, vec::IntoIter};
+
+use itertools::Itertools;
+
+use crate::internals::{
+    pars, ComponentValuesBuilderSetter, DateTime, EntityId, Mosaic, MosaicCRUD, MosaicIO,
+    MosaicIndices, MosaicTypelevelCRUD, Tile, TileFieldSetter, S32,
+};
+
+fn is_stamped(tile: &Tile) -> bool {
+    (tile.is_object() || tile.is_arrow())
+        && tile
+            .mosaic
+            .audited_components
+            .lock()
+            .unwrap()
+            .get(&tile.component)
+            .is_some_and(|stamping| *stamping)
+}
+
+fn stamp_of(mosaic: &Arc<Mosaic>, id: EntityId, component: &str) -> Option<Tile> {
+    mosaic.get_tiles_into_with(id, component).next()
+}
+
+/// The subjects of the `component` stamps taken at or after `since`.
+fn stamped_since(mosaic: &Arc<Mosaic>, component: &str, since: DateTime) -> HashSet<EntityId> {
+    mosaic
+        .get_tiles_with_component(component)
+        .filter(|stamp| stamp.get("at").as_datetime() >= since)
+        .map(|stamp| stamp.target_id())
+        .collect()
+}
+
+fn stamp(tile: &Tile, component: &str) {
+    // clearing the mosaic forgets the stamp types, but not the hooks that make stamps
+    tile.mosaic
+        .new_type(&format!("{}: {{ at: datetime }};", component))
+        .unwrap();
+
+    let now = DateTime::now();
+    match stamp_of(&tile.mosaic, tile.id, component) {
+        Some(mut stamp) => stamp.set("at", now),
+        None => {
+            tile.mosaic
+                .new_descriptor(tile, component, pars().set("at", now).ok());
+        }
+    }
+}
+
+/// Stamps `tile` as modified whenever one of its fields is set. The watches go away with the
+/// tile, and do nothing while its component isn't being stamped.
+fn watch_fields(tile: &Tile) {
+    for (field, _) in tile.data() {
+        tile.on_field_change(&field.to_string(), |tile, _, _| {
+            if is_stamped(tile) {
+                stamp(tile, "Modified");
+            }
+        });
+    }
+}
+
+/// Stamps objects and arrows of the enabled components with `Created { at: datetime }` and
+/// `Modified { at: datetime }` descriptors as they are made and changed.
+pub trait AuditCapability {
+    /// Starts stamping objects and arrows of `components`, through lifetime hooks on each of
+    /// them and field watches on their tiles.
+    fn enable_audit(&self, components: &[&str]);
+    /// Stops stamping tiles; the stamps made so far stay.
+    fn disable_audit(&self);
+    fn is_audit_enabled(&self) -> bool;
+    fn created_at(&self, tile: &Tile) -> Option<DateTime>;
+    /// When `tile` last had a field set, or was made if it hasn't changed since.
+    fn modified_at(&self, tile: &Tile) -> Option<DateTime>;
+    /// The tiles made at or after `since`, in order of id.
+    fn created_since(&self, since: DateTime) -> IntoIter<Tile>;
+    /// The tiles made or changed at or after `since`, in order of id.
+    fn modified_since(&self, since: DateTime) -> IntoIter<Tile>;
+}
+
+impl AuditCapability for Arc<Mosaic> {
+    fn enable_audit(&self, components: &[&str]) {
+        self.new_type("Created: { at: datetime };").unwrap();
+        self.new_type("Modified: { at: datetime };").unwrap();
+
+        for component in components {
+            let name = S32::from(*component);
+            let hooked = self
+                .audited_components
+                .lock()
+                .unwrap()
+                .insert(name, true)
+                .is_some();
+            // hooks can't be taken off again, so each component only ever gets one
+            if hooked {
+                continue;
+            }
+
+            self.component_registry.on_create(component, |tile| {
+                if tile.is_object() || tile.is_arrow() {
+                    watch_fields(tile);
+                }
+                if is_stamped(tile) {
+                    stamp(tile, "Created");
+                }
+            });
+            self.get_tiles_with_component(component)
+                .filter(|tile| tile.is_object() || tile.is_arrow())
+                .for_each(|tile| watch_fields(&tile));
+        }
+    }
+
+    fn disable_audit(&self) {
+        self.audited_components
+            .lock()
+            .unwrap()
+            .values_mut()
+            .for_each(|stamping| *stamping = false);
+    }
+
+    fn is_audit_enabled(&self) -> bool {
+        self.audited_components
+            .lock()
+            .unwrap()
+            .values()
+            .any(|stamping| *stamping)
+    }
+
+    fn created_at(&self, tile: &Tile) -> Option<DateTime> {
+        stamp_of(self, tile.id, "Created").map(|stamp| stamp.get("at").as_datetime())
+    }
+
+    fn modified_at(&self, tile: &Tile) -> Option<DateTime> {
+        stamp_of(self, tile.id, "Modified")
+            .map(|stamp| stamp.get("at").as_datetime())
+            .or_else(|| self.created_at(tile))
+    }
+
+    fn created_since(&self, since: DateTime) -> IntoIter<Tile> {
+        stamped_since(self, "Created", since)
+            .into_iter()
+            .sorted()
+            .flat_map(|id| self.get(id))
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn modified_since(&self, since: DateTime) -> IntoIter<Tile> {
+        let mut ids = stamped_since(self, "Created", since);
+        ids.extend(stamped_since(self, "Modified", since));
+        ids.into_iter()
+            .sorted()
+            .flat_map(|id| self.get(id))
+            .collect_vec()
+            .into_iter()
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::{
+    capabilities::first_binding,
+    internals::{Mosaic, MosaicIO, Tile, TileType},
+    iterators::tile_getters::TileGetters,
+};
+
+/// What a tile looks like without its id: its component, its fields and any data added to
+/// it, and the same of its descriptors and extensions, in no particular order.
+pub(crate) fn tile_signature(tile: &Tile) -> String {
+    let data = tile
+        .components()
+        .into_iter()
+        .sorted()
+        .map(|component| {
+            let fields = tile
+                .get_data(&component.to_string())
+                .unwrap_or_default()
+                .into_iter()
+                .sorted_by_key(|(field, _)| *field)
+                .map(|(field, value)| format!("{}={:?}", field, value))
+                .join(",");
+            format!("{}{{{}}}", component, fields)
+        })
+        .join("+");
+
+    let dependents = tile
+        .iter()
+        .get_descriptors()
+        .chain(tile.iter().get_extensions())
+        .map(|d| tile_signature(&d))
+        .sorted()
+        .join(",");
+
+    format!("{}[{}]", data, dependents)
+}
+
+/// How a tile is compared when it isn't bound on its own: arrows touching something other
+/// than objects are only told apart by what they hold and what their ends look like.
+fn loose_signature(tile: &Tile) -> String {
+    if tile.is_arrow() {
+        format!(
+            "{}({}->{})",
+            tile_signature(tile),
+            tile_signature(&tile.source()),
+            tile_signature(&tile.target())
+        )
+    } else {
+        tile_signature(tile)
+    }
+}
+
+fn kind_of(tile: &Tile) -> u8 {
+    match tile.tile_type {
+        TileType::Object => 0,
+        TileType::Arrow { .. } => 1,
+        TileType::Descriptor { .. } => 2,
+        TileType::Extension { .. } => 3,
+    }
+}
+
+fn is_object_arrow(tile: &Tile) -> bool {
+    tile.is_arrow() && tile.source().is_object() && tile.target().is_object()
+}
+
+pub trait MosaicComparison {
+    /// Whether `other` holds the same tiles as this mosaic up to ids, pairing tiles by their
+    /// component, fields, attachments and ends. Type definitions are not compared.
+    fn structurally_equals(&self, other: &Arc<Mosaic>) -> bool;
+    /// Whether the objects of `other` and the arrows between them form the same graph as
+    /// those of this mosaic, whatever their components and fields.
+    fn is_isomorphic_to(&self, other: &Arc<Mosaic>) -> bool;
+}
+
+impl MosaicComparison for Arc<Mosaic> {
+    fn structurally_equals(&self, other: &Arc<Mosaic>) -> bool {
+        let signatures = |m: &Arc<Mosaic>| {
+            m.get_all()
+                .map(|t| (kind_of(&t), loose_signature(&t)))
+                .sorted()
+                .collect_vec()
+        };
+        if signatures(self) != signatures(other) {
+            return false;
+        }
+
+        // with the same objects, a binding of all of them is a pairing of the two mosaics
+        !self.get_all().any(|t| t.is_object()) || first_binding(self, other, true).is_some()
+    }
+
+    fn is_isomorphic_to(&self, other: &Arc<Mosaic>) -> bool {
+        let counts = |m: &Arc<Mosaic>| {
+            let tiles = m.get_all().collect_vec();
+            (
+                tiles.iter().filter(|t| t.is_object()).count(),
+                tiles.iter().filter(|t| is_object_arrow(t)).count(),
+            )
+        };
+        let (objects, arrows) = counts(self);
+        if (objects, arrows) != counts(other) {
+            return false;
+        }
+
+        objects == 0 || first_binding(self, other, false).is_some()
+    }
+}
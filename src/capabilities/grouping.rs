@@ -1,9 +1,13 @@
 use std::vec::IntoIter;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
+use anyhow::anyhow;
 use itertools::Itertools;
 
-use crate::internals::{par, Logging, MosaicIO, MosaicTypelevelCRUD};
+use crate::internals::{par, Logging, MosaicIO, MosaicIndices, MosaicTypelevelCRUD, S32};
 use crate::internals::{Mosaic, MosaicCRUD, Tile};
 use crate::iterators::component_selectors::ComponentSelectors;
 use crate::iterators::tile_getters::TileGetters;
@@ -16,6 +20,20 @@ pub trait GroupingCapability {
     fn get_group_owner(&self, group: &str, tile: &Tile) -> Option<Tile>;
     fn get_group_members(&self, group: &str, tile: &Tile) -> IntoIter<Tile>;
     fn ungroup(&self, group: &str, tile: &Tile);
+    /// Adds `tile` to the group called `name`, making a `NamedGroup` object for it first if
+    /// there is none, and returns the group's tile.
+    fn add_to_group(&self, tile: &Tile, name: &str) -> Tile;
+    fn remove_from_group(&self, tile: &Tile, name: &str);
+    fn get_named_group(&self, name: &str) -> Option<Tile>;
+    /// The direct members of the group called `name`, with its subgroups as their group tiles.
+    fn get_named_group_members(&self, name: &str) -> IntoIter<Tile>;
+    /// Makes the group `child` a member of the group `parent`, unless that would make a cycle.
+    fn add_subgroup(&self, parent: &str, child: &str) -> anyhow::Result<()>;
+    /// The members of the group called `name` and of all its subgroups, without the subgroups.
+    fn get_all_group_members(&self, name: &str) -> IntoIter<Tile>;
+    /// Whether `tile` is in the group called `name`, directly or through subgroups. Only looks
+    /// at the groups `tile` is in, however big the groups are.
+    fn is_in_group(&self, tile: &Tile, name: &str) -> bool;
 }
 
 fn get_existing_owner_descriptor(group: &str, owner: &Tile) -> Option<Tile> {
@@ -26,10 +44,30 @@ fn get_existing_owner_descriptor(group: &str, owner: &Tile) -> Option<Tile> {
         .include_component("GroupOwner")
         .map(|t| (t.get("self").as_s32(), t))
         .collect::<HashMap<_, _>>()
-        .get(&group.into())
+        .get(&S32::from(group))
         .cloned()
 }
 
+/// The named groups `tile` is a direct member of.
+fn named_groups_of(tile: &Tile) -> Vec<Tile> {
+    tile.mosaic
+        .get_tiles_into_with(tile.id, "Group")
+        .flat_map(|arrow| tile.mosaic.get(arrow.source_id()))
+        .flat_map(|owner_descriptor| tile.mosaic.get(owner_descriptor.target_id()))
+        .filter(|owner| owner.component == "NamedGroup".into())
+        .unique()
+        .collect_vec()
+}
+
+fn ensure_named_group(mosaic: &Arc<Mosaic>, name: &str) -> Tile {
+    mosaic.get_named_group(name).unwrap_or_else(|| {
+        mosaic.new_type("NamedGroup: s32;").unwrap();
+        let group = mosaic.new_object("NamedGroup", par(name));
+        mosaic.group(name, &group, &[]);
+        group
+    })
+}
+
 impl GroupingCapability for Arc<Mosaic> {
     fn get_group_memberships(&self, tile: &Tile) -> Vec<Tile> {
         tile.clone()
@@ -77,7 +115,7 @@ impl GroupingCapability for Arc<Mosaic> {
                 .get_arrows_into()
                 .include_component("Group")
                 .map(|s| (s.get("self").as_s32(), s))
-                .filter(|(c, _)| c == &group.into())
+                .filter(|(c, _)| *c == S32::from(group))
                 .map(|(_, t)| t)
                 .get_sources()
                 .collect_vec()
@@ -110,4 +148,103 @@ impl GroupingCapability for Arc<Mosaic> {
             self.delete_tile(arrow.id);
         }
     }
+
+    fn add_to_group(&self, tile: &Tile, name: &str) -> Tile {
+        let group = ensure_named_group(self, name);
+        if !named_groups_of(tile).contains(&group) {
+            self.add_group_member(name, &group, tile).unwrap();
+        }
+
+        group
+    }
+
+    fn remove_from_group(&self, tile: &Tile, name: &str) {
+        let Some(group) = self.get_named_group(name) else {
+            return;
+        };
+
+        let arrows = self
+            .get_tiles_into_with(tile.id, "Group")
+            .filter(|arrow| {
+                self.get(arrow.source_id())
+                    .is_some_and(|owner_descriptor| owner_descriptor.target_id() == group.id)
+            })
+            .collect_vec();
+
+        for arrow in arrows {
+            self.delete_tile(arrow);
+        }
+    }
+
+    fn get_named_group(&self, name: &str) -> Option<Tile> {
+        self.get_tiles_with_component("NamedGroup")
+            .find(|t| t.get("self").as_s32() == S32::from(name))
+    }
+
+    fn get_named_group_members(&self, name: &str) -> IntoIter<Tile> {
+        match self.get_named_group(name) {
+            Some(group) => self.get_group_members(name, &group),
+            None => vec![].into_iter(),
+        }
+    }
+
+    fn add_subgroup(&self, parent: &str, child: &str) -> anyhow::Result<()> {
+        if parent == child {
+            return Err(anyhow!("Group {} cannot be its own subgroup", parent));
+        }
+
+        let parent_group = ensure_named_group(self, parent);
+        let child_group = ensure_named_group(self, child);
+
+        if self.is_in_group(&parent_group, child) {
+            return Err(anyhow!(
+                "Group {} is already inside group {}, cannot nest them the other way",
+                parent,
+                child
+            ));
+        }
+
+        self.add_to_group(&child_group, parent);
+        Ok(())
+    }
+
+    fn get_all_group_members(&self, name: &str) -> IntoIter<Tile> {
+        let mut members = vec![];
+        let mut visited = HashSet::new();
+        let mut pending = vec![name.to_string()];
+
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+
+            for member in self.get_named_group_members(&name) {
+                if member.component == "NamedGroup".into() {
+                    pending.push(member.get("self").as_s32().to_string());
+                } else {
+                    members.push(member);
+                }
+            }
+        }
+
+        members.into_iter().unique().collect_vec().into_iter()
+    }
+
+    fn is_in_group(&self, tile: &Tile, name: &str) -> bool {
+        let name = S32::from(name);
+        let mut visited = HashSet::new();
+        let mut pending = named_groups_of(tile);
+
+        while let Some(group) = pending.pop() {
+            if group.get("self").as_s32() == name {
+                return true;
+            }
+
+            if visited.insert(group.id) {
+                pending.extend(named_groups_of(&group));
+            }
+        }
+
+        false
+    }
 }
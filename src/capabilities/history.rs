@@ -0,0 +1,246 @@
+use std::sync::Arc;
+
+use crate::internals::{HistoryStep, Mosaic};
+
+pub trait HistoryCapability {
+    /// Starts recording changes, keeping at most `limit` steps to undo; `0` stops recording.
+    fn set_history_limit(&self, limit: usize);
+    /// Groups every change made until `end_history_step` into a single named step.
+    fn begin_history_step(&self, name: &str);
+    fn end_history_step(&self);
+    /// Reverts the last step and returns its name, if there was anything to undo.
+    fn undo(&self) -> Option<String>;
+    /// Replays the last undone step and returns its name, if there was anything to redo.
+    fn redo(&self) -> Option<String>;
+    fn can_undo(&self) -> bool;
+    fn can_redo(&self) -> bool;
+    fn clear_history(&self);
+}
+
+impl HistoryCapability for Arc<Mosaic> {
+    fn set_history_limit(&self, limit: usize) {
+        let mut history = self.history.lock().unwrap();
+        history.limit = limit;
+        while history.undo_stack.len() > limit {
+            let step = history.undo_stack.pop_front();
+            history.dropped.extend(step);
+        }
+    }
+
+    fn begin_history_step(&self, name: &str) {
+        let mut history = self.history.lock().unwrap();
+        if let Some(step) = history.open_step.take() {
+            history.push_step(step);
+        }
+
+        history.open_step = Some(HistoryStep {
+            name: name.to_string(),
+            operations: vec![],
+        });
+    }
+
+    fn end_history_step(&self) {
+        let mut history = self.history.lock().unwrap();
+        if let Some(step) = history.open_step.take() {
+            history.push_step(step);
+        }
+    }
+
+    fn undo(&self) -> Option<String> {
+        let step = {
+            let mut history = self.history.lock().unwrap();
+            if let Some(open) = history.open_step.take() {
+                history.push_step(open);
+            }
+
+            let step = history.undo_stack.pop_back()?;
+            history.replaying = true;
+            step
+        };
+
+        step.operations
+            .iter()
+            .rev()
+            .for_each(|operation| self.revert_history_operation(operation));
+
+        let mut history = self.history.lock().unwrap();
+        history.replaying = false;
+        let name = step.name.clone();
+        history.redo_stack.push(step);
+        Some(name)
+    }
+
+    fn redo(&self) -> Option<String> {
+        let step = {
+            let mut history = self.history.lock().unwrap();
+            let step = history.redo_stack.pop()?;
+            history.replaying = true;
+            step
+        };
+
+        step.operations
+            .iter()
+            .for_each(|operation| self.replay_history_operation(operation));
+
+        let mut history = self.history.lock().unwrap();
+        history.replaying = false;
+        let name = step.name.clone();
+        history.push_step(step);
+        Some(name)
+    }
+
+    fn can_undo(&self) -> bool {
+        let history = self.history.lock().unwrap();
+        !history.undo_stack.is_empty()
+            || history
+                .open_step
+                .as_ref()
+                .is_some_and(|step| !step.operations.is_empty())
+    }
+
+    fn can_redo(&self) -> bool {
+        !self.history.lock().unwrap().redo_stack.is_empty()
+    }
+
+    fn clear_history(&self) {
+        self.history.lock().unwrap().reset();
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::{
+        capabilities::HistoryCapability,
+        internals::{
+            par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, TileFieldSetter, Value,
+        },
+    };
+
+    #[test]
+    fn test_undo_redo_creation() {
+        let mosaic = Mosaic::new();
+        mosaic.set_history_limit(10);
+        let a = mosaic.new_object("void", void());
+        assert!(mosaic.can_undo());
+
+        assert_eq!(Some("create".to_string()), mosaic.undo());
+        assert!(!mosaic.is_tile_valid(&a));
+        assert!(!mosaic.can_undo());
+
+        assert_eq!(Some("create".to_string()), mosaic.redo());
+        assert!(mosaic.is_tile_valid(&a));
+        assert!(!mosaic.can_redo());
+    }
+
+    #[test]
+    fn test_undo_deletion_restores_dependents() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Label: s32;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let ab = mosaic.new_arrow(&a, &b, "void", void());
+        let d = mosaic.new_descriptor(&a, "Label", par("hello"));
+
+        mosaic.set_history_limit(10);
+        mosaic.delete_tile(a.clone());
+        assert!(!mosaic.is_tile_valid(&ab));
+        assert!(!mosaic.is_tile_valid(&d));
+
+        // the cascade is recorded as separate steps, one per deleted tile
+        while mosaic.undo().is_some() {}
+        assert!(mosaic.is_tile_valid(&a));
+        assert!(mosaic.is_tile_valid(&ab));
+        assert_eq!(
+            "hello",
+            mosaic.get(d.id).unwrap().get("self").as_s32().to_string()
+        );
+    }
+
+    #[test]
+    fn test_named_steps_and_field_changes() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Position: { x: i32, y: i32 };").unwrap();
+        mosaic.set_history_limit(10);
+
+        mosaic.begin_history_step("place");
+        let mut p = mosaic.new_object("Position", void());
+        p.set("x", 5i32);
+        p.set("y", 7i32);
+        mosaic.end_history_step();
+
+        mosaic.begin_history_step("move");
+        p.set("x", 10i32);
+        mosaic.end_history_step();
+
+        assert_eq!(Some("move".to_string()), mosaic.undo());
+        assert_eq!(5, mosaic.get(p.id).unwrap().get("x").as_i32());
+
+        assert_eq!(Some("place".to_string()), mosaic.undo());
+        assert!(!mosaic.is_tile_valid(&p));
+
+        assert_eq!(Some("place".to_string()), mosaic.redo());
+        assert_eq!(5, mosaic.get(p.id).unwrap().get("x").as_i32());
+        assert_eq!(7, mosaic.get(p.id).unwrap().get("y").as_i32());
+    }
+
+    #[test]
+    fn test_undo_added_data() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Label: s32;").unwrap();
+        let a = mosaic.new_object("void", void());
+        mosaic.set_history_limit(10);
+
+        a.add_data("Label", par("first")).unwrap();
+        a.add_data("Label", par("second")).unwrap();
+        assert_eq!(Some("data".to_string()), mosaic.undo());
+        assert_eq!(Some(par("first")), a.get_data("Label"));
+        assert_eq!(Some("data".to_string()), mosaic.undo());
+        assert!(!a.has_component("Label"));
+        assert_eq!(Some("data".to_string()), mosaic.redo());
+        assert_eq!(Some(par("first")), a.get_data("Label"));
+
+        // deleting the tile takes its added data along, and undoing it brings that back
+        mosaic.delete_tile(a.clone());
+        assert!(a.add_data("Label", par("gone")).is_err());
+        assert_eq!(Some("delete".to_string()), mosaic.undo());
+        assert_eq!(Some(par("first")), a.get_data("Label"));
+    }
+
+    #[test]
+    fn test_undo_redefinition() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Position: { x: f32, y: f32 };").unwrap();
+        let mut p = mosaic.new_object("Position", void());
+        p.set("y", 2.0f32);
+        mosaic.set_history_limit(10);
+
+        let version = mosaic.version();
+        mosaic
+            .redefine_type("Position: { x: f32, z: u8 };")
+            .unwrap();
+        assert!(mosaic.version() > version);
+        assert_eq!(Value::U8(0), p.get("z"));
+
+        assert_eq!(Some("redefine".to_string()), mosaic.undo());
+        assert_eq!(Value::F32(2.0), p.get("y"));
+        assert_eq!(2, p.data().len());
+
+        assert_eq!(Some("redefine".to_string()), mosaic.redo());
+        assert_eq!(Value::U8(0), p.get("z"));
+        assert_eq!(2, p.data().len());
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mosaic = Mosaic::new();
+        mosaic.set_history_limit(2);
+        let a = mosaic.new_object("void", void());
+        mosaic.new_object("void", void());
+        mosaic.new_object("void", void());
+
+        assert!(mosaic.undo().is_some());
+        assert!(mosaic.undo().is_some());
+        assert!(mosaic.undo().is_none());
+        assert!(mosaic.is_tile_valid(&a));
+    }
+}
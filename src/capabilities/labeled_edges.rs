@@ -0,0 +1,38 @@
+use ordered_multimap::ListOrderedMultimap;
+
+use crate::internals::{MosaicArrowQueries, MosaicIndices, Tile, S32};
+
+/// The arrows around a tile, keyed by their component, so a graph can be walked by edge label
+/// without going through every arrow and comparing components.
+pub trait LabeledEdges {
+    /// Arrows leaving this tile, by component, each in id order.
+    fn arrows_by_component(&self) -> ListOrderedMultimap<S32, Tile>;
+    /// Arrows entering this tile, by component, each in id order.
+    fn arrows_into_by_component(&self) -> ListOrderedMultimap<S32, Tile>;
+    /// The first arrow of `component` going from this tile into `other`.
+    fn arrow_to_with(&self, other: &Tile, component: &str) -> Option<Tile>;
+}
+
+impl LabeledEdges for Tile {
+    fn arrows_by_component(&self) -> ListOrderedMultimap<S32, Tile> {
+        self.mosaic
+            .get_tiles_from(self.id)
+            .filter(|t| t.is_arrow())
+            .map(|t| (t.component, t))
+            .collect()
+    }
+
+    fn arrows_into_by_component(&self) -> ListOrderedMultimap<S32, Tile> {
+        self.mosaic
+            .get_tiles_into(self.id)
+            .filter(|t| t.is_arrow())
+            .map(|t| (t.component, t))
+            .collect()
+    }
+
+    fn arrow_to_with(&self, other: &Tile, component: &str) -> Option<Tile> {
+        self.mosaic
+            .get_arrows_between_with(self, other, component)
+            .next()
+    }
+}
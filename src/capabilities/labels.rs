@@ -0,0 +1,160 @@
+use std::{sync::Arc, vec::IntoIter};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::internals::{
+    par, ComponentType, Datatype, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices, MosaicStrings,
+    MosaicTypelevelCRUD, Tile, Value,
+};
+
+/// The component of the descriptor naming a tile, shared by everything built on mosaic so
+/// labelled files can be read by any of them.
+pub const LABEL_COMPONENT: &str = "Label";
+pub const LABEL_DEFINITION: &str = "Label: str;";
+/// The component of descriptors holding free-form notes on a tile.
+pub const COMMENT_COMPONENT: &str = "Comment";
+pub const COMMENT_DEFINITION: &str = "Comment: str;";
+
+fn is_text_alias(component_type: &ComponentType) -> bool {
+    matches!(component_type, ComponentType::Alias(field) if field.datatype == Datatype::STR)
+}
+
+/// Adds `definition` unless its component is there already, and fails if the one already
+/// there isn't a `str` alias.
+fn ensure_text_component(
+    mosaic: &Arc<Mosaic>,
+    component: &str,
+    definition: &str,
+) -> anyhow::Result<()> {
+    mosaic.new_type(definition)?;
+    let component_type = mosaic
+        .component_registry
+        .get_component_type(component.into())?;
+    match is_text_alias(&component_type) {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Component {} is defined as something other than `{}`",
+            component,
+            definition
+        )),
+    }
+}
+
+/// Replaces the `component` descriptor of `tile` with one holding `text`.
+fn set_text(tile: &Tile, component: &str, definition: &str, text: &str) -> anyhow::Result<Tile> {
+    ensure_text_component(&tile.mosaic, component, definition)?;
+    remove_text(tile, component);
+    tile.mosaic
+        .try_new_descriptor(tile, component, par(text.to_string()))
+}
+
+/// The text held by a standard descriptor; `None` for tiles of a `Label` or `Comment` that
+/// was defined differently.
+fn text_in(descriptor: &Tile) -> Option<String> {
+    match descriptor.get("self") {
+        Value::STR(text) if descriptor.is_descriptor() => Some(text.to_string()),
+        _ => None,
+    }
+}
+
+fn text_of(tile: &Tile, component: &str) -> Option<String> {
+    tile.mosaic
+        .get_tiles_into_with(tile.id, component)
+        .find_map(|d| text_in(&d))
+}
+
+fn remove_text(tile: &Tile, component: &str) -> bool {
+    let descriptors = tile
+        .mosaic
+        .get_tiles_into_with(tile.id, component)
+        .filter(|d| d.is_descriptor())
+        .collect_vec();
+    let removed = !descriptors.is_empty();
+    for descriptor in descriptors {
+        tile.mosaic.delete_tile(descriptor.id);
+    }
+    removed
+}
+
+/// The standard `Label` and `Comment` descriptors of a tile, at most one of each.
+pub trait TileLabels {
+    /// Names this tile, replacing its label if it had one. Returns the label descriptor.
+    fn set_label(&self, label: &str) -> anyhow::Result<Tile>;
+    fn label(&self) -> Option<String>;
+    /// Returns whether there was a label to remove.
+    fn remove_label(&self) -> bool;
+    /// Puts a note on this tile, replacing its comment if it had one. Returns the comment
+    /// descriptor.
+    fn set_comment(&self, comment: &str) -> anyhow::Result<Tile>;
+    fn comment(&self) -> Option<String>;
+    /// Returns whether there was a comment to remove.
+    fn remove_comment(&self) -> bool;
+}
+
+impl TileLabels for Tile {
+    fn set_label(&self, label: &str) -> anyhow::Result<Tile> {
+        set_text(self, LABEL_COMPONENT, LABEL_DEFINITION, label)
+    }
+
+    fn label(&self) -> Option<String> {
+        text_of(self, LABEL_COMPONENT)
+    }
+
+    fn remove_label(&self) -> bool {
+        remove_text(self, LABEL_COMPONENT)
+    }
+
+    fn set_comment(&self, comment: &str) -> anyhow::Result<Tile> {
+        set_text(self, COMMENT_COMPONENT, COMMENT_DEFINITION, comment)
+    }
+
+    fn comment(&self) -> Option<String> {
+        text_of(self, COMMENT_COMPONENT)
+    }
+
+    fn remove_comment(&self) -> bool {
+        remove_text(self, COMMENT_COMPONENT)
+    }
+}
+
+/// Looks tiles up by their standard labels, see `TileLabels`.
+pub trait LabelCapability {
+    /// Adds the standard `Label` and `Comment` components, so they're there before any tile
+    /// is labelled; fails if either name is taken by a different definition.
+    fn add_label_components(&self) -> anyhow::Result<()>;
+    /// The tiles labelled exactly `label`, in order of id. Labels are looked up by their id in
+    /// the string pool rather than compared as text.
+    fn find_by_label(&self, label: &str) -> IntoIter<Tile>;
+    /// Every labelled tile with its label, in order of id.
+    fn labels(&self) -> Vec<(Tile, String)>;
+}
+
+impl LabelCapability for Arc<Mosaic> {
+    fn add_label_components(&self) -> anyhow::Result<()> {
+        ensure_text_component(self, LABEL_COMPONENT, LABEL_DEFINITION)?;
+        ensure_text_component(self, COMMENT_COMPONENT, COMMENT_DEFINITION)
+    }
+
+    fn find_by_label(&self, label: &str) -> IntoIter<Tile> {
+        // a string no field holds isn't in the pool, so nothing can be labelled with it
+        let Some(id) = self.string_id(label) else {
+            return vec![].into_iter();
+        };
+
+        self.get_tiles_with_component(LABEL_COMPONENT)
+            .filter(|d| d.is_descriptor() && d.get_str_id("self") == Some(id))
+            .flat_map(|d| self.get(d.target_id()))
+            .sorted_by_key(|t| t.id)
+            .dedup()
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn labels(&self) -> Vec<(Tile, String)> {
+        self.get_tiles_with_component(LABEL_COMPONENT)
+            .flat_map(|d| Some((self.get(d.target_id())?, text_in(&d)?)))
+            .sorted_by_key(|(t, _)| t.id)
+            .collect_vec()
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use crate::internals::{
+    pars, ComponentValuesBuilderSetter, EntityId, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices,
+    MosaicTypelevelCRUD, Tile,
+};
+
+/// The measures of a single tile, see `MetricsCapability`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TileMetrics {
+    pub degree: usize,
+    pub clustering: f64,
+    pub betweenness: f64,
+    pub closeness: f64,
+    pub pagerank: f64,
+}
+
+/// Every measure `MetricsCapability` knows, taken over the whole graph at once.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetricsReport {
+    /// How many tiles there are of each degree.
+    pub degree_distribution: BTreeMap<usize, usize>,
+    pub average_clustering: f64,
+    pub tiles: BTreeMap<EntityId, TileMetrics>,
+}
+
+/// The graph the traversals work on, with arrows between the same two tiles counted once
+/// and arrows from a tile to itself left out.
+struct MetricsGraph {
+    vertices: Vec<EntityId>,
+    outgoing: HashMap<EntityId, Vec<EntityId>>,
+    incoming: HashMap<EntityId, Vec<EntityId>>,
+}
+
+impl MetricsGraph {
+    fn of(mosaic: &Arc<Mosaic>) -> Self {
+        let (vertices, edges) = mosaic.traversal_graph();
+        let mut outgoing: HashMap<EntityId, Vec<EntityId>> = HashMap::new();
+        let mut incoming: HashMap<EntityId, Vec<EntityId>> = HashMap::new();
+        for v in &vertices {
+            let targets = edges[v].iter().filter(|t| *t != v).unique().copied();
+            for target in targets {
+                outgoing.entry(*v).or_default().push(target);
+                incoming.entry(target).or_default().push(*v);
+            }
+        }
+
+        MetricsGraph {
+            vertices,
+            outgoing,
+            incoming,
+        }
+    }
+
+    fn out_of(&self, v: EntityId) -> &[EntityId] {
+        self.outgoing.get(&v).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn in_of(&self, v: EntityId) -> &[EntityId] {
+        self.incoming.get(&v).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn neighbors(&self, v: EntityId) -> HashSet<EntityId> {
+        self.out_of(v)
+            .iter()
+            .chain(self.in_of(v))
+            .copied()
+            .collect()
+    }
+
+    fn degree(&self, v: EntityId) -> usize {
+        self.out_of(v).len() + self.in_of(v).len()
+    }
+
+    fn clustering(&self, v: EntityId) -> f64 {
+        let neighbors = self.neighbors(v);
+        let k = neighbors.len();
+        if k < 2 {
+            return 0.0;
+        }
+
+        let links = neighbors
+            .iter()
+            .tuple_combinations()
+            .filter(|(a, b)| self.out_of(**a).contains(b) || self.out_of(**b).contains(a))
+            .count();
+        2.0 * links as f64 / (k * (k - 1)) as f64
+    }
+
+    /// Brandes' algorithm, following arrows in their direction.
+    fn betweenness(&self) -> HashMap<EntityId, f64> {
+        let mut centrality: HashMap<EntityId, f64> =
+            self.vertices.iter().map(|v| (*v, 0.0)).collect();
+
+        for source in &self.vertices {
+            let mut order = vec![];
+            let mut predecessors: HashMap<EntityId, Vec<EntityId>> = HashMap::new();
+            let mut paths: HashMap<EntityId, f64> = HashMap::from([(*source, 1.0)]);
+            let mut distance: HashMap<EntityId, usize> = HashMap::from([(*source, 0)]);
+            let mut queue = VecDeque::from([*source]);
+
+            while let Some(v) = queue.pop_front() {
+                order.push(v);
+                for w in self.out_of(v) {
+                    if !distance.contains_key(w) {
+                        distance.insert(*w, distance[&v] + 1);
+                        queue.push_back(*w);
+                    }
+                    if distance[w] == distance[&v] + 1 {
+                        *paths.entry(*w).or_default() += paths[&v];
+                        predecessors.entry(*w).or_default().push(v);
+                    }
+                }
+            }
+
+            let mut dependency: HashMap<EntityId, f64> = HashMap::new();
+            for w in order.into_iter().rev() {
+                for v in predecessors.get(&w).into_iter().flatten() {
+                    let share = paths[v] / paths[&w] * (1.0 + dependency.get(&w).unwrap_or(&0.0));
+                    *dependency.entry(*v).or_default() += share;
+                }
+                if w != *source {
+                    *centrality.get_mut(&w).unwrap() += dependency.get(&w).unwrap_or(&0.0);
+                }
+            }
+        }
+
+        centrality
+    }
+
+    /// How close `v` is to the tiles it reaches, scaled by how many of them it reaches
+    /// (Wasserman and Faust), so tiles reaching only a few close ones don't come out on top.
+    fn closeness(&self, v: EntityId) -> f64 {
+        let mut distance: HashMap<EntityId, usize> = HashMap::from([(v, 0)]);
+        let mut queue = VecDeque::from([v]);
+        while let Some(u) = queue.pop_front() {
+            for w in self.out_of(u) {
+                if !distance.contains_key(w) {
+                    distance.insert(*w, distance[&u] + 1);
+                    queue.push_back(*w);
+                }
+            }
+        }
+
+        let reached = (distance.len() - 1) as f64;
+        let total: usize = distance.values().sum();
+        if total == 0 || self.vertices.len() < 2 {
+            return 0.0;
+        }
+        (reached / (self.vertices.len() - 1) as f64) * (reached / total as f64)
+    }
+
+    fn pagerank(&self, damping: f64) -> HashMap<EntityId, f64> {
+        const ITERATIONS: usize = 100;
+        const TOLERANCE: f64 = 1e-10;
+
+        let n = self.vertices.len() as f64;
+        let mut rank: HashMap<EntityId, f64> =
+            self.vertices.iter().map(|v| (*v, 1.0 / n)).collect();
+
+        for _ in 0..ITERATIONS {
+            // tiles with no way out hand their rank to everyone
+            let dangling: f64 = self
+                .vertices
+                .iter()
+                .filter(|v| self.out_of(**v).is_empty())
+                .map(|v| rank[v])
+                .sum();
+
+            let next: HashMap<EntityId, f64> = self
+                .vertices
+                .iter()
+                .map(|v| {
+                    let inflow: f64 = self
+                        .in_of(*v)
+                        .iter()
+                        .map(|u| rank[u] / self.out_of(*u).len() as f64)
+                        .sum();
+                    let r = (1.0 - damping) / n + damping * (inflow + dangling / n);
+                    (*v, r)
+                })
+                .collect();
+
+            let change: f64 = self
+                .vertices
+                .iter()
+                .map(|v| (next[v] - rank[v]).abs())
+                .sum();
+            rank = next;
+            if change < TOLERANCE {
+                break;
+            }
+        }
+
+        rank
+    }
+}
+
+/// Graph measures over the objects and arrows `TraversalCapability` walks; parallel arrows
+/// count once and self-loops not at all.
+pub trait MetricsCapability {
+    /// Arrows into and out of each tile.
+    fn degrees(&self) -> BTreeMap<EntityId, usize>;
+    /// How many tiles there are of each degree.
+    fn degree_distribution(&self) -> BTreeMap<usize, usize>;
+    /// How many of the pairs of a tile's neighbors are linked themselves, ignoring the
+    /// direction of arrows; `0` for tiles with fewer than two neighbors.
+    fn clustering_coefficients(&self) -> BTreeMap<EntityId, f64>;
+    /// How many shortest paths between other tiles go through each tile, not normalized.
+    fn betweenness_centrality(&self) -> BTreeMap<EntityId, f64>;
+    /// How close each tile is to the tiles it reaches over arrows, between `0` and `1`.
+    fn closeness_centrality(&self) -> BTreeMap<EntityId, f64>;
+    /// PageRank with the given damping, usually `0.85`; the ranks add up to `1`.
+    fn pagerank(&self, damping: f64) -> BTreeMap<EntityId, f64>;
+    /// Every measure at once, with a damping of `0.85` for PageRank.
+    fn metrics_report(&self) -> MetricsReport;
+    /// Puts the measures in `report` on its tiles as `Metrics` descriptors, one per tile,
+    /// replacing those from an earlier report. Tiles that are gone since are skipped.
+    fn attach_metrics(&self, report: &MetricsReport) -> Vec<Tile>;
+}
+
+impl MetricsCapability for Arc<Mosaic> {
+    fn degrees(&self) -> BTreeMap<EntityId, usize> {
+        let graph = MetricsGraph::of(self);
+        graph
+            .vertices
+            .iter()
+            .map(|v| (*v, graph.degree(*v)))
+            .collect()
+    }
+
+    fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        self.degrees().into_values().counts().into_iter().collect()
+    }
+
+    fn clustering_coefficients(&self) -> BTreeMap<EntityId, f64> {
+        let graph = MetricsGraph::of(self);
+        graph
+            .vertices
+            .iter()
+            .map(|v| (*v, graph.clustering(*v)))
+            .collect()
+    }
+
+    fn betweenness_centrality(&self) -> BTreeMap<EntityId, f64> {
+        MetricsGraph::of(self).betweenness().into_iter().collect()
+    }
+
+    fn closeness_centrality(&self) -> BTreeMap<EntityId, f64> {
+        let graph = MetricsGraph::of(self);
+        graph
+            .vertices
+            .iter()
+            .map(|v| (*v, graph.closeness(*v)))
+            .collect()
+    }
+
+    fn pagerank(&self, damping: f64) -> BTreeMap<EntityId, f64> {
+        MetricsGraph::of(self)
+            .pagerank(damping)
+            .into_iter()
+            .collect()
+    }
+
+    fn metrics_report(&self) -> MetricsReport {
+        let graph = MetricsGraph::of(self);
+        let betweenness = graph.betweenness();
+        let pagerank = graph.pagerank(0.85);
+        let tiles: BTreeMap<EntityId, TileMetrics> = graph
+            .vertices
+            .iter()
+            .map(|v| {
+                let metrics = TileMetrics {
+                    degree: graph.degree(*v),
+                    clustering: graph.clustering(*v),
+                    betweenness: betweenness[v],
+                    closeness: graph.closeness(*v),
+                    pagerank: pagerank[v],
+                };
+                (*v, metrics)
+            })
+            .collect();
+
+        let average_clustering = if tiles.is_empty() {
+            0.0
+        } else {
+            tiles.values().map(|m| m.clustering).sum::<f64>() / tiles.len() as f64
+        };
+
+        MetricsReport {
+            degree_distribution: tiles
+                .values()
+                .map(|m| m.degree)
+                .counts()
+                .into_iter()
+                .collect(),
+            average_clustering,
+            tiles,
+        }
+    }
+
+    fn attach_metrics(&self, report: &MetricsReport) -> Vec<Tile> {
+        self.new_type(
+            "Metrics: { degree: u64, clustering: f64, betweenness: f64, closeness: f64, pagerank: f64 };",
+        )
+        .unwrap();
+
+        let mut attached = vec![];
+        for (id, metrics) in &report.tiles {
+            let Some(tile) = self.get(*id) else {
+                continue;
+            };
+
+            for old in self.get_tiles_into_with(tile.id, "Metrics") {
+                self.delete_tile(old.id);
+            }
+
+            let fields = pars()
+                .set("degree", metrics.degree as u64)
+                .set("clustering", metrics.clustering)
+                .set("betweenness", metrics.betweenness)
+                .set("closeness", metrics.closeness)
+                .set("pagerank", metrics.pagerank)
+                .ok();
+            attached.push(self.new_descriptor(&tile, "Metrics", fields));
+        }
+
+        attached
+    }
+}
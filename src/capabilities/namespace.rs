@@ -0,0 +1,94 @@
+use std::{sync::Arc, vec::IntoIter};
+
+use itertools::Itertools;
+
+use crate::{
+    capabilities::ParentingCapability,
+    internals::{ComponentValues, Mosaic, MosaicIO, Tile},
+    iterators::query::MosaicQuery,
+};
+
+/// The subtree under a root tile, used as a scope for making and looking up objects. The
+/// root itself is not part of it.
+pub struct Scope {
+    mosaic: Arc<Mosaic>,
+    root: Tile,
+}
+
+impl Scope {
+    pub fn root(&self) -> &Tile {
+        &self.root
+    }
+
+    /// Makes an object and puts it right under the root.
+    pub fn new_object(&self, component: &str, defaults: ComponentValues) -> Tile {
+        let tile = self.mosaic.new_object(component, defaults);
+        // a fresh tile has no parent and can't be an ancestor of the root
+        self.mosaic.set_parent(&tile, &self.root).unwrap();
+        tile
+    }
+
+    /// Moves `tile` under the root, along with everything under it.
+    pub fn adopt(&self, tile: &Tile) -> anyhow::Result<()> {
+        self.mosaic.reparent(tile, &self.root)
+    }
+
+    /// A scope nested in this one, rooted at `parent`, which has to be inside this scope.
+    pub fn in_scope(&self, parent: &Tile) -> Option<Scope> {
+        self.contains(parent).then(|| self.mosaic.in_scope(parent))
+    }
+
+    pub fn contains(&self, tile: &Tile) -> bool {
+        self.mosaic.get_ancestors(tile).contains(&self.root)
+    }
+
+    /// Everything in the scope, breadth first.
+    pub fn get_all(&self) -> IntoIter<Tile> {
+        self.mosaic.get_descendants(&self.root)
+    }
+
+    pub fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile> {
+        self.get_all()
+            .filter(|t| t.component.is(component))
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// Runs a textual query, as `query_str` does, keeping only the tiles in this scope.
+    pub fn query_str(&self, query: &str) -> anyhow::Result<IntoIter<Tile>> {
+        Ok(self
+            .mosaic
+            .query_str(query)?
+            .filter(|t| self.contains(t))
+            .collect_vec()
+            .into_iter())
+    }
+}
+
+/// Scopes built on top of parenting, so a part of a mosaic (a pattern, a frame, a loaded
+/// document) can be worked with on its own.
+pub trait NamespaceCapability {
+    fn in_scope(&self, parent: &Tile) -> Scope;
+    /// The closest tile above `tile` that roots a scope `is_root` accepts.
+    fn scope_of<F>(&self, tile: &Tile, is_root: F) -> Option<Scope>
+    where
+        F: Fn(&Tile) -> bool;
+}
+
+impl NamespaceCapability for Arc<Mosaic> {
+    fn in_scope(&self, parent: &Tile) -> Scope {
+        Scope {
+            mosaic: Arc::clone(self),
+            root: parent.clone(),
+        }
+    }
+
+    fn scope_of<F>(&self, tile: &Tile, is_root: F) -> Option<Scope>
+    where
+        F: Fn(&Tile) -> bool,
+    {
+        self.get_ancestors(tile)
+            .find(|t| is_root(t))
+            .map(|root| self.in_scope(&root))
+    }
+}
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use crate::internals::{Mosaic, OperationLog};
+
+pub trait OperationLogCapability {
+    /// Starts logging every change, starting over from the tiles already here, so the log
+    /// can be replayed onto an empty mosaic.
+    fn start_operation_log(&self);
+    /// Stops logging and hands over what was logged.
+    fn stop_operation_log(&self) -> Option<OperationLog>;
+    /// What was logged so far, if logging is on.
+    fn operation_log(&self) -> Option<OperationLog>;
+}
+
+impl OperationLogCapability for Arc<Mosaic> {
+    fn start_operation_log(&self) {
+        *self.operation_log.lock().unwrap() = Some(OperationLog::default());
+        self.log_all_tiles();
+    }
+
+    fn stop_operation_log(&self) -> Option<OperationLog> {
+        self.operation_log.lock().unwrap().take()
+    }
+
+    fn operation_log(&self) -> Option<OperationLog> {
+        self.operation_log.lock().unwrap().clone()
+    }
+}
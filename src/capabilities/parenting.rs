@@ -0,0 +1,154 @@
+use std::{collections::HashSet, sync::Arc, vec::IntoIter};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::internals::{
+    par, Mosaic, MosaicCRUD, MosaicIndices, MosaicTypelevelCRUD, Tile, TileFieldSetter,
+};
+
+/// Trees of tiles, kept as `Parent` arrows going from each child to its parent. Each arrow
+/// holds the child's place among its siblings, so the order is saved along with the tiles.
+pub trait ParentingCapability {
+    /// Fails if `child` already has a parent, or if it would end up its own ancestor.
+    fn set_parent(&self, child: &Tile, parent: &Tile) -> anyhow::Result<()>;
+    /// Moves `child` under `new_parent`, whether it had a parent before or not.
+    fn reparent(&self, child: &Tile, new_parent: &Tile) -> anyhow::Result<()>;
+    fn unparent(&self, child: &Tile);
+    fn get_parent(&self, child: &Tile) -> Option<Tile>;
+    /// The children in the order they were added.
+    fn get_children(&self, parent: &Tile) -> IntoIter<Tile>;
+    /// The children in sibling order: as added, unless moved with `set_child_index`.
+    fn get_children_ordered(&self, parent: &Tile) -> IntoIter<Tile>;
+    /// Moves `child` to position `index` among its siblings, or last if `index` is past the end.
+    fn set_child_index(&self, child: &Tile, index: usize) -> anyhow::Result<()>;
+    /// The parent, its parent, and so on up to the root.
+    fn get_ancestors(&self, tile: &Tile) -> IntoIter<Tile>;
+    /// The children, their children, and so on, breadth first.
+    fn get_descendants(&self, tile: &Tile) -> IntoIter<Tile>;
+}
+
+fn parent_arrow(child: &Tile) -> Option<Tile> {
+    child.mosaic.get_tiles_from_with(child.id, "Parent").next()
+}
+
+impl ParentingCapability for Arc<Mosaic> {
+    fn set_parent(&self, child: &Tile, parent: &Tile) -> anyhow::Result<()> {
+        self.new_type("Parent: u64;").unwrap();
+
+        if let Some(current) = self.get_parent(child) {
+            return Err(anyhow!(
+                "Tile {} already has parent {}, reparent it instead",
+                child.id,
+                current.id
+            ));
+        }
+
+        if child == parent || self.get_ancestors(parent).contains(child) {
+            return Err(anyhow!(
+                "Cannot put tile {} under {}, it would become its own ancestor",
+                child.id,
+                parent.id
+            ));
+        }
+
+        // new children go last
+        let place = self
+            .get_tiles_into_with(parent.id, "Parent")
+            .map(|arrow| arrow.get("self").as_u64() + 1)
+            .max()
+            .unwrap_or(0);
+        self.new_arrow(child, parent, "Parent", par(place));
+        Ok(())
+    }
+
+    fn reparent(&self, child: &Tile, new_parent: &Tile) -> anyhow::Result<()> {
+        if child == new_parent || self.get_ancestors(new_parent).contains(child) {
+            return Err(anyhow!(
+                "Cannot move tile {} under {}, it would become its own ancestor",
+                child.id,
+                new_parent.id
+            ));
+        }
+
+        self.unparent(child);
+        self.set_parent(child, new_parent)
+    }
+
+    fn unparent(&self, child: &Tile) {
+        if let Some(arrow) = parent_arrow(child) {
+            self.delete_tile(arrow);
+        }
+    }
+
+    fn get_parent(&self, child: &Tile) -> Option<Tile> {
+        parent_arrow(child).map(|arrow| arrow.target())
+    }
+
+    fn get_children(&self, parent: &Tile) -> IntoIter<Tile> {
+        self.get_tiles_into_with(parent.id, "Parent")
+            .sorted_by_key(|arrow| arrow.id)
+            .map(|arrow| arrow.source())
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn get_children_ordered(&self, parent: &Tile) -> IntoIter<Tile> {
+        self.get_tiles_into_with(parent.id, "Parent")
+            .sorted_by_key(|arrow| (arrow.get("self").as_u64(), arrow.id))
+            .map(|arrow| arrow.source())
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn set_child_index(&self, child: &Tile, index: usize) -> anyhow::Result<()> {
+        let parent = self
+            .get_parent(child)
+            .ok_or_else(|| anyhow!("Tile {} has no parent to be ordered under", child.id))?;
+
+        let mut siblings = self
+            .get_children_ordered(&parent)
+            .filter(|t| t != child)
+            .collect_vec();
+        siblings.insert(index.min(siblings.len()), child.clone());
+
+        for (place, sibling) in siblings.iter().enumerate() {
+            if let Some(mut arrow) = parent_arrow(sibling) {
+                if arrow.get("self").as_u64() != place as u64 {
+                    arrow.set("self", place as u64);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_ancestors(&self, tile: &Tile) -> IntoIter<Tile> {
+        let mut ancestors: Vec<Tile> = vec![];
+        let mut current = self.get_parent(tile);
+        // a cycle can only come from arrows made by hand, but it shouldn't hang us
+        while let Some(parent) = current.filter(|p| !ancestors.contains(p)) {
+            current = self.get_parent(&parent);
+            ancestors.push(parent);
+        }
+
+        ancestors.into_iter()
+    }
+
+    fn get_descendants(&self, tile: &Tile) -> IntoIter<Tile> {
+        let mut descendants = vec![];
+        let mut visited = HashSet::from([tile.id]);
+        let mut index = 0;
+        descendants.extend(self.get_children(tile));
+
+        while index < descendants.len() {
+            let current = descendants[index].clone();
+            index += 1;
+            if visited.insert(current.id) {
+                descendants.extend(self.get_children(&current));
+            }
+        }
+
+        descendants.into_iter().unique().collect_vec().into_iter()
+    }
+}
@@ -0,0 +1,502 @@
+use std::{
+    cell::OnceCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{Arc, Mutex, Weak},
+};
+
+use itertools::Itertools;
+
+use crate::{
+    capabilities::tile_signature,
+    internals::{
+        par, pars, ComponentValuesBuilderSetter, EntityId, Mosaic, MosaicCRUD, MosaicIO,
+        MosaicObservable, MosaicObserver, MosaicTypelevelCRUD, SubscriptionId, Tile, TileType,
+        Value,
+    },
+    iterators::{
+        query::{has_component, matches_condition, QueryCondition, QueryLiteral},
+        tile_getters::TileGetters,
+    },
+};
+
+/// Maps each object and arrow of a pattern, by id, to the tile it was matched with.
+pub type PatternMatch = HashMap<EntityId, Tile>;
+
+/// What a pattern tile asks of the tiles it is matched with, read from its descriptors.
+enum MatchConstraint {
+    Component(String),
+    FieldEquals(QueryCondition),
+}
+
+fn field_condition(path: &str, value: &str) -> QueryCondition {
+    let (component, field) = match path.split_once('.') {
+        Some((component, field)) => (Some(component.to_string()), field.to_string()),
+        None => (None, path.to_string()),
+    };
+
+    let literal = match value.parse() {
+        Ok(number) => QueryLiteral::Number(number),
+        Err(_) if value.eq_ignore_ascii_case("true") => QueryLiteral::Bool(true),
+        Err(_) if value.eq_ignore_ascii_case("false") => QueryLiteral::Bool(false),
+        Err(_) => QueryLiteral::Text(value.to_string()),
+    };
+
+    QueryCondition {
+        component,
+        field,
+        operator: "=".to_string(),
+        literal,
+    }
+}
+
+fn constraints_of(tile: &Tile) -> Vec<MatchConstraint> {
+    tile.iter()
+        .get_descriptors()
+        .filter_map(|d| match d.component.to_string().as_str() {
+            "MatchComponent" => Some(MatchConstraint::Component(
+                d.get("self").as_s32().to_string(),
+            )),
+            "MatchFieldEquals" => Some(MatchConstraint::FieldEquals(field_condition(
+                &d.get("field").as_s32().to_string(),
+                &d.get("value").as_str(),
+            ))),
+            _ => None,
+        })
+        .collect_vec()
+}
+
+fn satisfies(tile: &Tile, constraints: &[MatchConstraint]) -> bool {
+    constraints.iter().all(|c| match c {
+        MatchConstraint::Component(component) => has_component(tile, component),
+        MatchConstraint::FieldEquals(condition) => matches_condition(tile, condition),
+    })
+}
+
+/// What the tiles of a pattern ask of the tiles they are matched with.
+#[derive(Clone, Copy, PartialEq)]
+enum MatchBy {
+    /// Whatever their match descriptors say.
+    Descriptors,
+    /// Nothing; only how objects and arrows connect counts.
+    Shape,
+    /// To look the same, down to their fields and descriptors, see `tile_signature`.
+    Content,
+}
+
+struct PatternNode {
+    id: EntityId,
+    constraints: Vec<MatchConstraint>,
+    signature: Option<String>,
+    out_degree: usize,
+    in_degree: usize,
+}
+
+struct PatternEdge {
+    id: EntityId,
+    source: EntityId,
+    target: EntityId,
+    constraints: Vec<MatchConstraint>,
+    signature: Option<String>,
+}
+
+struct Matcher {
+    target: Arc<Mosaic>,
+    nodes: Vec<PatternNode>,
+    edges: Vec<PatternEdge>,
+    /// All objects of the target, only gathered once a node has no bound neighbour to start from.
+    objects: OnceCell<Vec<Tile>>,
+    /// How many matches to look for before stopping.
+    limit: usize,
+}
+
+impl Matcher {
+    fn new(pattern: &Arc<Mosaic>, target: &Arc<Mosaic>) -> Self {
+        Self::matching_by(pattern, target, MatchBy::Descriptors)
+    }
+
+    fn matching_by(pattern: &Arc<Mosaic>, target: &Arc<Mosaic>, by: MatchBy) -> Self {
+        let constraints = |t: &Tile| match by {
+            MatchBy::Descriptors => constraints_of(t),
+            _ => vec![],
+        };
+        let signature = |t: &Tile| (by == MatchBy::Content).then(|| tile_signature(t));
+
+        let edges = pattern
+            .get_all()
+            .filter(|t| t.is_arrow())
+            .filter(|t| [t.source(), t.target()].iter().all(|e| e.is_object()))
+            .sorted_by_key(|t| t.id)
+            .map(|t| PatternEdge {
+                id: t.id,
+                source: t.source_id(),
+                target: t.target_id(),
+                constraints: constraints(&t),
+                signature: signature(&t),
+            })
+            .collect_vec();
+
+        let nodes = pattern
+            .get_all()
+            .filter(|t| t.is_object())
+            .sorted_by_key(|t| t.id)
+            .map(|node| PatternNode {
+                id: node.id,
+                constraints: constraints(&node),
+                signature: signature(&node),
+                out_degree: edges.iter().filter(|e| e.source == node.id).count(),
+                in_degree: edges.iter().filter(|e| e.target == node.id).count(),
+            })
+            .collect_vec();
+
+        Matcher {
+            target: Arc::clone(target),
+            nodes,
+            edges,
+            objects: OnceCell::new(),
+            limit: usize::MAX,
+        }
+    }
+
+    fn fits(&self, node: &PatternNode, tile: &Tile) -> bool {
+        tile.is_object()
+            && tile.iter().get_arrows_from().count() >= node.out_degree
+            && tile.iter().get_arrows_into().count() >= node.in_degree
+            && satisfies(tile, &node.constraints)
+            && node
+                .signature
+                .as_ref()
+                .is_none_or(|s| *s == tile_signature(tile))
+    }
+
+    /// The order to bind nodes in, starting with `first` and then always preferring a node
+    /// next to one already placed, so candidates can be taken from the neighbourhood.
+    fn order(&self, first: usize) -> Vec<usize> {
+        let mut order = vec![first];
+        while order.len() < self.nodes.len() {
+            let placed = order
+                .iter()
+                .map(|i| self.nodes[*i].id)
+                .collect::<HashSet<_>>();
+            let unplaced = (0..self.nodes.len()).filter(|i| !order.contains(i));
+            let next = unplaced
+                .clone()
+                .find(|i| {
+                    let id = self.nodes[*i].id;
+                    self.edges.iter().any(|e| {
+                        (e.source == id && placed.contains(&e.target))
+                            || (e.target == id && placed.contains(&e.source))
+                    })
+                })
+                .or_else(|| unplaced.min())
+                .unwrap();
+            order.push(next);
+        }
+
+        order
+    }
+
+    fn candidates(&self, node: &PatternNode, bound: &PatternMatch) -> Vec<Tile> {
+        let neighbours = self.edges.iter().find_map(|e| {
+            if e.source == node.id && bound.contains_key(&e.target) {
+                Some(bound[&e.target].iter().get_arrows_into().get_sources())
+            } else if e.target == node.id && bound.contains_key(&e.source) {
+                Some(bound[&e.source].iter().get_arrows_from().get_targets())
+            } else {
+                None
+            }
+        });
+
+        let pool = match neighbours {
+            Some(neighbours) => neighbours.collect_vec(),
+            None => self
+                .objects
+                .get_or_init(|| self.target.get_all().filter(|t| t.is_object()).collect())
+                .clone(),
+        };
+
+        pool.into_iter()
+            .unique_by(|t| t.id)
+            .sorted_by_key(|t| t.id)
+            .filter(|t| self.fits(node, t))
+            .collect_vec()
+    }
+
+    /// Every match, in the order of the bindings of the first pattern object.
+    fn all(&self) -> Vec<PatternMatch> {
+        let mut results = vec![];
+        if !self.nodes.is_empty() {
+            self.extend(
+                &self.order(0),
+                None,
+                &mut HashMap::new(),
+                &mut HashSet::new(),
+                &mut results,
+            );
+        }
+        results
+    }
+
+    /// Every match that binds some pattern object to `anchor`.
+    fn binding(&self, anchor: &Tile) -> Vec<PatternMatch> {
+        let mut results = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            if self.fits(node, anchor) {
+                let order = self.order(index);
+                self.extend(
+                    &order,
+                    Some(anchor),
+                    &mut HashMap::new(),
+                    &mut HashSet::new(),
+                    &mut results,
+                );
+            }
+        }
+        results
+    }
+
+    fn extend(
+        &self,
+        order: &[usize],
+        pinned: Option<&Tile>,
+        bound: &mut PatternMatch,
+        used: &mut HashSet<EntityId>,
+        results: &mut Vec<PatternMatch>,
+    ) {
+        let Some((index, rest)) = order.split_first() else {
+            results.push(bound.clone());
+            return;
+        };
+
+        let node = &self.nodes[*index];
+        let candidates = match pinned {
+            Some(tile) => vec![tile.clone()],
+            None => self.candidates(node, bound),
+        };
+
+        for candidate in candidates {
+            if results.len() >= self.limit {
+                return;
+            }
+            if used.contains(&candidate.id) {
+                continue;
+            }
+
+            bound.insert(node.id, candidate.clone());
+            used.insert(candidate.id);
+            // the edges whose other end got bound earlier, or that loop back onto this node
+            let closed = self
+                .edges
+                .iter()
+                .filter(|e| e.source == node.id || e.target == node.id)
+                .filter(|e| bound.contains_key(&e.source) && bound.contains_key(&e.target))
+                .collect_vec();
+            self.bind_edges(&closed, rest, bound, used, results);
+            used.remove(&candidate.id);
+            bound.remove(&node.id);
+        }
+    }
+
+    /// Binds each of `edges` to its own arrow between the bound endpoints, going the same way,
+    /// then moves on to the nodes in `order`.
+    fn bind_edges(
+        &self,
+        edges: &[&PatternEdge],
+        order: &[usize],
+        bound: &mut PatternMatch,
+        used: &mut HashSet<EntityId>,
+        results: &mut Vec<PatternMatch>,
+    ) {
+        let Some((edge, rest)) = edges.split_first() else {
+            self.extend(order, None, bound, used, results);
+            return;
+        };
+
+        let target = bound[&edge.target].id;
+        let arrows = bound[&edge.source]
+            .iter()
+            .get_arrows_from()
+            .filter(|a| a.target_id() == target && !used.contains(&a.id))
+            .filter(|a| satisfies(a, &edge.constraints))
+            .filter(|a| {
+                edge.signature
+                    .as_ref()
+                    .is_none_or(|s| *s == tile_signature(a))
+            })
+            .sorted_by_key(|a| a.id)
+            .collect_vec();
+
+        for arrow in arrows {
+            if results.len() >= self.limit {
+                return;
+            }
+            bound.insert(edge.id, arrow.clone());
+            used.insert(arrow.id);
+            self.bind_edges(rest, order, bound, used, results);
+            used.remove(&arrow.id);
+            bound.remove(&edge.id);
+        }
+    }
+}
+
+/// The first way of binding the objects and arrows between objects of `pattern` to those of
+/// `target`, each to a distinct one; with `by_content`, only to tiles that look the same.
+pub(crate) fn first_binding(
+    pattern: &Arc<Mosaic>,
+    target: &Arc<Mosaic>,
+    by_content: bool,
+) -> Option<PatternMatch> {
+    let by = match by_content {
+        true => MatchBy::Content,
+        false => MatchBy::Shape,
+    };
+    let mut matcher = Matcher::matching_by(pattern, target, by);
+    matcher.limit = 1;
+    matcher.all().pop()
+}
+
+/// The object a tile hangs off of: itself for objects, the source for arrows, and the
+/// subject for descriptors and extensions, followed until an object is reached.
+fn root_of(tile: &Tile) -> Option<Tile> {
+    let mut current = tile.clone();
+    loop {
+        let next = match current.tile_type {
+            TileType::Object => return Some(current),
+            TileType::Arrow { source, .. } => source,
+            TileType::Descriptor { subject } | TileType::Extension { subject } => subject,
+        };
+        current = tile.mosaic.get(next)?;
+    }
+}
+
+fn binds(m: &PatternMatch, id: EntityId) -> bool {
+    m.values().any(|t| t.id == id)
+}
+
+#[derive(Default)]
+struct IncrementalState {
+    matches: Vec<PatternMatch>,
+    /// Objects around which matches may have appeared since the last look.
+    dirty: BTreeSet<EntityId>,
+}
+
+/// The matches of a pattern, looked for again only around the objects that changed since
+/// they were last asked for.
+pub struct IncrementalMatch {
+    target: Weak<Mosaic>,
+    pattern: Arc<Mosaic>,
+    subscription: Mutex<Option<SubscriptionId>>,
+    state: Mutex<IncrementalState>,
+}
+
+impl IncrementalMatch {
+    /// The current matches, in the order they were found.
+    pub fn matches(&self) -> Vec<PatternMatch> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(target) = self.target.upgrade() {
+            let dirty = std::mem::take(&mut state.dirty);
+            let matcher = Matcher::new(&self.pattern, &target);
+            for anchor in dirty.into_iter().flat_map(|id| target.get(id)) {
+                for found in matcher.binding(&anchor) {
+                    if !state.matches.contains(&found) {
+                        state.matches.push(found);
+                    }
+                }
+            }
+        }
+
+        state.matches.clone()
+    }
+
+    /// Stops following changes; the matches stay as they were last seen.
+    pub fn stop(&self) {
+        if let (Some(target), Some(subscription)) = (
+            self.target.upgrade(),
+            self.subscription.lock().unwrap().take(),
+        ) {
+            target.unsubscribe(subscription);
+        }
+    }
+
+    fn invalidate(&self, tile: &Tile, deleted: bool) {
+        let root = root_of(tile);
+        let mut state = self.state.lock().unwrap();
+        state
+            .matches
+            .retain(|m| !binds(m, tile.id) && !root.as_ref().is_some_and(|r| binds(m, r.id)));
+
+        if let Some(root) = root {
+            if !(deleted && root.id == tile.id) {
+                state.dirty.insert(root.id);
+            }
+        }
+    }
+}
+
+impl MosaicObserver for IncrementalMatch {
+    fn on_tile_created(&self, tile: &Tile) {
+        self.invalidate(tile, false);
+    }
+
+    fn on_tile_deleted(&self, tile: &Tile) {
+        self.invalidate(tile, true);
+    }
+
+    fn on_field_changed(&self, tile: &Tile, _field: &str, _before: &Value, _after: &Value) {
+        self.invalidate(tile, false);
+    }
+}
+
+pub trait PatternMatchCapability {
+    /// Lets `pattern_tile` only match tiles that have `component`, be it their own, added with
+    /// `add_data`, or on a descriptor or extension.
+    fn match_component(&self, pattern_tile: &Tile, component: &str) -> Tile;
+    /// Lets `pattern_tile` only match tiles whose `field` equals `value`. The field is written
+    /// as `x` or `Position.x`; values that read as numbers or booleans are compared as such.
+    fn match_field_equals(&self, pattern_tile: &Tile, field: &str, value: &str) -> Tile;
+    /// Every binding of the objects and arrows of `pattern` to distinct ones of this mosaic
+    /// that satisfies its `MatchComponent` and `MatchFieldEquals` descriptors.
+    fn pattern_match(&self, pattern: &Arc<Mosaic>) -> Vec<PatternMatch>;
+    /// Like `pattern_match`, but keeps the matches up to date as this mosaic changes, until
+    /// `stop` is called on the result.
+    fn watch_pattern(&self, pattern: &Arc<Mosaic>) -> Arc<IncrementalMatch>;
+}
+
+impl PatternMatchCapability for Arc<Mosaic> {
+    fn match_component(&self, pattern_tile: &Tile, component: &str) -> Tile {
+        self.new_type("MatchComponent: s32;").unwrap();
+        self.new_descriptor(pattern_tile, "MatchComponent", par(component))
+    }
+
+    fn match_field_equals(&self, pattern_tile: &Tile, field: &str, value: &str) -> Tile {
+        self.new_type("MatchFieldEquals: { field: s32, value: str };")
+            .unwrap();
+        self.new_descriptor(
+            pattern_tile,
+            "MatchFieldEquals",
+            pars()
+                .set("field", field)
+                .set("value", value.to_string())
+                .ok(),
+        )
+    }
+
+    fn pattern_match(&self, pattern: &Arc<Mosaic>) -> Vec<PatternMatch> {
+        Matcher::new(pattern, self).all()
+    }
+
+    fn watch_pattern(&self, pattern: &Arc<Mosaic>) -> Arc<IncrementalMatch> {
+        let watch = Arc::new(IncrementalMatch {
+            target: Arc::downgrade(self),
+            pattern: Arc::clone(pattern),
+            subscription: Mutex::new(None),
+            state: Mutex::new(IncrementalState {
+                matches: self.pattern_match(pattern),
+                dirty: BTreeSet::new(),
+            }),
+        });
+
+        let subscription = self.subscribe(watch.clone());
+        *watch.subscription.lock().unwrap() = Some(subscription);
+        watch
+    }
+}
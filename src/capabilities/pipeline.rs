@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::{
+    internals::{
+        pars, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicTypelevelCRUD, Tile,
+    },
+    iterators::{component_selectors::ComponentSelectors, tile_getters::TileGetters},
+};
+
+use super::{ProcessCapability, ProcessStatus, TransformerRegistry};
+
+/// A chain of processes, linked by `ProcessLink` arrows from the result of one to the
+/// parameter of the next.
+pub struct Pipeline {
+    mosaic: Arc<Mosaic>,
+    processes: Vec<Tile>,
+}
+
+impl Pipeline {
+    /// Starts a pipeline with a single process running `transformer`.
+    pub fn new(mosaic: &Arc<Mosaic>, transformer: &str) -> Self {
+        mosaic
+            .new_type("ProcessLink: { result: s32, parameter: s32 };")
+            .unwrap();
+
+        Pipeline {
+            mosaic: Arc::clone(mosaic),
+            processes: vec![mosaic.make_process(transformer)],
+        }
+    }
+
+    /// Adds a process running `transformer`, with each `(result, parameter)` pair in `links`
+    /// passing a result of the previous process as a parameter of this one.
+    pub fn then(mut self, transformer: &str, links: &[(&str, &str)]) -> Self {
+        let process = self.mosaic.make_process(transformer);
+        let previous = self.last();
+        for (result, parameter) in links {
+            self.mosaic.new_arrow(
+                &previous,
+                &process,
+                "ProcessLink",
+                pars()
+                    .set("result", *result)
+                    .set("parameter", *parameter)
+                    .ok(),
+            );
+        }
+
+        self.processes.push(process);
+        self
+    }
+
+    pub fn processes(&self) -> &[Tile] {
+        &self.processes
+    }
+
+    /// The first process, which takes the parameters of the whole pipeline.
+    pub fn first(&self) -> Tile {
+        self.processes.first().cloned().unwrap()
+    }
+
+    /// The last process, which holds the results of the whole pipeline.
+    pub fn last(&self) -> Tile {
+        self.processes.last().cloned().unwrap()
+    }
+
+    /// Runs the processes one after another, stopping at the first one that doesn't finish,
+    /// and returns the status of the last one that ran.
+    pub fn run(&self, registry: &TransformerRegistry) -> anyhow::Result<ProcessStatus> {
+        let mut status = ProcessStatus::Pending;
+        for process in &self.processes {
+            status = self.mosaic.run_process(registry, process)?.wait();
+            if status != ProcessStatus::Done {
+                break;
+            }
+
+            for link in process
+                .iter()
+                .get_arrows_from()
+                .include_component("ProcessLink")
+            {
+                let result = link.get("result").as_s32().to_string();
+                let parameter = link.get("parameter").as_s32().to_string();
+                if let Some(tile) = self.mosaic.get_process_result(process, &result) {
+                    self.mosaic
+                        .add_process_parameter(&link.target(), &parameter, &tile);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "plugins")]
+use std::sync::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::internals::{Mosaic, MosaicTransaction, MosaicTypelevelCRUD, Tile};
+
+/// A transformer from outside this crate, registered with a `PluginRegistry` in code or
+/// loaded from a dynamic library with the `plugins` feature.
+pub trait MosaicPlugin: Send + Sync {
+    fn name(&self) -> &str;
+    /// Definitions of the component types the plugin works with, e.g. `"Weight: f32;"`; they
+    /// are added to the mosaic before the plugin first runs on it.
+    fn required_types(&self) -> Vec<String> {
+        vec![]
+    }
+    fn run(&self, mosaic: &Arc<Mosaic>, tile: &Tile) -> anyhow::Result<()>;
+}
+
+/// The entry point of a plugin library, a `#[no_mangle] pub fn mosaic_plugins(registry:
+/// &PluginRegistry)` built with the same compiler and crate version as the host.
+#[cfg(feature = "plugins")]
+pub const PLUGIN_ENTRY_POINT: &[u8] = b"mosaic_plugins";
+
+/// The plugins that can be run, by name.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: RwLock<HashMap<String, Arc<dyn MosaicPlugin>>>,
+    // dropped after the plugins, as their code lives in these
+    #[cfg(feature = "plugins")]
+    libraries: Mutex<Vec<libloading::Library>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `plugin` under its name, replacing any plugin registered under it before.
+    pub fn register<P: MosaicPlugin + 'static>(&self, plugin: P) {
+        self.plugins
+            .write()
+            .unwrap()
+            .insert(plugin.name().to_string(), Arc::new(plugin));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn MosaicPlugin>> {
+        self.plugins.read().unwrap().get(name).cloned()
+    }
+
+    /// The names of all registered plugins, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.plugins
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// Loads the library at `path` and lets its `PLUGIN_ENTRY_POINT` register its plugins.
+    ///
+    /// # Safety
+    ///
+    /// Runs the library's initialisation code, and can't check the entry point's signature.
+    #[cfg(feature = "plugins")]
+    pub unsafe fn load_library<P: AsRef<std::ffi::OsStr>>(&self, path: P) -> anyhow::Result<()> {
+        let library = libloading::Library::new(path)?;
+        {
+            let entry = library.get::<fn(&PluginRegistry)>(PLUGIN_ENTRY_POINT)?;
+            (*entry)(self);
+        }
+        self.libraries.lock().unwrap().push(library);
+        Ok(())
+    }
+}
+
+pub trait PluginCapability {
+    /// Runs the plugin `name` on `tile`, after adding the types it requires. The plugin runs
+    /// in a transaction, so if it fails, none of its changes are kept.
+    fn run_plugin(&self, registry: &PluginRegistry, name: &str, tile: &Tile) -> anyhow::Result<()>;
+}
+
+impl PluginCapability for Arc<Mosaic> {
+    fn run_plugin(&self, registry: &PluginRegistry, name: &str, tile: &Tile) -> anyhow::Result<()> {
+        let plugin = registry
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no plugin named {}", name))?;
+        for definition in plugin.required_types() {
+            self.new_type(&definition)?;
+        }
+
+        self.transaction(|mosaic| plugin.run(mosaic, tile))
+    }
+}
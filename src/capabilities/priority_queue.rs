@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::{
+    internals::{
+        pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+        MosaicTypelevelCRUD, Tile, Value,
+    },
+    iterators::{component_selectors::ComponentSelectors, tile_getters::TileGetters},
+};
+
+use super::ArchetypeSubject;
+
+/// Hands out enqueued tiles highest `component.field` first, read from the tile or its
+/// `component` descriptor; ties come out in order and tiles without the field come last.
+pub trait PriorityQueueCapability {
+    fn make_priority_queue(&self, component: &str, field: &str) -> Tile;
+    fn is_priority_queue_empty(&self, q: &Tile) -> bool;
+    fn priority_queue_len(&self, q: &Tile) -> usize;
+    fn enqueue_by_priority(&self, q: &Tile, v: &Tile);
+    fn dequeue_by_priority(&self, q: &Tile) -> Option<Tile>;
+    fn peek_by_priority(&self, q: &Tile) -> Option<Tile>;
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::I8(v) => Some(*v as f64),
+        Value::I16(v) => Some(*v as f64),
+        Value::I32(v) => Some(*v as f64),
+        Value::I64(v) => Some(*v as f64),
+        Value::U8(v) => Some(*v as f64),
+        Value::U16(v) => Some(*v as f64),
+        Value::U32(v) => Some(*v as f64),
+        Value::U64(v) => Some(*v as f64),
+        Value::F32(v) => Some(*v as f64),
+        Value::F64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn priority_of(tile: &Tile, component: &str, field: &str) -> Option<f64> {
+    let fields = match tile.get_data(component) {
+        Some(fields) => fields,
+        None => tile.get_component(component)?.get_data(component)?,
+    };
+
+    fields
+        .into_iter()
+        .find(|(name, _)| name.to_string() == field)
+        .and_then(|(_, value)| as_number(&value))
+}
+
+/// The arrow holding the next tile to dequeue, if any.
+fn next_by_priority(q: &Tile) -> Option<Tile> {
+    let queue = q.get_component("PriorityQueue")?;
+    let component = queue.get("component").as_s32().to_string();
+    let field = queue.get("field").as_s32().to_string();
+
+    queue
+        .iter()
+        .get_arrows_from()
+        .include_component("PriorityEnqueued")
+        .map(|arrow| {
+            let priority = priority_of(&arrow.target(), &component, &field);
+            (arrow, priority.unwrap_or(f64::NEG_INFINITY))
+        })
+        // ties go to the arrow made first, which has the lowest id
+        .sorted_by(|(a, p), (b, q)| q.total_cmp(p).then(a.id.cmp(&b.id)))
+        .map(|(arrow, _)| arrow)
+        .next()
+}
+
+impl PriorityQueueCapability for Arc<Mosaic> {
+    fn make_priority_queue(&self, component: &str, field: &str) -> Tile {
+        self.new_type("PriorityQueue: { component: s32, field: s32 };")
+            .unwrap();
+        self.new_type("PriorityEnqueued: unit;").unwrap();
+
+        self.new_object(
+            "PriorityQueue",
+            pars().set("component", component).set("field", field).ok(),
+        )
+    }
+
+    fn is_priority_queue_empty(&self, q: &Tile) -> bool {
+        self.priority_queue_len(q) == 0
+    }
+
+    fn priority_queue_len(&self, q: &Tile) -> usize {
+        q.iter()
+            .get_arrows_from()
+            .include_component("PriorityEnqueued")
+            .count()
+    }
+
+    fn enqueue_by_priority(&self, q: &Tile, v: &Tile) {
+        let queue = q
+            .get_component("PriorityQueue")
+            .expect("No PriorityQueue found");
+        self.new_arrow(&queue, v, "PriorityEnqueued", void());
+    }
+
+    fn dequeue_by_priority(&self, q: &Tile) -> Option<Tile> {
+        next_by_priority(q).map(|arrow| {
+            let tile = arrow.target();
+            self.delete_tile(arrow);
+            tile
+        })
+    }
+
+    fn peek_by_priority(&self, q: &Tile) -> Option<Tile> {
+        next_by_priority(q).map(|arrow| arrow.target())
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, RwLock,
+    },
+    thread::JoinHandle,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::{
+    internals::{
+        par, pars, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD,
+        Tile, TileFieldSetter, S32,
+    },
+    iterators::{component_selectors::ComponentSelectors, tile_getters::TileGetters},
+};
+
+use super::ArchetypeSubject;
+
+/// Where a process is at, as kept in its `ProcessStatus` descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessStatus {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+}
+
+/// Sent by a running process every time its progress or status changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessUpdate {
+    pub status: ProcessStatus,
+    pub progress: f32,
+}
+
+/// The body of a process; it reads its parameters from the context, and writes its results
+/// and progress back through it.
+pub type Transformer = Arc<dyn Fn(&ProcessContext) -> anyhow::Result<()> + Send + Sync>;
+
+/// The parameters a transformer expects and the results it promises, by name and component;
+/// processes are checked against it before and after running.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransformerSignature {
+    pub parameters: Vec<(String, String)>,
+    pub results: Vec<(String, String)>,
+}
+
+impl TransformerSignature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parameter(mut self, name: &str, component: &str) -> Self {
+        self.parameters
+            .push((name.to_string(), component.to_string()));
+        self
+    }
+
+    pub fn result(mut self, name: &str, component: &str) -> Self {
+        self.results.push((name.to_string(), component.to_string()));
+        self
+    }
+}
+
+/// The transformers processes can be run with, by name.
+#[derive(Default)]
+pub struct TransformerRegistry {
+    transformers: RwLock<HashMap<String, (Transformer, TransformerSignature)>>,
+}
+
+impl TransformerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a transformer that takes and gives anything.
+    pub fn register<F>(&self, name: &str, transformer: F)
+    where
+        F: Fn(&ProcessContext) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        self.register_with_signature(name, TransformerSignature::new(), transformer);
+    }
+
+    pub fn register_with_signature<F>(
+        &self,
+        name: &str,
+        signature: TransformerSignature,
+        transformer: F,
+    ) where
+        F: Fn(&ProcessContext) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        self.transformers
+            .write()
+            .unwrap()
+            .insert(name.to_string(), (Arc::new(transformer), signature));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Transformer> {
+        self.transformers
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|(transformer, _)| Arc::clone(transformer))
+    }
+
+    pub fn signature(&self, name: &str) -> Option<TransformerSignature> {
+        self.transformers
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|(_, signature)| signature.clone())
+    }
+
+    /// The names of all registered transformers, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.transformers
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .sorted()
+            .collect()
+    }
+}
+
+/// What a transformer sees of the process it runs for.
+pub struct ProcessContext {
+    pub mosaic: Arc<Mosaic>,
+    pub process: Tile,
+    cancelled: Arc<AtomicBool>,
+    updates: Sender<ProcessUpdate>,
+}
+
+impl ProcessContext {
+    pub fn parameter(&self, name: &str) -> Option<Tile> {
+        self.mosaic.get_process_parameter(&self.process, name)
+    }
+
+    pub fn set_result(&self, name: &str, result: &Tile) {
+        self.mosaic.set_process_result(&self.process, name, result);
+    }
+
+    /// Records how far along the process is, from `0.0` to `1.0`.
+    pub fn report_progress(&self, progress: f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        set_status(&self.process, &ProcessStatus::Running, progress);
+        let _ = self.updates.send(ProcessUpdate {
+            status: ProcessStatus::Running,
+            progress,
+        });
+    }
+
+    /// Whether the process was asked to stop; long transformers should check this now and then.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// A process running on a worker thread.
+pub struct ProcessHandle {
+    pub process: Tile,
+    cancelled: Arc<AtomicBool>,
+    updates: Receiver<ProcessUpdate>,
+    worker: JoinHandle<()>,
+}
+
+impl ProcessHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Progress updates in the order they happened; the last one carries the final status.
+    pub fn updates(&self) -> &Receiver<ProcessUpdate> {
+        &self.updates
+    }
+
+    /// Blocks until the process is done and returns how it ended.
+    pub fn wait(self) -> ProcessStatus {
+        let _ = self.worker.join();
+        self.process.mosaic.get_process_status(&self.process)
+    }
+}
+
+fn status_descriptor(process: &Tile) -> Tile {
+    process
+        .get_component("ProcessStatus")
+        .expect("Process has no status, panicking!")
+}
+
+fn set_status(process: &Tile, status: &ProcessStatus, progress: f32) {
+    let (state, message) = match status {
+        ProcessStatus::Pending => ("Pending", ""),
+        ProcessStatus::Running => ("Running", ""),
+        ProcessStatus::Done => ("Done", ""),
+        ProcessStatus::Failed(message) => ("Failed", message.as_str()),
+    };
+
+    let mut descriptor = status_descriptor(process);
+    descriptor.set("state", S32::from(state));
+    descriptor.set("progress", progress);
+    descriptor.set("message", message.to_string());
+}
+
+/// Checks that every declared `(name, component)` is bound to a tile with that component.
+fn check_declared(
+    kind: &str,
+    declared: &[(String, String)],
+    get: impl Fn(&str) -> Option<Tile>,
+) -> anyhow::Result<()> {
+    for (name, component) in declared {
+        match get(name) {
+            Some(tile) if tile.has_component(component) => {}
+            Some(tile) => {
+                return Err(anyhow!(
+                    "The {} {} should be a {}, but tile {} is not",
+                    kind,
+                    name,
+                    component,
+                    tile.id
+                ))
+            }
+            None => return Err(anyhow!("The {} {} is missing", kind, name)),
+        }
+    }
+
+    Ok(())
+}
+
+pub trait ProcessCapability {
+    /// Makes a pending process that runs the transformer registered under `transformer`.
+    fn make_process(&self, transformer: &str) -> Tile;
+    /// Binds the parameter `name` of `process` to `value`, replacing what it was bound to before.
+    fn add_process_parameter(&self, process: &Tile, name: &str, value: &Tile);
+    fn get_process_parameter(&self, process: &Tile, name: &str) -> Option<Tile>;
+    /// Points the result `name` of `process` at `result`, replacing what it pointed at before.
+    fn set_process_result(&self, process: &Tile, name: &str, result: &Tile);
+    fn get_process_result(&self, process: &Tile, name: &str) -> Option<Tile>;
+    fn get_process_status(&self, process: &Tile) -> ProcessStatus;
+    fn get_process_progress(&self, process: &Tile) -> f32;
+    /// Runs the process on a worker thread; fails if its transformer isn't registered, its
+    /// parameters don't fit, or it is already running.
+    fn run_process(
+        &self,
+        registry: &TransformerRegistry,
+        process: &Tile,
+    ) -> anyhow::Result<ProcessHandle>;
+}
+
+impl ProcessCapability for Arc<Mosaic> {
+    fn make_process(&self, transformer: &str) -> Tile {
+        self.new_type("Process: s32;").unwrap();
+        self.new_type("ProcessParameter: s32;").unwrap();
+        self.new_type("ProcessResult: s32;").unwrap();
+        self.new_type("ProcessStatus: { state: s32, progress: f32, message: str };")
+            .unwrap();
+
+        let process = self.new_object("Process", par(transformer));
+        self.new_descriptor(
+            &process,
+            "ProcessStatus",
+            pars()
+                .set("state", "Pending")
+                .set("progress", 0.0f32)
+                .set("message", String::new())
+                .ok(),
+        );
+        process
+    }
+
+    fn add_process_parameter(&self, process: &Tile, name: &str, value: &Tile) {
+        if let Some(old) = process
+            .iter()
+            .get_arrows_from()
+            .include_component("ProcessParameter")
+            .find(|a| a.get("self").as_s32() == name.into())
+        {
+            self.delete_tile(old);
+        }
+
+        self.new_arrow(process, value, "ProcessParameter", par(name));
+    }
+
+    fn get_process_parameter(&self, process: &Tile, name: &str) -> Option<Tile> {
+        process
+            .iter()
+            .get_arrows_from()
+            .include_component("ProcessParameter")
+            .find(|a| a.get("self").as_s32() == name.into())
+            .map(|a| a.target())
+    }
+
+    fn set_process_result(&self, process: &Tile, name: &str, result: &Tile) {
+        if let Some(old) = process
+            .iter()
+            .get_arrows_from()
+            .include_component("ProcessResult")
+            .find(|a| a.get("self").as_s32() == name.into())
+        {
+            self.delete_tile(old);
+        }
+
+        self.new_arrow(process, result, "ProcessResult", par(name));
+    }
+
+    fn get_process_result(&self, process: &Tile, name: &str) -> Option<Tile> {
+        process
+            .iter()
+            .get_arrows_from()
+            .include_component("ProcessResult")
+            .find(|a| a.get("self").as_s32() == name.into())
+            .map(|a| a.target())
+    }
+
+    fn get_process_status(&self, process: &Tile) -> ProcessStatus {
+        let descriptor = status_descriptor(process);
+        match descriptor.get("state").as_s32().to_string().as_str() {
+            "Running" => ProcessStatus::Running,
+            "Done" => ProcessStatus::Done,
+            "Failed" => ProcessStatus::Failed(descriptor.get("message").as_str()),
+            _ => ProcessStatus::Pending,
+        }
+    }
+
+    fn get_process_progress(&self, process: &Tile) -> f32 {
+        status_descriptor(process).get("progress").as_f32()
+    }
+
+    fn run_process(
+        &self,
+        registry: &TransformerRegistry,
+        process: &Tile,
+    ) -> anyhow::Result<ProcessHandle> {
+        let name = process.get("self").as_s32().to_string();
+        let (transformer, signature) = registry
+            .get(&name)
+            .zip(registry.signature(&name))
+            .ok_or_else(|| anyhow!("No transformer named {} is registered", name))?;
+        if self.get_process_status(process) == ProcessStatus::Running {
+            return Err(anyhow!("Process {} is already running", process.id));
+        }
+        check_declared("parameter", &signature.parameters, |name| {
+            self.get_process_parameter(process, name)
+        })?;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, updates) = channel();
+        set_status(process, &ProcessStatus::Running, 0.0);
+
+        let context = ProcessContext {
+            mosaic: Arc::clone(self),
+            process: process.clone(),
+            cancelled: Arc::clone(&cancelled),
+            updates: sender,
+        };
+
+        let worker = std::thread::spawn(move || {
+            let outcome = catch_unwind(AssertUnwindSafe(|| {
+                transformer(&context)?;
+                check_declared("result", &signature.results, |name| {
+                    context.mosaic.get_process_result(&context.process, name)
+                })
+            }));
+            let progress = context.mosaic.get_process_progress(&context.process);
+            let (status, progress) = match outcome {
+                _ if context.is_cancelled() => {
+                    (ProcessStatus::Failed("cancelled".to_string()), progress)
+                }
+                Ok(Ok(())) => (ProcessStatus::Done, 1.0),
+                Ok(Err(e)) => (ProcessStatus::Failed(e.to_string()), progress),
+                Err(_) => (ProcessStatus::Failed("panicked".to_string()), progress),
+            };
+
+            set_status(&context.process, &status, progress);
+            let _ = context.updates.send(ProcessUpdate { status, progress });
+        });
+
+        Ok(ProcessHandle {
+            process: process.clone(),
+            cancelled,
+            updates,
+            worker,
+        })
+    }
+}
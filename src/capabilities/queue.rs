@@ -1,7 +1,12 @@
 use std::sync::Arc;
 
+use anyhow::anyhow;
+
 use crate::{
-    internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, Tile},
+    internals::{
+        pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+        MosaicTypelevelCRUD, Tile,
+    },
     iterators::{
         component_selectors::ComponentSelectors, tile_deletion::TileDeletion,
         tile_getters::TileGetters,
@@ -10,10 +15,25 @@ use crate::{
 
 use super::ArchetypeSubject;
 
+/// What a bounded queue does with a tile enqueued while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOverflow {
+    /// The new tile is turned away and the queue stays as it is.
+    Reject,
+    /// The oldest tile is dequeued to make room for the new one.
+    Overwrite,
+}
+
 pub trait QueueCapability {
     fn make_queue(&self) -> Tile;
+    /// A queue that holds at most `capacity` tiles, with `overflow` deciding what happens after.
+    fn make_bounded_queue(&self, capacity: usize, overflow: QueueOverflow) -> Tile;
     fn is_queue_empty(&self, q: &Tile) -> bool;
+    fn queue_len(&self, q: &Tile) -> usize;
     fn enqueue(&self, q: &Tile, v: &Tile);
+    /// Like `enqueue`, but returns an error instead of panicking when a bounded queue rejects
+    /// the tile; returns the tile that was dropped to make room, if any.
+    fn try_enqueue(&self, q: &Tile, v: &Tile) -> anyhow::Result<Option<Tile>>;
     fn dequeue(&self, q: &Tile) -> Option<Tile>;
     fn peek_queue(&self, q: &Tile) -> Option<Tile>;
 }
@@ -23,6 +43,7 @@ pub trait PrivateQueueCapability {
     fn get_prev_from_end_in_queue(&self, queue: &Tile) -> Option<Tile>;
     fn get_prev_from_queue(&self, stop: &Tile) -> Option<Tile>;
     fn get_sentinel_in_queue(&self, queue: &Tile) -> Tile;
+    fn enqueue_unbounded(&self, q: &Tile, v: &Tile);
 }
 
 impl PrivateQueueCapability for Arc<Mosaic> {
@@ -51,6 +72,23 @@ impl PrivateQueueCapability for Arc<Mosaic> {
     fn get_sentinel_in_queue(&self, queue: &Tile) -> Tile {
         queue.get_component("QueueSentinel").unwrap()
     }
+
+    fn enqueue_unbounded(&self, q: &Tile, v: &Tile) {
+        if let Some(queue) = q.get_component("Queue") {
+            if let Some(next) = self.get_next_in_queue(q) {
+                let old_enq_arrows = next.iter().get_arrows_into().include_component("Enqueued");
+
+                self.new_arrow(&queue, v, "Enqueued", void());
+                self.new_arrow(v, &next, "Enqueued", void());
+
+                old_enq_arrows.delete();
+            } else {
+                panic!("No next element found in queue - tail potentially lost");
+            }
+        } else {
+            panic!("No Queue found");
+        }
+    }
 }
 
 pub type QueueTile = Tile;
@@ -58,6 +96,8 @@ pub type QueueTile = Tile;
 impl QueueCapability for Arc<Mosaic> {
     fn make_queue(&self) -> Tile {
         self.new_type("Queue: unit;").unwrap();
+        self.new_type("QueueBound: { capacity: u64, overwrite: bool };")
+            .unwrap();
         self.new_type("QueueSentinel: unit;").unwrap();
         self.new_type("Enqueued: unit;").unwrap();
 
@@ -79,21 +119,52 @@ impl QueueCapability for Arc<Mosaic> {
         }
     }
 
+    fn make_bounded_queue(&self, capacity: usize, overflow: QueueOverflow) -> Tile {
+        let queue = self.make_queue();
+        self.new_descriptor(
+            &queue,
+            "QueueBound",
+            pars()
+                .set("capacity", capacity as u64)
+                .set("overwrite", overflow == QueueOverflow::Overwrite)
+                .ok(),
+        );
+        queue
+    }
+
+    fn queue_len(&self, q: &Tile) -> usize {
+        let Some(queue) = q.get_component("Queue") else {
+            return 0;
+        };
+
+        let end = self.get_sentinel_in_queue(&queue);
+        let mut len = 0;
+        let mut current = self.get_next_in_queue(&queue);
+        while let Some(tile) = current.filter(|t| *t != end) {
+            len += 1;
+            current = self.get_next_in_queue(&tile);
+        }
+        len
+    }
+
     fn enqueue(&self, q: &Tile, v: &Tile) {
-        if let Some(queue) = q.get_component("Queue") {
-            if let Some(next) = self.get_next_in_queue(q) {
-                let old_enq_arrows = next.iter().get_arrows_into().include_component("Enqueued");
+        self.try_enqueue(q, v).expect("Cannot enqueue, panicking!");
+    }
 
-                self.new_arrow(&queue, v, "Enqueued", void());
-                self.new_arrow(v, &next, "Enqueued", void());
+    fn try_enqueue(&self, q: &Tile, v: &Tile) -> anyhow::Result<Option<Tile>> {
+        let mut dropped = None;
+        if let Some(bound) = q.get_component("QueueBound") {
+            if self.queue_len(q) as u64 >= bound.get("capacity").as_u64() {
+                if !bound.get("overwrite").as_bool() {
+                    return Err(anyhow!("Queue {} is full", q.id));
+                }
 
-                old_enq_arrows.delete();
-            } else {
-                panic!("No next element found in queue - tail potentially lost");
+                dropped = self.dequeue(q);
             }
-        } else {
-            panic!("No Queue found");
         }
+
+        self.enqueue_unbounded(q, v);
+        Ok(dropped)
     }
 
     fn dequeue(&self, q: &Tile) -> Option<Tile> {
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::internals::{EntityId, Mosaic, MosaicCRUD, MosaicIO, MosaicTransaction, Tile};
+
+use super::PatternMatchCapability;
+
+/// Replaces what `pattern` matches with a copy of `replacement`; matched tiles mapped to a
+/// replacement tile are kept, the rest are deleted.
+pub struct RewriteRule {
+    pub pattern: Arc<Mosaic>,
+    pub replacement: Arc<Mosaic>,
+    /// Pattern tile id to replacement tile id, for the tiles that survive the rewrite.
+    pub mapping: HashMap<EntityId, EntityId>,
+}
+
+impl RewriteRule {
+    pub fn new(pattern: &Arc<Mosaic>, replacement: &Arc<Mosaic>) -> Self {
+        RewriteRule {
+            pattern: Arc::clone(pattern),
+            replacement: Arc::clone(replacement),
+            mapping: HashMap::new(),
+        }
+    }
+
+    /// Keeps the tile matched by `pattern_tile`, standing in for `replacement_tile`.
+    pub fn keep(mut self, pattern_tile: &Tile, replacement_tile: &Tile) -> Self {
+        self.mapping.insert(pattern_tile.id, replacement_tile.id);
+        self
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for (from, to) in &self.mapping {
+            let kept = self
+                .pattern
+                .get(*from)
+                .filter(|t| t.is_object() || t.is_arrow())
+                .ok_or_else(|| anyhow!("Pattern has no object or arrow {} to keep", from))?;
+            if !self.replacement.is_tile_valid(to) {
+                return Err(anyhow!(
+                    "Replacement has no tile {} to keep {} as",
+                    to,
+                    from
+                ));
+            }
+
+            let endpoints = [kept.source_id(), kept.target_id()];
+            if endpoints.iter().any(|e| !self.mapping.contains_key(e)) {
+                return Err(anyhow!("Arrow {} is kept, but not both of its ends", from));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub trait RewriteCapability {
+    /// Rewrites every match of `rule.pattern` in one transaction and returns how many were
+    /// rewritten, skipping matches an earlier rewrite deleted.
+    fn apply_rule(&self, rule: &RewriteRule) -> anyhow::Result<usize>;
+}
+
+impl RewriteCapability for Arc<Mosaic> {
+    fn apply_rule(&self, rule: &RewriteRule) -> anyhow::Result<usize> {
+        rule.validate()?;
+        let matches = self.pattern_match(&rule.pattern);
+        let created = rule
+            .replacement
+            .get_all()
+            .filter(|t| !rule.mapping.values().contains(&t.id))
+            .collect_vec();
+
+        self.transaction(|mosaic| {
+            mosaic.copy_component_types(&rule.replacement)?;
+
+            let mut rewritten = 0;
+            for found in matches {
+                if found.values().any(|t| !mosaic.is_tile_valid(&t.id)) {
+                    continue;
+                }
+
+                let seed = rule
+                    .mapping
+                    .iter()
+                    .map(|(from, to)| (*to, found[from].id))
+                    .collect();
+                mosaic.copy_tiles(created.clone(), seed);
+
+                let doomed = found
+                    .iter()
+                    .filter(|(id, _)| !rule.mapping.contains_key(id))
+                    .map(|(_, tile)| tile.id)
+                    .sorted()
+                    .rev()
+                    .collect_vec();
+                // deleting an object takes its arrows along, so some may be gone already
+                for id in doomed {
+                    if mosaic.is_tile_valid(&id) {
+                        mosaic.delete_tile(id);
+                    }
+                }
+
+                rewritten += 1;
+            }
+
+            Ok(rewritten)
+        })
+    }
+}
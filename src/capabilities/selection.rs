@@ -20,12 +20,24 @@ pub trait SelectionCapability {
     fn update_selection(&self, selection: &Tile, members: &[Tile]);
     fn get_selection(&self, selection: &Tile) -> IntoIter<Tile>;
     fn clear_selection(&self, selection: &Tile);
+    /// Makes a selection that can be found again by `name`; if there already is one, its
+    /// members are replaced instead, and it is returned.
+    fn make_named_selection(&self, name: &str, members: &[Tile]) -> Tile;
+    fn get_named_selection(&self, name: &str) -> Option<Tile>;
+    /// Makes a new selection of the members of `a`, followed by those of `b` not in `a`.
+    fn selection_union(&self, a: &Tile, b: &Tile) -> Tile;
+    /// Makes a new selection of the members of `a` that are also in `b`.
+    fn selection_intersect(&self, a: &Tile, b: &Tile) -> Tile;
+    /// Makes a new selection of the members of `a` that are not in `b`.
+    fn selection_difference(&self, a: &Tile, b: &Tile) -> Tile;
 }
 
 impl SelectionCapability for Arc<Mosaic> {
     fn make_selection(&self, members: &[Tile]) -> Tile {
         self.new_type("SelectionOwner: unit;").unwrap();
         self.new_type("Selection: u64;").unwrap();
+        self.new_type("Color: { r: f32, g: f32, b: f32, a: f32 };")
+            .unwrap();
 
         let owner = self.new_object("SelectionOwner", void());
         println!("SET COLOR!");
@@ -87,4 +99,51 @@ impl SelectionCapability for Arc<Mosaic> {
             .include_component("Selection")
             .delete();
     }
+
+    fn make_named_selection(&self, name: &str, members: &[Tile]) -> Tile {
+        if let Some(selection) = self.get_named_selection(name) {
+            self.clear_selection(&selection);
+            self.update_selection(&selection, members);
+            return selection;
+        }
+
+        self.new_type("SelectionName: s32;").unwrap();
+        let selection = self.make_selection(members);
+        self.new_descriptor(&selection, "SelectionName", par(name));
+        selection
+    }
+
+    fn get_named_selection(&self, name: &str) -> Option<Tile> {
+        self.get_all()
+            .include_component("SelectionName")
+            .find(|d| d.get("self").as_s32() == name.into())
+            .map(|d| d.target())
+    }
+
+    fn selection_union(&self, a: &Tile, b: &Tile) -> Tile {
+        let members = self
+            .get_selection(a)
+            .chain(self.get_selection(b))
+            .unique()
+            .collect_vec();
+        self.make_selection(&members)
+    }
+
+    fn selection_intersect(&self, a: &Tile, b: &Tile) -> Tile {
+        let other = self.get_selection(b).collect::<HashSet<_>>();
+        let members = self
+            .get_selection(a)
+            .filter(|t| other.contains(t))
+            .collect_vec();
+        self.make_selection(&members)
+    }
+
+    fn selection_difference(&self, a: &Tile, b: &Tile) -> Tile {
+        let other = self.get_selection(b).collect::<HashSet<_>>();
+        let members = self
+            .get_selection(a)
+            .filter(|t| !other.contains(t))
+            .collect_vec();
+        self.make_selection(&members)
+    }
 }
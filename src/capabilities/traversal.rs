@@ -0,0 +1,513 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    vec::IntoIter,
+};
+
+use atomic_counter::AtomicCounter;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+
+use crate::internals::{
+    sparse_matrix::{BidirectionalMatrix, Matrix},
+    EntityId, Mosaic, MosaicIO, MosaicIndices, MosaicObservable, MosaicObserver, SubscriptionId,
+    Tile,
+};
+
+/// A frontier entry for the shortest path search, ordered so the heap pops the lowest estimate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frontier {
+    estimate: f64,
+    id: EntityId,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+type TileFilter = Box<dyn Fn(&Tile) -> bool>;
+
+/// Narrows down which arrows a traversal follows and which tiles it steps onto, checked as
+/// each tile is expanded.
+#[derive(Default)]
+pub struct TraversalFilter {
+    arrow_filters: Vec<TileFilter>,
+    tile_filters: Vec<TileFilter>,
+}
+
+impl TraversalFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only follows arrows `filter` holds for, e.g. `|a| a.get("weight").as_f32() > 0.5`.
+    pub fn with_arrow_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Tile) -> bool + 'static,
+    {
+        self.arrow_filters.push(Box::new(filter));
+        self
+    }
+
+    /// Only steps onto tiles `filter` holds for; the tile a traversal starts from is always
+    /// part of it.
+    pub fn with_tile_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Tile) -> bool + 'static,
+    {
+        self.tile_filters.push(Box::new(filter));
+        self
+    }
+
+    /// The tiles the arrows leaving `tile` lead to, each once, in the order of the arrows.
+    fn next_tiles(&self, mosaic: &Arc<Mosaic>, tile: &Tile) -> IntoIter<Tile> {
+        mosaic
+            .get_outgoing_arrows(tile)
+            .filter(|arrow| self.arrow_filters.iter().all(|f| f(arrow)))
+            .filter_map(|arrow| mosaic.get(arrow.target_id()))
+            .filter(|next| self.tile_filters.iter().all(|f| f(next)))
+            .unique()
+            .collect_vec()
+            .into_iter()
+    }
+}
+
+/// The simple paths leaving a tile, found one at a time by walking arrows depth-first; see
+/// `TraversalCapability::paths_iter`.
+pub struct PathIter {
+    mosaic: Arc<Mosaic>,
+    filter: TraversalFilter,
+    path: Vec<Tile>,
+    /// For every tile on the path, the tiles it leads to that haven't been tried yet.
+    choices: Vec<IntoIter<Tile>>,
+    /// Whether the path was ever taken any further from each of its tiles.
+    extended: Vec<bool>,
+}
+
+impl PathIter {
+    fn new(mosaic: &Arc<Mosaic>, start: &Tile, filter: TraversalFilter) -> Self {
+        PathIter {
+            mosaic: Arc::clone(mosaic),
+            path: vec![start.clone()],
+            choices: vec![filter.next_tiles(mosaic, start)],
+            extended: vec![false],
+            filter,
+        }
+    }
+}
+
+impl Iterator for PathIter {
+    type Item = Vec<Tile>;
+
+    fn next(&mut self) -> Option<Vec<Tile>> {
+        loop {
+            let choices = self.choices.last_mut()?;
+            if let Some(next) = choices.find(|t| !self.path.contains(t)) {
+                *self.extended.last_mut().unwrap() = true;
+                self.choices
+                    .push(self.filter.next_tiles(&self.mosaic, &next));
+                self.extended.push(false);
+                self.path.push(next);
+                continue;
+            }
+
+            let finished = (!self.extended.pop().unwrap()).then(|| self.path.clone());
+            self.path.pop();
+            self.choices.pop();
+            if finished.is_some() {
+                return finished;
+            }
+        }
+    }
+}
+
+/// The arrows as an adjacency matrix, built on first use and kept in step with new and
+/// deleted arrows; anything observers don't hear about throws it away.
+#[derive(Default)]
+struct AdjacencyCache {
+    /// The matrix, and the count of unobserved changes to the mosaic it was built at.
+    matrix: Mutex<Option<(usize, Arc<BidirectionalMatrix>)>>,
+}
+
+type AdjacencyCaches = HashMap<usize, (SubscriptionId, Arc<AdjacencyCache>)>;
+
+static ADJACENCY_CACHES: Lazy<Mutex<AdjacencyCaches>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl MosaicObserver for AdjacencyCache {
+    fn on_tile_created(&self, tile: &Tile) {
+        if let Some((_, matrix)) = self.matrix.lock().unwrap().as_mut() {
+            if tile.is_arrow() {
+                Arc::make_mut(matrix).add_edge(tile.id, tile.source_id(), tile.target_id());
+            } else if tile.is_object() {
+                Arc::make_mut(matrix).add_node(tile.id);
+            }
+        }
+    }
+
+    fn on_tile_deleted(&self, tile: &Tile) {
+        let mut matrix = self.matrix.lock().unwrap();
+        if tile.is_arrow() {
+            if let Some((_, matrix)) = matrix.as_mut() {
+                Arc::make_mut(matrix).remove_edge(tile.id);
+            }
+        } else if tile.is_object() {
+            *matrix = None;
+        }
+    }
+
+    fn on_arrow_reconnected(&self, arrow: &Tile, _before: (EntityId, EntityId)) {
+        if let Some((_, matrix)) = self.matrix.lock().unwrap().as_mut() {
+            let matrix = Arc::make_mut(matrix);
+            matrix.remove_edge(arrow.id);
+            matrix.add_edge(arrow.id, arrow.source_id(), arrow.target_id());
+        }
+    }
+}
+
+impl Mosaic {
+    fn build_adjacency(self: &Arc<Self>) -> BidirectionalMatrix {
+        let mut matrix = BidirectionalMatrix::default();
+        let tiles = self.get_all().sorted_by_key(|t| t.id).collect_vec();
+        for object in tiles.iter().filter(|t| t.is_object()) {
+            matrix.add_node(object.id);
+        }
+        for arrow in tiles.iter().filter(|t| t.is_arrow()) {
+            matrix.add_edge(arrow.id, arrow.source_id(), arrow.target_id());
+        }
+        matrix
+    }
+}
+
+/// Graph traversals that follow arrows from their source to their target.
+pub trait TraversalCapability {
+    /// The arrows leaving `tile`, in id order.
+    fn get_outgoing_arrows(&self, tile: &Tile) -> IntoIter<Tile>;
+    /// Every tile reachable from `start` over arrows, `start` included, in depth-first order.
+    fn reachable_from(&self, start: &Tile) -> IntoIter<Tile>;
+    fn is_reachable(&self, source: &Tile, target: &Tile) -> bool;
+    /// Every path from `start` that can't be taken further without visiting a tile twice,
+    /// produced lazily, so stopping at the first one that fits costs only the paths before it.
+    fn paths_iter(&self, start: &Tile) -> PathIter;
+    /// Same as `reachable_from`, going only where `filter` allows.
+    fn reachable_from_filtered(&self, start: &Tile, filter: &TraversalFilter) -> IntoIter<Tile>;
+    /// Same as `paths_iter`, going only where `filter` allows.
+    fn paths_iter_filtered(&self, start: &Tile, filter: TraversalFilter) -> PathIter;
+    /// The cheapest path from `source` to `target` by `weight`; negative or NaN weights are
+    /// impassable.
+    fn shortest_path<W>(&self, source: &Tile, target: &Tile, weight: W) -> Option<Vec<Tile>>
+    where
+        W: Fn(&Tile) -> f64;
+    /// Same as `shortest_path`, guided by `heuristic`, which estimates the remaining cost
+    /// from a tile to `target` and must never overestimate it.
+    fn shortest_path_astar<W, H>(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        weight: W,
+        heuristic: H,
+    ) -> Option<Vec<Tile>>
+    where
+        W: Fn(&Tile) -> f64,
+        H: Fn(&Tile) -> f64;
+    /// Every object and arrow endpoint, and the arrows between them, as a matrix shared
+    /// between calls and updated as arrows come and go, rather than rebuilt each time.
+    fn adjacency(&self) -> Arc<BidirectionalMatrix>;
+    /// Stops keeping the matrix `adjacency` hands out up to date, and frees it.
+    fn drop_adjacency(&self);
+    /// Groups of tiles that can all reach each other (Tarjan's), each sorted by id and
+    /// ordered by their first id.
+    fn strongly_connected_components(&self) -> Vec<Vec<Tile>>;
+    /// Same as `strongly_connected_components`, but ignoring the direction of arrows.
+    fn connected_components(&self) -> Vec<Vec<Tile>>;
+}
+
+fn find_root(parents: &mut HashMap<EntityId, EntityId>, id: EntityId) -> EntityId {
+    let mut root = id;
+    while parents[&root] != root {
+        root = parents[&root];
+    }
+
+    let mut current = id;
+    while parents[&current] != root {
+        let next = parents[&current];
+        parents.insert(current, root);
+        current = next;
+    }
+
+    root
+}
+
+impl Mosaic {
+    /// The vertices and edges the connectivity algorithms work on, with vertices in id order.
+    pub(crate) fn traversal_graph(
+        self: &Arc<Self>,
+    ) -> (Vec<EntityId>, HashMap<EntityId, Vec<EntityId>>) {
+        let matrix = self.adjacency();
+        let vertices = matrix.get_all_nodes().into_iter().sorted().collect_vec();
+        let edges = vertices
+            .iter()
+            .map(|v| {
+                let targets = matrix.get_front_neighbors(*v).into_iter().sorted();
+                (*v, targets.collect_vec())
+            })
+            .collect();
+
+        (vertices, edges)
+    }
+
+    fn into_components(self: &Arc<Self>, groups: Vec<Vec<EntityId>>) -> Vec<Vec<Tile>> {
+        groups
+            .into_iter()
+            .map(|group| group.into_iter().sorted().collect_vec())
+            .sorted()
+            .map(|group| group.into_iter().flat_map(|id| self.get(id)).collect_vec())
+            .collect_vec()
+    }
+}
+
+impl TraversalCapability for Arc<Mosaic> {
+    fn get_outgoing_arrows(&self, tile: &Tile) -> IntoIter<Tile> {
+        self.get_tiles_from(tile.id)
+            .filter(|t| t.is_arrow())
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn reachable_from(&self, start: &Tile) -> IntoIter<Tile> {
+        self.reachable_from_filtered(start, &TraversalFilter::new())
+    }
+
+    fn reachable_from_filtered(&self, start: &Tile, filter: &TraversalFilter) -> IntoIter<Tile> {
+        let mut visited = HashSet::new();
+        let mut result = vec![];
+        let mut stack = vec![start.clone()];
+
+        while let Some(tile) = stack.pop() {
+            if !visited.insert(tile.id) {
+                continue;
+            }
+
+            stack.extend(
+                filter
+                    .next_tiles(self, &tile)
+                    .rev()
+                    .filter(|next| !visited.contains(&next.id)),
+            );
+            result.push(tile);
+        }
+
+        result.into_iter()
+    }
+
+    fn is_reachable(&self, source: &Tile, target: &Tile) -> bool {
+        self.reachable_from(source).any(|t| t.id == target.id)
+    }
+
+    fn paths_iter(&self, start: &Tile) -> PathIter {
+        self.paths_iter_filtered(start, TraversalFilter::new())
+    }
+
+    fn paths_iter_filtered(&self, start: &Tile, filter: TraversalFilter) -> PathIter {
+        PathIter::new(self, start, filter)
+    }
+
+    fn shortest_path<W>(&self, source: &Tile, target: &Tile, weight: W) -> Option<Vec<Tile>>
+    where
+        W: Fn(&Tile) -> f64,
+    {
+        self.shortest_path_astar(source, target, weight, |_| 0.0)
+    }
+
+    fn shortest_path_astar<W, H>(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        weight: W,
+        heuristic: H,
+    ) -> Option<Vec<Tile>>
+    where
+        W: Fn(&Tile) -> f64,
+        H: Fn(&Tile) -> f64,
+    {
+        let mut cost: HashMap<EntityId, f64> = HashMap::from([(source.id, 0.0)]);
+        let mut came_from: HashMap<EntityId, EntityId> = HashMap::new();
+        let mut done = HashSet::new();
+        let mut frontier = BinaryHeap::from([Frontier {
+            estimate: heuristic(source),
+            id: source.id,
+        }]);
+
+        while let Some(Frontier { id, .. }) = frontier.pop() {
+            if id == target.id {
+                let mut path = vec![id];
+                while let Some(previous) = came_from.get(path.last().unwrap()) {
+                    path.push(*previous);
+                }
+
+                return path.into_iter().rev().map(|id| self.get(id)).collect();
+            }
+
+            if !done.insert(id) {
+                continue;
+            }
+
+            let Some(tile) = self.get(id) else {
+                continue;
+            };
+
+            for arrow in self.get_outgoing_arrows(&tile) {
+                let step = weight(&arrow);
+                if step.is_nan() || step < 0.0 {
+                    continue;
+                }
+
+                let next = arrow.target_id();
+                let next_cost = cost[&id] + step;
+                if cost.get(&next).is_some_and(|c| *c <= next_cost) {
+                    continue;
+                }
+
+                let Some(next_tile) = self.get(next) else {
+                    continue;
+                };
+
+                cost.insert(next, next_cost);
+                came_from.insert(next, id);
+                frontier.push(Frontier {
+                    estimate: next_cost + heuristic(&next_tile),
+                    id: next,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn adjacency(&self) -> Arc<BidirectionalMatrix> {
+        let cache = {
+            let mut caches = ADJACENCY_CACHES.lock().unwrap();
+            let (_, cache) = caches.entry(self.id).or_insert_with(|| {
+                let cache = Arc::new(AdjacencyCache::default());
+                let subscription = self.subscribe(Arc::clone(&cache) as Arc<dyn MosaicObserver>);
+                (subscription, cache)
+            });
+            Arc::clone(cache)
+        };
+
+        let built_at = self.unobserved_changes.get();
+        if let Some((at, matrix)) = cache.matrix.lock().unwrap().as_ref() {
+            if *at == built_at {
+                return Arc::clone(matrix);
+            }
+        }
+
+        // built without holding the cache, which observers lock while tiles are being made
+        let built = Arc::new(self.build_adjacency());
+        *cache.matrix.lock().unwrap() = Some((built_at, Arc::clone(&built)));
+        built
+    }
+
+    fn drop_adjacency(&self) {
+        let removed = ADJACENCY_CACHES.lock().unwrap().remove(&self.id);
+        if let Some((subscription, _)) = removed {
+            self.unsubscribe(subscription);
+        }
+    }
+
+    fn strongly_connected_components(&self) -> Vec<Vec<Tile>> {
+        let (vertices, edges) = self.traversal_graph();
+        let mut index: HashMap<EntityId, usize> = HashMap::new();
+        let mut lowlink: HashMap<EntityId, usize> = HashMap::new();
+        let mut on_stack = HashSet::new();
+        let mut stack = vec![];
+        let mut groups = vec![];
+
+        // Tarjan's algorithm, with an explicit call stack of (vertex, next edge to visit)
+        for root in vertices {
+            if index.contains_key(&root) {
+                continue;
+            }
+
+            let mut calls = vec![(root, 0)];
+            while let Some((vertex, edge)) = calls.pop() {
+                if edge == 0 {
+                    index.insert(vertex, index.len());
+                    lowlink.insert(vertex, index[&vertex]);
+                    stack.push(vertex);
+                    on_stack.insert(vertex);
+                }
+
+                let neighbors = edges.get(&vertex).map(Vec::as_slice).unwrap_or_default();
+                if let Some(&next) = neighbors.get(edge) {
+                    calls.push((vertex, edge + 1));
+                    if !index.contains_key(&next) {
+                        calls.push((next, 0));
+                    } else if on_stack.contains(&next) {
+                        lowlink.insert(vertex, lowlink[&vertex].min(index[&next]));
+                    }
+                    continue;
+                }
+
+                if lowlink[&vertex] == index[&vertex] {
+                    let mut group = vec![];
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(&member);
+                        group.push(member);
+                        if member == vertex {
+                            break;
+                        }
+                    }
+                    groups.push(group);
+                }
+
+                if let Some((caller, _)) = calls.last() {
+                    let low = lowlink[caller].min(lowlink[&vertex]);
+                    lowlink.insert(*caller, low);
+                }
+            }
+        }
+
+        self.into_components(groups)
+    }
+
+    fn connected_components(&self) -> Vec<Vec<Tile>> {
+        let (vertices, edges) = self.traversal_graph();
+        let mut parents: HashMap<EntityId, EntityId> = vertices.iter().map(|v| (*v, *v)).collect();
+
+        for (source, targets) in &edges {
+            for target in targets {
+                let a = find_root(&mut parents, *source);
+                let b = find_root(&mut parents, *target);
+                if a != b {
+                    parents.insert(a.max(b), a.min(b));
+                }
+            }
+        }
+
+        let roots = vertices
+            .iter()
+            .map(|v| (find_root(&mut parents, *v), *v))
+            .collect_vec();
+        let groups = roots
+            .into_iter()
+            .into_group_map()
+            .into_values()
+            .collect_vec();
+
+        self.into_components(groups)
+    }
+}
@@ -4,7 +4,7 @@ mod selection_tests {
 
     use crate::{
         capabilities::SelectionCapability,
-        internals::{void, Mosaic, MosaicCRUD, MosaicIO},
+        internals::{void, Mosaic, MosaicCRUD, MosaicIO, Tile},
     };
 
     #[test]
@@ -55,15 +55,44 @@ mod selection_tests {
                 .collect_vec()
         );
     }
+
+    #[test]
+    fn test_named_selections() {
+        let mosaic = Mosaic::new();
+        let t = (0..4)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        let ids = |s: &Tile| mosaic.get_selection(s).map(|t| t.id).sorted().collect_vec();
+
+        assert!(mosaic.get_named_selection("ui_layer").is_none());
+        let ui = mosaic.make_named_selection("ui_layer", &t[0..3]);
+        let bg = mosaic.make_named_selection("background", &t[2..4]);
+        assert_eq!(Some(ui.clone()), mosaic.get_named_selection("ui_layer"));
+        assert_eq!(Some(bg.clone()), mosaic.get_named_selection("background"));
+
+        assert_eq!(
+            vec![t[0].id, t[1].id, t[2].id, t[3].id],
+            ids(&mosaic.selection_union(&ui, &bg))
+        );
+        assert_eq!(vec![t[2].id], ids(&mosaic.selection_intersect(&ui, &bg)));
+        assert_eq!(
+            vec![t[0].id, t[1].id],
+            ids(&mosaic.selection_difference(&ui, &bg))
+        );
+
+        let again = mosaic.make_named_selection("ui_layer", &t[3..4]);
+        assert_eq!(ui, again);
+        assert_eq!(vec![t[3].id], ids(&ui));
+    }
 }
 
 #[cfg(test)]
 mod archetype_tests {
     use crate::{
-        capabilities::ArchetypeSubject,
+        capabilities::{Archetype, ArchetypeSubject},
         internals::{
-            pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
-            MosaicTypelevelCRUD, Value,
+            pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicConstraints,
+            MosaicIO, MosaicTypelevelCRUD, Value,
         },
     };
 
@@ -112,6 +141,82 @@ mod archetype_tests {
             assert_eq!(lab, &l);
         }
     }
+
+    #[test]
+    fn test_tiles_with_archetype() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Position: { x: f32, y: f32 };").unwrap();
+        mosaic.new_type("Label: s32;").unwrap();
+
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        a.add_component("Position", pars().set("x", 1.0f32).set("y", 2.0f32).ok());
+        a.add_component("Label", pars().set("self", "a").ok());
+        b.add_component("Position", pars().set("x", 3.0f32).set("y", 4.0f32).ok());
+        c.add_component("Label", pars().set("self", "c").ok());
+
+        let matching = mosaic
+            .get_tiles_with_archetype(&["Position", "Label"])
+            .collect::<Vec<_>>();
+        assert_eq!(vec![a.clone()], matching);
+        assert!(matching
+            .iter()
+            .all(|t| t.match_archetype(&["Position", "Label"])));
+
+        let labelled = mosaic
+            .get_tiles_with_archetype(&["Label"])
+            .filter(|t| t.is_object())
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![a.id, c.id], labelled);
+
+        b.add_component("Label", pars().set("self", "b").ok());
+        assert_eq!(
+            2,
+            mosaic
+                .get_tiles_with_archetype(&["Position", "Label"])
+                .count()
+        );
+        assert_eq!(0, mosaic.get_tiles_with_archetype(&[]).count());
+    }
+
+    #[test]
+    fn test_unique_and_required_components() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Node: unit;").unwrap();
+        mosaic
+            .new_type("Label: s32 unique required on Node;")
+            .unwrap();
+        assert_eq!(
+            "Label: s32 unique required on Node;",
+            mosaic
+                .component_registry
+                .to_definition_string("Label")
+                .unwrap()
+        );
+
+        let a = mosaic.new_object("Node", void());
+        let b = mosaic.new_object("Node", void());
+        let first = a.add_component("Label", pars().set("self", "first").ok());
+        let second = a.add_component("Label", pars().set("self", "second").ok());
+        assert!(!mosaic.is_tile_valid(&first));
+        assert_eq!(vec![second], a.get_components("Label"));
+        assert!(mosaic
+            .try_new_descriptor(&a, "Label", pars().set("self", "third").ok())
+            .is_err());
+
+        assert_eq!(
+            vec![b.id],
+            mosaic
+                .validate()
+                .into_iter()
+                .map(|v| v.tile)
+                .collect::<Vec<_>>()
+        );
+        b.add_component("Label", pars().set("self", "b").ok());
+        assert!(mosaic.validate().is_empty());
+    }
 }
 
 #[cfg(test)]
@@ -119,8 +224,11 @@ mod queue_tests {
     use itertools::Itertools;
 
     use crate::{
-        capabilities::QueueCapability,
-        internals::{void, Mosaic, MosaicIO},
+        capabilities::{PriorityQueueCapability, QueueCapability, QueueOverflow},
+        internals::{
+            par, pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+            MosaicTypelevelCRUD,
+        },
         iterators::tile_getters::TileGetters,
     };
 
@@ -147,4 +255,1441 @@ mod queue_tests {
         assert_eq!(Some(c), mosaic.dequeue(&q));
         assert_eq!(None, mosaic.dequeue(&q));
     }
+
+    #[test]
+    fn test_bounded_queues() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+
+        let q = mosaic.make_bounded_queue(2, QueueOverflow::Reject);
+        assert_eq!(0, mosaic.queue_len(&q));
+        mosaic.enqueue(&q, &a);
+        mosaic.enqueue(&q, &b);
+        assert_eq!(2, mosaic.queue_len(&q));
+        assert!(mosaic.try_enqueue(&q, &c).is_err());
+        assert_eq!(2, mosaic.queue_len(&q));
+        assert_eq!(Some(a.clone()), mosaic.peek_queue(&q));
+
+        // a tile only ever sits in one queue
+        let (a, b, c) = (
+            mosaic.new_object("void", void()),
+            mosaic.new_object("void", void()),
+            mosaic.new_object("void", void()),
+        );
+        let q = mosaic.make_bounded_queue(2, QueueOverflow::Overwrite);
+        mosaic.enqueue(&q, &a);
+        mosaic.enqueue(&q, &b);
+        assert_eq!(Some(a), mosaic.try_enqueue(&q, &c).unwrap());
+        assert_eq!(2, mosaic.queue_len(&q));
+        assert_eq!(Some(b), mosaic.dequeue(&q));
+        assert_eq!(Some(c), mosaic.dequeue(&q));
+        assert_eq!(0, mosaic.queue_len(&q));
+    }
+
+    #[test]
+    fn test_priority_queues() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Task: i32;").unwrap();
+        mosaic.new_type("Urgency: { level: f32 };").unwrap();
+        let low = mosaic.new_object("Task", par(1i32));
+        let high = mosaic.new_object("Task", par(9i32));
+        let tied = mosaic.new_object("Task", par(9i32));
+        let unranked = mosaic.new_object("void", void());
+
+        let q = mosaic.make_priority_queue("Task", "self");
+        assert!(mosaic.is_priority_queue_empty(&q));
+        for t in [&unranked, &low, &high, &tied] {
+            mosaic.enqueue_by_priority(&q, t);
+        }
+
+        assert_eq!(4, mosaic.priority_queue_len(&q));
+        assert_eq!(Some(high.clone()), mosaic.peek_by_priority(&q));
+        assert_eq!(Some(high), mosaic.dequeue_by_priority(&q));
+        assert_eq!(Some(tied), mosaic.dequeue_by_priority(&q));
+        assert_eq!(Some(low), mosaic.dequeue_by_priority(&q));
+        assert_eq!(Some(unranked), mosaic.dequeue_by_priority(&q));
+        assert_eq!(None, mosaic.dequeue_by_priority(&q));
+
+        // priorities can also come from a descriptor
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        mosaic.new_descriptor(&a, "Urgency", pars().set("level", 0.5f32).ok());
+        mosaic.new_descriptor(&b, "Urgency", pars().set("level", 2.0f32).ok());
+        let q = mosaic.make_priority_queue("Urgency", "level");
+        mosaic.enqueue_by_priority(&q, &a);
+        mosaic.enqueue_by_priority(&q, &b);
+        assert_eq!(Some(b), mosaic.dequeue_by_priority(&q));
+        assert_eq!(Some(a), mosaic.dequeue_by_priority(&q));
+    }
+}
+
+#[cfg(test)]
+mod process_tests {
+    use std::{thread, time::Duration};
+
+    use anyhow::anyhow;
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{
+            Pipeline, ProcessCapability, ProcessContext, ProcessStatus, TransformerRegistry,
+            TransformerSignature,
+        },
+        internals::{par, Mosaic, MosaicIO, MosaicTypelevelCRUD, Tile},
+    };
+
+    #[test]
+    fn test_process_runs_to_completion() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Number: i32;").unwrap();
+        let registry = TransformerRegistry::new();
+        registry.register("double", |ctx| {
+            let input = ctx.parameter("input").ok_or_else(|| anyhow!("no input"))?;
+            ctx.report_progress(0.5);
+            let output = ctx
+                .mosaic
+                .new_object("Number", par(input.get("self").as_i32() * 2));
+            ctx.set_result("output", &output);
+            Ok(())
+        });
+
+        let input = mosaic.new_object("Number", par(21i32));
+        let process = mosaic.make_process("double");
+        mosaic.add_process_parameter(&process, "input", &input);
+        assert_eq!(ProcessStatus::Pending, mosaic.get_process_status(&process));
+
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        let updates = handle.updates().iter().collect_vec();
+        assert_eq!(ProcessStatus::Done, handle.wait());
+
+        assert_eq!(
+            vec![(ProcessStatus::Running, 0.5), (ProcessStatus::Done, 1.0)],
+            updates
+                .into_iter()
+                .map(|u| (u.status, u.progress))
+                .collect_vec()
+        );
+        assert_eq!(1.0, mosaic.get_process_progress(&process));
+        let output = mosaic.get_process_result(&process, "output").unwrap();
+        assert_eq!(42, output.get("self").as_i32());
+    }
+
+    #[test]
+    fn test_process_failures() {
+        let mosaic = Mosaic::new();
+        let registry = TransformerRegistry::new();
+        registry.register("fail", |_| Err(anyhow!("nothing to do")));
+        registry.register("panic", |_| panic!("oops"));
+
+        let process = mosaic.make_process("missing");
+        assert!(mosaic.run_process(&registry, &process).is_err());
+
+        let process = mosaic.make_process("fail");
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        assert_eq!(
+            ProcessStatus::Failed("nothing to do".to_string()),
+            handle.wait()
+        );
+
+        let process = mosaic.make_process("panic");
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        assert_eq!(ProcessStatus::Failed("panicked".to_string()), handle.wait());
+    }
+
+    #[test]
+    fn test_process_cancellation() {
+        let mosaic = Mosaic::new();
+        let registry = TransformerRegistry::new();
+        registry.register("forever", |ctx| {
+            let mut step = 0;
+            while !ctx.is_cancelled() {
+                step += 1;
+                ctx.report_progress(1.0 - 1.0 / step as f32);
+                thread::sleep(Duration::from_millis(1));
+            }
+            Ok(())
+        });
+
+        let process = mosaic.make_process("forever");
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        handle.updates().recv().unwrap();
+        assert_eq!(ProcessStatus::Running, mosaic.get_process_status(&process));
+        assert!(mosaic.run_process(&registry, &process).is_err());
+
+        handle.cancel();
+        assert_eq!(
+            ProcessStatus::Failed("cancelled".to_string()),
+            handle.wait()
+        );
+        assert!(mosaic.get_process_progress(&process) < 1.0);
+    }
+
+    fn number_transformers() -> TransformerRegistry {
+        let registry = TransformerRegistry::new();
+        let signature = TransformerSignature::new()
+            .parameter("input", "Number")
+            .result("output", "Number");
+        let apply = |f: fn(i32) -> i32| {
+            move |ctx: &ProcessContext| -> anyhow::Result<()> {
+                let input = ctx.parameter("input").unwrap().get("self").as_i32();
+                let output = ctx.mosaic.new_object("Number", par(f(input)));
+                ctx.set_result("output", &output);
+                Ok(())
+            }
+        };
+
+        registry.register_with_signature("double", signature.clone(), apply(|n| n * 2));
+        registry.register_with_signature("increment", signature.clone(), apply(|n| n + 1));
+        registry.register_with_signature("forgetful", signature, |_| Ok(()));
+        registry
+    }
+
+    #[test]
+    fn test_transformer_signatures() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Number: i32;").unwrap();
+        mosaic.new_type("Text: s32;").unwrap();
+        let registry = number_transformers();
+        assert_eq!(vec!["double", "forgetful", "increment"], registry.names());
+
+        let process = mosaic.make_process("double");
+        assert!(mosaic.run_process(&registry, &process).is_err());
+        mosaic.add_process_parameter(&process, "input", &mosaic.new_object("Text", par("1")));
+        assert!(mosaic.run_process(&registry, &process).is_err());
+        mosaic.add_process_parameter(&process, "input", &mosaic.new_object("Number", par(1i32)));
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        assert_eq!(ProcessStatus::Done, handle.wait());
+
+        let process = mosaic.make_process("forgetful");
+        mosaic.add_process_parameter(&process, "input", &mosaic.new_object("Number", par(1i32)));
+        let handle = mosaic.run_process(&registry, &process).unwrap();
+        assert_eq!(
+            ProcessStatus::Failed("The result output is missing".to_string()),
+            handle.wait()
+        );
+    }
+
+    #[test]
+    fn test_pipelines() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Number: i32;").unwrap();
+        let registry = number_transformers();
+        let output = |p: &Tile| mosaic.get_process_result(p, "output").unwrap();
+
+        let pipeline = Pipeline::new(&mosaic, "double")
+            .then("increment", &[("output", "input")])
+            .then("double", &[("output", "input")]);
+        assert_eq!(3, pipeline.processes().len());
+
+        mosaic.add_process_parameter(
+            &pipeline.first(),
+            "input",
+            &mosaic.new_object("Number", par(5i32)),
+        );
+        assert_eq!(ProcessStatus::Done, pipeline.run(&registry).unwrap());
+        assert_eq!(22, output(&pipeline.last()).get("self").as_i32());
+
+        // running again picks up the new input
+        mosaic.add_process_parameter(
+            &pipeline.first(),
+            "input",
+            &mosaic.new_object("Number", par(1i32)),
+        );
+        assert_eq!(ProcessStatus::Done, pipeline.run(&registry).unwrap());
+        assert_eq!(6, output(&pipeline.last()).get("self").as_i32());
+
+        // a failing step stops the pipeline
+        let pipeline = Pipeline::new(&mosaic, "double")
+            .then("forgetful", &[("output", "input")])
+            .then("double", &[("output", "input")]);
+        mosaic.add_process_parameter(
+            &pipeline.first(),
+            "input",
+            &mosaic.new_object("Number", par(5i32)),
+        );
+        assert_eq!(
+            ProcessStatus::Failed("The result output is missing".to_string()),
+            pipeline.run(&registry).unwrap()
+        );
+        assert_eq!(
+            ProcessStatus::Pending,
+            mosaic.get_process_status(&pipeline.last())
+        );
+    }
+}
+
+#[cfg(test)]
+mod pattern_match_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{PatternMatch, PatternMatchCapability},
+        internals::{
+            par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, TileFieldSetter, S32,
+        },
+    };
+
+    #[test]
+    fn test_pattern_match_constraints() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Label: s32;").unwrap();
+        mosaic.new_type("Position: { x: f32, y: f32 };").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        mosaic.new_arrow(&a, &b, "void", void());
+        mosaic.new_arrow(&a, &c, "void", void());
+        mosaic.new_descriptor(&b, "Label", par("button"));
+        mosaic.new_descriptor(&c, "Label", par("canvas"));
+
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        pattern.new_arrow(&p, &q, "void", void());
+
+        // purely structural, either arrow fits
+        let found = mosaic.pattern_match(&pattern);
+        assert_eq!(
+            vec![(a.id, b.id), (a.id, c.id)],
+            found
+                .iter()
+                .map(|m| (m[&p.id].id, m[&q.id].id))
+                .collect_vec()
+        );
+
+        pattern.match_component(&q, "Label");
+        assert_eq!(2, mosaic.pattern_match(&pattern).len());
+
+        pattern.match_field_equals(&q, "Label.self", "canvas");
+        let found = mosaic.pattern_match(&pattern);
+        assert_eq!(1, found.len());
+        assert_eq!(c, found[0][&q.id]);
+
+        let mut lone = mosaic.new_object("Position", void());
+        lone.set("x", 3.0f32);
+        let pattern = Mosaic::new();
+        let r = pattern.new_object("void", void());
+        pattern.match_field_equals(&r, "x", "3");
+        let found = mosaic.pattern_match(&pattern);
+        assert_eq!(
+            vec![lone.id],
+            found.iter().map(|m| m[&r.id].id).collect_vec()
+        );
+
+        pattern.match_component(&r, "Label");
+        assert!(mosaic.pattern_match(&pattern).is_empty());
+    }
+
+    #[test]
+    fn test_pattern_match_arrows() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let ab = mosaic.new_arrow(&a, &b, "void", void());
+        let bc1 = mosaic.new_arrow(&b, &c, "void", void());
+        let bc2 = mosaic.new_arrow(&b, &c, "void", void());
+
+        // a single arrow only matches the way it points, once for each arrow it can bind to
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        let pq = pattern.new_arrow(&p, &q, "void", void());
+        assert_eq!(
+            vec![
+                (a.id, b.id, ab.id),
+                (b.id, c.id, bc1.id),
+                (b.id, c.id, bc2.id)
+            ],
+            mosaic
+                .pattern_match(&pattern)
+                .iter()
+                .map(|m| (m[&p.id].id, m[&q.id].id, m[&pq.id].id))
+                .collect_vec()
+        );
+
+        // two parallel arrows need two in the target, each bound to its own
+        let qp = pattern.new_arrow(&p, &q, "void", void());
+        let found = mosaic.pattern_match(&pattern);
+        assert_eq!(2, found.len());
+        for m in &found {
+            assert_eq!((b.id, c.id), (m[&p.id].id, m[&q.id].id));
+            assert_ne!(m[&pq.id], m[&qp.id]);
+        }
+
+        // a reversed arrow is not the same arrow
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        pattern.new_arrow(&p, &q, "void", void());
+        pattern.new_arrow(&q, &p, "void", void());
+        assert!(mosaic.pattern_match(&pattern).is_empty());
+    }
+
+    #[test]
+    fn test_incremental_pattern_match() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Label: s32;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        mosaic.new_arrow(&a, &b, "void", void());
+
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        pattern.new_arrow(&p, &q, "void", void());
+        pattern.match_component(&q, "Label");
+
+        let pairs = |matches: Vec<PatternMatch>| {
+            matches
+                .iter()
+                .map(|m| (m[&p.id].id, m[&q.id].id))
+                .sorted()
+                .collect_vec()
+        };
+
+        let watch = mosaic.watch_pattern(&pattern);
+        assert!(watch.matches().is_empty());
+
+        let mut label = mosaic.new_descriptor(&b, "Label", par("b"));
+        assert_eq!(vec![(a.id, b.id)], pairs(watch.matches()));
+
+        let bc = mosaic.new_arrow(&b, &c, "void", void());
+        mosaic.new_descriptor(&c, "Label", par("c"));
+        assert_eq!(vec![(a.id, b.id), (b.id, c.id)], pairs(watch.matches()));
+
+        label.set("self", S32::from("still b"));
+        assert_eq!(vec![(a.id, b.id), (b.id, c.id)], pairs(watch.matches()));
+
+        mosaic.delete_tile(bc);
+        assert_eq!(vec![(a.id, b.id)], pairs(watch.matches()));
+
+        mosaic.delete_tile(label);
+        assert!(watch.matches().is_empty());
+        assert_eq!(
+            pairs(mosaic.pattern_match(&pattern)),
+            pairs(watch.matches())
+        );
+
+        watch.stop();
+        mosaic.new_descriptor(&b, "Label", par("b"));
+        assert!(watch.matches().is_empty());
+        assert_eq!(1, mosaic.pattern_match(&pattern).len());
+    }
+}
+
+#[cfg(test)]
+mod rewrite_tests {
+    use crate::{
+        capabilities::{PatternMatchCapability, RewriteCapability, RewriteRule},
+        internals::{par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD},
+        iterators::{tile_filters::TileFilters, tile_getters::TileGetters},
+    };
+
+    #[test]
+    fn test_rewrite_rules() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        mosaic.new_arrow(&a, &b, "void", void());
+        mosaic.new_arrow(&b, &c, "void", void());
+
+        // split every arrow in two, with a new Midpoint object in between
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        pattern.new_arrow(&p, &q, "void", void());
+
+        let replacement = Mosaic::new();
+        replacement.new_type("Midpoint: u8;").unwrap();
+        let rp = replacement.new_object("void", void());
+        let rq = replacement.new_object("void", void());
+        let mid = replacement.new_object("Midpoint", par(1u8));
+        replacement.new_arrow(&rp, &mid, "void", void());
+        replacement.new_arrow(&mid, &rq, "void", void());
+
+        let rule = RewriteRule::new(&pattern, &replacement)
+            .keep(&p, &rp)
+            .keep(&q, &rq);
+        assert_eq!(2, mosaic.apply_rule(&rule).unwrap());
+        assert_eq!(5, mosaic.get_all().filter_objects().count());
+        assert_eq!(4, mosaic.get_all().filter_arrows().count());
+        assert_eq!(
+            vec![1, 1],
+            [&a, &b]
+                .iter()
+                .map(|t| t.iter().get_arrows_from().get_targets().next().unwrap())
+                .map(|m| m.get("self").as_u8())
+                .collect::<Vec<_>>()
+        );
+
+        // deleting whatever is marked Doomed
+        mosaic.new_type("Doomed: unit;").unwrap();
+        mosaic.new_descriptor(&c, "Doomed", void());
+        let pattern = Mosaic::new();
+        let doomed = pattern.new_object("void", void());
+        pattern.match_component(&doomed, "Doomed");
+        let rule = RewriteRule::new(&pattern, &Mosaic::new());
+        assert_eq!(1, mosaic.apply_rule(&rule).unwrap());
+        assert!(!mosaic.is_tile_valid(&c.id));
+        assert_eq!(3, mosaic.get_all().filter_arrows().count());
+        assert_eq!(0, mosaic.apply_rule(&rule).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_rewrite_rules() {
+        let pattern = Mosaic::new();
+        let p = pattern.new_object("void", void());
+        let q = pattern.new_object("void", void());
+        let pq = pattern.new_arrow(&p, &q, "void", void());
+        let replacement = Mosaic::new();
+        let r = replacement.new_object("void", void());
+
+        let mosaic = Mosaic::new();
+        let rule = RewriteRule::new(&pattern, &replacement).keep(&pq, &r);
+        assert!(mosaic.apply_rule(&rule).is_err());
+        let rule = RewriteRule::new(&pattern, &replacement).keep(&p, &q);
+        assert!(mosaic.apply_rule(&rule).is_err());
+    }
+}
+
+#[cfg(test)]
+mod grouping_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::GroupingCapability,
+        internals::{void, Mosaic, MosaicIO, Tile},
+    };
+
+    #[test]
+    fn test_owned_groups() {
+        let mosaic = Mosaic::new();
+        let owner = mosaic.new_object("void", void());
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+
+        mosaic.group("team", &owner, std::slice::from_ref(&a));
+        mosaic.add_group_member("team", &owner, &b).unwrap();
+        assert!(mosaic.add_group_member("other", &owner, &b).is_err());
+        assert_eq!(Some(owner.clone()), mosaic.get_group_owner("team", &a));
+        assert_eq!(2, mosaic.get_group_members("team", &owner).len());
+
+        mosaic.ungroup("team", &owner);
+        assert_eq!(None, mosaic.get_group_owner("team", &a));
+    }
+
+    #[test]
+    fn test_named_groups() {
+        let mosaic = Mosaic::new();
+        let t = (0..4)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        let ids = |tiles: Vec<Tile>| tiles.into_iter().map(|t| t.id).sorted().collect_vec();
+
+        assert!(mosaic.get_named_group("ui").is_none());
+        let ui = mosaic.add_to_group(&t[0], "ui");
+        assert_eq!(ui, mosaic.add_to_group(&t[1], "ui"));
+        mosaic.add_to_group(&t[1], "ui");
+        mosaic.add_to_group(&t[2], "buttons");
+        mosaic.add_to_group(&t[3], "icons");
+        assert_eq!(Some(ui.clone()), mosaic.get_named_group("ui"));
+        assert_eq!(
+            vec![t[0].id, t[1].id],
+            ids(mosaic.get_named_group_members("ui").collect_vec())
+        );
+
+        mosaic.add_subgroup("ui", "buttons").unwrap();
+        mosaic.add_subgroup("buttons", "icons").unwrap();
+        assert!(mosaic.add_subgroup("icons", "ui").is_err());
+        assert!(mosaic.add_subgroup("ui", "ui").is_err());
+
+        assert_eq!(
+            vec![t[0].id, t[1].id, t[2].id, t[3].id],
+            ids(mosaic.get_all_group_members("ui").collect_vec())
+        );
+        assert!(mosaic.is_in_group(&t[3], "ui"));
+        assert!(mosaic.is_in_group(&t[3], "buttons"));
+        assert!(!mosaic.is_in_group(&t[0], "buttons"));
+
+        mosaic.remove_from_group(&t[1], "ui");
+        assert!(!mosaic.is_in_group(&t[1], "ui"));
+        assert_eq!(
+            vec![t[0].id, t[2].id, t[3].id],
+            ids(mosaic.get_all_group_members("ui").collect_vec())
+        );
+    }
+}
+
+#[cfg(test)]
+mod parenting_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::ParentingCapability,
+        internals::{void, Mosaic, MosaicIO, Tile},
+    };
+
+    #[test]
+    fn test_parenting() {
+        let mosaic = Mosaic::new();
+        let t = (0..5)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        let ids = |tiles: std::vec::IntoIter<Tile>| tiles.map(|t| t.id).collect_vec();
+
+        mosaic.set_parent(&t[1], &t[0]).unwrap();
+        mosaic.set_parent(&t[2], &t[0]).unwrap();
+        mosaic.set_parent(&t[3], &t[1]).unwrap();
+        mosaic.set_parent(&t[4], &t[3]).unwrap();
+
+        assert_eq!(Some(t[0].clone()), mosaic.get_parent(&t[1]));
+        assert_eq!(None, mosaic.get_parent(&t[0]));
+        assert_eq!(vec![t[1].id, t[2].id], ids(mosaic.get_children(&t[0])));
+        assert_eq!(
+            vec![t[3].id, t[1].id, t[0].id],
+            ids(mosaic.get_ancestors(&t[4]))
+        );
+        assert_eq!(
+            vec![t[1].id, t[2].id, t[3].id, t[4].id],
+            ids(mosaic.get_descendants(&t[0]))
+        );
+
+        // no second parents, and no cycles
+        assert!(mosaic.set_parent(&t[1], &t[2]).is_err());
+        assert!(mosaic.set_parent(&t[0], &t[4]).is_err());
+        assert!(mosaic.reparent(&t[1], &t[4]).is_err());
+        assert!(mosaic.reparent(&t[1], &t[1]).is_err());
+        assert_eq!(Some(t[0].clone()), mosaic.get_parent(&t[1]));
+
+        mosaic.reparent(&t[3], &t[2]).unwrap();
+        assert_eq!(vec![t[3].id, t[4].id], ids(mosaic.get_descendants(&t[2])));
+        assert!(mosaic.get_children(&t[1]).next().is_none());
+
+        mosaic.unparent(&t[2]);
+        assert_eq!(vec![t[1].id], ids(mosaic.get_descendants(&t[0])));
+        assert_eq!(vec![t[3].id, t[2].id], ids(mosaic.get_ancestors(&t[4])));
+    }
+
+    #[test]
+    fn test_ordered_children() {
+        let mosaic = Mosaic::new();
+        let root = mosaic.new_object("void", void());
+        let t = (0..4)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        for child in &t {
+            mosaic.set_parent(child, &root).unwrap();
+        }
+        let ids = |tiles: std::vec::IntoIter<Tile>| tiles.map(|t| t.id).collect_vec();
+
+        mosaic.set_child_index(&t[3], 0).unwrap();
+        mosaic.set_child_index(&t[0], 2).unwrap();
+        mosaic.set_child_index(&t[1], 100).unwrap();
+        let order = vec![t[3].id, t[0].id, t[2].id, t[1].id];
+        assert_eq!(order, ids(mosaic.get_children_ordered(&root)));
+        assert_eq!(
+            vec![t[0].id, t[1].id, t[2].id, t[3].id],
+            ids(mosaic.get_children(&root))
+        );
+        assert!(mosaic.set_child_index(&root, 0).is_err());
+
+        let loaded = Mosaic::new();
+        loaded.load(&mosaic.save()).unwrap();
+        let root = loaded.get(root.id).unwrap();
+        assert_eq!(order, ids(loaded.get_children_ordered(&root)));
+
+        // newcomers go last
+        let late = loaded.new_object("void", void());
+        loaded.set_parent(&late, &root).unwrap();
+        assert_eq!(
+            Some(late.id),
+            loaded.get_children_ordered(&root).last().map(|t| t.id)
+        );
+    }
+}
+
+#[cfg(test)]
+mod labeled_edges_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::LabeledEdges,
+        internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, S32},
+    };
+
+    #[test]
+    fn test_arrows_by_component() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Likes: unit;").unwrap();
+        mosaic.new_type("Knows: unit;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let a_b = mosaic.new_arrow(&a, &b, "Likes", void());
+        let a_c = mosaic.new_arrow(&a, &c, "Likes", void());
+        let knows = mosaic.new_arrow(&a, &b, "Knows", void());
+        mosaic.new_extension(&a, "void", void());
+
+        let arrows = a.arrows_by_component();
+        assert_eq!(2, arrows.keys_len());
+        assert_eq!(
+            vec![a_b.id, a_c.id],
+            arrows
+                .get_all(&S32::from("Likes"))
+                .map(|t| t.id)
+                .collect_vec()
+        );
+        assert_eq!(
+            vec![knows.id],
+            arrows
+                .get_all(&S32::from("Knows"))
+                .map(|t| t.id)
+                .collect_vec()
+        );
+        assert_eq!(
+            vec![a_b.id],
+            b.arrows_into_by_component()
+                .get_all(&S32::from("Likes"))
+                .map(|t| t.id)
+                .collect_vec()
+        );
+
+        assert_eq!(Some(knows.clone()), a.arrow_to_with(&b, "Knows"));
+        assert_eq!(Some(a_c), a.arrow_to_with(&c, "Likes"));
+        assert_eq!(None, a.arrow_to_with(&c, "Knows"));
+        assert_eq!(None, b.arrow_to_with(&a, "Likes"));
+
+        mosaic.delete_tile(knows.id);
+        assert_eq!(None, a.arrow_to_with(&b, "Knows"));
+        assert_eq!(1, a.arrows_by_component().keys_len());
+    }
+}
+
+#[cfg(test)]
+mod namespace_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{NamespaceCapability, ParentingCapability},
+        internals::{void, Mosaic, MosaicIO, MosaicTypelevelCRUD},
+    };
+
+    #[test]
+    fn test_scopes() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Frame: unit;").unwrap();
+        let outside = mosaic.new_object("void", void());
+        let frame = mosaic.new_object("Frame", void());
+
+        let scope = mosaic.in_scope(&frame);
+        let a = scope.new_object("void", void());
+        let inner = scope.new_object("Frame", void());
+        let b = scope.in_scope(&inner).unwrap().new_object("void", void());
+        assert!(scope.in_scope(&outside).is_none());
+
+        assert_eq!(Some(frame.clone()), mosaic.get_parent(&a));
+        assert!(scope.contains(&b));
+        assert!(!scope.contains(&outside));
+        assert_eq!(
+            vec![a.id, inner.id, b.id],
+            scope.get_all().map(|t| t.id).collect_vec()
+        );
+        assert_eq!(
+            vec![a.id, b.id],
+            scope
+                .get_tiles_with_component("void")
+                .map(|t| t.id)
+                .sorted()
+                .collect_vec()
+        );
+        assert_eq!(
+            vec![inner.id],
+            scope
+                .query_str("SELECT tiles WITH Frame")
+                .unwrap()
+                .map(|t| t.id)
+                .collect_vec()
+        );
+
+        let closest = mosaic.scope_of(&b, |t| t.component.is("Frame")).unwrap();
+        assert_eq!(inner.id, closest.root().id);
+        scope.adopt(&outside).unwrap();
+        assert!(scope.contains(&outside));
+    }
+}
+
+#[cfg(test)]
+mod traversal_tests {
+    use std::sync::Arc;
+
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{TraversalCapability, TraversalFilter},
+        internals::{
+            par, pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+            MosaicTypelevelCRUD, Tile,
+        },
+    };
+
+    #[test]
+    fn test_reachability() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let d = mosaic.new_object("void", void());
+        mosaic.new_arrow(&a, &b, "void", void());
+        mosaic.new_arrow(&b, &c, "void", void());
+        mosaic.new_arrow(&c, &a, "void", void());
+        mosaic.new_arrow(&d, &a, "void", void());
+
+        assert_eq!(
+            vec![a.id, b.id, c.id],
+            mosaic.reachable_from(&a).map(|t| t.id).collect_vec()
+        );
+        assert!(mosaic.is_reachable(&d, &c));
+        assert!(!mosaic.is_reachable(&a, &d));
+    }
+
+    #[test]
+    fn test_lazy_paths() {
+        let mosaic = Mosaic::new();
+        let t = (0..4)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        mosaic.new_arrow(&t[0], &t[1], "void", void());
+        mosaic.new_arrow(&t[1], &t[2], "void", void());
+        mosaic.new_arrow(&t[0], &t[2], "void", void());
+        mosaic.new_arrow(&t[2], &t[0], "void", void());
+        mosaic.new_arrow(&t[2], &t[3], "void", void());
+
+        let ids = |path: Vec<Tile>| path.into_iter().map(|t| t.id).collect_vec();
+        assert_eq!(
+            vec![
+                vec![t[0].id, t[1].id, t[2].id, t[3].id],
+                vec![t[0].id, t[2].id, t[3].id],
+            ],
+            mosaic.paths_iter(&t[0]).map(ids).collect_vec()
+        );
+        assert_eq!(
+            Some(vec![t[0].id, t[2].id, t[3].id]),
+            mosaic
+                .paths_iter(&t[0])
+                .map(ids)
+                .find(|path| path.len() == 3)
+        );
+        assert_eq!(
+            vec![vec![t[3].id]],
+            mosaic.paths_iter(&t[3]).map(ids).collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_filtered_traversal() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Link: f32;").unwrap();
+        mosaic.new_type("Node: bool;").unwrap();
+        let node = |open: bool| mosaic.new_object("Node", par(open));
+        let (a, b, c, d) = (node(true), node(true), node(true), node(false));
+        mosaic.new_arrow(&a, &b, "Link", par(0.9f32));
+        mosaic.new_arrow(&a, &c, "Link", par(0.1f32));
+        mosaic.new_arrow(&b, &c, "Link", par(0.7f32));
+        mosaic.new_arrow(&b, &d, "Link", par(0.8f32));
+
+        let heavy = || {
+            TraversalFilter::new()
+                .with_arrow_filter(|arrow| arrow.get("self").as_f32() > 0.5)
+                .with_tile_filter(|tile| tile.get("self").as_bool())
+        };
+        assert_eq!(
+            vec![a.id, b.id, c.id],
+            mosaic
+                .reachable_from_filtered(&a, &heavy())
+                .map(|t| t.id)
+                .collect_vec()
+        );
+        assert_eq!(
+            vec![vec![a.id, b.id, c.id]],
+            mosaic
+                .paths_iter_filtered(&a, heavy())
+                .map(|path| path.into_iter().map(|t| t.id).collect_vec())
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_shortest_paths() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Road: f32;").unwrap();
+        mosaic.new_type("Point: { x: f32, y: f32 };").unwrap();
+
+        let point =
+            |x: f32, y: f32| mosaic.new_object("Point", pars().set("x", x).set("y", y).ok());
+        let a = point(0.0, 0.0);
+        let b = point(1.0, 0.0);
+        let c = point(2.0, 0.0);
+        let d = point(3.0, 0.0);
+        let e = point(9.0, 9.0);
+
+        mosaic.new_arrow(&a, &d, "Road", par(10.0f32));
+        mosaic.new_arrow(&a, &b, "Road", par(1.0f32));
+        mosaic.new_arrow(&b, &c, "Road", par(1.0f32));
+        mosaic.new_arrow(&c, &d, "Road", par(1.0f32));
+        mosaic.new_arrow(&b, &d, "Road", par(-1.0f32));
+
+        let weight = |arrow: &Tile| arrow.get("self").as_f32() as f64;
+        let ids = |path: Option<Vec<Tile>>| path.map(|p| p.iter().map(|t| t.id).collect_vec());
+
+        assert_eq!(
+            Some(vec![a.id, b.id, c.id, d.id]),
+            ids(mosaic.shortest_path(&a, &d, weight))
+        );
+
+        let heuristic = |t: &Tile| (d.get("x").as_f32() - t.get("x").as_f32()).abs() as f64;
+        assert_eq!(
+            Some(vec![a.id, b.id, c.id, d.id]),
+            ids(mosaic.shortest_path_astar(&a, &d, weight, heuristic))
+        );
+
+        assert_eq!(Some(vec![a.id]), ids(mosaic.shortest_path(&a, &a, weight)));
+        assert_eq!(None, ids(mosaic.shortest_path(&a, &e, weight)));
+        assert_eq!(None, ids(mosaic.shortest_path(&d, &a, weight)));
+    }
+
+    #[test]
+    fn test_cached_adjacency() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let ab = mosaic.new_arrow(&a, &b, "void", void());
+
+        let matrix = mosaic.adjacency();
+        assert!(matrix.are_adjacent(a.id, b.id));
+        assert!(Arc::ptr_eq(&matrix, &mosaic.adjacency()));
+
+        let c = mosaic.new_object("void", void());
+        mosaic.new_arrow(&b, &c, "void", void());
+        mosaic.delete_tile(ab.id);
+        let matrix = mosaic.adjacency();
+        assert!(matrix.get_front_neighbors(a.id).is_empty());
+        assert_eq!(vec![c.id], matrix.get_front_neighbors(b.id));
+
+        mosaic.delete_tile(c.id);
+        assert!(!mosaic.adjacency().are_adjacent(b.id, c.id));
+        mosaic.drop_adjacency();
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let mosaic = Mosaic::new();
+        let t = (0..7)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        let link = |a: usize, b: usize| {
+            mosaic.new_arrow(&t[a], &t[b], "void", void());
+        };
+        link(0, 1);
+        link(1, 2);
+        link(2, 0);
+        link(2, 3);
+        link(3, 4);
+        link(4, 3);
+        link(5, 5);
+
+        let ids = |groups: Vec<Vec<Tile>>| {
+            groups
+                .into_iter()
+                .map(|g| g.into_iter().map(|t| t.id).collect_vec())
+                .collect_vec()
+        };
+
+        assert_eq!(
+            vec![
+                vec![t[0].id, t[1].id, t[2].id],
+                vec![t[3].id, t[4].id],
+                vec![t[5].id],
+                vec![t[6].id],
+            ],
+            ids(mosaic.strongly_connected_components())
+        );
+        assert_eq!(
+            vec![
+                vec![t[0].id, t[1].id, t[2].id, t[3].id, t[4].id],
+                vec![t[5].id],
+                vec![t[6].id],
+            ],
+            ids(mosaic.connected_components())
+        );
+    }
+}
+
+#[cfg(test)]
+mod versioning_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{VersionedTile, VersioningCapability},
+        internals::{
+            par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, TileFieldSetter, Value,
+        },
+    };
+
+    #[test]
+    fn test_tile_versions() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Count: u32;").unwrap();
+        let mut counter = mosaic.new_object("Count", par(1u32));
+        mosaic.enable_versioning();
+        let start = mosaic.version();
+
+        counter.set("self", 2u32);
+        let middle = mosaic.version();
+        counter.set("self", 3u32);
+
+        let history = counter.history();
+        assert_eq!(2, history.len());
+        assert_eq!(Value::U32(2), history[0].after);
+        assert_eq!(Value::U32(2), history[1].before);
+        assert_eq!(
+            vec![("self".into(), Value::U32(1))],
+            counter.at(start).unwrap()
+        );
+        assert_eq!(
+            vec![("self".into(), Value::U32(2))],
+            counter.at(middle).unwrap()
+        );
+        assert!(counter.at(start - 1).is_none());
+
+        mosaic.disable_versioning();
+        assert!(counter.history().is_empty());
+    }
+
+    #[test]
+    fn test_checkout() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Count: u32;").unwrap();
+        mosaic.enable_versioning();
+        let mut a = mosaic.new_object("Count", par(1u32));
+        let b = mosaic.new_object("Count", par(5u32));
+        let arrow = mosaic.new_arrow(&a, &b, "void", void());
+        let before = mosaic.version();
+
+        a.set("self", 10u32);
+        mosaic.delete_tile(b.id);
+        let c = mosaic.new_object("Count", par(7u32));
+
+        let past = mosaic.checkout(before).unwrap();
+        let ids =
+            |tiles: std::vec::IntoIter<crate::internals::Tile>| tiles.map(|t| t.id).collect_vec();
+        assert_eq!(vec![a.id, b.id, arrow.id], ids(past.get_all()));
+        assert_eq!(Value::U32(1), past.get(a.id).unwrap().get("self"));
+        assert_eq!(Value::U32(5), past.get(b.id).unwrap().get("self"));
+        assert!(past.get(c.id).is_none());
+        assert_eq!(Value::U32(10), a.get("self"));
+
+        let now = mosaic.checkout(mosaic.version()).unwrap();
+        assert_eq!(vec![a.id, c.id], ids(now.get_all()));
+
+        mosaic.disable_versioning();
+        assert!(mosaic.checkout(before).is_err());
+    }
+
+    #[test]
+    fn test_checkout_restores_dependents() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Count: u32;").unwrap();
+        mosaic.enable_versioning();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let arrow = mosaic.new_arrow(&a, &b, "void", void());
+        let loop_arrow = mosaic.new_arrow(&a, &a, "void", void());
+        let count = mosaic.new_descriptor(&arrow, "Count", par(3u32));
+        let before = mosaic.version();
+
+        // everything hanging off of a goes with it, and only comes back after its endpoints
+        mosaic.delete_tile(a.id);
+        assert_eq!(vec![b.id], mosaic.get_all().map(|t| t.id).collect_vec());
+
+        let past = mosaic.checkout(before).unwrap();
+        assert_eq!(
+            vec![a.id, b.id, arrow.id, loop_arrow.id, count.id],
+            past.get_all().map(|t| t.id).sorted().collect_vec()
+        );
+        assert!(past.get(loop_arrow.id).unwrap().is_loop());
+        let restored = past.get(count.id).unwrap();
+        assert_eq!(arrow.id, restored.target_id());
+        assert_eq!(Value::U32(3), restored.get("self"));
+    }
+}
+
+#[cfg(test)]
+mod plugin_tests {
+    use std::sync::Arc;
+
+    use anyhow::anyhow;
+
+    use crate::{
+        capabilities::{MosaicPlugin, PluginCapability, PluginRegistry},
+        internals::{par, void, Mosaic, MosaicCRUD, MosaicIO, Tile},
+    };
+
+    struct Weigh;
+
+    impl MosaicPlugin for Weigh {
+        fn name(&self) -> &str {
+            "weigh"
+        }
+
+        fn required_types(&self) -> Vec<String> {
+            vec!["Weight: f32;".to_string()]
+        }
+
+        fn run(&self, mosaic: &Arc<Mosaic>, tile: &Tile) -> anyhow::Result<()> {
+            mosaic.new_extension(tile, "Weight", par(1.5f32));
+            Ok(())
+        }
+    }
+
+    struct Broken;
+
+    impl MosaicPlugin for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn run(&self, mosaic: &Arc<Mosaic>, tile: &Tile) -> anyhow::Result<()> {
+            mosaic.new_extension(tile, "void", void());
+            Err(anyhow!("broke after adding an extension"))
+        }
+    }
+
+    #[test]
+    fn test_plugins_run_on_tiles() {
+        let registry = PluginRegistry::new();
+        registry.register(Weigh);
+        registry.register(Broken);
+        assert_eq!(vec!["broken", "weigh"], registry.names());
+
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        mosaic.run_plugin(&registry, "weigh", &a).unwrap();
+        let weight = mosaic.get_all().find(|t| t.component.is("Weight")).unwrap();
+        assert_eq!(1.5, weight.get("self").as_f32());
+
+        assert!(mosaic.run_plugin(&registry, "broken", &a).is_err());
+        assert_eq!(2, mosaic.get_all().count());
+        assert!(mosaic.run_plugin(&registry, "missing", &a).is_err());
+    }
+}
+
+#[cfg(test)]
+mod operation_log_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::OperationLogCapability,
+        internals::{
+            par, void, LoggedChange, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD,
+            TileFieldSetter, Value,
+        },
+    };
+
+    #[test]
+    fn test_operation_log_replays() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Count: i32;").unwrap();
+        mosaic.new_type("Edge: unit;").unwrap();
+        let a = mosaic.new_object("Count", par(1i32));
+
+        mosaic.start_operation_log();
+        let mut b = mosaic.new_object("Count", par(2i32));
+        let a_b = mosaic.new_arrow(&a, &b, "Edge", void());
+        b.set("self", 5i32);
+        mosaic.delete_tile(a.id);
+
+        let log = mosaic.stop_operation_log().unwrap();
+        assert!(mosaic.operation_log().is_none());
+        assert_eq!(8, log.len());
+        assert_eq!(
+            LoggedChange::Type("Count: i32;".to_string()),
+            log.operations[0].change
+        );
+        assert!(log
+            .operations
+            .iter()
+            .tuple_windows()
+            .all(|(x, y)| x.timestamp <= y.timestamp));
+
+        let before = log.replay_until(5).unwrap();
+        assert_eq!(
+            vec![a.id, b.id, a_b.id],
+            before.get_all().map(|t| t.id).sorted().collect_vec()
+        );
+        assert_eq!(Value::I32(2), before.get(b.id).unwrap().get("self"));
+
+        let after = log.replay().unwrap();
+        assert_eq!(vec![b.id], after.get_all().map(|t| t.id).collect_vec());
+        assert_eq!(Value::I32(5), after.get(b.id).unwrap().get("self"));
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use crate::{
+        capabilities::MetricsCapability,
+        internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices, MosaicTypelevelCRUD},
+    };
+
+    #[test]
+    fn test_graph_metrics() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Edge: unit;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let d = mosaic.new_object("void", void());
+        // a triangle a -> b -> c -> a, with d hanging off c
+        mosaic.new_arrow(&a, &b, "Edge", void());
+        mosaic.new_arrow(&b, &c, "Edge", void());
+        mosaic.new_arrow(&c, &a, "Edge", void());
+        mosaic.new_arrow(&c, &d, "Edge", void());
+        mosaic.new_arrow(&c, &d, "Edge", void());
+
+        let degrees = mosaic.degrees();
+        assert_eq!(2, degrees[&a.id]);
+        assert_eq!(3, degrees[&c.id]);
+        assert_eq!(1, degrees[&d.id]);
+        assert_eq!(
+            vec![(1, 1), (2, 2), (3, 1)],
+            mosaic.degree_distribution().into_iter().collect::<Vec<_>>()
+        );
+
+        let clustering = mosaic.clustering_coefficients();
+        assert_eq!(1.0, clustering[&a.id]);
+        assert!((clustering[&c.id] - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(0.0, clustering[&d.id]);
+
+        // the paths from a and b into d go through c, as does the one from b to a
+        let betweenness = mosaic.betweenness_centrality();
+        assert_eq!(3.0, betweenness[&c.id]);
+        assert_eq!(2.0, betweenness[&b.id]);
+        assert_eq!(0.0, betweenness[&d.id]);
+
+        let closeness = mosaic.closeness_centrality();
+        assert_eq!(0.0, closeness[&d.id]);
+        assert_eq!(0.75, closeness[&c.id]);
+
+        let pagerank = mosaic.pagerank(0.85);
+        assert!((pagerank.values().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert!(pagerank[&c.id] > pagerank[&b.id]);
+
+        let report = mosaic.metrics_report();
+        assert_eq!(mosaic.degree_distribution(), report.degree_distribution);
+        assert_eq!(pagerank[&a.id], report.tiles[&a.id].pagerank);
+        assert_eq!(4, mosaic.attach_metrics(&report).len());
+        mosaic.attach_metrics(&report);
+        let metrics = mosaic
+            .get_tiles_into_with(c.id, "Metrics")
+            .collect::<Vec<_>>();
+        assert_eq!(1, metrics.len());
+        assert_eq!(3u64, metrics[0].get("degree").as_u64());
+    }
+}
+
+#[cfg(test)]
+mod comparison_tests {
+    use std::sync::Arc;
+
+    use crate::{
+        capabilities::MosaicComparison,
+        internals::{par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, Tile},
+    };
+
+    /// A path a -> b -> c of `Node`s with a label on b, made in the given order of nodes.
+    fn path(order: [usize; 3], weight: i32) -> (Arc<Mosaic>, Vec<Tile>) {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Node: i32;").unwrap();
+        mosaic.new_type("Edge: unit;").unwrap();
+        mosaic.new_type("Label: s32;").unwrap();
+        let mut nodes: Vec<Option<Tile>> = vec![None, None, None];
+        for i in order {
+            nodes[i] = Some(mosaic.new_object("Node", par(i as i32 * weight)));
+        }
+        let nodes = nodes.into_iter().flatten().collect::<Vec<_>>();
+        mosaic.new_descriptor(&nodes[1], "Label", par("middle"));
+        mosaic.new_arrow(&nodes[0], &nodes[1], "Edge", void());
+        mosaic.new_arrow(&nodes[1], &nodes[2], "Edge", void());
+        (mosaic, nodes)
+    }
+
+    #[test]
+    fn test_structural_equality() {
+        let (a, _) = path([0, 1, 2], 1);
+        let (b, nodes) = path([2, 0, 1], 1);
+        assert_ne!(a, b);
+        assert!(a.structurally_equals(&b));
+        assert!(b.structurally_equals(&a));
+        assert!(a.is_isomorphic_to(&b));
+
+        // different fields, same shape
+        let (c, _) = path([0, 1, 2], 2);
+        assert!(!a.structurally_equals(&c));
+        assert!(a.is_isomorphic_to(&c));
+
+        // a label moved off the middle node
+        let label = b.get_all().find(|t| t.is_descriptor()).unwrap();
+        b.delete_tile(label);
+        b.new_descriptor(&nodes[0], "Label", par("middle"));
+        assert!(!a.structurally_equals(&b));
+        assert!(a.is_isomorphic_to(&b));
+
+        // a -> b <- c isn't a path
+        let d = Mosaic::new();
+        let x = d.new_object("void", void());
+        let y = d.new_object("void", void());
+        let z = d.new_object("void", void());
+        d.new_arrow(&x, &y, "void", void());
+        d.new_arrow(&z, &y, "void", void());
+        assert!(!a.is_isomorphic_to(&d));
+        assert!(Mosaic::new().is_isomorphic_to(&Mosaic::new()));
+        assert!(Mosaic::new().structurally_equals(&Mosaic::new()));
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::AuditCapability,
+        internals::{
+            par, void, DateTime, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices, MosaicTypelevelCRUD,
+            TileFieldSetter,
+        },
+    };
+
+    #[test]
+    fn test_audit() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Count: i32;").unwrap();
+        mosaic.new_type("Note: s32;").unwrap();
+        let before = mosaic.new_object("Count", par(0i32));
+        mosaic.enable_audit(&["Count", "void"]);
+        mosaic.enable_audit(&["Count"]);
+        assert!(mosaic.is_audit_enabled());
+
+        let start = DateTime::now();
+        let mut a = mosaic.new_object("Count", par(1i32));
+        let b = mosaic.new_object("void", void());
+        let ab = mosaic.new_arrow(&a, &b, "void", void());
+        // descriptors, and tiles of components that aren't audited, aren't stamped
+        mosaic.new_descriptor(&a, "Count", par(4i32));
+        let note = mosaic.new_object("Note", par("unaudited"));
+        assert_eq!(None, mosaic.created_at(&before));
+        assert_eq!(None, mosaic.created_at(&note));
+        assert!(mosaic.created_at(&a).is_some_and(|at| at >= start));
+        assert_eq!(mosaic.created_at(&b), mosaic.modified_at(&b));
+        assert!(mosaic.created_at(&ab).is_some());
+        assert_eq!(3, mosaic.get_tiles_with_component("Created").count());
+        assert_eq!(0, mosaic.get_tiles_with_component("Modified").count());
+
+        let ids = |since| mosaic.modified_since(since).map(|t| t.id).collect_vec();
+        assert_eq!(vec![a.id, b.id, ab.id], ids(start));
+
+        let later = DateTime::now();
+        a.set("self", 2i32);
+        a.set("self", 3i32);
+        let mut before_tile = before.clone();
+        before_tile.set("self", 5i32);
+        assert_eq!(2, mosaic.get_tiles_with_component("Modified").count());
+        assert!(mosaic.modified_at(&a) >= mosaic.created_at(&a));
+        assert!(mosaic.modified_at(&before).is_some_and(|at| at >= later));
+        assert_eq!(vec![before.id, a.id], ids(later));
+        assert_eq!(
+            vec![a.id, b.id, ab.id],
+            mosaic.created_since(start).map(|t| t.id).collect_vec()
+        );
+        assert!(ids(DateTime::now() + crate::internals::Duration::from_secs(60)).is_empty());
+
+        mosaic.disable_audit();
+        assert!(!mosaic.is_audit_enabled());
+        let c = mosaic.new_object("void", void());
+        a.set("self", 6i32);
+        assert_eq!(None, mosaic.created_at(&c));
+        assert!(mosaic.created_at(&a).is_some());
+
+        // enabling again doesn't hook the components twice
+        mosaic.enable_audit(&["Count"]);
+        let d = mosaic.new_object("Count", par(7i32));
+        assert_eq!(1, mosaic.get_tiles_into_with(d.id, "Created").count());
+        a.set("self", 8i32);
+        assert_eq!(1, mosaic.get_tiles_into_with(a.id, "Modified").count());
+        assert_eq!(None, mosaic.created_at(&mosaic.new_object("void", void())));
+    }
+}
+
+#[cfg(test)]
+mod labels_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::{LabelCapability, TileLabels},
+        internals::{void, Mosaic, MosaicIO, MosaicIndices, MosaicTypelevelCRUD},
+    };
+
+    #[test]
+    fn test_labels_and_comments() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        assert_eq!(None, a.label());
+        assert_eq!(0, mosaic.find_by_label("start").count());
+
+        a.set_label("start").unwrap();
+        b.set_label("start").unwrap();
+        c.set_label("end").unwrap();
+        b.set_label("middle").unwrap();
+        assert_eq!(Some("middle".to_string()), b.label());
+        assert_eq!(1, mosaic.get_tiles_into_with(b.id, "Label").count());
+        assert_eq!(
+            vec![a.id],
+            mosaic.find_by_label("start").map(|t| t.id).collect_vec()
+        );
+        assert_eq!(
+            vec!["start", "middle", "end"],
+            mosaic.labels().into_iter().map(|(_, l)| l).collect_vec()
+        );
+
+        c.set_comment("the last one").unwrap();
+        assert_eq!(Some("the last one".to_string()), c.comment());
+        assert_eq!(0, mosaic.find_by_label("the last one").count());
+        assert!(c.remove_comment());
+        assert!(!c.remove_comment());
+        assert_eq!(None, c.comment());
+
+        // labels travel with saves under the standard names
+        let loaded = Mosaic::new();
+        loaded.load(&mosaic.save()).unwrap();
+        assert_eq!(
+            vec![c.id],
+            loaded.find_by_label("end").map(|t| t.id).collect_vec()
+        );
+
+        assert!(a.remove_label());
+        assert_eq!(0, mosaic.find_by_label("start").count());
+
+        let other = Mosaic::new();
+        other.new_type("Label: s32;").unwrap();
+        let d = other.new_object("void", void());
+        assert!(d.set_label("taken").is_err());
+        assert!(other.add_label_components().is_err());
+        assert!(other.labels().is_empty());
+    }
 }
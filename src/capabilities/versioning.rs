@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    vec::IntoIter,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+
+use crate::internals::{
+    ComponentValues, EntityId, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices, MosaicObservable,
+    MosaicObserver, SubscriptionId, Tile, TileType, Value, Version, S32,
+};
+
+/// One change to a field of a tile, stamped with the version of the mosaic it made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileVersion {
+    pub version: Version,
+    pub field: S32,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// A tile that has been deleted since versioning started, as it was right before.
+struct RemovedTile {
+    id: EntityId,
+    tile_type: TileType,
+    component: S32,
+    data: ComponentValues,
+    created: Option<Version>,
+    deleted: Version,
+}
+
+#[derive(Default)]
+struct VersionState {
+    /// When each live tile was made, for tiles made after versioning started.
+    created: HashMap<EntityId, Version>,
+    changes: HashMap<EntityId, Vec<TileVersion>>,
+    removed: Vec<RemovedTile>,
+}
+
+struct VersionLog {
+    since: Version,
+    state: Mutex<VersionState>,
+}
+
+type VersionLogs = HashMap<usize, (SubscriptionId, Arc<VersionLog>)>;
+
+static VERSION_LOGS: Lazy<Mutex<VersionLogs>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn log_of(mosaic: &Mosaic) -> Option<Arc<VersionLog>> {
+    VERSION_LOGS
+        .lock()
+        .unwrap()
+        .get(&mosaic.id)
+        .map(|(_, log)| Arc::clone(log))
+}
+
+impl MosaicObserver for VersionLog {
+    fn on_tile_created(&self, tile: &Tile) {
+        let version = tile.mosaic.version();
+        self.state.lock().unwrap().created.insert(tile.id, version);
+    }
+
+    fn on_tile_deleted(&self, tile: &Tile) {
+        let (deleted, data) = (tile.mosaic.version(), tile.data());
+        let mut state = self.state.lock().unwrap();
+        let created = state.created.remove(&tile.id);
+        state.removed.push(RemovedTile {
+            id: tile.id,
+            tile_type: tile.tile_type,
+            component: tile.component,
+            data,
+            created,
+            deleted,
+        });
+    }
+
+    fn on_field_changed(&self, tile: &Tile, field: &str, before: &Value, after: &Value) {
+        let version = tile.mosaic.version();
+        self.state
+            .lock()
+            .unwrap()
+            .changes
+            .entry(tile.id)
+            .or_default()
+            .push(TileVersion {
+                version,
+                field: field.into(),
+                before: before.clone(),
+                after: after.clone(),
+            });
+    }
+}
+
+impl VersionState {
+    /// The changes made to one life of tile `id`, the one between `created` and `deleted`;
+    /// ids can be handed out again once their tile is gone.
+    fn changes_between(
+        &self,
+        id: EntityId,
+        created: Option<Version>,
+        deleted: Option<Version>,
+    ) -> Vec<TileVersion> {
+        self.changes
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter(|c| created.is_none_or(|v| c.version > v))
+            .filter(|c| deleted.is_none_or(|v| c.version < v))
+            .cloned()
+            .collect_vec()
+    }
+}
+
+/// Winds `data` back to what it was at `version`, by undoing every later change.
+fn rewind(mut data: ComponentValues, changes: &[TileVersion], version: Version) -> ComponentValues {
+    for change in changes.iter().filter(|c| c.version > version).rev() {
+        if let Some((_, value)) = data.iter_mut().find(|(f, _)| *f == change.field) {
+            *value = change.before.clone();
+        }
+    }
+    data
+}
+
+fn alive_at(created: Option<Version>, deleted: Option<Version>, version: Version) -> bool {
+    created.is_none_or(|c| c <= version) && deleted.is_none_or(|d| d > version)
+}
+
+/// A copy of a mosaic as it was at some version, rebuilt from the version log; tiles keep
+/// their ids, but changing them doesn't reach the original.
+pub struct MosaicCheckout {
+    pub version: Version,
+    mosaic: Arc<Mosaic>,
+}
+
+impl MosaicCheckout {
+    pub fn get(&self, id: EntityId) -> Option<Tile> {
+        self.mosaic.get(id)
+    }
+
+    pub fn get_all(&self) -> IntoIter<Tile> {
+        self.mosaic
+            .get_all()
+            .sorted_by_key(|t| t.id)
+            .collect_vec()
+            .into_iter()
+    }
+
+    pub fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile> {
+        self.mosaic.get_tiles_with_component(component)
+    }
+}
+
+/// Keeps every change made to the fields of tiles, so tiles and whole mosaics can be looked
+/// at as they were at an earlier version. History only goes back to when it was turned on.
+pub trait VersioningCapability {
+    fn enable_versioning(&self);
+    /// Stops keeping versions, and forgets those kept so far.
+    fn disable_versioning(&self);
+    fn is_versioning_enabled(&self) -> bool;
+    /// Rebuilds the mosaic as it was at `version`; fails if versioning is off, or was turned
+    /// on only after `version`.
+    fn checkout(&self, version: Version) -> anyhow::Result<MosaicCheckout>;
+}
+
+impl VersioningCapability for Arc<Mosaic> {
+    fn enable_versioning(&self) {
+        let mut logs = VERSION_LOGS.lock().unwrap();
+        if logs.contains_key(&self.id) {
+            return;
+        }
+
+        let log = Arc::new(VersionLog {
+            since: self.version(),
+            state: Mutex::new(VersionState::default()),
+        });
+        let subscription = self.subscribe(Arc::clone(&log) as Arc<dyn MosaicObserver>);
+        logs.insert(self.id, (subscription, log));
+    }
+
+    fn disable_versioning(&self) {
+        let removed = VERSION_LOGS.lock().unwrap().remove(&self.id);
+        if let Some((subscription, _)) = removed {
+            self.unsubscribe(subscription);
+        }
+    }
+
+    fn is_versioning_enabled(&self) -> bool {
+        VERSION_LOGS.lock().unwrap().contains_key(&self.id)
+    }
+
+    fn checkout(&self, version: Version) -> anyhow::Result<MosaicCheckout> {
+        let log = log_of(self).ok_or_else(|| anyhow!("Versioning is not enabled"))?;
+        if version < log.since {
+            return Err(anyhow!(
+                "Versions are only kept from {} on, there is nothing for {}",
+                log.since,
+                version
+            ));
+        }
+
+        let mut tiles = vec![];
+        {
+            let state = log.state.lock().unwrap();
+            for tile in self.get_all() {
+                let created = state.created.get(&tile.id).copied();
+                if alive_at(created, None, version) {
+                    let changes = state.changes_between(tile.id, created, None);
+                    let data = rewind(tile.data(), &changes, version);
+                    tiles.push((tile.id, tile.tile_type, tile.component, data));
+                }
+            }
+
+            for removed in &state.removed {
+                if alive_at(removed.created, Some(removed.deleted), version) {
+                    let changes =
+                        state.changes_between(removed.id, removed.created, Some(removed.deleted));
+                    let data = rewind(removed.data.clone(), &changes, version);
+                    tiles.push((removed.id, removed.tile_type, removed.component, data));
+                }
+            }
+        }
+
+        let copy = Mosaic::new();
+        copy.copy_component_types(self)?;
+        // endpoints go in before the tiles that hang off of them
+        let mut pending = tiles.into_iter().sorted_by_key(|t| t.0).collect_vec();
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<_>, Vec<_>) =
+                pending.into_iter().partition(|(id, tile_type, _, _)| {
+                    let endpoints = match *tile_type {
+                        TileType::Object => vec![],
+                        TileType::Arrow { source, target } => vec![source, target],
+                        TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                            vec![subject]
+                        }
+                    };
+                    endpoints.iter().all(|e| e == id || copy.is_tile_valid(e))
+                });
+            if ready.is_empty() {
+                break;
+            }
+
+            for (id, tile_type, component, data) in ready {
+                copy.restore_tile(id, tile_type, component, data);
+            }
+            pending = waiting;
+        }
+
+        Ok(MosaicCheckout {
+            version,
+            mosaic: copy,
+        })
+    }
+}
+
+/// The versions of a single tile, for mosaics with versioning enabled.
+pub trait VersionedTile {
+    /// Every change made to this tile since versioning started, oldest first.
+    fn history(&self) -> Vec<TileVersion>;
+    /// The field values of this tile at `version`, or `None` if it didn't exist yet then, or
+    /// versioning wasn't on.
+    fn at(&self, version: Version) -> Option<ComponentValues>;
+}
+
+impl VersionedTile for Tile {
+    fn history(&self) -> Vec<TileVersion> {
+        let Some(log) = log_of(&self.mosaic) else {
+            return vec![];
+        };
+
+        let state = log.state.lock().unwrap();
+        let created = state.created.get(&self.id).copied();
+        state.changes_between(self.id, created, None)
+    }
+
+    fn at(&self, version: Version) -> Option<ComponentValues> {
+        let log = log_of(&self.mosaic).filter(|log| version >= log.since)?;
+        let state = log.state.lock().unwrap();
+        let created = state.created.get(&self.id).copied();
+        alive_at(created, None, version).then(|| {
+            let changes = state.changes_between(self.id, created, None);
+            rewind(self.data(), &changes, version)
+        })
+    }
+}
@@ -1,26 +1,88 @@
 #![allow(dead_code)]
 
+pub mod access;
+pub mod blob;
+pub mod bulk;
 pub mod byte_utilities;
+pub mod compaction;
 pub mod component_grammar;
 pub mod component_registry;
+pub mod computed_fields;
+pub mod constraints;
+pub mod crdt;
 pub mod datatypes;
+pub mod datetime;
 pub mod either;
+pub mod error;
 pub mod freelist;
+pub mod garbage_collection;
+pub mod history;
+pub mod import;
 pub mod logging;
+pub mod merge;
 pub mod mosaic;
+pub mod object_builder;
+pub mod observer;
+pub mod operation_log;
+pub mod restructure;
+pub mod save_format;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod snapshot;
 pub mod sparse_matrix;
 pub mod sparse_set;
+pub mod statistics;
+pub mod storage;
+pub mod string_pool;
+pub mod subgraph;
 pub mod tile;
 pub mod tile_access;
+pub mod tile_handle;
+pub mod tile_indices;
+pub mod tile_ref;
+pub mod transaction;
+pub mod typed_component;
+pub mod weak_tile;
 
 mod unit_tests;
 
+pub use access::*;
+pub use blob::*;
+pub use bulk::*;
 pub use byte_utilities::*;
+pub use compaction::*;
 pub use component_registry::*;
+pub use computed_fields::*;
+pub use constraints::*;
+pub use crdt::*;
 pub use datatypes::*;
+pub use datetime::*;
+pub use error::*;
 pub use freelist::*;
+pub use garbage_collection::*;
+pub use history::*;
+pub use import::*;
 pub use logging::*;
+pub use merge::*;
 pub use mosaic::*;
+pub use object_builder::*;
+pub use observer::*;
+pub use operation_log::*;
+pub use restructure::*;
+pub use save_format::*;
+#[cfg(feature = "serde")]
+pub use serialization::*;
+pub use snapshot::*;
 pub use sparse_set::*;
+pub use statistics::*;
+pub use storage::*;
+pub use string_pool::*;
+pub use subgraph::*;
 pub use tile::*;
 pub use tile_access::*;
+pub use tile_handle::*;
+pub use tile_indices::*;
+pub use tile_ref::*;
+pub use transaction::*;
+pub use typed_component::*;
+pub use weak_tile::*;
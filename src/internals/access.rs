@@ -0,0 +1,109 @@
+use std::{sync::Arc, vec::IntoIter};
+
+use super::{
+    EntityId, Mosaic, MosaicCRUD, MosaicError, MosaicIO, MosaicIndices, Tile, Uuid, Version, S32,
+};
+
+/// A mosaic that can only be read from, as it has none of the CRUD traits. Its tiles are
+/// plain tiles, so guard components with `MosaicAccess::guard_component` to keep them as is.
+#[derive(Clone)]
+pub struct MosaicView {
+    mosaic: Arc<Mosaic>,
+}
+
+/// What can be done with a `MosaicView`.
+pub trait MosaicReadOnly {
+    fn get(&self, id: EntityId) -> Option<Tile>;
+    fn get_all(&self) -> IntoIter<Tile>;
+    fn is_tile_valid(&self, id: &EntityId) -> bool;
+    fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile>;
+    fn get_tiles_from(&self, source: EntityId) -> IntoIter<Tile>;
+    fn get_tiles_into(&self, target: EntityId) -> IntoIter<Tile>;
+    fn version(&self) -> Version;
+    fn uuid(&self) -> Uuid;
+    fn save(&self) -> Vec<u8>;
+}
+
+impl MosaicReadOnly for MosaicView {
+    fn get(&self, id: EntityId) -> Option<Tile> {
+        self.mosaic.get(id)
+    }
+
+    fn get_all(&self) -> IntoIter<Tile> {
+        self.mosaic.get_all()
+    }
+
+    fn is_tile_valid(&self, id: &EntityId) -> bool {
+        self.mosaic.is_tile_valid(id)
+    }
+
+    fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile> {
+        self.mosaic.get_tiles_with_component(component)
+    }
+
+    fn get_tiles_from(&self, source: EntityId) -> IntoIter<Tile> {
+        self.mosaic.get_tiles_from(source)
+    }
+
+    fn get_tiles_into(&self, target: EntityId) -> IntoIter<Tile> {
+        self.mosaic.get_tiles_into(target)
+    }
+
+    fn version(&self) -> Version {
+        self.mosaic.version()
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.mosaic.uuid()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.mosaic.save()
+    }
+}
+
+pub trait MosaicAccess {
+    fn read_only_view(&self) -> MosaicView;
+    /// Refuses writes to `component` until `unguard_component`, failing with
+    /// `MosaicError::ReadOnlyComponent`; undo, redo, rollbacks and deletes still go through.
+    fn guard_component(&self, component: &str);
+    fn unguard_component(&self, component: &str);
+    fn is_component_guarded(&self, component: &str) -> bool;
+}
+
+impl MosaicAccess for Arc<Mosaic> {
+    fn read_only_view(&self) -> MosaicView {
+        MosaicView {
+            mosaic: Arc::clone(self),
+        }
+    }
+
+    fn guard_component(&self, component: &str) {
+        let component = self.component_registry.resolve_name(component.into());
+        self.guarded_components.lock().unwrap().insert(component);
+    }
+
+    fn unguard_component(&self, component: &str) {
+        let component = self.component_registry.resolve_name(component.into());
+        self.guarded_components.lock().unwrap().remove(&component);
+    }
+
+    fn is_component_guarded(&self, component: &str) -> bool {
+        let component = self.component_registry.resolve_name(component.into());
+        self.guarded_components.lock().unwrap().contains(&component)
+    }
+}
+
+impl Mosaic {
+    /// Fails if `component` is guarded, unless history is being replayed.
+    pub(crate) fn check_writable(&self, component: S32) -> anyhow::Result<()> {
+        let component = self.component_registry.resolve_name(component);
+        if !self.guarded_components.lock().unwrap().contains(&component)
+            || self.history.lock().unwrap().replaying
+        {
+            return Ok(());
+        }
+
+        Err(MosaicError::ReadOnlyComponent(component).into())
+    }
+}
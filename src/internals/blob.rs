@@ -0,0 +1,190 @@
+use std::{
+    fmt::Display,
+    io::{Cursor, Read},
+    sync::Arc,
+};
+
+use super::{FromByteArray, Mosaic, ToByteArray};
+
+/// Names a blob by its contents: the BLAKE3 hash of its bytes, or all zeros for no bytes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlobId(pub [u8; 32]);
+
+impl BlobId {
+    pub const EMPTY: BlobId = BlobId([0; 32]);
+
+    pub fn of(bytes: &[u8]) -> BlobId {
+        match bytes.is_empty() {
+            true => BlobId::EMPTY,
+            false => BlobId(*blake3::hash(bytes).as_bytes()),
+        }
+    }
+}
+
+impl Display for BlobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|b| f.write_fmt(format_args!("{:02x}", b)))
+    }
+}
+
+impl ToByteArray for BlobId {
+    fn to_byte_array(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+}
+
+impl FromByteArray for BlobId {
+    fn from_byte_array(data: &[u8]) -> Self {
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&data[0..32]);
+        BlobId(id)
+    }
+}
+
+/// Reads the bytes of a blob, see `Blob::reader`.
+pub type BlobReader = Cursor<Arc<[u8]>>;
+
+/// Bytes kept in `blob` fields, e.g. an image. Fields hold only the id; the bytes are kept
+/// once in the string pool, however many fields hold them.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Blob {
+    id: BlobId,
+    bytes: Arc<[u8]>,
+}
+
+impl Blob {
+    pub fn new<B: Into<Arc<[u8]>>>(bytes: B) -> Blob {
+        let bytes = bytes.into();
+        Blob {
+            id: BlobId::of(&bytes),
+            bytes,
+        }
+    }
+
+    pub fn empty() -> Blob {
+        Blob {
+            id: BlobId::EMPTY,
+            bytes: Arc::from([]),
+        }
+    }
+
+    /// Reads `reader` to its end into a new blob, hashing it along the way.
+    pub fn read_from<R: Read>(mut reader: R) -> std::io::Result<Blob> {
+        let mut hasher = blake3::Hasher::new();
+        let mut bytes = vec![];
+        let mut chunk = [0u8; 64 * 1024];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    hasher.update(&chunk[..n]);
+                    bytes.extend_from_slice(&chunk[..n]);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        match bytes.is_empty() {
+            true => Ok(Blob::empty()),
+            false => Ok(Blob {
+                id: BlobId(*hasher.finalize().as_bytes()),
+                bytes: bytes.into(),
+            }),
+        }
+    }
+
+    /// A blob only known by its id, as read from the data of a saved tile, until its bytes
+    /// are looked up in the string pool.
+    pub(crate) fn unresolved(id: BlobId) -> Blob {
+        Blob {
+            id,
+            bytes: Arc::from([]),
+        }
+    }
+
+    pub(crate) fn shared_bytes(&self) -> &Arc<[u8]> {
+        &self.bytes
+    }
+
+    pub fn id(&self) -> BlobId {
+        self.id
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn reader(&self) -> BlobReader {
+        Cursor::new(Arc::clone(&self.bytes))
+    }
+}
+
+/// Only the id, which is what a field holds.
+impl ToByteArray for Blob {
+    fn to_byte_array(&self) -> Vec<u8> {
+        self.id.to_byte_array()
+    }
+}
+
+impl Default for Blob {
+    fn default() -> Self {
+        Blob::empty()
+    }
+}
+
+/// Blobs are the same if their contents are.
+impl PartialEq for Blob {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl std::fmt::Debug for Blob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("Blob({}, {} bytes)", self.id, self.len()))
+    }
+}
+
+impl Display for Blob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("blob {} ({} bytes)", self.id, self.len()))
+    }
+}
+
+pub trait MosaicBlobs {
+    /// Reads `reader` into a blob of this mosaic, to be put in a `blob` field before the next
+    /// garbage collection takes it.
+    fn write_blob<R: Read>(&self, reader: R) -> anyhow::Result<Blob>;
+    /// The blob with `id`, if this mosaic keeps it.
+    fn get_blob(&self, id: BlobId) -> Option<Blob>;
+    /// Reads the bytes of the blob with `id`, if this mosaic keeps it.
+    fn read_blob(&self, id: BlobId) -> Option<BlobReader>;
+}
+
+impl MosaicBlobs for Arc<Mosaic> {
+    fn write_blob<R: Read>(&self, reader: R) -> anyhow::Result<Blob> {
+        let blob = Blob::read_from(reader)?;
+        Ok(self.strings.write().unwrap().intern_blob(blob))
+    }
+
+    fn get_blob(&self, id: BlobId) -> Option<Blob> {
+        self.strings.read().unwrap().resolve_blob(id)
+    }
+
+    fn read_blob(&self, id: BlobId) -> Option<BlobReader> {
+        self.get_blob(id).map(|blob| blob.reader())
+    }
+}
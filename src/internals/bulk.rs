@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use super::{ComponentValues, FieldCache, Mosaic, MosaicCRUD, Tile, TileType};
+
+pub trait MosaicBulkCRUD {
+    /// Creates `count` objects that all start with the same `defaults`.
+    fn new_objects(&self, component: &str, count: usize, defaults: ComponentValues) -> Vec<Tile>;
+    /// Creates one arrow for every `(source, target)` pair, in order, with default field values.
+    fn new_arrows(&self, edges: &[(Tile, Tile)], component: &str) -> Vec<Tile>;
+    /// Like `new_arrows`, but returns an error instead of panicking when a constraint is broken;
+    /// the arrows made before the broken one are kept.
+    fn try_new_arrows(&self, edges: &[(Tile, Tile)], component: &str) -> anyhow::Result<Vec<Tile>>;
+}
+
+impl MosaicBulkCRUD for Arc<Mosaic> {
+    fn new_objects(&self, component: &str, count: usize, defaults: ComponentValues) -> Vec<Tile> {
+        let component = self.component_registry.resolve_name(component.into());
+        self.check_writable(component)
+            .expect("Cannot create objects, panicking!");
+        let fields = Tile::resolve_data_fields(self, component, defaults)
+            .expect("Cannot create data fields, panicking!");
+
+        let tiles = self
+            .next_ids(count)
+            .into_iter()
+            .map(|id| Tile {
+                id,
+                mosaic: Arc::clone(self),
+                tile_type: TileType::Object,
+                component,
+                generation: self.next_generation(),
+                fields_cache: FieldCache::default(),
+            })
+            .collect_vec();
+
+        self.insert_tiles(&tiles, fields);
+        tiles
+    }
+
+    fn new_arrows(&self, edges: &[(Tile, Tile)], component: &str) -> Vec<Tile> {
+        self.try_new_arrows(edges, component)
+            .expect("Cannot create arrows, panicking!")
+    }
+
+    fn try_new_arrows(&self, edges: &[(Tile, Tile)], component: &str) -> anyhow::Result<Vec<Tile>> {
+        // constraints count the arrows already there, so each one has to see the ones before it
+        if !self.constraints.lock().unwrap().is_empty() {
+            return edges
+                .iter()
+                .map(|(source, target)| self.try_new_arrow(source, target, component, vec![]))
+                .collect();
+        }
+
+        let component = self.component_registry.resolve_name(component.into());
+        self.check_writable(component)?;
+        let fields = Tile::resolve_data_fields(self, component, vec![])?;
+        let tiles = self
+            .next_ids(edges.len())
+            .into_iter()
+            .zip(edges)
+            .map(|(id, (source, target))| Tile {
+                id,
+                mosaic: Arc::clone(self),
+                tile_type: TileType::Arrow {
+                    source: source.id,
+                    target: target.id,
+                },
+                component,
+                generation: self.next_generation(),
+                fields_cache: FieldCache::default(),
+            })
+            .collect_vec();
+
+        self.insert_tiles(&tiles, fields);
+        Ok(tiles)
+    }
+}
@@ -202,8 +202,8 @@ impl ToByteArray for f64 {
 /// The `FromByteArray` implementation for `s32`
 impl FromByteArray for S32 {
     fn from_byte_array(data: &[u8]) -> Self {
-        let str = std::str::from_utf8(data);
-        S32(FStr::<32>::from_str_lossy(str.unwrap(), b'\0'))
+        let str = String::from_utf8_lossy(data);
+        S32(FStr::<32>::from_str_lossy(&str, b'\0'))
     }
 }
 
@@ -225,8 +225,7 @@ impl ToByteArray for String {
 impl FromByteArray for String {
     fn from_byte_array(data: &[u8]) -> Self {
         let len = u64::from_byte_array(&data[0..8]);
-        let str = std::str::from_utf8(&data[8..(8 + len as usize)]);
-        String::from_utf8_lossy(str.unwrap().as_bytes()).into_owned()
+        String::from_utf8_lossy(&data[8..(8 + len as usize)]).into_owned()
     }
 }
 
@@ -247,6 +246,9 @@ impl Bytesize for ComponentType {
                 .fold(0usize, |old, ComponentField { datatype, .. }| {
                     old + datatype.bytesize(engine, data)
                 }),
+            ComponentType::Sum { variants, .. } => {
+                Datatype::SUM(variants.clone()).bytesize(engine, data)
+            }
         }
     }
 }
@@ -261,16 +263,64 @@ impl Bytesize for Datatype {
             Datatype::I16 | Datatype::U16 => 2usize,
             Datatype::I32 | Datatype::U32 | Datatype::F32 => 4usize,
             Datatype::I64 | Datatype::U64 | Datatype::F64 => 8usize,
-            Datatype::S32 => 32usize,
-            Datatype::STR => 8usize + u64::from_be_bytes(slice_into_array(&data[0..8])) as usize,
+            Datatype::DATETIME | Datatype::DURATION => 8usize,
+            Datatype::REF => 24usize,
+            Datatype::S32 | Datatype::BLOB => 32usize,
+            Datatype::STR if data.len() < 8 => 8usize,
+            Datatype::STR => {
+                8usize.saturating_add(u64::from_be_bytes(slice_into_array(&data[0..8])) as usize)
+            }
             Datatype::COMP(component_name) => engine
                 .get_component_type(*component_name)
                 .map(|t| t.bytesize(engine, data))
                 .unwrap_or(0usize),
+            // the variant tag comes first, followed by whatever the variant holds
+            Datatype::SUM(_) if data.len() < 32 => 32usize,
+            Datatype::SUM(variants) => {
+                let tag = S32::from_byte_array(&data[0..32]);
+                32usize.saturating_add(
+                    variants
+                        .iter()
+                        .find(|v| v.name == tag)
+                        .map(|v| v.datatype.bytesize(engine, &data[32..]))
+                        .unwrap_or(0usize),
+                )
+            }
+            Datatype::ARR(element, size) => element.bytesize_of_many(engine, data, *size),
+            Datatype::LIST(_) if data.len() < 8 => 8usize,
+            Datatype::LIST(element) => {
+                let count = u64::from_be_bytes(slice_into_array(&data[0..8])) as usize;
+                8usize.saturating_add(element.bytesize_of_many(engine, &data[8..], count))
+            }
         }
     }
 }
 
+impl Datatype {
+    /// The bytesize of `count` elements of this datatype laid out one after the other. Stops
+    /// counting once past the end of `data`, as a count read from bad data can be huge.
+    pub(crate) fn bytesize_of_many(
+        &self,
+        engine: &ComponentRegistry,
+        data: &[u8],
+        count: usize,
+    ) -> usize {
+        // elements taking no space at all take none wherever they are
+        if self.bytesize(engine, &[]) == 0 {
+            return 0;
+        }
+
+        let mut ptr = 0usize;
+        for _ in 0..count {
+            if ptr > data.len() {
+                break;
+            }
+            ptr = ptr.saturating_add(self.bytesize(engine, &data[ptr..]));
+        }
+        ptr
+    }
+}
+
 pub fn slice_into_array<A, T>(slice: &[T]) -> A
 where
     A: Default + AsMut<[T]>,
@@ -296,8 +346,23 @@ impl ToByteArray for Value {
             Value::F32(f) => (*f).to_byte_array(),
             Value::F64(f) => (*f).to_byte_array(),
             Value::S32(s) => s.to_byte_array(),
-            Value::STR(b) => b.to_byte_array(),
+            Value::STR(b) => b.to_string().to_byte_array(),
             Value::BOOL(b) => b.to_byte_array(),
+            Value::REF(r) => r.to_byte_array(),
+            Value::BLOB(b) => b.id().to_byte_array(),
+            Value::DATETIME(t) => t.to_byte_array(),
+            Value::DURATION(d) => d.to_byte_array(),
+            Value::SUM(tag, v) => {
+                let mut bytes = tag.to_byte_array();
+                bytes.extend(v.to_byte_array());
+                bytes
+            }
+            Value::ARR(values) => values.iter().flat_map(|v| v.to_byte_array()).collect(),
+            Value::LIST(values) => {
+                let mut bytes = (values.len() as u64).to_byte_array();
+                bytes.extend(values.iter().flat_map(|v| v.to_byte_array()));
+                bytes
+            }
         }
     }
 }
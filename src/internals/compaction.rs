@@ -0,0 +1,191 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use atomic_counter::AtomicCounter;
+use itertools::Itertools;
+use ordered_multimap::ListOrderedMultimap;
+
+use super::{
+    mosaic::TileChange, EntityId, FieldCache, LoggedChange, Mosaic, MosaicCrdt, MosaicStatistics,
+    SparseSet, Tile, TileType,
+};
+
+/// What a single compaction has done.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// Component buckets in the data storage that held no data.
+    pub empty_buckets: usize,
+    /// Slots the id sets still kept for tiles that are gone.
+    pub stale_slots: usize,
+    /// The new id of every tile, by old id; empty unless tiles were renumbered.
+    pub remap: HashMap<EntityId, EntityId>,
+}
+
+pub trait MosaicCompaction {
+    /// Prunes empty buckets and stale slots and gives back memory left over from churn; run
+    /// `collect_garbage` first to also drop leftovers of deleted tiles.
+    fn compact(&self) -> CompactionStats;
+    /// Compacts and renumbers tiles `0..n` in order, returning the old to new ids. Held ids go
+    /// wrong and history is dropped; fails in CRDT mode or with orphaned tiles around.
+    fn compact_and_renumber(&self) -> anyhow::Result<CompactionStats>;
+}
+
+impl MosaicCompaction for Arc<Mosaic> {
+    fn compact(&self) -> CompactionStats {
+        let mut stats = CompactionStats::default();
+
+        self.tile_registry.write().unwrap().shrink_to_fit();
+        self.dependent_ids_map.write().unwrap().pack_to_fit();
+        for ids in [
+            &self.object_ids,
+            &self.arrow_ids,
+            &self.descriptor_ids,
+            &self.extension_ids,
+        ] {
+            stats.stale_slots += ids.write().unwrap().shrink_to_fit();
+        }
+        self.indices.write().unwrap().shrink_to_fit();
+
+        {
+            let mut storage = self.data_storage.write().unwrap();
+            let before = storage.len();
+            // buckets are made again on first write, so empty ones can go
+            storage.retain(|_, entities| !entities.is_empty());
+            stats.empty_buckets = before - storage.len();
+            for entities in storage.values_mut() {
+                entities
+                    .values_mut()
+                    .for_each(|fields| fields.shrink_to_fit());
+                entities.shrink_to_fit();
+            }
+            storage.shrink_to_fit();
+        }
+
+        if let Some(recycled) = self.recycled_ids.lock().unwrap().as_mut() {
+            recycled.shrink_to_fit();
+        }
+
+        stats
+    }
+
+    fn compact_and_renumber(&self) -> anyhow::Result<CompactionStats> {
+        if self.is_crdt_enabled() {
+            return Err(anyhow!("Cannot renumber tiles in CRDT mode"));
+        }
+        if !self.check_leaks().orphaned_tiles.is_empty() {
+            return Err(anyhow!(
+                "Cannot renumber tiles while some have lost their endpoints, collect garbage first"
+            ));
+        }
+
+        let mut stats = self.compact();
+        let old_ids = {
+            let mut registry = self.tile_registry.write().unwrap();
+            let mut dependents = self.dependent_ids_map.write().unwrap();
+            let mut object_ids = self.object_ids.write().unwrap();
+            let mut arrow_ids = self.arrow_ids.write().unwrap();
+            let mut descriptor_ids = self.descriptor_ids.write().unwrap();
+            let mut extension_ids = self.extension_ids.write().unwrap();
+            let mut indices = self.indices.write().unwrap();
+            let mut storage = self.data_storage.write().unwrap();
+
+            let old_ids = registry.keys().copied().sorted().collect_vec();
+            let remap: HashMap<EntityId, EntityId> = old_ids
+                .iter()
+                .enumerate()
+                .map(|(new, old)| (*old, new))
+                .collect();
+            let new_id = |old: &EntityId| remap[old];
+
+            let attached = old_ids
+                .iter()
+                .map(|id| (new_id(id), indices.attached_to(*id)))
+                .collect::<HashMap<_, _>>();
+
+            let tiles = old_ids
+                .iter()
+                .map(|old| {
+                    let tile = &registry[old];
+                    Tile {
+                        id: new_id(old),
+                        mosaic: Arc::clone(self),
+                        tile_type: match tile.tile_type {
+                            TileType::Object => TileType::Object,
+                            TileType::Arrow { source, target } => TileType::Arrow {
+                                source: new_id(&source),
+                                target: new_id(&target),
+                            },
+                            TileType::Descriptor { subject } => TileType::Descriptor {
+                                subject: new_id(&subject),
+                            },
+                            TileType::Extension { subject } => TileType::Extension {
+                                subject: new_id(&subject),
+                            },
+                        },
+                        component: tile.component,
+                        // handles to moved tiles go stale, as their old ids point elsewhere
+                        generation: tile.generation,
+                        fields_cache: FieldCache::default(),
+                    }
+                })
+                .collect_vec();
+
+            *dependents = dependents
+                .iter()
+                .filter(|(owner, dependent)| {
+                    remap.contains_key(owner) && remap.contains_key(dependent)
+                })
+                .map(|(owner, dependent)| (new_id(owner), new_id(dependent)))
+                .collect::<ListOrderedMultimap<_, _>>();
+
+            let (mut objects, mut arrows, mut descriptors, mut extensions) = (
+                SparseSet::new(),
+                SparseSet::new(),
+                SparseSet::new(),
+                SparseSet::new(),
+            );
+            indices.clear();
+            for tile in &tiles {
+                match tile.tile_type {
+                    TileType::Object => objects.add(tile.id),
+                    TileType::Arrow { .. } => arrows.add(tile.id),
+                    TileType::Descriptor { .. } => descriptors.add(tile.id),
+                    TileType::Extension { .. } => extensions.add(tile.id),
+                }
+                indices.insert(tile);
+                for component in &attached[&tile.id] {
+                    indices.attach(tile, *component);
+                }
+            }
+            (*object_ids, *arrow_ids, *descriptor_ids, *extension_ids) =
+                (objects, arrows, descriptors, extensions);
+
+            for entities in storage.values_mut() {
+                *entities = std::mem::take(entities)
+                    .into_iter()
+                    .filter(|(id, _)| remap.contains_key(id))
+                    .map(|(id, fields)| (new_id(&id), fields))
+                    .collect();
+            }
+
+            *registry = tiles.into_iter().map(|t| (t.id, t)).collect();
+            stats.remap = remap;
+            old_ids
+        };
+
+        self.entity_counter.reset();
+        self.entity_counter.add(old_ids.len());
+        if let Some(recycled) = self.recycled_ids.lock().unwrap().as_mut() {
+            recycled.clear();
+        }
+        self.history.lock().unwrap().reset();
+        self.observers.lock().unwrap().clear_field_watches();
+        self.unobserved_changes.inc();
+        self.log_change(TileChange::Cleared);
+        // the log goes on from the tiles under their new ids
+        self.log_operation(LoggedChange::Cleared);
+        self.log_all_tiles();
+
+        Ok(stats)
+    }
+}
@@ -1,6 +1,9 @@
 use super::{
-    datatypes::{ComponentField, ComponentType, Datatype},
+    datatypes::{
+        ComponentField, ComponentModifiers, ComponentType, Datatype, FieldDefaults, Value, S32,
+    },
     logging::Logging,
+    MosaicError,
 };
 use crate::pest::Parser;
 use pest::iterators::Pair;
@@ -10,13 +13,24 @@ use pest_derive::*;
 #[grammar = "internals/component_grammar.pest"]
 pub struct ComponentParser;
 
+/// The name of the field that stands for the fields of the type a product extends, until
+/// they are laid out in its place.
+pub(crate) const INHERITED_FIELDS: &str = "";
+
 #[derive(Debug, PartialEq, Eq)]
 enum ComponentTypeKindNames {
     Product,
+    Sum,
     Alias,
 }
 
 impl ComponentParser {
+    /// Type, field and variant names are kept as `S32`s, so a longer one is an error rather
+    /// than being cut short into a name that might already be taken.
+    fn name(text: &str) -> anyhow::Result<S32> {
+        Ok(S32::new(text.trim())?)
+    }
+
     fn parse_base_type(v: &str) -> Option<Datatype> {
         match v {
             "unit" => Some(Datatype::UNIT),
@@ -33,44 +47,140 @@ impl ComponentParser {
             "s32" => Some(Datatype::S32),
             "str" => Some(Datatype::STR),
             "bool" => Some(Datatype::BOOL),
+            "ref" => Some(Datatype::REF),
+            "blob" => Some(Datatype::BLOB),
+            "datetime" => Some(Datatype::DATETIME),
+            "duration" => Some(Datatype::DURATION),
             _ => None,
         }
     }
 
-    fn parse_field(pair: Pair<'_, Rule>) -> anyhow::Result<ComponentField> {
-        let mut subs = pair.into_inner();
-        let mut val = subs.next().unwrap();
-        let name = val.as_str().trim().into();
+    /// Reads a datatype expression, looking into array and list brackets; anything that
+    /// isn't a base type is taken to be the name of another component.
+    fn parse_datatype(pair: Pair<'_, Rule>) -> anyhow::Result<Datatype> {
+        let v = pair.as_str().trim();
+        match pair.into_inner().next() {
+            Some(inner) if inner.as_rule() == Rule::array_datatype_expr => {
+                let mut subs = inner.into_inner();
+                let element = Self::parse_datatype(subs.next().unwrap())?;
+                let size = subs.next().unwrap().as_str();
+                match size.parse::<usize>() {
+                    Ok(size) => Ok(Datatype::ARR(Box::new(element), size)),
+                    Err(_) => format!("Array size {} is out of range.", size).to_error(),
+                }
+            }
 
-        val = subs.next().unwrap();
-        match val.as_rule() {
-            Rule::datatype_expr | Rule::field_datatype_expr => {
-                let v = val.as_str();
-                let typ = Self::parse_base_type(v);
+            Some(inner) if inner.as_rule() == Rule::list_datatype_expr => {
+                let element = Self::parse_datatype(inner.into_inner().next().unwrap())?;
+                Ok(Datatype::LIST(Box::new(element)))
+            }
 
-                if let Some(t) = typ {
-                    Ok(ComponentField { name, datatype: t })
+            _ => match Self::parse_base_type(v) {
+                Some(datatype) => Ok(datatype),
+                None => Ok(Datatype::COMP(Self::name(v)?)),
+            },
+        }
+    }
+
+    /// Reads the literal after `=` in a field, which has to fit the field's datatype.
+    fn parse_default(datatype: &Datatype, pair: Pair<'_, Rule>) -> anyhow::Result<Value> {
+        let literal = pair.into_inner().next().unwrap();
+        let text = literal.as_str().trim();
+        let value = match (literal.as_rule(), datatype) {
+            (Rule::number, Datatype::I8) => text.parse().map(Value::I8).ok(),
+            (Rule::number, Datatype::I16) => text.parse().map(Value::I16).ok(),
+            (Rule::number, Datatype::I32) => text.parse().map(Value::I32).ok(),
+            (Rule::number, Datatype::I64) => text.parse().map(Value::I64).ok(),
+            (Rule::number, Datatype::U8) => text.parse().map(Value::U8).ok(),
+            (Rule::number, Datatype::U16) => text.parse().map(Value::U16).ok(),
+            (Rule::number, Datatype::U32) => text.parse().map(Value::U32).ok(),
+            (Rule::number, Datatype::U64) => text.parse().map(Value::U64).ok(),
+            (Rule::number, Datatype::F32) => text.parse().map(Value::F32).ok(),
+            (Rule::number, Datatype::F64) => text.parse().map(Value::F64).ok(),
+            (Rule::boolean, Datatype::BOOL) => Some(Value::BOOL(text == "true")),
+            (Rule::string_expr, Datatype::S32 | Datatype::STR) => {
+                let string = literal.into_inner().next().unwrap().as_str();
+                if *datatype == Datatype::S32 {
+                    S32::new(string).ok().map(Value::S32)
                 } else {
-                    Ok(ComponentField {
-                        name,
-                        datatype: Datatype::COMP(v.into()),
-                    })
+                    Some(Value::STR(string.into()))
                 }
             }
+            (Rule::string_expr, Datatype::DATETIME | Datatype::DURATION) => {
+                let string = literal.into_inner().next().unwrap().as_str();
+                Value::parse_temporal(datatype, string).and_then(Result::ok)
+            }
+            _ => None,
+        };
 
-            Rule::identifier => Ok(ComponentField {
-                name,
-                datatype: Datatype::COMP(val.as_str().trim().into()),
-            }),
-
-            e => format!(
-                "Expected datatype or identifier when parsing field '{:?}', {:?} found.",
-                name, e
+        match value {
+            Some(value) => Ok(value),
+            None => format!(
+                "Default value {} doesn't fit datatype {:?}.",
+                text, datatype
             )
             .to_error(),
         }
     }
 
+    /// Reads a field, along with the value it takes when left out: its declared default,
+    /// the datatype's default if it's marked optional, or nothing if it's required.
+    fn parse_field(pair: Pair<'_, Rule>) -> anyhow::Result<(ComponentField, Option<Value>)> {
+        let mut subs = pair.into_inner();
+        let mut val = subs.next().unwrap();
+        let name = Self::name(val.as_str())?;
+
+        val = subs.next().unwrap();
+        let optional = val.as_rule() == Rule::optional_marker;
+        if optional {
+            val = subs.next().unwrap();
+        }
+
+        let field = match val.as_rule() {
+            Rule::datatype_expr | Rule::field_datatype_expr => ComponentField {
+                name,
+                datatype: Self::parse_datatype(val)?,
+            },
+
+            Rule::identifier | Rule::type_name => ComponentField {
+                name,
+                datatype: Datatype::COMP(Self::name(val.as_str())?),
+            },
+
+            e => {
+                return format!(
+                    "Expected datatype or identifier when parsing field '{:?}', {:?} found.",
+                    name, e
+                )
+                .to_error()
+            }
+        };
+
+        let default = match subs.next() {
+            Some(default) => Some(Self::parse_default(&field.datatype, default)?),
+            None if optional => Some(field.datatype.get_default()),
+            None => None,
+        };
+
+        Ok((field, default))
+    }
+
+    /// The name a definition gives its type: everything before the first lone `:`, so that
+    /// namespaces, as in `physics::Position@2: { x: f32, y: f32 };`, stay part of it.
+    pub fn type_name_of(definition: &str) -> &str {
+        let mut rest = definition;
+        let mut end = 0;
+        while let Some(at) = rest.find(':') {
+            if rest[at + 1..].starts_with(':') {
+                end += at + 2;
+                rest = &rest[at + 2..];
+            } else {
+                return definition[..end + at].trim();
+            }
+        }
+        definition.trim()
+    }
+
     fn check_keywords(name: &str) -> anyhow::Result<()> {
         if name == "product" {
             "Keyword 'product' can't be used as an identifier.".to_error()
@@ -81,14 +191,53 @@ impl ComponentParser {
         }
     }
 
-    fn parse_product(pair: Pair<'_, Rule>) -> anyhow::Result<ComponentType> {
+    fn parse_modifiers<'a>(
+        pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    ) -> anyhow::Result<ComponentModifiers> {
+        let mut modifiers = ComponentModifiers::default();
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::unique_modifier => modifiers.unique = true,
+                Rule::required_modifier => {
+                    let subject = pair.into_inner().next().unwrap().as_str().trim();
+                    modifiers.required_on.push(Self::name(subject)?);
+                }
+                Rule::extends_modifier => {
+                    let parent = pair.into_inner().last().unwrap().as_str().trim();
+                    if modifiers.extends.replace(Self::name(parent)?).is_some() {
+                        return "A type can only extend one other type.".to_error();
+                    }
+                }
+                e => return format!("Unexpected rule {:?} found among modifiers.", e).to_error(),
+            }
+        }
+
+        Ok(modifiers)
+    }
+
+    fn parse_product(
+        pair: Pair<'_, Rule>,
+    ) -> anyhow::Result<(ComponentType, FieldDefaults, ComponentModifiers)> {
         let mut pairs = pair.into_inner();
         let mut val = pairs.next().unwrap();
         let name = val.as_str().trim();
+        Self::name(name)?;
         val = pairs.next().unwrap();
 
+        let parent = match val.as_rule() {
+            Rule::extends_expr => {
+                let parent = val.into_inner().last().unwrap().as_str().trim().to_string();
+                val = pairs.next().unwrap();
+                Some(parent)
+            }
+            _ => None,
+        };
+
+        let mut modifiers = Self::parse_modifiers(pairs)?;
+
         let kind = match val.as_rule() {
             Rule::product_type_expr => ComponentTypeKindNames::Product,
+            Rule::sum_type_expr => ComponentTypeKindNames::Sum,
             Rule::datatype_expr => ComponentTypeKindNames::Alias,
             e => {
                 return format!(
@@ -99,39 +248,67 @@ impl ComponentParser {
             }
         };
 
-        return if kind == ComponentTypeKindNames::Alias {
-            let v = val.as_str();
-            Self::check_keywords(v)?;
-            let typ = Self::parse_base_type(v);
-            if let Some(t) = typ {
-                Ok(ComponentType::Alias({
-                    ComponentField {
-                        name: name.into(),
-                        datatype: t,
-                    }
-                }))
-            } else {
-                Ok(ComponentType::Alias({
+        if kind == ComponentTypeKindNames::Alias {
+            Self::check_keywords(val.as_str())?;
+            Ok((
+                ComponentType::Alias({
                     ComponentField {
                         name: name.into(),
-                        datatype: Datatype::COMP(v.into()),
+                        datatype: Self::parse_datatype(val)?,
                     }
-                }))
-            }
+                }),
+                FieldDefaults::new(),
+                modifiers,
+            ))
         } else {
             let subs = val.into_inner();
             let mut fields = vec![];
+            let mut defaults = FieldDefaults::new();
+
+            // the parent goes in front as a nameless field, which the registry lays out inline
+            if let Some(parent) = parent {
+                let parent = Self::name(&parent)?;
+                if modifiers.extends.replace(parent).is_some() {
+                    return "A type can only extend one other type.".to_error();
+                }
+                fields.push(ComponentField {
+                    name: INHERITED_FIELDS.into(),
+                    datatype: Datatype::COMP(parent),
+                });
+            }
 
             for n in subs {
-                let field = Self::parse_field(n.clone())?;
+                let (field, default) = Self::parse_field(n.clone())?;
+                if let Some(default) = default {
+                    defaults.insert(field.name, default);
+                }
                 fields.push(field);
             }
 
-            Ok(ComponentType::Product {
-                name: name.into(),
-                fields,
-            })
-        };
+            if kind == ComponentTypeKindNames::Sum {
+                if !defaults.is_empty() {
+                    return format!("Variants of sum {} can't have defaults.", name).to_error();
+                }
+
+                Ok((
+                    ComponentType::Sum {
+                        name: name.into(),
+                        variants: fields,
+                    },
+                    defaults,
+                    modifiers,
+                ))
+            } else {
+                Ok((
+                    ComponentType::Product {
+                        name: name.into(),
+                        fields,
+                    },
+                    defaults,
+                    modifiers,
+                ))
+            }
+        }
     }
 
     pub fn parse_type<S: AsRef<str>>(s: S) -> anyhow::Result<ComponentType> {
@@ -139,7 +316,7 @@ impl ComponentParser {
             Ok(pairs) => {
                 let pair = pairs.into_iter().next().unwrap();
                 match pair.as_rule() {
-                    Rule::struct_expr => Self::parse_product(pair),
+                    Rule::struct_expr => Self::parse_product(pair).map(|(typ, _, _)| typ),
                     _ => "Wrong structure found!".to_error(),
                 }
             }
@@ -148,15 +325,30 @@ impl ComponentParser {
     }
 
     pub fn parse_types<S: AsRef<str>>(s: S) -> Vec<anyhow::Result<ComponentType>> {
+        Self::parse_types_with_defaults(s)
+            .into_iter()
+            .map(|result| result.map(|(typ, _)| typ))
+            .collect()
+    }
+
+    pub fn parse_types_with_defaults<S: AsRef<str>>(
+        s: S,
+    ) -> Vec<anyhow::Result<(ComponentType, FieldDefaults)>> {
+        Self::parse_types_with_modifiers(s)
+            .into_iter()
+            .map(|result| result.map(|(typ, defaults, _)| (typ, defaults)))
+            .collect()
+    }
+
+    /// Like `parse_types_with_defaults`, along with the modifiers written after each type.
+    pub fn parse_types_with_modifiers<S: AsRef<str>>(
+        s: S,
+    ) -> Vec<anyhow::Result<(ComponentType, FieldDefaults, ComponentModifiers)>> {
         match Self::parse(Rule::structures_expr, s.as_ref()) {
             Ok(pairs) => pairs
                 .into_iter()
                 .map(|pair| match pair.as_rule() {
-                    Rule::struct_expr => {
-                        let typ = Self::parse_product(pair).unwrap();
-                        Ok(typ)
-                    }
-
+                    Rule::struct_expr => Self::parse_product(pair),
                     e => format!("Wrong structure found: {:?}!", e).to_error(),
                 })
                 .collect(),
@@ -166,29 +358,46 @@ impl ComponentParser {
     }
 
     pub fn parse_all<S: AsRef<str>>(s: S) -> anyhow::Result<Vec<ComponentType>> {
-        let result = Self::parse_types(s);
+        Self::parse_all_with_defaults(s)
+            .map(|types| types.into_iter().map(|(typ, _)| typ).collect())
+    }
+
+    pub fn parse_all_with_defaults<S: AsRef<str>>(
+        s: S,
+    ) -> anyhow::Result<Vec<(ComponentType, FieldDefaults)>> {
+        Self::parse_all_with_modifiers(s).map(|types| {
+            types
+                .into_iter()
+                .map(|(typ, defaults, _)| (typ, defaults))
+                .collect()
+        })
+    }
+
+    pub fn parse_all_with_modifiers<S: AsRef<str>>(
+        s: S,
+    ) -> anyhow::Result<Vec<(ComponentType, FieldDefaults, ComponentModifiers)>> {
+        let result = Self::parse_types_with_modifiers(s);
         if result.iter().all(|x| x.is_ok()) {
-            let result: Vec<ComponentType> = result.into_iter().map(|x| x.unwrap()).collect();
-            Ok(result)
+            Ok(result.into_iter().map(|x| x.unwrap()).collect())
         } else {
-            result
+            let errors = result
                 .into_iter()
                 .filter(|x| x.is_err())
                 .map(|x| x.err().unwrap().to_string())
                 .collect::<Vec<String>>()
-                .join(";")
-                .to_error()
+                .join(";");
+            Err(MosaicError::ParseError(errors).into())
         }
     }
 }
 
 /* /////////////////////////////////////////////////////////////////////////////////// */
-/// Unit Tests
+// Unit Tests
 /* /////////////////////////////////////////////////////////////////////////////////// */
 
 #[cfg(test)]
 mod component_grammar_testing {
-    use crate::internals::datatypes::{ComponentField, ComponentType, Datatype};
+    use crate::internals::datatypes::{ComponentField, ComponentType, Datatype, Value};
 
     use super::ComponentParser;
 
@@ -238,10 +447,93 @@ mod component_grammar_testing {
         assert!(matches!(ComponentParser::parse_type(input), Ok(_expected)));
     }
 
+    #[test]
+    fn test_parse_sum_type() {
+        let input = "Shape : sum { circle: f32, square: f32, nothing: unit };";
+        let expected = ComponentType::Sum {
+            name: "Shape".into(),
+            variants: vec![
+                ComponentField {
+                    name: "circle".into(),
+                    datatype: Datatype::F32,
+                },
+                ComponentField {
+                    name: "square".into(),
+                    datatype: Datatype::F32,
+                },
+                ComponentField {
+                    name: "nothing".into(),
+                    datatype: Datatype::UNIT,
+                },
+            ],
+        };
+
+        assert_eq!(ComponentParser::parse_type(input).unwrap(), expected);
+        assert!(ComponentParser::parse_type("Shape : sum { };").is_err());
+    }
+
+    #[test]
+    fn test_parse_arrays_and_lists() {
+        let input = "Polygon : { points: [f32; 8], tags: [s32], grid: [[u8; 3]; 3] };";
+        let expected = ComponentType::Product {
+            name: "Polygon".into(),
+            fields: vec![
+                ComponentField {
+                    name: "points".into(),
+                    datatype: Datatype::ARR(Box::new(Datatype::F32), 8),
+                },
+                ComponentField {
+                    name: "tags".into(),
+                    datatype: Datatype::LIST(Box::new(Datatype::S32)),
+                },
+                ComponentField {
+                    name: "grid".into(),
+                    datatype: Datatype::ARR(Box::new(Datatype::ARR(Box::new(Datatype::U8), 3)), 3),
+                },
+            ],
+        };
+
+        assert_eq!(ComponentParser::parse_type(input).unwrap(), expected);
+        assert!(ComponentParser::parse_type("Polygon : { points: [f32; -8] };").is_err());
+    }
+
+    #[test]
+    fn test_parse_defaults_and_optional_fields() {
+        let input =
+            r#"Label : { x: f32 = 1.5, y?: i32, text: s32 = "hi", shown: bool = true, z: u8 };"#;
+        let (_, defaults) = ComponentParser::parse_all_with_defaults(input)
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        assert_eq!(Some(&Value::F32(1.5)), defaults.get(&"x".into()));
+        assert_eq!(Some(&Value::I32(0)), defaults.get(&"y".into()));
+        assert_eq!(Some(&Value::S32("hi".into())), defaults.get(&"text".into()));
+        assert_eq!(Some(&Value::BOOL(true)), defaults.get(&"shown".into()));
+        assert_eq!(None, defaults.get(&"z".into()));
+
+        assert!(ComponentParser::parse_type("Bad : { x: i32 = 1.5 };").is_err());
+        assert!(ComponentParser::parse_type("Bad : { x: u8 = -1 };").is_err());
+        assert!(ComponentParser::parse_type("Bad : sum { x: u8 = 1 };").is_err());
+    }
+
     #[test]
     fn test_parse_product_type_with_comp_field() {
         let input = "Position : { x: i32, y: Foo };";
-        // println!("{}", ComponentParser::parse_type(input).unwrap_err());
-        assert!(ComponentParser::parse_type(input).is_err());
+        let expected = ComponentType::Product {
+            name: "Position".into(),
+            fields: vec![
+                ComponentField {
+                    name: "x".into(),
+                    datatype: Datatype::I32,
+                },
+                ComponentField {
+                    name: "y".into(),
+                    datatype: Datatype::COMP("Foo".into()),
+                },
+            ],
+        };
+
+        assert_eq!(ComponentParser::parse_type(input).unwrap(), expected);
     }
 }
@@ -1,23 +1,57 @@
 use itertools::Itertools;
 
 use super::{
-    component_grammar::ComponentParser,
-    datatypes::{ComponentType, S32 as ComponentName},
+    component_grammar::{ComponentParser, INHERITED_FIELDS},
+    datatypes::{ComponentModifiers, ComponentType, FieldDefaults, S32 as ComponentName},
     logging::Logging,
-    ComponentField, Datatype, ToByteArray, Value,
+    ComponentField, Datatype, MosaicError, Tile, ToByteArray, Value,
 };
 
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
 };
 
 type FieldName = ComponentName;
 
+/// Puts `modifiers` at the end of a single type definition, before its `;`.
+fn with_modifiers(definition: String, modifiers: &ComponentModifiers) -> String {
+    format!(
+        "{}{};",
+        definition.trim_end().trim_end_matches(';'),
+        modifiers.to_definition()
+    )
+}
+
+/// Called with a tile of the component the hook was registered for.
+pub type LifetimeHook = Arc<dyn Fn(&Tile) + Send + Sync>;
+
+#[derive(Default)]
+pub struct LifetimeHooks {
+    on_create: HashMap<ComponentName, Vec<LifetimeHook>>,
+    on_delete: HashMap<ComponentName, Vec<LifetimeHook>>,
+}
+
+impl std::fmt::Debug for LifetimeHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "LifetimeHooks({}, {})",
+            self.on_create.values().map(Vec::len).sum::<usize>(),
+            self.on_delete.values().map(Vec::len).sum::<usize>()
+        ))
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct ComponentRegistry {
-    pub component_type_map: Mutex<HashMap<ComponentName, ComponentType>>,
-    pub component_definitions: Mutex<Vec<String>>,
+    pub component_type_map: RwLock<HashMap<ComponentName, ComponentType>>,
+    pub component_definitions: RwLock<Vec<String>>,
+    pub component_defaults: RwLock<HashMap<ComponentName, FieldDefaults>>,
+    /// Names that stand for other types, such as old versions of a type for its newest one.
+    pub component_aliases: RwLock<HashMap<ComponentName, ComponentName>>,
+    /// Only types declared with modifiers are listed.
+    pub component_modifiers: RwLock<HashMap<ComponentName, ComponentModifiers>>,
+    pub lifetime_hooks: RwLock<LifetimeHooks>,
 }
 
 impl PartialEq for ComponentRegistry {
@@ -29,33 +63,199 @@ impl PartialEq for ComponentRegistry {
 impl Eq for ComponentRegistry {}
 
 impl ComponentRegistry {
+    /// Forgets all component types; lifetime hooks stay, as they belong to whoever set them up
+    /// rather than to the data.
     pub fn clear(&self) {
-        self.component_definitions.lock().unwrap().clear();
-        self.component_type_map.lock().unwrap().clear();
+        self.component_definitions.write().unwrap().clear();
+        self.component_type_map.write().unwrap().clear();
+        self.component_defaults.write().unwrap().clear();
+        self.component_aliases.write().unwrap().clear();
+        self.component_modifiers.write().unwrap().clear();
+    }
+
+    /// Calls `hook` right after every tile of `component` is created, be it an object, arrow,
+    /// descriptor, or extension.
+    pub fn on_create<F>(&self, component: &str, hook: F)
+    where
+        F: Fn(&Tile) + Send + Sync + 'static,
+    {
+        self.lifetime_hooks
+            .write()
+            .unwrap()
+            .on_create
+            .entry(component.into())
+            .or_default()
+            .push(Arc::new(hook));
+    }
+
+    /// Calls `hook` right before every tile of `component` is deleted, while it can still be read.
+    pub fn on_delete<F>(&self, component: &str, hook: F)
+    where
+        F: Fn(&Tile) + Send + Sync + 'static,
+    {
+        self.lifetime_hooks
+            .write()
+            .unwrap()
+            .on_delete
+            .entry(component.into())
+            .or_default()
+            .push(Arc::new(hook));
     }
 
-    fn flatten_component_type(&self, definition: ComponentType) -> anyhow::Result<ComponentType> {
+    pub(crate) fn create_hooks(&self, component: ComponentName) -> Vec<LifetimeHook> {
+        let hooks = self.lifetime_hooks.read().unwrap();
+        hooks.on_create.get(&component).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn delete_hooks(&self, component: ComponentName) -> Vec<LifetimeHook> {
+        let hooks = self.lifetime_hooks.read().unwrap();
+        hooks.on_delete.get(&component).cloned().unwrap_or_default()
+    }
+
+    fn flatten_component_type(
+        &self,
+        definition: ComponentType,
+        mut defaults: FieldDefaults,
+    ) -> anyhow::Result<(ComponentType, FieldDefaults)> {
         use ComponentType::*;
-        match &definition {
+        let flat = match &definition {
             Alias(ComponentField {
                 name: _,
                 datatype: Datatype::COMP(other),
             }) => {
                 let other_type = self.get_component_type(*other)?;
-                Ok(other_type.duplicate_as(definition.name().into()))
+                defaults = self.get_field_defaults(*other);
+                other_type.duplicate_as(definition.name().into())
+            }
+            Alias(field) => Alias(ComponentField {
+                name: field.name,
+                datatype: self.resolve_datatype(&field.datatype)?,
+            }),
+            Product { name, fields } => {
+                let mut flat = vec![];
+                for field in fields {
+                    let inherited = field.name.is(INHERITED_FIELDS);
+                    let nested = match &field.datatype {
+                        Datatype::COMP(other) => {
+                            Some(self.get_component_type(*other)?).filter(|t| t.is_product())
+                        }
+                        _ => None,
+                    };
+
+                    if inherited && nested.is_none() {
+                        return format!("{} can only extend a product", name).to_error();
+                    }
+
+                    // nested products are laid out inline, their fields prefixed with ours;
+                    // those of an extended product come in as they are
+                    if let Some(nested) = nested {
+                        let nested_defaults = self.get_field_defaults(nested.name().into());
+                        for inner in nested.get_fields() {
+                            let name = if inherited {
+                                inner.name
+                            } else {
+                                Self::nested_field_name(field.name, inner.name)?
+                            };
+                            if let Some(default) = nested_defaults.get(&inner.name) {
+                                defaults.insert(name, default.clone());
+                            }
+                            flat.push(ComponentField {
+                                name,
+                                datatype: inner.datatype,
+                            });
+                        }
+                    } else {
+                        flat.extend(self.resolve_fields(std::slice::from_ref(field))?);
+                    }
+                }
+
+                if let Some(duplicate) = flat.iter().map(|f| f.name).duplicates().next() {
+                    return format!("{} has more than one field named {}", name, duplicate)
+                        .to_error();
+                }
+
+                Product {
+                    name: *name,
+                    fields: flat,
+                }
             }
-            _ => Ok(definition),
+            Sum { name, variants } => Sum {
+                name: *name,
+                variants: self.resolve_fields(variants)?,
+            },
+        };
+
+        Ok((flat, defaults))
+    }
+
+    fn nested_field_name(outer: FieldName, inner: FieldName) -> anyhow::Result<FieldName> {
+        let name = format!("{}.{}", outer, inner);
+        if name.len() > 32 {
+            format!("Nested field name {} is longer than 32 bytes", name).to_error()
+        } else {
+            Ok(name.as_str().into())
+        }
+    }
+
+    /// Replaces references to other components with what they hold. Only products get
+    /// laid out inline, and only directly inside other products.
+    fn resolve_datatype(&self, datatype: &Datatype) -> anyhow::Result<Datatype> {
+        match datatype {
+            Datatype::COMP(other) => match self.get_component_type(*other)? {
+                ComponentType::Product { .. } => format!(
+                    "Product {} can only be nested directly inside another product",
+                    other
+                )
+                .to_error(),
+                other_type => Ok(other_type.get_fields().first().unwrap().datatype.clone()),
+            },
+            Datatype::ARR(element, size) => Ok(Datatype::ARR(
+                Box::new(self.resolve_datatype(element)?),
+                *size,
+            )),
+            Datatype::LIST(element) => {
+                Ok(Datatype::LIST(Box::new(self.resolve_datatype(element)?)))
+            }
+            Datatype::SUM(variants) => Ok(Datatype::SUM(self.resolve_fields(variants)?)),
+            datatype => Ok(datatype.clone()),
         }
     }
 
-    fn add_raw_component_type(&self, definition: ComponentType) -> ComponentType {
-        let mut type_map = self.component_type_map.lock().unwrap();
+    fn resolve_fields(&self, fields: &[ComponentField]) -> anyhow::Result<Vec<ComponentField>> {
+        fields
+            .iter()
+            .map(|f| {
+                Ok(ComponentField {
+                    name: f.name,
+                    datatype: self.resolve_datatype(&f.datatype)?,
+                })
+            })
+            .collect()
+    }
+
+    fn add_raw_component_type(
+        &self,
+        definition: ComponentType,
+        defaults: FieldDefaults,
+        modifiers: ComponentModifiers,
+    ) -> ComponentType {
+        let mut type_map = self.component_type_map.write().unwrap();
         if type_map.contains_key(&definition.name().into()) {
             println!(" -- type already found {:?}", definition.name());
             return definition;
         }
 
         type_map.insert(definition.name().into(), definition.clone());
+        self.component_defaults
+            .write()
+            .unwrap()
+            .insert(definition.name().into(), defaults);
+        if !modifiers.is_empty() {
+            self.component_modifiers
+                .write()
+                .unwrap()
+                .insert(definition.name().into(), modifiers);
+        }
 
         definition
     }
@@ -65,7 +265,7 @@ impl ComponentRegistry {
         component: ComponentName,
         fields: Vec<Value>,
     ) -> Result<Vec<Vec<u8>>, Box<(ComponentField, Value)>> {
-        let components = self.component_type_map.lock().unwrap();
+        let components = self.component_type_map.read().unwrap();
         let component_type = components
             .get(&component)
             .ok_or((
@@ -82,11 +282,11 @@ impl ComponentRegistry {
             .into_iter()
             .zip(fields)
             .map(|(field, datatype_value)| {
-                if datatype_value.get_datatype() == field.datatype {
+                if field.datatype.accepts(&datatype_value) {
                     Ok(datatype_value.to_byte_array())
                 } else {
-                    has_error = Some((field.clone(), datatype_value.clone()));
-                    Err((field, datatype_value))
+                    has_error = Some((field, datatype_value));
+                    Err(())
                 }
             })
             .collect::<Vec<_>>();
@@ -105,33 +305,253 @@ impl ComponentRegistry {
     }
 
     pub fn add_component_types(&self, definition: &str) -> anyhow::Result<Vec<ComponentType>> {
-        let types = ComponentParser::parse_all(definition)?
+        let parsed = ComponentParser::parse_all_with_modifiers(definition)?;
+        let refers_to_others = parsed.iter().any(|(t, _, _)| t.refers_to_components());
+        let flattened = parsed
             .into_iter()
-            .flat_map(|t| self.flatten_component_type(t))
-            .map(|t| self.add_raw_component_type(t))
+            .map(|(t, defaults, modifiers)| {
+                let (t, defaults) = self.flatten_component_type(t, defaults)?;
+                Ok((t, defaults, modifiers))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // flattened definitions stand on their own, so they load without the types they used
+        {
+            let mut definitions = self.component_definitions.write().unwrap();
+            if refers_to_others {
+                definitions.extend(
+                    flattened
+                        .iter()
+                        .map(|(t, d, m)| with_modifiers(t.to_definition(d), m)),
+                );
+            } else {
+                definitions.push(definition.to_owned());
+            }
+        }
+
+        let types = flattened
+            .into_iter()
+            .map(|(t, defaults, modifiers)| self.add_raw_component_type(t, defaults, modifiers))
             .collect_vec();
 
-        self.component_definitions
-            .lock()
+        Ok(types)
+    }
+
+    /// Replaces the registered type of the same name as `definition`, returning the old type
+    /// and the new one. Types nesting the old one keep its fields.
+    pub(crate) fn redefine_component_type(
+        &self,
+        definition: &str,
+    ) -> anyhow::Result<(ComponentType, ComponentType)> {
+        let mut parsed = ComponentParser::parse_all_with_modifiers(definition)?;
+        if parsed.len() != 1 {
+            return Err(MosaicError::ParseError(
+                "Cannot redefine more than one type at once.".to_string(),
+            )
+            .into());
+        }
+
+        let (parsed, defaults, modifiers) = parsed.remove(0);
+        let name: ComponentName = parsed.name().into();
+        let old = self
+            .component_type_map
+            .read()
             .unwrap()
-            .push(definition.to_owned());
+            .get(&name)
+            .cloned()
+            .ok_or(MosaicError::UnknownComponent(name))?;
+        let refers_to_others = parsed.refers_to_components();
+        let (new, defaults) = self.flatten_component_type(parsed, defaults)?;
 
-        Ok(types)
+        // definitions that held the old type are written out again without it
+        {
+            let mut definitions = self.component_definitions.write().unwrap();
+            let mut rewritten = vec![];
+            for stored in definitions.iter() {
+                let names = ComponentParser::parse_types(stored)
+                    .into_iter()
+                    .flatten()
+                    .map(|t| ComponentName::from(t.name()))
+                    .collect_vec();
+                if !names.contains(&name) {
+                    rewritten.push(stored.clone());
+                    continue;
+                }
+
+                for other in names.into_iter().filter(|n| *n != name) {
+                    rewritten.push(self.to_definition_string(&other.to_string())?);
+                }
+            }
+
+            if refers_to_others {
+                rewritten.push(with_modifiers(new.to_definition(&defaults), &modifiers));
+            } else {
+                rewritten.push(definition.to_owned());
+            }
+            *definitions = rewritten;
+        }
+
+        self.component_type_map
+            .write()
+            .unwrap()
+            .insert(name, new.clone());
+        self.component_defaults
+            .write()
+            .unwrap()
+            .insert(name, defaults);
+        let mut all_modifiers = self.component_modifiers.write().unwrap();
+        if modifiers.is_empty() {
+            all_modifiers.remove(&name);
+        } else {
+            all_modifiers.insert(name, modifiers);
+        }
+
+        Ok((old, new))
     }
 
     pub fn has_component_type(&self, name: &ComponentName) -> bool {
-        self.component_type_map.lock().unwrap().contains_key(name)
+        let name = self.resolve_name(*name);
+        self.component_type_map.read().unwrap().contains_key(&name)
     }
 
-    pub fn get_component_type(&self, name: ComponentName) -> anyhow::Result<ComponentType> {
-        if self.has_component_type(&name) {
-            if let Some(typ) = self.component_type_map.lock().unwrap().get(&name).cloned() {
-                Ok(typ)
-            } else {
-                format!("Component with name {} not found", name).to_error()
+    /// The values fields of `name` take when they're left out; required fields aren't listed.
+    pub fn get_field_defaults(&self, name: ComponentName) -> FieldDefaults {
+        let name = self.resolve_name(name);
+        self.component_defaults
+            .read()
+            .unwrap()
+            .get(&name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The type `name` stands for, following aliases; names that aren't aliases stand for
+    /// themselves.
+    pub fn resolve_name(&self, name: ComponentName) -> ComponentName {
+        let aliases = self.component_aliases.read().unwrap();
+        let mut resolved = name;
+        // aliases are checked for cycles when made, the bound is only a safeguard
+        for _ in 0..=aliases.len() {
+            match aliases.get(&resolved) {
+                Some(next) => resolved = *next,
+                None => break,
             }
-        } else {
-            format!("Component with name {} not found", name).to_error()
+        }
+        resolved
+    }
+
+    /// Makes `old` stand for the type `new`; data saved under `old` has to have the layout of
+    /// `new` to load.
+    pub fn alias_component_type(&self, old: &str, new: &str) -> anyhow::Result<()> {
+        let (old, new): (ComponentName, ComponentName) = (old.into(), new.into());
+        if !self.has_component_type(&new) {
+            return Err(MosaicError::UnknownComponent(new).into());
+        }
+        if self.resolve_name(new) == old {
+            return format!("Aliasing {} to {} would make a cycle", old, new).to_error();
+        }
+
+        self.component_aliases.write().unwrap().insert(old, new);
+        Ok(())
+    }
+
+    /// The types whose name starts with `namespace::`, nested namespaces included, by name.
+    pub fn types_in_namespace(&self, namespace: &str) -> Vec<ComponentName> {
+        let prefix = format!("{}::", namespace.trim_end_matches("::"));
+        self.component_type_map
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|name| name.to_string().starts_with(&prefix))
+            .copied()
+            .sorted()
+            .collect_vec()
+    }
+
+    /// Every registered type, by name; aliases made through `alias_component_type` aren't
+    /// listed separately.
+    pub fn iter_types(&self) -> std::vec::IntoIter<ComponentType> {
+        self.component_type_map
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .sorted_by_key(|t| t.name())
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// The definition of `name` as the component grammar reads it, defaults and modifiers
+    /// included, so that adding it to an empty registry gives back the same type.
+    pub fn to_definition_string(&self, name: &str) -> anyhow::Result<String> {
+        let component_type = self.get_component_type(name.into())?;
+        let defaults = self.get_field_defaults(name.into());
+        let modifiers = self.get_modifiers(name.into());
+        Ok(with_modifiers(
+            component_type.to_definition(&defaults),
+            &modifiers,
+        ))
+    }
+
+    pub fn get_modifiers(&self, name: ComponentName) -> ComponentModifiers {
+        let name = self.resolve_name(name);
+        self.component_modifiers
+            .read()
+            .unwrap()
+            .get(&name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The type `name` was declared to extend, if any.
+    pub fn parent_of(&self, name: ComponentName) -> Option<ComponentName> {
+        self.get_modifiers(name)
+            .extends
+            .map(|parent| self.resolve_name(parent))
+    }
+
+    /// Whether `name` is `ancestor` or extends it, directly or through other types.
+    pub fn is_kind_of(&self, name: ComponentName, ancestor: ComponentName) -> bool {
+        let ancestor = self.resolve_name(ancestor);
+        let mut current = Some(self.resolve_name(name));
+        // a type can only extend types that came before it, the bound is only a safeguard
+        for _ in 0..=self.component_modifiers.read().unwrap().len() {
+            match current {
+                Some(name) if name == ancestor => return true,
+                Some(name) => current = self.parent_of(name),
+                None => break,
+            }
+        }
+        false
+    }
+
+    /// `name` and every type that extends it, directly or not, by name.
+    pub fn kinds_of(&self, name: ComponentName) -> Vec<ComponentName> {
+        let name = self.resolve_name(name);
+        let extending = self
+            .component_modifiers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, modifiers)| modifiers.extends.is_some())
+            .map(|(derived, _)| *derived)
+            .collect_vec();
+
+        std::iter::once(name)
+            .chain(
+                extending
+                    .into_iter()
+                    .filter(|derived| *derived != name && self.is_kind_of(*derived, name)),
+            )
+            .sorted()
+            .collect_vec()
+    }
+
+    pub fn get_component_type(&self, name: ComponentName) -> anyhow::Result<ComponentType> {
+        let name = self.resolve_name(name);
+        match self.component_type_map.read().unwrap().get(&name) {
+            Some(typ) => Ok(typ.clone()),
+            _ => Err(MosaicError::UnknownComponent(name).into()),
         }
     }
 }
@@ -0,0 +1,332 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use pest::iterators::Pair;
+
+use self::grammar::{ComputedParser, Rule};
+use super::{Datatype, Mosaic, MosaicError, Tile, Value, S32};
+use crate::pest::Parser;
+
+// kept apart so the generated `Rule` isn't exported along with the rest of this module
+mod grammar {
+    use pest_derive::*;
+
+    #[derive(Parser)]
+    #[grammar = "internals/computed_grammar.pest"]
+    pub(super) struct ComputedParser;
+}
+
+/// Works out the value of a computed field from the tile it is read on.
+pub type ComputedField = Arc<dyn Fn(&Tile) -> Value + Send + Sync>;
+
+/// The computed fields of each component, by component and field name, with the fields of
+/// the same component each of them reads.
+#[derive(Default)]
+pub(crate) struct ComputedFields {
+    fields: HashMap<(S32, S32), ComputedField>,
+    reads: HashMap<(S32, S32), Vec<S32>>,
+}
+
+impl ComputedFields {
+    /// Whether `field` of `component` reading `reads` would have it read itself, be it
+    /// directly or through the computed fields it reads.
+    fn would_cycle(&self, component: S32, field: S32, reads: &[S32]) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = reads.to_vec();
+        while let Some(read) = pending.pop() {
+            if read == field {
+                return true;
+            }
+            if seen.insert(read) {
+                pending.extend(self.reads.get(&(component, read)).into_iter().flatten());
+            }
+        }
+        false
+    }
+}
+
+impl std::fmt::Debug for ComputedFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("ComputedFields({})", self.fields.len()))
+    }
+}
+
+/// An arithmetic expression over the numeric fields of a tile, e.g. `w * h`.
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Number(f64),
+    Field(S32),
+    Negate(Box<Expression>),
+    Binary(char, Box<Expression>, Box<Expression>),
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::I8(v) => Some(*v as f64),
+        Value::I16(v) => Some(*v as f64),
+        Value::I32(v) => Some(*v as f64),
+        Value::I64(v) => Some(*v as f64),
+        Value::U8(v) => Some(*v as f64),
+        Value::U16(v) => Some(*v as f64),
+        Value::U32(v) => Some(*v as f64),
+        Value::U64(v) => Some(*v as f64),
+        Value::F32(v) => Some(*v as f64),
+        Value::F64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+impl Expression {
+    fn parse(text: &str) -> anyhow::Result<Expression> {
+        let mut parsed = ComputedParser::parse(Rule::expression, text)
+            .map_err(|e| MosaicError::ParseError(e.to_string()))?;
+        let sum = parsed.next().unwrap().into_inner().next().unwrap();
+        Ok(Self::from_pair(sum))
+    }
+
+    fn from_pair(pair: Pair<'_, Rule>) -> Expression {
+        match pair.as_rule() {
+            Rule::sum | Rule::product => {
+                let mut inner = pair.into_inner();
+                let first = Self::from_pair(inner.next().unwrap());
+                inner.tuples().fold(first, |left, (operator, right)| {
+                    let operator = operator.as_str().chars().next().unwrap();
+                    Expression::Binary(operator, Box::new(left), Box::new(Self::from_pair(right)))
+                })
+            }
+            Rule::factor => {
+                let inner = pair.into_inner().collect_vec();
+                let (negations, operand) = inner.split_at(inner.len() - 1);
+                let operand = Self::from_pair(operand[0].clone());
+                negations
+                    .iter()
+                    .fold(operand, |e, _| Expression::Negate(Box::new(e)))
+            }
+            Rule::number => Expression::Number(pair.as_str().parse().unwrap()),
+            Rule::field => Expression::Field(pair.as_str().into()),
+            _ => unreachable!("{:?} is not an expression", pair.as_rule()),
+        }
+    }
+
+    fn fields(&self) -> Vec<S32> {
+        match self {
+            Expression::Number(_) => vec![],
+            Expression::Field(field) => vec![*field],
+            Expression::Negate(e) => e.fields(),
+            Expression::Binary(_, left, right) => {
+                left.fields().into_iter().chain(right.fields()).collect()
+            }
+        }
+    }
+
+    fn evaluate(&self, tile: &Tile) -> f64 {
+        match self {
+            Expression::Number(n) => *n,
+            Expression::Field(field) => {
+                as_number(&tile.get(&field.to_string())).unwrap_or(f64::NAN)
+            }
+            Expression::Negate(e) => -e.evaluate(tile),
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(tile), right.evaluate(tile));
+                match operator {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left % right,
+                }
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// The computed fields being worked out on this thread, by tile, component, and field.
+    static COMPUTING: RefCell<Vec<(usize, S32, S32)>> = const { RefCell::new(vec![]) };
+}
+
+/// Takes the field last worked out off `COMPUTING` when dropped, panicking or not.
+struct Computing;
+
+impl Drop for Computing {
+    fn drop(&mut self) {
+        COMPUTING.with(|computing| computing.borrow_mut().pop());
+    }
+}
+
+impl Mosaic {
+    /// The value of the computed `field` of `component` on `tile`, if there is one; panics if
+    /// it reads itself.
+    pub(crate) fn computed_value(&self, tile: &Tile, component: S32, field: S32) -> Option<Value> {
+        let compute = self
+            .computed_fields
+            .read()
+            .unwrap()
+            .fields
+            .get(&(component, field))
+            .cloned()?;
+
+        let key = (tile.id, component, field);
+        if COMPUTING.with(|computing| computing.borrow().contains(&key)) {
+            panic!(
+                "Cannot compute field {} of {}, it depends on itself, panicking!",
+                field, component
+            );
+        }
+
+        // the lock is let go first, as computing may read other computed fields
+        COMPUTING.with(|computing| computing.borrow_mut().push(key));
+        let _computed = Computing;
+        Some(compute(tile))
+    }
+
+    fn is_computed(&self, component: S32, field: S32) -> bool {
+        self.computed_fields
+            .read()
+            .unwrap()
+            .fields
+            .contains_key(&(component, field))
+    }
+
+    pub(crate) fn clear_computed_fields(&self) {
+        let mut computed = self.computed_fields.write().unwrap();
+        computed.fields.clear();
+        computed.reads.clear();
+    }
+
+    /// Adds the computed field, failing if it would read itself through `reads`.
+    fn insert_computed_field(
+        &self,
+        component: S32,
+        field: S32,
+        reads: Vec<S32>,
+        compute: ComputedField,
+    ) -> anyhow::Result<()> {
+        let component_type = self.component_registry.get_component_type(component)?;
+        if component_type.get_field(field).is_some() {
+            return Err(anyhow!(
+                "Component {} already has a stored field {}",
+                component,
+                field
+            ));
+        }
+
+        let mut computed = self.computed_fields.write().unwrap();
+        if computed.would_cycle(component, field, &reads) {
+            return Err(MosaicError::ComputedCycle { component, field }.into());
+        }
+        computed.fields.insert((component, field), compute);
+        computed.reads.insert((component, field), reads);
+        Ok(())
+    }
+}
+
+/// Fields worked out from the rest of the tile whenever they are read, e.g. the `area` of a
+/// `Rect`. They can be read and queried, but aren't in `data` or saves.
+pub trait MosaicComputedFields {
+    /// Makes `field` of `component` computed by `compute`. Closures reading other computed
+    /// fields should declare them with `add_computed_field_reading`.
+    fn add_computed_field<F>(&self, component: &str, field: &str, compute: F) -> anyhow::Result<()>
+    where
+        F: Fn(&Tile) -> Value + Send + Sync + 'static;
+    /// Like `add_computed_field`, for a closure that reads the fields `reads` of the same
+    /// tile; fails if that has the field read itself, through other computed fields or not.
+    fn add_computed_field_reading<F>(
+        &self,
+        component: &str,
+        field: &str,
+        reads: &[&str],
+        compute: F,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(&Tile) -> Value + Send + Sync + 'static;
+    /// Makes `field` of `component` an `f64` computed by an arithmetic `expression` over its
+    /// fields, e.g. `"w * h"`; fails if the field would read itself.
+    fn add_computed_expression(
+        &self,
+        component: &str,
+        field: &str,
+        expression: &str,
+    ) -> anyhow::Result<()>;
+    /// Returns whether there was such a computed field.
+    fn remove_computed_field(&self, component: &str, field: &str) -> bool;
+}
+
+impl MosaicComputedFields for Arc<Mosaic> {
+    fn add_computed_field<F>(&self, component: &str, field: &str, compute: F) -> anyhow::Result<()>
+    where
+        F: Fn(&Tile) -> Value + Send + Sync + 'static,
+    {
+        self.add_computed_field_reading(component, field, &[], compute)
+    }
+
+    fn add_computed_field_reading<F>(
+        &self,
+        component: &str,
+        field: &str,
+        reads: &[&str],
+        compute: F,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(&Tile) -> Value + Send + Sync + 'static,
+    {
+        let reads = reads.iter().map(|read| S32::from(*read)).collect();
+        self.insert_computed_field(component.into(), field.into(), reads, Arc::new(compute))
+    }
+
+    fn add_computed_expression(
+        &self,
+        component: &str,
+        field: &str,
+        expression: &str,
+    ) -> anyhow::Result<()> {
+        let name = S32::from(component);
+        let expression = Expression::parse(expression)?;
+        let component_type = self.component_registry.get_component_type(name)?;
+        for used in expression.fields() {
+            let numeric = match component_type.get_field(used).map(|f| &f.datatype) {
+                Some(
+                    Datatype::I8
+                    | Datatype::I16
+                    | Datatype::I32
+                    | Datatype::I64
+                    | Datatype::U8
+                    | Datatype::U16
+                    | Datatype::U32
+                    | Datatype::U64
+                    | Datatype::F32
+                    | Datatype::F64,
+                ) => true,
+                Some(_) => false,
+                None => used == S32::from(field) || self.is_computed(name, used),
+            };
+            if !numeric {
+                return Err(MosaicError::UnknownField {
+                    component: name,
+                    field: used,
+                }
+                .into());
+            }
+        }
+
+        let reads = expression.fields();
+        self.insert_computed_field(
+            name,
+            field.into(),
+            reads,
+            Arc::new(move |tile| Value::F64(expression.evaluate(tile))),
+        )
+    }
+
+    fn remove_computed_field(&self, component: &str, field: &str) -> bool {
+        let key = (component.into(), field.into());
+        let mut computed = self.computed_fields.write().unwrap();
+        computed.reads.remove(&key);
+        computed.fields.remove(&key).is_some()
+    }
+}
@@ -0,0 +1,536 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use itertools::Itertools;
+
+use super::{EntityId, Logging, Mosaic, MosaicIO, TileType, Value, S32};
+
+/// A rule that arrows and descriptors have to follow in a mosaic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// `arrow` arrows may only go from a `source` tile into a `target` tile.
+    ArrowEndpoints {
+        arrow: S32,
+        source: S32,
+        target: S32,
+    },
+    /// A tile may have at most `max` outgoing `arrow` arrows.
+    MaxOutgoing { arrow: S32, max: usize },
+    /// A tile may have at most `max` incoming `arrow` arrows.
+    MaxIncoming { arrow: S32, max: usize },
+    /// `descriptor` descriptors may only be put on `subject` tiles.
+    DescriptorSubject { descriptor: S32, subject: S32 },
+    /// `subject` tiles have between `min` and `max` outgoing (or incoming) `arrow` arrows;
+    /// going over is refused, falling short is only found by `validate`.
+    ArrowCount {
+        subject: S32,
+        arrow: S32,
+        outgoing: bool,
+        min: usize,
+        max: usize,
+    },
+    /// Floating point `field` of `component` tiles must be neither infinite nor NaN.
+    /// Only checked by `validate`.
+    FiniteField { component: S32, field: S32 },
+    /// A tile may have at most one `descriptor` descriptor.
+    UniqueDescriptor { descriptor: S32 },
+    /// Every `subject` tile must have a `descriptor` descriptor. Only checked by `validate`.
+    RequiredDescriptor { descriptor: S32, subject: S32 },
+}
+
+impl Constraint {
+    pub fn arrow_endpoints(arrow: &str, source: &str, target: &str) -> Constraint {
+        Constraint::ArrowEndpoints {
+            arrow: arrow.into(),
+            source: source.into(),
+            target: target.into(),
+        }
+    }
+
+    pub fn max_outgoing(arrow: &str, max: usize) -> Constraint {
+        Constraint::MaxOutgoing {
+            arrow: arrow.into(),
+            max,
+        }
+    }
+
+    pub fn max_incoming(arrow: &str, max: usize) -> Constraint {
+        Constraint::MaxIncoming {
+            arrow: arrow.into(),
+            max,
+        }
+    }
+
+    pub fn descriptor_subject(descriptor: &str, subject: &str) -> Constraint {
+        Constraint::DescriptorSubject {
+            descriptor: descriptor.into(),
+            subject: subject.into(),
+        }
+    }
+
+    pub fn outgoing_arrows(subject: &str, arrow: &str, min: usize, max: usize) -> Constraint {
+        Constraint::ArrowCount {
+            subject: subject.into(),
+            arrow: arrow.into(),
+            outgoing: true,
+            min,
+            max,
+        }
+    }
+
+    pub fn incoming_arrows(subject: &str, arrow: &str, min: usize, max: usize) -> Constraint {
+        Constraint::ArrowCount {
+            subject: subject.into(),
+            arrow: arrow.into(),
+            outgoing: false,
+            min,
+            max,
+        }
+    }
+
+    pub fn finite_field(component: &str, field: &str) -> Constraint {
+        Constraint::FiniteField {
+            component: component.into(),
+            field: field.into(),
+        }
+    }
+
+    pub fn unique_descriptor(descriptor: &str) -> Constraint {
+        Constraint::UniqueDescriptor {
+            descriptor: descriptor.into(),
+        }
+    }
+
+    pub fn required_descriptor(descriptor: &str, subject: &str) -> Constraint {
+        Constraint::RequiredDescriptor {
+            descriptor: descriptor.into(),
+            subject: subject.into(),
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constraint::ArrowEndpoints {
+                arrow,
+                source,
+                target,
+            } => f.write_fmt(format_args!(
+                "{} arrows must go from {} into {}",
+                arrow, source, target
+            )),
+            Constraint::MaxOutgoing { arrow, max } => f.write_fmt(format_args!(
+                "a tile can have at most {} outgoing {} arrows",
+                max, arrow
+            )),
+            Constraint::MaxIncoming { arrow, max } => f.write_fmt(format_args!(
+                "a tile can have at most {} incoming {} arrows",
+                max, arrow
+            )),
+            Constraint::DescriptorSubject {
+                descriptor,
+                subject,
+            } => f.write_fmt(format_args!(
+                "{} descriptors can only describe {} tiles",
+                descriptor, subject
+            )),
+            Constraint::ArrowCount {
+                subject,
+                arrow,
+                outgoing,
+                min,
+                max,
+            } => f.write_fmt(format_args!(
+                "a {} tile must have between {} and {} {} {} arrows",
+                subject,
+                min,
+                max,
+                if *outgoing { "outgoing" } else { "incoming" },
+                arrow
+            )),
+            Constraint::FiniteField { component, field } => f.write_fmt(format_args!(
+                "field {} of {} tiles must be finite",
+                field, component
+            )),
+            Constraint::UniqueDescriptor { descriptor } => f.write_fmt(format_args!(
+                "a tile can have at most one {} descriptor",
+                descriptor
+            )),
+            Constraint::RequiredDescriptor {
+                descriptor,
+                subject,
+            } => f.write_fmt(format_args!(
+                "a {} tile must have a {} descriptor",
+                subject, descriptor
+            )),
+        }
+    }
+}
+
+/// A tile that breaks one of the registered constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    pub tile: EntityId,
+    pub constraint: Constraint,
+}
+
+impl Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Tile {} violates constraint: {}",
+            self.tile, self.constraint
+        ))
+    }
+}
+
+pub trait MosaicConstraints {
+    fn add_constraint(&self, constraint: Constraint);
+    fn clear_constraints(&self);
+    /// Checks all the tiles already in the mosaic against the registered constraints.
+    fn validate(&self) -> Vec<ConstraintViolation>;
+}
+
+impl MosaicConstraints for Arc<Mosaic> {
+    fn add_constraint(&self, constraint: Constraint) {
+        let mut constraints = self.constraints.lock().unwrap();
+        if !constraints.contains(&constraint) {
+            constraints.push(constraint);
+        }
+    }
+
+    fn clear_constraints(&self) {
+        self.constraints.lock().unwrap().clear();
+    }
+
+    fn validate(&self) -> Vec<ConstraintViolation> {
+        let constraints = self.all_constraints();
+        let tiles = self.get_all().sorted_by_key(|t| t.id).collect_vec();
+        let component_of = |id: EntityId| self.get(id).map(|t| t.component);
+        let mut violations = vec![];
+
+        for constraint in constraints {
+            match constraint {
+                Constraint::ArrowEndpoints {
+                    arrow,
+                    source,
+                    target,
+                } => {
+                    for tile in tiles.iter().filter(|t| t.component == arrow) {
+                        if let TileType::Arrow {
+                            source: src,
+                            target: tgt,
+                        } = tile.tile_type
+                        {
+                            if component_of(src) != Some(source)
+                                || component_of(tgt) != Some(target)
+                            {
+                                violations.push(ConstraintViolation {
+                                    tile: tile.id,
+                                    constraint: constraint.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Constraint::MaxOutgoing { arrow, max } | Constraint::MaxIncoming { arrow, max } => {
+                    let outgoing = matches!(constraint, Constraint::MaxOutgoing { .. });
+                    let mut counts: HashMap<EntityId, usize> = HashMap::new();
+                    for tile in tiles.iter().filter(|t| t.component == arrow) {
+                        if let TileType::Arrow { source, target } = tile.tile_type {
+                            *counts
+                                .entry(if outgoing { source } else { target })
+                                .or_default() += 1;
+                        }
+                    }
+
+                    for (tile, _) in counts
+                        .into_iter()
+                        .filter(|(_, count)| *count > max)
+                        .sorted()
+                    {
+                        violations.push(ConstraintViolation {
+                            tile,
+                            constraint: constraint.clone(),
+                        });
+                    }
+                }
+                Constraint::DescriptorSubject {
+                    descriptor,
+                    subject,
+                } => {
+                    for tile in tiles.iter().filter(|t| t.component == descriptor) {
+                        if let TileType::Descriptor { subject: s } = tile.tile_type {
+                            if component_of(s) != Some(subject) {
+                                violations.push(ConstraintViolation {
+                                    tile: tile.id,
+                                    constraint: constraint.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Constraint::ArrowCount {
+                    subject,
+                    arrow,
+                    outgoing,
+                    min,
+                    max,
+                } => {
+                    for tile in tiles.iter().filter(|t| t.component == subject) {
+                        let count = self.count_arrows(tile.id, arrow, outgoing);
+                        if count < min || count > max {
+                            violations.push(ConstraintViolation {
+                                tile: tile.id,
+                                constraint: constraint.clone(),
+                            });
+                        }
+                    }
+                }
+                Constraint::FiniteField { component, field } => {
+                    for tile in tiles.iter().filter(|t| t.component == component) {
+                        let value = tile.data().into_iter().find(|(name, _)| *name == field);
+                        let finite = match value {
+                            Some((_, Value::F32(v))) => v.is_finite(),
+                            Some((_, Value::F64(v))) => v.is_finite(),
+                            _ => true,
+                        };
+
+                        if !finite {
+                            violations.push(ConstraintViolation {
+                                tile: tile.id,
+                                constraint: constraint.clone(),
+                            });
+                        }
+                    }
+                }
+                Constraint::UniqueDescriptor { descriptor } => {
+                    let mut counts: HashMap<EntityId, usize> = HashMap::new();
+                    for tile in tiles.iter().filter(|t| t.component == descriptor) {
+                        if let TileType::Descriptor { subject } = tile.tile_type {
+                            *counts.entry(subject).or_default() += 1;
+                        }
+                    }
+
+                    for (tile, _) in counts.into_iter().filter(|(_, count)| *count > 1).sorted() {
+                        violations.push(ConstraintViolation {
+                            tile,
+                            constraint: constraint.clone(),
+                        });
+                    }
+                }
+                Constraint::RequiredDescriptor {
+                    descriptor,
+                    subject,
+                } => {
+                    for tile in tiles.iter().filter(|t| t.component == subject) {
+                        if self.count_descriptors(tile.id, descriptor) == 0 {
+                            violations.push(ConstraintViolation {
+                                tile: tile.id,
+                                constraint: constraint.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+impl Mosaic {
+    /// The registered constraints, followed by those the component definitions declare
+    /// through their modifiers.
+    fn all_constraints(&self) -> Vec<Constraint> {
+        let mut constraints = self.constraints.lock().unwrap().clone();
+        let declared =
+            self.component_registry
+                .component_modifiers
+                .read()
+                .unwrap()
+                .iter()
+                .sorted_by_key(|(name, _)| **name)
+                .flat_map(|(name, modifiers)| {
+                    let unique = modifiers
+                        .unique
+                        .then_some(Constraint::UniqueDescriptor { descriptor: *name });
+                    let required = modifiers.required_on.iter().map(|subject| {
+                        Constraint::RequiredDescriptor {
+                            descriptor: *name,
+                            subject: *subject,
+                        }
+                    });
+                    unique.into_iter().chain(required).collect_vec()
+                })
+                .collect_vec();
+
+        for constraint in declared {
+            if !constraints.contains(&constraint) {
+                constraints.push(constraint);
+            }
+        }
+        constraints
+    }
+
+    pub(crate) fn count_descriptors(&self, tile: EntityId, component: S32) -> usize {
+        let dependents = self
+            .dependent_ids_map
+            .read()
+            .unwrap()
+            .get_all(&tile)
+            .cloned()
+            .unique()
+            .collect_vec();
+
+        let registry = self.tile_registry.read().unwrap();
+        dependents
+            .into_iter()
+            .filter_map(|id| registry.get(&id))
+            .filter(|t| t.component == component)
+            .filter(|t| matches!(t.tile_type, TileType::Descriptor { subject } if subject == tile))
+            .count()
+    }
+
+    fn count_arrows(&self, tile: EntityId, component: S32, outgoing: bool) -> usize {
+        let dependents = self
+            .dependent_ids_map
+            .read()
+            .unwrap()
+            .get_all(&tile)
+            .cloned()
+            .unique()
+            .collect_vec();
+
+        let registry = self.tile_registry.read().unwrap();
+        dependents
+            .into_iter()
+            .filter_map(|id| registry.get(&id))
+            .filter(|t| t.component == component)
+            .filter(|t| match t.tile_type {
+                TileType::Arrow { source, target } => {
+                    if outgoing {
+                        source == tile
+                    } else {
+                        target == tile
+                    }
+                }
+                _ => false,
+            })
+            .count()
+    }
+
+    fn component_of(&self, id: EntityId) -> Option<S32> {
+        self.tile_registry
+            .read()
+            .unwrap()
+            .get(&id)
+            .map(|t| t.component)
+    }
+
+    /// Checks whether a new arrow would break any of the registered constraints.
+    pub(crate) fn check_new_arrow(
+        &self,
+        source: EntityId,
+        target: EntityId,
+        component: S32,
+    ) -> anyhow::Result<()> {
+        self.check_arrow(source, target, component, (true, true))
+    }
+
+    /// Checks whether an arrow can go from `source` into `target`. Arrow counts are only held
+    /// against the ends in `moved`, the ones the arrow isn't already counted at.
+    pub(crate) fn check_arrow(
+        &self,
+        source: EntityId,
+        target: EntityId,
+        component: S32,
+        moved: (bool, bool),
+    ) -> anyhow::Result<()> {
+        let constraints = self.constraints.lock().unwrap().clone();
+        for constraint in constraints {
+            let violated = match &constraint {
+                Constraint::ArrowEndpoints {
+                    arrow,
+                    source: src,
+                    target: tgt,
+                } => {
+                    *arrow == component
+                        && (self.component_of(source) != Some(*src)
+                            || self.component_of(target) != Some(*tgt))
+                }
+                Constraint::MaxOutgoing { arrow, max } => {
+                    *arrow == component
+                        && moved.0
+                        && self.count_arrows(source, component, true) >= *max
+                }
+                Constraint::MaxIncoming { arrow, max } => {
+                    *arrow == component
+                        && moved.1
+                        && self.count_arrows(target, component, false) >= *max
+                }
+                Constraint::ArrowCount {
+                    subject,
+                    arrow,
+                    outgoing,
+                    max,
+                    ..
+                } => {
+                    let (end, end_moved) = if *outgoing {
+                        (source, moved.0)
+                    } else {
+                        (target, moved.1)
+                    };
+                    *arrow == component
+                        && end_moved
+                        && self.component_of(end) == Some(*subject)
+                        && self.count_arrows(end, component, *outgoing) >= *max
+                }
+                Constraint::DescriptorSubject { .. }
+                | Constraint::FiniteField { .. }
+                | Constraint::UniqueDescriptor { .. }
+                | Constraint::RequiredDescriptor { .. } => false,
+            };
+
+            if violated {
+                return format!(
+                    "Cannot have {} arrow from {} to {}: {}",
+                    component, source, target, constraint
+                )
+                .to_error();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether a new descriptor would break any of the registered constraints.
+    pub(crate) fn check_new_descriptor(
+        &self,
+        subject: EntityId,
+        component: S32,
+    ) -> anyhow::Result<()> {
+        let component = self.component_registry.resolve_name(component);
+        for constraint in self.all_constraints() {
+            let violated = match &constraint {
+                Constraint::DescriptorSubject {
+                    descriptor,
+                    subject: expected,
+                } => *descriptor == component && self.component_of(subject) != Some(*expected),
+                Constraint::UniqueDescriptor { descriptor } => {
+                    *descriptor == component && self.count_descriptors(subject, component) > 0
+                }
+                _ => false,
+            };
+
+            if violated {
+                return format!(
+                    "Cannot create {} descriptor on {}: {}",
+                    component, subject, constraint
+                )
+                .to_error();
+            }
+        }
+
+        Ok(())
+    }
+}
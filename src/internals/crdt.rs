@@ -0,0 +1,464 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use super::{
+    ComponentValues, EntityId, HistoryOperation, MergeStrategy, Mosaic, MosaicCRUD, MosaicIO,
+    MosaicMerge, MosaicTypelevelCRUD, Tile, TileType, S32,
+};
+
+/// Where a tile was first made, as the replica and its clock then; dots never collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dot {
+    pub replica: u64,
+    pub counter: u64,
+}
+
+/// When something was last written, as `(clock, replica)`; the replica breaks ties.
+pub type Stamp = (u64, u64);
+
+/// The registers of a tile: its fields, its kind and ends, and its added data.
+#[derive(Debug, Clone, Default)]
+struct TileStamps {
+    fields: HashMap<S32, Stamp>,
+    kind: Stamp,
+    attached: HashMap<S32, Stamp>,
+}
+
+/// What a mosaic in CRDT mode keeps next to its tiles: a dot and a version vector for each
+/// tile's existence, and Lamport stamps for everything else, which the later write wins.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CrdtState {
+    replica: u64,
+    clock: u64,
+    dots: HashMap<EntityId, Dot>,
+    ids: HashMap<Dot, EntityId>,
+    seen: HashMap<u64, u64>,
+    tiles: HashMap<Dot, TileStamps>,
+    definitions: HashMap<S32, Stamp>,
+    /// The stamp of the remote change being applied, recorded instead of a new one.
+    remote: Option<Stamp>,
+}
+
+impl CrdtState {
+    fn new(replica: u64) -> Self {
+        CrdtState {
+            replica,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self) -> Stamp {
+        match self.remote {
+            Some(stamp) => stamp,
+            None => {
+                self.clock += 1;
+                (self.clock, self.replica)
+            }
+        }
+    }
+
+    fn has_seen(&self, dot: &Dot) -> bool {
+        self.seen
+            .get(&dot.replica)
+            .is_some_and(|counter| *counter >= dot.counter)
+    }
+
+    fn see(&mut self, dot: Dot) {
+        let counter = self.seen.entry(dot.replica).or_default();
+        *counter = dot.counter.max(*counter);
+    }
+
+    fn adopt(&mut self, id: EntityId, dot: Dot, stamps: TileStamps) {
+        // restoring a merged tile records it as made here first, that dot has to go
+        if let Some(old) = self.dots.insert(id, dot).filter(|old| *old != dot) {
+            self.ids.remove(&old);
+            self.tiles.remove(&old);
+        }
+        self.see(dot);
+        self.ids.insert(dot, id);
+        self.tiles.insert(dot, stamps);
+    }
+
+    fn created(&mut self, id: EntityId, fields: impl Iterator<Item = S32>) {
+        self.clock += 1;
+        let stamp = (self.clock, self.replica);
+        let dot = Dot {
+            replica: self.replica,
+            counter: self.clock,
+        };
+        let stamps = TileStamps {
+            fields: fields.map(|field| (field, stamp)).collect(),
+            kind: stamp,
+            attached: HashMap::new(),
+        };
+        self.adopt(id, dot, stamps);
+    }
+
+    fn deleted(&mut self, id: EntityId) {
+        if let Some(dot) = self.dots.remove(&id) {
+            self.ids.remove(&dot);
+            self.tiles.remove(&dot);
+        }
+    }
+
+    /// Removes every tile, as deleted, keeping what was seen.
+    fn cleared(&mut self) {
+        self.dots.clear();
+        self.ids.clear();
+        self.tiles.clear();
+    }
+
+    fn stamps_mut(&mut self, id: EntityId) -> Option<(Stamp, &mut TileStamps)> {
+        let dot = self.dots.get(&id).copied()?;
+        let stamp = self.tick();
+        Some((stamp, self.tiles.entry(dot).or_default()))
+    }
+
+    fn changed(&mut self, id: EntityId, field: S32) {
+        if let Some((stamp, stamps)) = self.stamps_mut(id) {
+            stamps.fields.insert(field, stamp);
+        }
+    }
+
+    fn retyped(&mut self, id: EntityId) {
+        if let Some((stamp, stamps)) = self.stamps_mut(id) {
+            stamps.kind = stamp;
+        }
+    }
+
+    fn data_changed(&mut self, id: EntityId, component: S32) {
+        if let Some((stamp, stamps)) = self.stamps_mut(id) {
+            stamps.attached.insert(component, stamp);
+        }
+    }
+
+    fn redefined(
+        &mut self,
+        component: S32,
+        migrated: &[(EntityId, ComponentValues, ComponentValues)],
+    ) {
+        let stamp = self.tick();
+        self.definitions.insert(component, stamp);
+        for (id, before, after) in migrated {
+            let Some(stamps) = self.dots.get(id).and_then(|dot| self.tiles.get_mut(dot)) else {
+                continue;
+            };
+            // fields the migration changed are as new as the definition, unless written since
+            for (field, value) in after {
+                if !before.contains(&(*field, value.clone())) {
+                    let written = stamps.fields.entry(*field).or_default();
+                    *written = stamp.max(*written);
+                }
+            }
+        }
+    }
+}
+
+impl Mosaic {
+    /// Keeps the replica state in step with a change made here, when in CRDT mode.
+    pub(crate) fn record_crdt(&self, operation: &HistoryOperation) {
+        if let Some(state) = self.crdt.lock().unwrap().as_mut() {
+            match operation {
+                HistoryOperation::Created { id, fields, .. } => {
+                    state.created(*id, fields.iter().map(|(field, _)| *field))
+                }
+                HistoryOperation::Deleted { id, .. } => state.deleted(*id),
+                HistoryOperation::FieldChanged { id, field, .. } => state.changed(*id, *field),
+                HistoryOperation::Reconnected { id, .. } | HistoryOperation::Retyped { id, .. } => {
+                    state.retyped(*id)
+                }
+                HistoryOperation::DataChanged { id, component, .. } => {
+                    state.data_changed(*id, *component)
+                }
+                HistoryOperation::Redefined {
+                    component,
+                    migrated,
+                    ..
+                } => state.redefined(*component, migrated),
+            }
+        }
+    }
+
+    /// Drops every tile from the replica state, as `clear` deletes them all.
+    pub(crate) fn clear_crdt(&self) {
+        if let Some(state) = self.crdt.lock().unwrap().as_mut() {
+            state.cleared();
+        }
+    }
+
+    fn crdt_state(&self) -> anyhow::Result<CrdtState> {
+        self.crdt
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("Mosaic {} is not in CRDT mode", self.id))
+    }
+
+    /// Runs `change`, which brings in a write another replica stamped with `stamp`.
+    fn apply_remote<T>(&self, stamp: Stamp, change: impl FnOnce() -> T) -> T {
+        let set_remote = |remote| {
+            if let Some(state) = self.crdt.lock().unwrap().as_mut() {
+                state.remote = remote;
+            }
+        };
+        set_remote(Some(stamp));
+        let result = change();
+        set_remote(None);
+        result
+    }
+}
+
+/// The ends of `tile` other than itself.
+fn endpoints(tile: &Tile) -> Vec<EntityId> {
+    [tile.source_id(), tile.target_id()]
+        .into_iter()
+        .filter(|e| *e != tile.id)
+        .unique()
+        .collect_vec()
+}
+
+/// The tiles a tile of `tile_type` hangs off of.
+fn endpoints_of(tile_type: TileType) -> Vec<EntityId> {
+    match tile_type {
+        TileType::Object => vec![],
+        TileType::Arrow { source, target } => vec![source, target],
+        TileType::Descriptor { subject } | TileType::Extension { subject } => vec![subject],
+    }
+}
+
+/// `tile_type` with its ends as they are here, if they all are.
+fn relink(tile_type: TileType, local: &HashMap<EntityId, EntityId>) -> Option<TileType> {
+    Some(match tile_type {
+        TileType::Object => TileType::Object,
+        TileType::Arrow { source, target } => TileType::Arrow {
+            source: *local.get(&source)?,
+            target: *local.get(&target)?,
+        },
+        TileType::Descriptor { subject } => TileType::Descriptor {
+            subject: *local.get(&subject)?,
+        },
+        TileType::Extension { subject } => TileType::Extension {
+            subject: *local.get(&subject)?,
+        },
+    })
+}
+
+/// Conflict-free merging for copies of a mosaic changed apart from each other.
+pub trait MosaicCrdt {
+    /// Starts tracking changes as `replica`, which has to differ between copies that merge;
+    /// does nothing in CRDT mode already.
+    fn enable_crdt(&self, replica: u64);
+    fn is_crdt_enabled(&self) -> bool;
+    /// Copies this mosaic into a new replica named `replica`, to be merged back later.
+    fn fork_crdt(&self, replica: u64) -> anyhow::Result<Arc<Mosaic>>;
+    /// Brings in everything `other` has seen, in any order of merges with the same outcome:
+    /// deletes win, and otherwise the later write does.
+    fn merge_crdt(&self, other: &Arc<Mosaic>) -> anyhow::Result<()>;
+}
+
+impl MosaicCrdt for Arc<Mosaic> {
+    fn enable_crdt(&self, replica: u64) {
+        let tiles = self.get_all().sorted_by_key(|t| t.id).collect_vec();
+        let mut crdt = self.crdt.lock().unwrap();
+        if crdt.is_none() {
+            let mut state = CrdtState::new(replica);
+            for tile in tiles {
+                state.created(tile.id, tile.data().into_iter().map(|(field, _)| field));
+                for component in self.indices.read().unwrap().attached_to(tile.id) {
+                    state.data_changed(tile.id, component);
+                }
+            }
+            *crdt = Some(state);
+        }
+    }
+
+    fn is_crdt_enabled(&self) -> bool {
+        self.crdt.lock().unwrap().is_some()
+    }
+
+    fn fork_crdt(&self, replica: u64) -> anyhow::Result<Arc<Mosaic>> {
+        let state = self.crdt_state()?;
+        if state.replica == replica {
+            return Err(anyhow!("Replica {} cannot be forked into itself", replica));
+        }
+
+        let fork = Mosaic::new();
+        let mapping = fork.merge_from(self, MergeStrategy::overwrite())?;
+        let mut forked = CrdtState {
+            replica,
+            clock: state.clock,
+            seen: state.seen,
+            definitions: state.definitions,
+            ..Default::default()
+        };
+        for (id, dot) in state.dots {
+            if let Some(local) = mapping.get(&id) {
+                let stamps = state.tiles.get(&dot).cloned().unwrap_or_default();
+                forked.adopt(*local, dot, stamps);
+            }
+        }
+
+        *fork.crdt.lock().unwrap() = Some(forked);
+        Ok(fork)
+    }
+
+    fn merge_crdt(&self, other: &Arc<Mosaic>) -> anyhow::Result<()> {
+        if Arc::ptr_eq(self, other) {
+            return Ok(());
+        }
+
+        let ours = self.crdt_state()?;
+        let theirs = other.crdt_state()?;
+        if ours.replica == theirs.replica {
+            return Err(anyhow!(
+                "Both mosaics are replica {}, they cannot be told apart",
+                ours.replica
+            ));
+        }
+
+        self.copy_component_types(other)?;
+        for (component, stamp) in theirs.definitions.iter().sorted_by_key(|(_, s)| **s) {
+            if ours.definitions.get(component) >= Some(stamp) {
+                continue;
+            }
+            match other.definition_of(*component) {
+                Some(definition) if self.definition_of(*component) != Some(definition.clone()) => {
+                    self.apply_remote(*stamp, || self.redefine_type(&definition))?
+                }
+                _ => {
+                    if let Some(state) = self.crdt.lock().unwrap().as_mut() {
+                        state.definitions.insert(*component, *stamp);
+                    }
+                }
+            }
+        }
+
+        // their tiles we haven't seen yet go in once their endpoints are here; the ones left
+        // waiting hang off of tiles deleted here, and count as deleted along with them
+        let mut local: HashMap<EntityId, EntityId> = theirs
+            .ids
+            .iter()
+            .filter_map(|(dot, id)| ours.ids.get(dot).map(|l| (*id, *l)))
+            .collect();
+        let mut adopted = vec![];
+        let mut pending = theirs
+            .ids
+            .iter()
+            .filter(|(dot, _)| !ours.has_seen(dot))
+            .filter_map(|(dot, id)| other.get(*id).map(|tile| (*dot, tile)))
+            .sorted_by_key(|(dot, _)| *dot)
+            .collect_vec();
+        loop {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|(_, tile)| endpoints(tile).iter().all(|e| local.contains_key(e)));
+            pending = waiting;
+            if ready.is_empty() {
+                break;
+            }
+
+            for (dot, tile) in ready {
+                let id = self.next_ids(1)[0];
+                let tile_type = relink(tile.tile_type, &local).unwrap();
+                let restored = self.restore_tile(id, tile_type, tile.component, tile.data());
+                for component in other.indices.read().unwrap().attached_to(tile.id) {
+                    if let Some(data) = tile.get_data(&component.to_string()) {
+                        let _ = restored.add_data(&component.to_string(), data);
+                    }
+                }
+                local.insert(tile.id, id);
+                adopted.push((id, dot));
+            }
+        }
+
+        {
+            let mut crdt = self.crdt.lock().unwrap();
+            let state = crdt.as_mut().unwrap();
+            for (id, dot) in adopted {
+                let stamps = theirs.tiles.get(&dot).cloned().unwrap_or_default();
+                state.adopt(id, dot, stamps);
+            }
+        }
+
+        // every register they wrote later than we did, tiles moving first so that deleting
+        // what they moved off of doesn't take them along
+        let shared = {
+            let crdt = self.crdt.lock().unwrap();
+            let state = crdt.as_ref().unwrap();
+            theirs
+                .ids
+                .iter()
+                .filter_map(|(dot, id)| {
+                    let stamps = state.tiles.get(dot)?;
+                    Some((
+                        *state.ids.get(dot)?,
+                        other.get(*id)?,
+                        stamps.clone(),
+                        theirs.tiles.get(dot)?,
+                    ))
+                })
+                .sorted_by_key(|(id, ..)| *id)
+                .collect_vec()
+        };
+        for (id, tile, stamps, their_stamps) in shared {
+            if their_stamps.kind > stamps.kind {
+                let before = self.get(id).map(|t| t.tile_type);
+                let ends_here = relink(tile.tile_type, &local)
+                    .filter(|t| endpoints_of(*t).into_iter().all(|e| self.is_tile_valid(&e)));
+                match (before, ends_here) {
+                    (None, _) => continue,
+                    // moved onto a tile deleted here, where it goes as well
+                    (Some(_), None) => {
+                        self.delete_tile(id);
+                        continue;
+                    }
+                    (Some(TileType::Arrow { .. }), Some(TileType::Arrow { source, target })) => {
+                        self.apply_remote(their_stamps.kind, || self.move_arrow(id, source, target))
+                    }
+                    (Some(_), Some(after)) => {
+                        self.apply_remote(their_stamps.kind, || self.retype_tile(id, after))
+                    }
+                }
+            }
+
+            for (component, stamp) in &their_stamps.attached {
+                if stamps.attached.get(component) < Some(stamp) {
+                    let data = tile.get_data(&component.to_string());
+                    self.apply_remote(*stamp, || self.restore_data(id, *component, data));
+                }
+            }
+
+            let data = tile.data().into_iter().collect::<HashMap<_, _>>();
+            for (field, stamp) in &their_stamps.fields {
+                if stamps.fields.get(field) >= Some(stamp) {
+                    continue;
+                }
+                if let (Some(mut tile), Some(value)) = (self.get(id), data.get(field)) {
+                    self.apply_remote(*stamp, || tile.set_field(&field.to_string(), value.clone()));
+                }
+            }
+        }
+
+        // tiles they saw and no longer have were deleted there
+        let doomed = ours
+            .ids
+            .iter()
+            .filter(|(dot, _)| theirs.has_seen(dot) && !theirs.ids.contains_key(dot))
+            .map(|(_, id)| *id)
+            .sorted()
+            .collect_vec();
+        for id in doomed {
+            self.delete_tile(id);
+        }
+
+        let mut crdt = self.crdt.lock().unwrap();
+        let state = crdt.as_mut().unwrap();
+        for (replica, counter) in theirs.seen {
+            state.see(Dot { replica, counter });
+        }
+        state.clock = state.clock.max(theirs.clock);
+        Ok(())
+    }
+}
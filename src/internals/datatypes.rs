@@ -1,8 +1,11 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Arc};
 
 use fstr::FStr;
+use itertools::Itertools;
 
-use super::{logging::Logging, Bytesize, ComponentRegistry};
+use super::{
+    logging::Logging, Blob, Bytesize, ComponentRegistry, DateTime, Duration, MosaicError, TileRef,
+};
 
 pub type EntityId = usize;
 
@@ -10,6 +13,27 @@ pub type EntityId = usize;
 pub struct S32(pub FStr<32>);
 
 impl S32 {
+    /// How many bytes of utf-8 an `S32` holds.
+    pub const CAPACITY: usize = 32;
+
+    /// `s` as an `S32`, failing if it is longer than `CAPACITY` bytes rather than cutting it
+    /// short, which is what the `From` conversions do.
+    pub fn new(s: &str) -> Result<S32, MosaicError> {
+        match S32::fits(s) {
+            true => Ok(S32::truncating(s)),
+            false => Err(MosaicError::NameTooLong(s.to_string())),
+        }
+    }
+
+    /// `s` cut down to the whole characters in its first `CAPACITY` bytes.
+    pub fn truncating(s: &str) -> S32 {
+        S32(FStr::<32>::from_str_lossy(s, b'\0'))
+    }
+
+    pub fn fits(s: &str) -> bool {
+        s.len() <= S32::CAPACITY
+    }
+
     pub fn is(&self, s: &str) -> bool {
         self.to_string().as_str() == s
     }
@@ -23,9 +47,10 @@ impl From<S32> for String {
     }
 }
 
+/// Cuts `value` short if it doesn't fit; see `S32::new` to catch that instead.
 impl From<&str> for S32 {
     fn from(value: &str) -> Self {
-        S32(FStr::<32>::from_str_lossy(value, b'\0'))
+        S32::truncating(value)
     }
 }
 
@@ -46,9 +71,25 @@ impl From<&[u8]> for S32 {
     }
 }
 
+/// Cuts `value` short if it doesn't fit; see `S32::new` to catch that instead.
 impl From<String> for S32 {
     fn from(value: String) -> Self {
-        S32(FStr::<32>::from_str_lossy(value.as_str(), b'\0'))
+        S32::truncating(value.as_str())
+    }
+}
+
+// the padding is an artifact of the fixed-size buffer, so names travel as plain strings
+#[cfg(feature = "serde")]
+impl serde::Serialize for S32 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for S32 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer).map(S32::from)
     }
 }
 
@@ -64,10 +105,12 @@ impl std::fmt::Debug for S32 {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+/// The id of a string in a mosaic's string pool.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Str(pub u64);
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Datatype {
     UNIT,
     I8,
@@ -84,6 +127,20 @@ pub enum Datatype {
     STR,
     BOOL,
     COMP(S32),
+    /// A tagged union: exactly one of the variants holds a value at any time.
+    SUM(Vec<ComponentField>),
+    /// A fixed number of elements of the same datatype.
+    ARR(Box<Datatype>, usize),
+    /// Any number of elements of the same datatype.
+    LIST(Box<Datatype>),
+    /// A tile, possibly in another mosaic; see `TileRef`.
+    REF,
+    /// Any number of bytes, kept apart from the field; see `Blob`.
+    BLOB,
+    /// A point in time, UTC; see `DateTime`.
+    DATETIME,
+    /// A signed span of time; see `Duration`.
+    DURATION,
 }
 
 pub fn void() -> Vec<(S32, Value)> {
@@ -108,19 +165,178 @@ impl Datatype {
             Datatype::F32 => Value::F32(0.0),
             Datatype::F64 => Value::F64(0.0),
             Datatype::S32 => Value::S32("".into()),
-            Datatype::STR => Value::STR("".to_string()),
+            Datatype::STR => Value::STR("".into()),
             Datatype::BOOL => Value::BOOL(false),
+            Datatype::SUM(variants) => variants
+                .first()
+                .map(|v| Value::SUM(v.name, Box::new(v.datatype.get_default())))
+                .unwrap_or(Value::UNIT),
+            Datatype::ARR(element, size) => Value::ARR(vec![element.get_default(); *size]),
+            Datatype::LIST(_) => Value::LIST(vec![]),
+            Datatype::REF => Value::REF(TileRef::NONE),
+            Datatype::BLOB => Value::BLOB(Blob::empty()),
+            Datatype::DATETIME => Value::DATETIME(DateTime::UNIX_EPOCH),
+            Datatype::DURATION => Value::DURATION(Duration::ZERO),
+        }
+    }
+
+    /// Whether this datatype names another component, directly or inside it.
+    pub fn refers_to_components(&self) -> bool {
+        match self {
+            Datatype::COMP(_) => true,
+            Datatype::ARR(element, _) | Datatype::LIST(element) => element.refers_to_components(),
+            Datatype::SUM(variants) => variants.iter().any(|v| v.datatype.refers_to_components()),
+            _ => false,
+        }
+    }
+
+    /// Writes this datatype the way the component grammar reads it.
+    pub fn to_definition(&self) -> String {
+        match self {
+            Datatype::UNIT => "unit".to_string(),
+            Datatype::I8 => "i8".to_string(),
+            Datatype::I16 => "i16".to_string(),
+            Datatype::I32 => "i32".to_string(),
+            Datatype::I64 => "i64".to_string(),
+            Datatype::U8 => "u8".to_string(),
+            Datatype::U16 => "u16".to_string(),
+            Datatype::U32 => "u32".to_string(),
+            Datatype::U64 => "u64".to_string(),
+            Datatype::F32 => "f32".to_string(),
+            Datatype::F64 => "f64".to_string(),
+            Datatype::S32 => "s32".to_string(),
+            Datatype::STR => "str".to_string(),
+            Datatype::BOOL => "bool".to_string(),
+            Datatype::COMP(name) => name.to_string(),
+            Datatype::SUM(variants) => {
+                format!(
+                    "sum {{ {} }}",
+                    fields_to_definition(variants, &HashMap::new())
+                )
+            }
+            Datatype::ARR(element, size) => format!("[{}; {}]", element.to_definition(), size),
+            Datatype::LIST(element) => format!("[{}]", element.to_definition()),
+            Datatype::REF => "ref".to_string(),
+            Datatype::BLOB => "blob".to_string(),
+            Datatype::DATETIME => "datetime".to_string(),
+            Datatype::DURATION => "duration".to_string(),
+        }
+    }
+
+    /// How many bytes a value of this datatype takes up when encoded, if that's the same for
+    /// every value: strings, lists, references and sums with differently sized variants vary.
+    pub fn fixed_bytesize(&self) -> Option<usize> {
+        match self {
+            Datatype::UNIT => Some(0),
+            Datatype::BOOL | Datatype::I8 | Datatype::U8 => Some(1),
+            Datatype::I16 | Datatype::U16 => Some(2),
+            Datatype::I32 | Datatype::U32 | Datatype::F32 => Some(4),
+            Datatype::I64 | Datatype::U64 | Datatype::F64 => Some(8),
+            Datatype::DATETIME | Datatype::DURATION => Some(8),
+            Datatype::REF => Some(24),
+            Datatype::S32 | Datatype::BLOB => Some(32),
+            Datatype::STR | Datatype::LIST(_) | Datatype::COMP(_) => None,
+            Datatype::SUM(variants) => variants
+                .iter()
+                .map(|v| v.datatype.fixed_bytesize())
+                .all_equal_value()
+                .ok()
+                .flatten()
+                .map(|size| 32 + size),
+            Datatype::ARR(element, size) => element.fixed_bytesize().map(|e| e * size),
+        }
+    }
+
+    /// Whether `value` can be stored in a field of this datatype, which for sums and lists
+    /// isn't the same as comparing to `Value::get_datatype`.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Datatype::SUM(variants), Value::SUM(tag, inner)) => variants
+                .iter()
+                .any(|v| v.name == *tag && v.datatype.accepts(inner)),
+            (Datatype::ARR(element, size), Value::ARR(values)) => {
+                values.len() == *size && values.iter().all(|v| element.accepts(v))
+            }
+            (Datatype::LIST(element), Value::LIST(values)) => {
+                values.iter().all(|v| element.accepts(v))
+            }
+            (datatype, value) => *datatype == value.get_datatype(),
         }
     }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComponentField {
     pub name: S32,
     pub datatype: Datatype,
 }
 
+/// Where a field sits in the encoded data of its tile. Offsets are only known up to the first
+/// field whose size varies.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldLayout {
+    pub name: S32,
+    pub datatype: Datatype,
+    pub offset: Option<usize>,
+    pub size: Option<usize>,
+}
+
+/// Values fields take when they're left out while creating a tile; fields without one
+/// are required.
+pub type FieldDefaults = HashMap<S32, Value>;
+
+/// Rules a definition puts on where its component can be used as a descriptor, written
+/// after the type, as in `Label: s32 unique required on Node;`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ComponentModifiers {
+    /// A subject can have at most one descriptor of this component.
+    pub unique: bool,
+    /// Tiles of these components each need a descriptor of this component.
+    pub required_on: Vec<S32>,
+    /// The product this one extends, whose fields come first when written in front, as in
+    /// `Sprite: extends Position { texture: s32 };`.
+    pub extends: Option<S32>,
+}
+
+impl ComponentModifiers {
+    pub fn is_empty(&self) -> bool {
+        !self.unique && self.required_on.is_empty() && self.extends.is_none()
+    }
+
+    /// Writes the modifiers the way the component grammar reads them, with a leading space
+    /// unless there are none.
+    pub fn to_definition(&self) -> String {
+        let unique = self.unique.then(|| " unique".to_string());
+        let required = self
+            .required_on
+            .iter()
+            .map(|subject| format!(" required on {}", subject));
+        let extends = self.extends.map(|parent| format!(" extends {}", parent));
+        unique.into_iter().chain(required).chain(extends).collect()
+    }
+}
+
+fn fields_to_definition(fields: &[ComponentField], defaults: &FieldDefaults) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            let datatype = f.datatype.to_definition();
+            match defaults.get(&f.name) {
+                None => format!("{}: {}", f.name, datatype),
+                Some(d) if *d == f.datatype.get_default() => format!("{}?: {}", f.name, datatype),
+                Some(d) => match d.to_literal() {
+                    Some(literal) => format!("{}: {} = {}", f.name, datatype, literal),
+                    None => format!("{}?: {}", f.name, datatype),
+                },
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ComponentType {
     Alias(ComponentField),
 
@@ -128,6 +344,11 @@ pub enum ComponentType {
         name: S32,
         fields: Vec<ComponentField>,
     },
+
+    Sum {
+        name: S32,
+        variants: Vec<ComponentField>,
+    },
 }
 
 impl ComponentType {
@@ -139,6 +360,69 @@ impl ComponentType {
         matches!(self, ComponentType::Product { .. })
     }
 
+    pub fn is_sum(&self) -> bool {
+        matches!(self, ComponentType::Sum { .. })
+    }
+
+    /// Aliases and sums keep their whole value in a single field called `self`.
+    pub fn has_self_field(&self) -> bool {
+        self.is_alias() || self.is_sum()
+    }
+
+    pub fn refers_to_components(&self) -> bool {
+        match self {
+            ComponentType::Alias(field) => field.datatype.refers_to_components(),
+            ComponentType::Product { fields, .. } => {
+                fields.iter().any(|f| f.datatype.refers_to_components())
+            }
+            ComponentType::Sum { variants, .. } => {
+                variants.iter().any(|v| v.datatype.refers_to_components())
+            }
+        }
+    }
+
+    /// Writes this type the way the component grammar reads it.
+    pub fn to_definition(&self, defaults: &FieldDefaults) -> String {
+        match self {
+            ComponentType::Alias(field) => {
+                format!("{}: {};", field.name, field.datatype.to_definition())
+            }
+            ComponentType::Product { name, fields } => {
+                format!(
+                    "{}: {{ {} }};",
+                    name,
+                    fields_to_definition(fields, defaults)
+                )
+            }
+            ComponentType::Sum { name, variants } => {
+                format!(
+                    "{}: sum {{ {} }};",
+                    name,
+                    fields_to_definition(variants, defaults)
+                )
+            }
+        }
+    }
+
+    /// The fields of this type in the order they're encoded, with their offsets and sizes.
+    pub fn layout(&self) -> Vec<FieldLayout> {
+        let mut offset = Some(0usize);
+        self.get_fields()
+            .into_iter()
+            .map(|field| {
+                let size = field.datatype.fixed_bytesize();
+                let layout = FieldLayout {
+                    name: field.name,
+                    datatype: field.datatype,
+                    offset,
+                    size,
+                };
+                offset = offset.zip(size).map(|(o, s)| o + s);
+                layout
+            })
+            .collect()
+    }
+
     pub fn duplicate_as(&self, new_name: S32) -> ComponentType {
         match self {
             ComponentType::Alias(ComponentField { name: _, datatype }) => {
@@ -151,6 +435,10 @@ impl ComponentType {
                 name: new_name,
                 fields: fields.clone(),
             },
+            ComponentType::Sum { name: _, variants } => ComponentType::Sum {
+                name: new_name,
+                variants: variants.clone(),
+            },
         }
     }
 
@@ -158,6 +446,7 @@ impl ComponentType {
         let s = match self {
             ComponentType::Alias(ComponentField { name, .. }) => name.0.to_string(),
             ComponentType::Product { name, .. } => name.0.to_string(),
+            ComponentType::Sum { name, .. } => name.0.to_string(),
         };
 
         s.replace('\0', "")
@@ -171,6 +460,17 @@ impl ComponentType {
         match self {
             ComponentType::Alias(field) => vec![field.clone()],
             ComponentType::Product { fields, .. } => fields.clone(),
+            ComponentType::Sum { name, variants } => vec![ComponentField {
+                name: *name,
+                datatype: Datatype::SUM(variants.clone()),
+            }],
+        }
+    }
+
+    pub fn get_variants(&self) -> Vec<ComponentField> {
+        match self {
+            ComponentType::Sum { variants, .. } => variants.clone(),
+            _ => vec![],
         }
     }
 
@@ -178,6 +478,7 @@ impl ComponentType {
         match self {
             ComponentType::Alias(field) if field.name == "self".into() => Some(field),
             ComponentType::Product { fields, .. } => fields.iter().find(|f| f.name == field_name),
+            ComponentType::Sum { variants, .. } => variants.iter().find(|v| v.name == field_name),
             _ => None,
         }
     }
@@ -215,7 +516,8 @@ pub fn try_read_component_type(
 
 pub type ComponentValues = Vec<(S32, Value)>;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum Value {
     UNIT,
@@ -230,8 +532,46 @@ pub enum Value {
     F32(f32),
     F64(f64),
     S32(S32),
-    STR(String),
+    /// Strings stored in a mosaic are shared through its string pool, see `StringPool`.
+    STR(Arc<str>),
     BOOL(bool),
+    /// The value of a sum, tagged with the name of the variant it belongs to.
+    SUM(S32, Box<Value>),
+    ARR(Vec<Value>),
+    LIST(Vec<Value>),
+    REF(TileRef),
+    BLOB(Blob),
+    DATETIME(DateTime),
+    DURATION(Duration),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::UNIT, Value::UNIT) => true,
+            (Value::I8(a), Value::I8(b)) => a == b,
+            (Value::I16(a), Value::I16(b)) => a == b,
+            (Value::I32(a), Value::I32(b)) => a == b,
+            (Value::I64(a), Value::I64(b)) => a == b,
+            (Value::U8(a), Value::U8(b)) => a == b,
+            (Value::U16(a), Value::U16(b)) => a == b,
+            (Value::U32(a), Value::U32(b)) => a == b,
+            (Value::U64(a), Value::U64(b)) => a == b,
+            (Value::F32(a), Value::F32(b)) => a == b,
+            (Value::F64(a), Value::F64(b)) => a == b,
+            (Value::S32(a), Value::S32(b)) => a == b,
+            // pooled strings are the same allocation exactly when they hold the same text
+            (Value::STR(a), Value::STR(b)) => Arc::ptr_eq(a, b) || a == b,
+            (Value::BOOL(a), Value::BOOL(b)) => a == b,
+            (Value::SUM(a, x), Value::SUM(b, y)) => a == b && x == y,
+            (Value::ARR(a), Value::ARR(b)) | (Value::LIST(a), Value::LIST(b)) => a == b,
+            (Value::REF(a), Value::REF(b)) => a == b,
+            (Value::BLOB(a), Value::BLOB(b)) => a == b,
+            (Value::DATETIME(a), Value::DATETIME(b)) => a == b,
+            (Value::DURATION(a), Value::DURATION(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl Value {
@@ -251,9 +591,52 @@ impl Value {
             Value::S32(_) => Datatype::S32,
             Value::STR(_) => Datatype::STR,
             Value::BOOL(_) => Datatype::BOOL,
+            // a lone value only knows its own variant, not the rest of the sum
+            Value::SUM(tag, inner) => Datatype::SUM(vec![ComponentField {
+                name: *tag,
+                datatype: inner.get_datatype(),
+            }]),
+            Value::ARR(values) => Datatype::ARR(
+                Box::new(values.first().map_or(Datatype::UNIT, |v| v.get_datatype())),
+                values.len(),
+            ),
+            Value::LIST(values) => Datatype::LIST(Box::new(
+                values.first().map_or(Datatype::UNIT, |v| v.get_datatype()),
+            )),
+            Value::REF(_) => Datatype::REF,
+            Value::BLOB(_) => Datatype::BLOB,
+            Value::DATETIME(_) => Datatype::DATETIME,
+            Value::DURATION(_) => Datatype::DURATION,
+        }
+    }
+
+    /// Writes this value the way a field default is written in a component definition,
+    /// if it can be written there at all.
+    pub fn to_literal(&self) -> Option<String> {
+        match self {
+            Value::I8(v) => Some(v.to_string()),
+            Value::I16(v) => Some(v.to_string()),
+            Value::I32(v) => Some(v.to_string()),
+            Value::I64(v) => Some(v.to_string()),
+            Value::U8(v) => Some(v.to_string()),
+            Value::U16(v) => Some(v.to_string()),
+            Value::U32(v) => Some(v.to_string()),
+            Value::U64(v) => Some(v.to_string()),
+            Value::F32(v) if v.is_finite() => Some(v.to_string()),
+            Value::F64(v) if v.is_finite() => Some(v.to_string()),
+            Value::BOOL(v) => Some(v.to_string()),
+            Value::S32(v) if !v.to_string().contains('"') => Some(format!("\"{}\"", v)),
+            Value::STR(v) if !v.contains('"') => Some(format!("\"{}\"", v)),
+            Value::DATETIME(v) => Some(format!("\"{}\"", v)),
+            Value::DURATION(v) => Some(format!("\"{}\"", v)),
+            _ => None,
         }
     }
 
+    pub fn sum(tag: &str, value: Value) -> Value {
+        Value::SUM(tag.into(), Box::new(value))
+    }
+
     pub fn as_i8(&self) -> i8 {
         match self {
             Value::I8(v) => *v,
@@ -333,7 +716,7 @@ impl Value {
 
     pub fn as_str(&self) -> String {
         match self {
-            Value::STR(v) => v.clone(),
+            Value::STR(v) => v.to_string(),
             _ => panic!("Cannot get type variant STR from {:?}", self),
         }
     }
@@ -344,6 +727,65 @@ impl Value {
             _ => panic!("Cannot get type variant BOOL"),
         }
     }
+
+    pub fn as_arr(&self) -> Vec<Value> {
+        match self {
+            Value::ARR(v) => v.clone(),
+            _ => panic!("Cannot get type variant ARR from {:?}", self),
+        }
+    }
+
+    pub fn as_list(&self) -> Vec<Value> {
+        match self {
+            Value::LIST(v) => v.clone(),
+            _ => panic!("Cannot get type variant LIST from {:?}", self),
+        }
+    }
+
+    pub fn as_tile_ref(&self) -> TileRef {
+        match self {
+            Value::REF(v) => *v,
+            _ => panic!("Cannot get type variant REF from {:?}", self),
+        }
+    }
+
+    pub fn as_blob(&self) -> Blob {
+        match self {
+            Value::BLOB(v) => v.clone(),
+            _ => panic!("Cannot get type variant BLOB from {:?}", self),
+        }
+    }
+
+    pub fn as_datetime(&self) -> DateTime {
+        match self {
+            Value::DATETIME(v) => *v,
+            _ => panic!("Cannot get type variant DATETIME from {:?}", self),
+        }
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        match self {
+            Value::DURATION(v) => *v,
+            _ => panic!("Cannot get type variant DURATION from {:?}", self),
+        }
+    }
+
+    /// Reads a datetime or duration out of `text`, e.g. from a query or a default, to fit
+    /// `datatype`; `None` for other datatypes.
+    pub fn parse_temporal(datatype: &Datatype, text: &str) -> Option<anyhow::Result<Value>> {
+        match datatype {
+            Datatype::DATETIME => Some(text.parse().map(Value::DATETIME)),
+            Datatype::DURATION => Some(text.parse().map(Value::DURATION)),
+            _ => None,
+        }
+    }
+
+    pub fn as_sum(&self) -> (S32, Value) {
+        match self {
+            Value::SUM(tag, v) => (*tag, *v.clone()),
+            _ => panic!("Cannot get type variant SUM from {:?}", self),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,486 @@
+use std::{
+    fmt::Display,
+    ops::{Add, Neg, Sub},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+
+use super::{FromByteArray, ToByteArray};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// A point in time, in microseconds since the Unix epoch, UTC. Fields of `datetime` hold
+/// these, written as big-endian `i64`s, so they sort the same as numbers or as bytes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateTime(i64);
+
+/// A signed span of time, in microseconds, held by fields of `duration`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Duration(i64);
+
+/// Days since the epoch of a date in the proleptic Gregorian calendar (Howard Hinnant's
+/// `days_from_civil`); `None` for years too far out for an `i64` of days.
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era.checked_mul(146_097)?.checked_add(day_of_era - 719_468)
+}
+
+/// The date of a number of days since the epoch, the inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Reads the digits of a fraction of a second into microseconds, dropping any past six.
+fn parse_fraction(digits: &str) -> Option<i64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = format!("{:0<6}", &digits[..digits.len().min(6)]);
+    digits.parse().ok()
+}
+
+impl DateTime {
+    pub const UNIX_EPOCH: DateTime = DateTime(0);
+
+    pub fn from_unix_micros(micros: i64) -> DateTime {
+        DateTime(micros)
+    }
+
+    pub fn from_unix_millis(millis: i64) -> DateTime {
+        DateTime(millis.saturating_mul(1000))
+    }
+
+    pub fn from_unix_secs(secs: i64) -> DateTime {
+        DateTime(secs.saturating_mul(MICROS_PER_SECOND))
+    }
+
+    /// The given date and time of day, UTC; `None` if there is no such date or time.
+    pub fn from_ymd_hms(
+        year: i64,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+    ) -> Option<DateTime> {
+        let valid = (1..=12).contains(&month)
+            && (1..=days_in_month(year, month)).contains(&day)
+            && hour < 24
+            && minute < 60
+            && second < 60;
+        if !valid {
+            return None;
+        }
+
+        let seconds = hour as i64 * 3600 + minute as i64 * 60 + second as i64;
+        days_from_civil(year, month, day)?
+            .checked_mul(MICROS_PER_DAY)?
+            .checked_add(seconds * MICROS_PER_SECOND)
+            .map(DateTime)
+    }
+
+    /// The current time. On wasm it is read from the JavaScript clock, so without the `wasm`
+    /// feature it is always the Unix epoch.
+    pub fn now() -> DateTime {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            std::time::SystemTime::now().into()
+        }
+        #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+        {
+            DateTime::from_unix_micros((js_sys::Date::now() * 1000.0) as i64)
+        }
+        #[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+        {
+            DateTime::UNIX_EPOCH
+        }
+    }
+
+    pub fn unix_micros(&self) -> i64 {
+        self.0
+    }
+
+    pub fn unix_millis(&self) -> i64 {
+        self.0.div_euclid(1000)
+    }
+
+    pub fn unix_secs(&self) -> i64 {
+        self.0.div_euclid(MICROS_PER_SECOND)
+    }
+
+    /// The year, month, and day, UTC.
+    pub fn date(&self) -> (i64, u32, u32) {
+        civil_from_days(self.0.div_euclid(MICROS_PER_DAY))
+    }
+
+    /// The hour, minute, second, and microsecond, UTC.
+    pub fn time(&self) -> (u32, u32, u32, u32) {
+        let micros = self.0.rem_euclid(MICROS_PER_DAY);
+        let seconds = micros / MICROS_PER_SECOND;
+        (
+            (seconds / 3600) as u32,
+            (seconds / 60 % 60) as u32,
+            (seconds % 60) as u32,
+            (micros % MICROS_PER_SECOND) as u32,
+        )
+    }
+}
+
+impl From<std::time::SystemTime> for DateTime {
+    fn from(time: std::time::SystemTime) -> Self {
+        let micros =
+            |duration: std::time::Duration| i64::try_from(duration.as_micros()).unwrap_or(i64::MAX);
+        match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => DateTime(micros(after)),
+            Err(before) => DateTime(-micros(before.duration())),
+        }
+    }
+}
+
+impl From<DateTime> for std::time::SystemTime {
+    fn from(time: DateTime) -> Self {
+        let offset = std::time::Duration::from_micros(time.0.unsigned_abs());
+        match time.0 >= 0 {
+            true => std::time::UNIX_EPOCH + offset,
+            false => std::time::UNIX_EPOCH - offset,
+        }
+    }
+}
+
+/// RFC 3339 in UTC, e.g. `2024-05-01T09:30:00Z`, with as many digits of a fraction of a
+/// second as it takes.
+impl Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = self.date();
+        let (hour, minute, second, micros) = self.time();
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year, month, day, hour, minute, second
+        )?;
+        if micros != 0 {
+            write!(f, ".{}", format!("{:06}", micros).trim_end_matches('0'))?;
+        }
+        write!(f, "Z")
+    }
+}
+
+/// Reads RFC 3339 and the looser ISO 8601 forms around it: a date alone, a space for the
+/// `T`, no seconds, and no offset, which is taken as UTC.
+impl FromStr for DateTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("Cannot read {:?} as a datetime", s);
+        let out_of_range = |what: &str| anyhow!("Cannot read {:?} as a datetime, {}", s, what);
+        let s = s.trim();
+        let number = |text: &str| -> anyhow::Result<u32> {
+            match text.bytes().all(|b| b.is_ascii_digit()) && !text.is_empty() {
+                true => text.parse().map_err(|_| invalid()),
+                false => Err(invalid()),
+            }
+        };
+
+        let (date, time) = match s.find(['T', 't', ' ']) {
+            Some(at) => (&s[..at], &s[at + 1..]),
+            None => (s, ""),
+        };
+        let (year, rest) = match date.strip_prefix('-') {
+            Some(date) => date.split_once('-').map(|(y, r)| (format!("-{}", y), r)),
+            None => date.split_once('-').map(|(y, r)| (y.to_string(), r)),
+        }
+        .ok_or_else(invalid)?;
+        let year: i64 = year
+            .parse()
+            .map_err(|_| out_of_range("the year is not a number that fits"))?;
+        let (month, day) = rest.split_once('-').ok_or_else(invalid)?;
+        let (month, day) = (number(month)?, number(day)?);
+
+        if time.is_empty() {
+            return DateTime::from_ymd_hms(year, month, day, 0, 0, 0)
+                .ok_or_else(|| out_of_range("there is no such date"));
+        }
+
+        let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+            Some(at) => (&time[..at], &time[at..]),
+            None => (time, ""),
+        };
+        let offset_minutes = match offset {
+            "" | "Z" | "z" => 0i64,
+            _ => {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let (hours, minutes) = offset[1..].split_once(':').ok_or_else(invalid)?;
+                let (hours, minutes) = (number(hours)?, number(minutes)?);
+                if hours > 23 || minutes > 59 {
+                    return Err(out_of_range("the offset is out of range"));
+                }
+                sign * (hours as i64 * 60 + minutes as i64)
+            }
+        };
+
+        let (time, fraction) = match time.split_once('.') {
+            Some((time, fraction)) => (time, parse_fraction(fraction).ok_or_else(invalid)?),
+            None => (time, 0),
+        };
+        let mut parts = time.split(':');
+        let hour = number(parts.next().ok_or_else(invalid)?)?;
+        let minute = number(parts.next().ok_or_else(invalid)?)?;
+        let second = parts.next().map(number).transpose()?.unwrap_or(0);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let local = DateTime::from_ymd_hms(year, month, day, hour, minute, second)
+            .ok_or_else(|| out_of_range("there is no such date or time"))?;
+        local
+            .0
+            .checked_add(fraction)
+            .and_then(|micros| micros.checked_sub(offset_minutes * 60 * MICROS_PER_SECOND))
+            .map(DateTime)
+            .ok_or_else(|| out_of_range("it is too far from the epoch"))
+    }
+}
+
+impl ToByteArray for DateTime {
+    fn to_byte_array(&self) -> Vec<u8> {
+        self.0.to_byte_array()
+    }
+}
+
+impl FromByteArray for DateTime {
+    fn from_byte_array(data: &[u8]) -> Self {
+        DateTime(i64::from_byte_array(data))
+    }
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration(0);
+
+    pub fn from_micros(micros: i64) -> Duration {
+        Duration(micros)
+    }
+
+    pub fn from_millis(millis: i64) -> Duration {
+        Duration(millis.saturating_mul(1000))
+    }
+
+    pub fn from_secs(secs: i64) -> Duration {
+        Duration(secs.saturating_mul(MICROS_PER_SECOND))
+    }
+
+    pub fn from_secs_f64(secs: f64) -> Duration {
+        Duration((secs * MICROS_PER_SECOND as f64).round() as i64)
+    }
+
+    pub fn as_micros(&self) -> i64 {
+        self.0
+    }
+
+    pub fn as_millis(&self) -> i64 {
+        self.0 / 1000
+    }
+
+    pub fn as_secs(&self) -> i64 {
+        self.0 / MICROS_PER_SECOND
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        self.0 as f64 / MICROS_PER_SECOND as f64
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn abs(&self) -> Duration {
+        Duration(self.0.saturating_abs())
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Duration(i64::try_from(duration.as_micros()).unwrap_or(i64::MAX))
+    }
+}
+
+/// Fails for negative durations, which `std::time::Duration` can't hold.
+impl TryFrom<Duration> for std::time::Duration {
+    type Error = anyhow::Error;
+
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        match u64::try_from(duration.0) {
+            Ok(micros) => Ok(std::time::Duration::from_micros(micros)),
+            Err(_) => Err(anyhow!("Duration {} is negative", duration)),
+        }
+    }
+}
+
+const DURATION_UNITS: [(&str, i64); 6] = [
+    ("d", MICROS_PER_DAY),
+    ("h", 3600 * MICROS_PER_SECOND),
+    ("m", 60 * MICROS_PER_SECOND),
+    ("s", MICROS_PER_SECOND),
+    ("ms", 1000),
+    ("us", 1),
+];
+
+/// Each unit from days down to microseconds that isn't zero, e.g. `1h30m` or `-2s500ms`;
+/// `0s` for no time at all.
+impl Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "0s");
+        }
+        if self.0 < 0 {
+            write!(f, "-")?;
+        }
+
+        let mut rest = self.0.unsigned_abs();
+        for (unit, micros) in DURATION_UNITS {
+            let count = rest / micros as u64;
+            if count > 0 {
+                write!(f, "{}{}", count, unit)?;
+            }
+            rest %= micros as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Reads what `Display` writes, with any order of units, fractions like `1.5h`, and spaces
+/// between the parts allowed.
+impl FromStr for Duration {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("Cannot read {:?} as a duration", s);
+        let text = s.trim();
+        let (sign, mut rest) = match text.strip_prefix('-') {
+            Some(rest) => (-1.0, rest.trim_start()),
+            None => (1.0, text),
+        };
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut micros = 0.0;
+        while !rest.is_empty() {
+            let number_len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .ok_or_else(invalid)?;
+            let count: f64 = rest[..number_len].parse().map_err(|_| invalid())?;
+            rest = &rest[number_len..];
+
+            let unit_len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let unit = DURATION_UNITS
+                .iter()
+                .find(|(name, _)| *name == &rest[..unit_len])
+                .ok_or_else(invalid)?;
+            micros += count * unit.1 as f64;
+            rest = rest[unit_len..].trim_start();
+        }
+
+        // past this, the cast below would quietly stop at the largest duration there is
+        let micros = (sign * micros).round();
+        if !(i64::MIN as f64..i64::MAX as f64).contains(&micros) {
+            return Err(anyhow!("Cannot read {:?} as a duration, it is too long", s));
+        }
+        Ok(Duration(micros as i64))
+    }
+}
+
+impl ToByteArray for Duration {
+    fn to_byte_array(&self) -> Vec<u8> {
+        self.0.to_byte_array()
+    }
+}
+
+impl FromByteArray for Duration {
+    fn from_byte_array(data: &[u8]) -> Self {
+        Duration(i64::from_byte_array(data))
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        Duration(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        Duration(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Duration {
+        Duration(self.0.saturating_neg())
+    }
+}
+
+impl Add<Duration> for DateTime {
+    type Output = DateTime;
+
+    fn add(self, duration: Duration) -> DateTime {
+        DateTime(self.0.saturating_add(duration.0))
+    }
+}
+
+impl Sub<Duration> for DateTime {
+    type Output = DateTime;
+
+    fn sub(self, duration: Duration) -> DateTime {
+        DateTime(self.0.saturating_sub(duration.0))
+    }
+}
+
+/// The time from `other` until `self`.
+impl Sub for DateTime {
+    type Output = Duration;
+
+    fn sub(self, other: DateTime) -> Duration {
+        Duration(self.0.saturating_sub(other.0))
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+use super::{Datatype, EntityId, S32};
+
+/// What went wrong inside a mosaic, for callers that need to tell failures apart. Functions
+/// still return `anyhow::Result`; get at the cause with `error.downcast_ref::<MosaicError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MosaicError {
+    UnknownComponent(S32),
+    UnknownField {
+        component: S32,
+        field: S32,
+    },
+    MissingField {
+        component: S32,
+        field: S32,
+    },
+    FieldTypeMismatch {
+        field: S32,
+        expected: Datatype,
+        found: Datatype,
+    },
+    /// There is no tile with this id.
+    InvalidTile(EntityId),
+    /// A tile was to be made under an id that is already taken.
+    TileExists(EntityId),
+    ParseError(String),
+    /// Stored data doesn't fit the layout of its component any more.
+    DataLayout {
+        component: S32,
+        field: S32,
+    },
+    /// The component is guarded against writes.
+    ReadOnlyComponent(S32),
+    /// A name or `s32` value is longer than an `S32` holds, and would have been cut short.
+    NameTooLong(String),
+    /// A computed field would read itself, directly or through other computed fields.
+    ComputedCycle {
+        component: S32,
+        field: S32,
+    },
+}
+
+impl Display for MosaicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MosaicError::UnknownComponent(name) => {
+                f.write_fmt(format_args!("Component with name {} not found", name))
+            }
+            MosaicError::UnknownField { component, field } => f.write_fmt(format_args!(
+                "Component {} has no field {}",
+                component, field
+            )),
+            MosaicError::MissingField { component, field } => f.write_fmt(format_args!(
+                "Missing field {} in type {}",
+                field, component
+            )),
+            MosaicError::FieldTypeMismatch {
+                field,
+                expected,
+                found,
+            } => f.write_fmt(format_args!(
+                "Expected type for field {} is {:?}, but found type {:?}",
+                field, expected, found
+            )),
+            MosaicError::InvalidTile(id) => f.write_fmt(format_args!("There is no tile {}", id)),
+            MosaicError::TileExists(id) => f.write_fmt(format_args!(
+                "Cannot create specific object at id {}, it already exists",
+                id
+            )),
+            MosaicError::ParseError(reason) => f.write_str(reason),
+            MosaicError::DataLayout { component, field } => f.write_fmt(format_args!(
+                "Wrong data layout in component {} with field {} -- maybe it changed recently?",
+                component, field
+            )),
+            MosaicError::ReadOnlyComponent(name) => {
+                f.write_fmt(format_args!("Component {} is read-only", name))
+            }
+            MosaicError::NameTooLong(name) => f.write_fmt(format_args!(
+                "{} is longer than the {} bytes an s32 holds",
+                name,
+                S32::CAPACITY
+            )),
+            MosaicError::ComputedCycle { component, field } => f.write_fmt(format_args!(
+                "Computed field {} of {} would depend on itself",
+                field, component
+            )),
+        }
+    }
+}
+
+impl std::error::Error for MosaicError {}
@@ -0,0 +1,126 @@
+use std::{
+    collections::HashSet,
+    sync::{atomic::Ordering, Arc},
+};
+
+use atomic_counter::AtomicCounter;
+use itertools::Itertools;
+
+use super::{EntityId, Mosaic, MosaicCRUD, MosaicIO, TileType};
+
+/// What a single garbage collection pass has removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageCollectionStats {
+    pub arrows: usize,
+    pub descriptors: usize,
+    pub extensions: usize,
+    pub data_entries: usize,
+    pub dependent_entries: usize,
+    pub strings: usize,
+    pub blobs: usize,
+}
+
+impl GarbageCollectionStats {
+    pub fn tiles(&self) -> usize {
+        self.arrows + self.descriptors + self.extensions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles() + self.data_entries + self.dependent_entries + self.strings + self.blobs == 0
+    }
+}
+
+pub trait MosaicGarbageCollection {
+    /// Deletes arrows, descriptors, and extensions whose endpoints no longer exist, and prunes
+    /// component data, dependency entries, and pooled strings and blobs that belong to no tile.
+    fn collect_garbage(&self) -> GarbageCollectionStats;
+    /// Runs `collect_garbage` automatically after every `every` deletions; `None` turns it off.
+    fn set_auto_garbage_collection(&self, every: Option<usize>);
+}
+
+impl MosaicGarbageCollection for Arc<Mosaic> {
+    fn collect_garbage(&self) -> GarbageCollectionStats {
+        let mut stats = GarbageCollectionStats::default();
+
+        loop {
+            let orphans = self
+                .get_all()
+                .filter(|t| match t.tile_type {
+                    TileType::Object => false,
+                    TileType::Arrow { source, target } => {
+                        !self.is_tile_valid(&source) || !self.is_tile_valid(&target)
+                    }
+                    TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                        !self.is_tile_valid(&subject)
+                    }
+                })
+                .collect_vec();
+
+            if orphans.is_empty() {
+                break;
+            }
+
+            for orphan in orphans {
+                if !self.is_tile_valid(&orphan.id) {
+                    continue;
+                }
+
+                match orphan.tile_type {
+                    TileType::Object => {}
+                    TileType::Arrow { .. } => stats.arrows += 1,
+                    TileType::Descriptor { .. } => stats.descriptors += 1,
+                    TileType::Extension { .. } => stats.extensions += 1,
+                }
+
+                self.delete_tile(orphan.id);
+            }
+        }
+
+        // tiles being made can't have data written without being registered until we are done
+        let _creating = self.creating.write().unwrap();
+        let alive: HashSet<EntityId> = self.tile_registry.read().unwrap().keys().cloned().collect();
+
+        for entities in self.data_storage.write().unwrap().values_mut() {
+            let before = entities.len();
+            entities.retain(|id, _| alive.contains(id));
+            stats.data_entries += before - entities.len();
+        }
+
+        let mut dependents = self.dependent_ids_map.write().unwrap();
+        let before = dependents.values_len();
+        *dependents = std::mem::take(&mut *dependents)
+            .into_iter()
+            .filter(|(owner, dependent)| alive.contains(owner) && alive.contains(dependent))
+            .collect();
+        stats.dependent_entries += before - dependents.values_len();
+        drop(dependents);
+
+        let mut strings = self.strings.write().unwrap();
+        stats.strings += strings.prune();
+        stats.blobs += strings.prune_blobs();
+
+        stats
+    }
+
+    fn set_auto_garbage_collection(&self, every: Option<usize>) {
+        self.auto_gc_threshold
+            .store(every.unwrap_or(0), Ordering::Relaxed);
+        self.deletions_since_gc.reset();
+    }
+}
+
+impl Mosaic {
+    pub(crate) fn note_deletion(self: &Arc<Self>) {
+        let threshold = self.auto_gc_threshold.load(Ordering::Relaxed);
+        if threshold == 0 || self.collecting_garbage.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if self.deletions_since_gc.inc() + 1 >= threshold {
+            self.deletions_since_gc.reset();
+            self.collecting_garbage.store(true, Ordering::Relaxed);
+            self.collect_garbage();
+            self.collecting_garbage.store(false, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use super::{
+    ComponentValues, EntityId, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, TileType, Value,
+    S32,
+};
+
+/// A single change to the mosaic, with enough data to both revert and replay it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HistoryOperation {
+    Created {
+        id: EntityId,
+        /// The generation of the tile, which it takes back when the creation is redone.
+        generation: u64,
+        tile_type: TileType,
+        component: S32,
+        fields: ComponentValues,
+    },
+    Deleted {
+        id: EntityId,
+        /// The generation of the tile, which it takes back when the deletion is undone.
+        generation: u64,
+        tile_type: TileType,
+        component: S32,
+        fields: ComponentValues,
+        /// Data the tile had from `Tile::add_data`, by component.
+        attached: Vec<(S32, ComponentValues)>,
+    },
+    FieldChanged {
+        id: EntityId,
+        field: S32,
+        before: Value,
+        after: Value,
+    },
+    /// An arrow moved from one pair of endpoints, source first, to another.
+    Reconnected {
+        id: EntityId,
+        before: (EntityId, EntityId),
+        after: (EntityId, EntityId),
+    },
+    /// A tile changed what kind of tile it is, such as a descriptor becoming an extension.
+    Retyped {
+        id: EntityId,
+        before: TileType,
+        after: TileType,
+    },
+    /// Data of another component was added to a tile with `Tile::add_data`, replaced, or
+    /// removed; `None` stands for the tile not having any.
+    DataChanged {
+        id: EntityId,
+        component: S32,
+        before: Option<ComponentValues>,
+        after: Option<ComponentValues>,
+    },
+    /// A type was redefined with `redefine_type`, from one definition to another, and the
+    /// tiles holding it migrated, each with its fields before and after.
+    Redefined {
+        component: S32,
+        before: String,
+        after: String,
+        migrated: Vec<(EntityId, ComponentValues, ComponentValues)>,
+    },
+}
+
+impl HistoryOperation {
+    /// Every field value the operation holds, before and after.
+    pub(crate) fn values(&self) -> Vec<&Value> {
+        fn fields(values: &ComponentValues) -> Vec<&Value> {
+            values.iter().map(|(_, v)| v).collect()
+        }
+        match self {
+            HistoryOperation::Created { fields: values, .. } => fields(values),
+            HistoryOperation::Deleted {
+                fields: values,
+                attached,
+                ..
+            } => std::iter::once(values)
+                .chain(attached.iter().map(|(_, data)| data))
+                .flat_map(fields)
+                .collect(),
+            HistoryOperation::FieldChanged { before, after, .. } => vec![before, after],
+            HistoryOperation::Reconnected { .. } | HistoryOperation::Retyped { .. } => vec![],
+            HistoryOperation::DataChanged { before, after, .. } => {
+                before.iter().chain(after).flat_map(fields).collect()
+            }
+            HistoryOperation::Redefined { migrated, .. } => migrated
+                .iter()
+                .flat_map(|(_, before, after)| [before, after])
+                .flat_map(fields)
+                .collect(),
+        }
+    }
+}
+
+/// A named group of operations that gets undone and redone as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryStep {
+    pub name: String,
+    pub operations: Vec<HistoryOperation>,
+}
+
+/// The journal that backs the history capability. Recording is off until a limit is set.
+#[derive(Debug, Default)]
+pub struct HistoryJournal {
+    pub(crate) limit: usize,
+    pub(crate) replaying: bool,
+    /// Set while a load runs, which takes back what it added itself if it fails.
+    pub(crate) loading: bool,
+    pub(crate) open_step: Option<HistoryStep>,
+    pub(crate) undo_stack: VecDeque<HistoryStep>,
+    pub(crate) redo_stack: Vec<HistoryStep>,
+    pub(crate) transactions: Vec<Vec<HistoryOperation>>,
+    /// Steps let go of since the last recorded operation, whose strings get released then.
+    pub(crate) dropped: Vec<HistoryStep>,
+}
+
+impl HistoryJournal {
+    pub(crate) fn is_recording(&self) -> bool {
+        self.limit > 0 && !self.replaying
+    }
+
+    pub(crate) fn record(&mut self, operation: HistoryOperation) {
+        if self.replaying || self.loading {
+            return;
+        }
+
+        // transactions hold on to their operations until they are committed or rolled back
+        if let Some(transaction) = self.transactions.last_mut() {
+            transaction.push(operation);
+            return;
+        }
+
+        if !self.is_recording() {
+            return;
+        }
+
+        self.dropped.append(&mut self.redo_stack);
+        if let Some(step) = self.open_step.as_mut() {
+            step.operations.push(operation);
+        } else {
+            let name = match operation {
+                HistoryOperation::Created { .. } => "create",
+                HistoryOperation::Deleted { .. } => "delete",
+                HistoryOperation::FieldChanged { .. } => "set",
+                HistoryOperation::Reconnected { .. } => "reconnect",
+                HistoryOperation::Retyped { .. } => "retype",
+                HistoryOperation::DataChanged { .. } => "data",
+                HistoryOperation::Redefined { .. } => "redefine",
+            };
+
+            self.push_step(HistoryStep {
+                name: name.to_string(),
+                operations: vec![operation],
+            });
+        }
+    }
+
+    pub(crate) fn push_step(&mut self, step: HistoryStep) {
+        if step.operations.is_empty() {
+            return;
+        }
+
+        self.undo_stack.push_back(step);
+        while self.undo_stack.len() > self.limit {
+            self.dropped.extend(self.undo_stack.pop_front());
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.replaying = false;
+        self.open_step = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.transactions.clear();
+        self.dropped.clear();
+    }
+}
+
+impl Mosaic {
+    pub(crate) fn revert_history_operation(self: &Arc<Self>, operation: &HistoryOperation) {
+        match operation {
+            HistoryOperation::Created { id, .. } => self.delete_tile(*id),
+            HistoryOperation::Deleted {
+                id,
+                generation,
+                tile_type,
+                component,
+                fields,
+                attached,
+            } => {
+                let tile = self.restore_tile(*id, *tile_type, *component, fields.clone());
+                self.set_generation(*id, *generation);
+                for (component, values) in attached {
+                    let _ = tile.add_data(&component.to_string(), values.clone());
+                }
+            }
+            HistoryOperation::FieldChanged {
+                id, field, before, ..
+            } => {
+                if let Some(mut tile) = self.get(*id) {
+                    tile.set_field(&field.to_string(), before.clone());
+                }
+            }
+            HistoryOperation::Reconnected { id, before, .. } => {
+                self.move_arrow(*id, before.0, before.1);
+            }
+            HistoryOperation::Retyped { id, before, .. } => self.retype_tile(*id, *before),
+            HistoryOperation::DataChanged {
+                id,
+                component,
+                before,
+                ..
+            } => self.restore_data(*id, *component, before.clone()),
+            HistoryOperation::Redefined {
+                before, migrated, ..
+            } => {
+                let values = migrated.iter().map(|(id, before, _)| (*id, before.clone()));
+                let _ = self.migrate_type(before, Some(values.collect()));
+            }
+        }
+    }
+
+    pub(crate) fn replay_history_operation(self: &Arc<Self>, operation: &HistoryOperation) {
+        match operation {
+            HistoryOperation::Created {
+                id,
+                generation,
+                tile_type,
+                component,
+                fields,
+            } => {
+                self.restore_tile(*id, *tile_type, *component, fields.clone());
+                self.set_generation(*id, *generation);
+            }
+            HistoryOperation::Deleted { id, .. } => self.delete_tile(*id),
+            HistoryOperation::FieldChanged {
+                id, field, after, ..
+            } => {
+                if let Some(mut tile) = self.get(*id) {
+                    tile.set_field(&field.to_string(), after.clone());
+                }
+            }
+            HistoryOperation::Reconnected { id, after, .. } => {
+                self.move_arrow(*id, after.0, after.1);
+            }
+            HistoryOperation::Retyped { id, after, .. } => self.retype_tile(*id, *after),
+            HistoryOperation::DataChanged {
+                id,
+                component,
+                after,
+                ..
+            } => self.restore_data(*id, *component, after.clone()),
+            HistoryOperation::Redefined {
+                after, migrated, ..
+            } => {
+                let values = migrated.iter().map(|(id, _, after)| (*id, after.clone()));
+                // an operation log replays it onto a mosaic that may not have the type yet
+                let _ = self
+                    .migrate_type(after, Some(values.collect()))
+                    .or_else(|_| self.new_type(after));
+            }
+        }
+    }
+
+    pub(crate) fn restore_data(
+        self: &Arc<Self>,
+        id: EntityId,
+        component: S32,
+        data: Option<ComponentValues>,
+    ) {
+        if let Some(tile) = self.get(id) {
+            match data {
+                Some(values) => {
+                    let _ = tile.add_data(&component.to_string(), values);
+                }
+                None => {
+                    tile.remove_data(&component.to_string());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Read},
+};
+
+use anyhow::anyhow;
+
+use super::{
+    ComponentRegistry, ComponentValues, Datatype, EntityId, MosaicCRUD, MosaicIO, Tile, Value, S32,
+};
+
+/// How datasets are turned into tiles by `MosaicIO::import_graphml_with` and
+/// `MosaicIO::import_edge_list`.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// The component of the objects made for nodes.
+    pub node_component: String,
+    /// The component of the arrows made for edges.
+    pub arrow_component: String,
+    /// What separates the columns of an edge list; `None` splits on any run of whitespace.
+    pub delimiter: Option<char>,
+    /// Whether the first line of an edge list names its columns.
+    pub header: bool,
+    /// The names of the edge list columns after the source and target, for lists without a
+    /// header.
+    pub columns: Vec<String>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            node_component: "void".to_string(),
+            arrow_component: "void".to_string(),
+            delimiter: None,
+            header: false,
+            columns: vec![],
+        }
+    }
+}
+
+/// The tiles made by an import.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedGraph {
+    /// The object made for each node, by the name the dataset gave it.
+    pub nodes: HashMap<String, Tile>,
+    /// The arrow made for each edge, in the order they were read.
+    pub arrows: Vec<Tile>,
+}
+
+/// Attributes as (name, text), in the order they were read.
+type Attributes = Vec<(String, String)>;
+
+/// A dataset read but not yet made into tiles.
+#[derive(Default)]
+pub(crate) struct GraphData {
+    nodes: Vec<(String, Attributes)>,
+    edges: Vec<(String, String, Attributes)>,
+}
+
+impl GraphData {
+    fn add_node(&mut self, name: &str, attributes: Attributes) {
+        self.nodes.push((name.to_string(), attributes));
+    }
+}
+
+fn value_from_text(datatype: &Datatype, text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    match datatype {
+        Datatype::I8 => trimmed.parse().map(Value::I8).ok(),
+        Datatype::I16 => trimmed.parse().map(Value::I16).ok(),
+        Datatype::I32 => trimmed.parse().map(Value::I32).ok(),
+        Datatype::I64 => trimmed.parse().map(Value::I64).ok(),
+        Datatype::U8 => trimmed.parse().map(Value::U8).ok(),
+        Datatype::U16 => trimmed.parse().map(Value::U16).ok(),
+        Datatype::U32 => trimmed.parse().map(Value::U32).ok(),
+        Datatype::U64 => trimmed.parse().map(Value::U64).ok(),
+        Datatype::F32 => trimmed.parse().map(Value::F32).ok(),
+        Datatype::F64 => trimmed.parse().map(Value::F64).ok(),
+        Datatype::BOOL => match trimmed {
+            "true" | "1" => Some(Value::BOOL(true)),
+            "false" | "0" => Some(Value::BOOL(false)),
+            _ => None,
+        },
+        Datatype::S32 => S32::new(text).ok().map(Value::S32),
+        Datatype::STR => Some(Value::STR(text.into())),
+        Datatype::DATETIME | Datatype::DURATION => {
+            Value::parse_temporal(datatype, trimmed).and_then(Result::ok)
+        }
+        _ => None,
+    }
+}
+
+/// The values of the attributes named after fields of `component`; the others are left out,
+/// so everything but the structure is dropped for `void`.
+fn fields_from_text(
+    registry: &ComponentRegistry,
+    component: &str,
+    attributes: &[(String, String)],
+) -> anyhow::Result<ComponentValues> {
+    let fields = registry.get_component_type(component.into())?.get_fields();
+    attributes
+        .iter()
+        .filter_map(|(name, text)| {
+            let field = fields.iter().find(|f| f.name.is(name))?;
+            Some(
+                value_from_text(&field.datatype, text)
+                    .map(|value| (field.name, value))
+                    .ok_or(anyhow!(
+                        "{} can't be read as {:?} for field {} of {}",
+                        text,
+                        field.datatype,
+                        name,
+                        component
+                    )),
+            )
+        })
+        .collect()
+}
+
+/// Makes the tiles for `graph`, after checking all of it can be made.
+pub(crate) fn import_graph<M>(
+    mosaic: &M,
+    registry: &ComponentRegistry,
+    graph: GraphData,
+    options: &ImportOptions,
+) -> anyhow::Result<ImportedGraph>
+where
+    M: MosaicIO + MosaicCRUD<EntityId>,
+{
+    let mut names = HashSet::new();
+    let mut nodes = vec![];
+    for (name, attributes) in &graph.nodes {
+        if !names.insert(name) {
+            return Err(anyhow!("Node {} is in the graph twice", name));
+        }
+        nodes.push((
+            name.clone(),
+            fields_from_text(registry, &options.node_component, attributes)?,
+        ));
+    }
+
+    let mut edges = vec![];
+    for (source, target, attributes) in &graph.edges {
+        for end in [source, target] {
+            if !names.contains(end) {
+                return Err(anyhow!(
+                    "An edge goes to node {}, which isn't in the graph",
+                    end
+                ));
+            }
+        }
+        edges.push((
+            source,
+            target,
+            fields_from_text(registry, &options.arrow_component, attributes)?,
+        ));
+    }
+
+    let mut imported = ImportedGraph::default();
+    for (name, fields) in nodes {
+        let object = mosaic.new_object(&options.node_component, fields);
+        imported.nodes.insert(name, object);
+    }
+    for (source, target, fields) in edges {
+        imported.arrows.push(mosaic.try_new_arrow(
+            &imported.nodes[source].id,
+            &imported.nodes[target].id,
+            &options.arrow_component,
+            fields,
+        )?);
+    }
+    Ok(imported)
+}
+
+/// A piece of an XML document, as far as GraphML needs: attributes are unescaped, while
+/// declarations, comments, and doctypes are skipped.
+enum Xml {
+    Open {
+        name: String,
+        attributes: Vec<(String, String)>,
+        empty: bool,
+    },
+    Close(String),
+    Text(String),
+}
+
+fn unescape(text: &str) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or(anyhow!("Unterminated entity in {}", text))?
+            + start;
+        let c = match &rest[start + 1..end] {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32)
+                .ok_or(anyhow!("Unknown entity &{};", entity))?,
+        };
+        result.push(c);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Drops the namespace prefix, if any, from an element or attribute name.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn parse_tag(tag: &str) -> anyhow::Result<Xml> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return Ok(Xml::Close(local_name(name.trim()).to_string()));
+    }
+
+    let (tag, empty) = match tag.strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = local_name(&tag[..name_end]).to_string();
+
+    let mut attributes = vec![];
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or(anyhow!("Attribute without a value in <{}>", tag))?;
+        let after = after.trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or(anyhow!("Unquoted attribute {} in <{}>", key.trim(), tag))?;
+        let end = after[1..].find(quote).ok_or(anyhow!(
+            "Unterminated attribute {} in <{}>",
+            key.trim(),
+            tag
+        ))? + 1;
+        attributes.push((
+            local_name(key.trim()).to_string(),
+            unescape(&after[1..end])?,
+        ));
+        rest = after[end + 1..].trim_start();
+    }
+
+    Ok(Xml::Open {
+        name,
+        attributes,
+        empty,
+    })
+}
+
+fn tokenize_xml(text: &str) -> anyhow::Result<Vec<Xml>> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Xml::Text(unescape(rest)?));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Xml::Text(unescape(&rest[..start])?));
+        }
+        rest = &rest[start..];
+
+        let (close, skip) = if rest.starts_with("<!--") {
+            ("-->", true)
+        } else if rest.starts_with("<![CDATA[") {
+            ("]]>", false)
+        } else if rest.starts_with("<?") {
+            ("?>", true)
+        } else {
+            (">", rest.starts_with("<!"))
+        };
+        let end = rest
+            .find(close)
+            .ok_or(anyhow!("Unterminated markup at {:.32}", rest))?;
+        if let Some(cdata) = rest[..end].strip_prefix("<![CDATA[") {
+            tokens.push(Xml::Text(cdata.to_string()));
+        } else if !skip {
+            tokens.push(parse_tag(&rest[1..end])?);
+        }
+        rest = &rest[end + close.len()..];
+    }
+    Ok(tokens)
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads the nodes and edges of a GraphML document, naming each `<data>` after its key's
+/// `attr.name`; markup GraphML doesn't define is skipped.
+pub(crate) fn parse_graphml(text: &str) -> anyhow::Result<GraphData> {
+    enum Owner {
+        Node(String),
+        Edge(String, String),
+        Other,
+    }
+
+    let mut graph = GraphData::default();
+    let mut keys = HashMap::new();
+    let mut owners: Vec<(Owner, Vec<(String, String)>)> = vec![];
+    let mut data: Option<(String, String)> = None;
+    let mut found_root = false;
+
+    for token in tokenize_xml(text)? {
+        match token {
+            Xml::Text(text) => {
+                if let Some((_, value)) = data.as_mut() {
+                    value.push_str(&text);
+                }
+            }
+            Xml::Open {
+                name,
+                attributes,
+                empty,
+            } => {
+                let required = |attr: &str| {
+                    attribute(&attributes, attr)
+                        .map(str::to_string)
+                        .ok_or(anyhow!("<{}> has no {}", name, attr))
+                };
+                let owner = match name.as_str() {
+                    "graphml" => {
+                        found_root = true;
+                        continue;
+                    }
+                    "key" => {
+                        let id = required("id")?;
+                        let attr_name = attribute(&attributes, "attr.name")
+                            .unwrap_or(&id)
+                            .to_string();
+                        keys.insert(id, attr_name);
+                        Owner::Other
+                    }
+                    "node" => Owner::Node(required("id")?),
+                    "edge" => Owner::Edge(required("source")?, required("target")?),
+                    "data" if data.is_none() => {
+                        let key = required("key")?;
+                        let name = keys.get(&key).cloned().unwrap_or(key);
+                        match empty {
+                            true => {
+                                if let Some((_, owned)) = owners.last_mut() {
+                                    owned.push((name, String::new()));
+                                }
+                            }
+                            false => data = Some((name, String::new())),
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                match empty {
+                    true => match owner {
+                        Owner::Node(id) => graph.add_node(&id, vec![]),
+                        Owner::Edge(source, target) => graph.edges.push((source, target, vec![])),
+                        Owner::Other => {}
+                    },
+                    false => owners.push((owner, vec![])),
+                }
+            }
+            Xml::Close(name) => match name.as_str() {
+                "data" => {
+                    if let (Some(data), Some((_, owned))) = (data.take(), owners.last_mut()) {
+                        owned.push(data);
+                    }
+                }
+                "key" | "node" | "edge" => match owners.pop() {
+                    Some((Owner::Node(id), attributes)) => graph.add_node(&id, attributes),
+                    Some((Owner::Edge(source, target), attributes)) => {
+                        graph.edges.push((source, target, attributes))
+                    }
+                    Some((Owner::Other, _)) => {}
+                    None => return Err(anyhow!("</{}> closes nothing", name)),
+                },
+                _ => {}
+            },
+        }
+    }
+
+    match found_root {
+        true => Ok(graph),
+        false => Err(anyhow!("There is no <graphml> element in the document")),
+    }
+}
+
+/// Splits a line of an edge list into its columns; with a delimiter, a column in double
+/// quotes may hold the delimiter, and `""` inside it stands for a quote, as in CSV.
+fn split_columns(line: &str, delimiter: Option<char>) -> Vec<String> {
+    let Some(delimiter) = delimiter else {
+        return line.split_whitespace().map(str::to_string).collect();
+    };
+
+    // a quoted column is kept as it is, anything between its closing quote and the
+    // delimiter is dropped
+    let finish = |column: String, quoted: bool| match quoted {
+        true => column,
+        false => column.trim().to_string(),
+    };
+
+    let mut columns = vec![];
+    let mut column = String::new();
+    let (mut in_quotes, mut quoted) = (false, false);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                column.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if !quoted && column.trim().is_empty() => {
+                column.clear();
+                (in_quotes, quoted) = (true, true);
+            }
+            c if c == delimiter && !in_quotes => {
+                columns.push(finish(std::mem::take(&mut column), quoted));
+                quoted = false;
+            }
+            _ if quoted && !in_quotes => {}
+            c => column.push(c),
+        }
+    }
+    columns.push(finish(column, quoted));
+    columns
+}
+
+/// Reads an edge list, a source, a target and any further columns on each line, skipping
+/// blank lines and `#` comments.
+pub(crate) fn parse_edge_list(
+    reader: &mut dyn Read,
+    options: &ImportOptions,
+) -> anyhow::Result<GraphData> {
+    let mut graph = GraphData::default();
+    let mut columns = options.columns.clone();
+    let mut needs_header = options.header;
+    let mut seen = HashSet::new();
+
+    for (number, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut values = split_columns(&line, options.delimiter);
+        if needs_header {
+            columns = values.into_iter().skip(2).collect();
+            needs_header = false;
+            continue;
+        }
+        if values.len() < 2 {
+            return Err(anyhow!(
+                "Line {} has no target: {}",
+                number + 1,
+                line.trim()
+            ));
+        }
+
+        let attributes = columns.iter().cloned().zip(values.drain(2..)).collect();
+        let (source, target) = (values.remove(0), values.remove(0));
+        for node in [&source, &target] {
+            if seen.insert(node.clone()) {
+                graph.add_node(node, vec![]);
+            }
+        }
+        graph.edges.push((source, target, attributes));
+    }
+
+    Ok(graph)
+}
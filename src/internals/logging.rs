@@ -8,7 +8,7 @@ pub trait Logging {
     fn to_error<T>(self) -> anyhow::Result<T>;
 }
 
-impl<'a> Logging for &'a str {
+impl Logging for &str {
     fn to_error<T>(self) -> anyhow::Result<T> {
         Err(anyhow!(self.to_string()))
     }
@@ -0,0 +1,146 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use super::{EntityId, Logging, Mosaic, MosaicCRUD, MosaicIO, Tile, S32};
+
+/// What to do with a foreign tile whose id is already taken in the receiving mosaic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdCollision {
+    /// Give every foreign tile a fresh id; nothing local is touched.
+    #[default]
+    Remap,
+    /// Keep foreign ids, replacing local tiles that have the same id.
+    Overwrite,
+    /// Keep foreign ids, leaving local tiles that have the same id as they are.
+    Skip,
+}
+
+/// What to do when both mosaics define a component with the same name but different fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComponentConflict {
+    /// Refuse to merge anything.
+    #[default]
+    Fail,
+    /// Keep the local definition and leave out foreign tiles of that component.
+    KeepLocal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeStrategy {
+    pub ids: IdCollision,
+    pub components: ComponentConflict,
+}
+
+impl MergeStrategy {
+    pub fn remap() -> Self {
+        MergeStrategy {
+            ids: IdCollision::Remap,
+            ..Default::default()
+        }
+    }
+
+    pub fn overwrite() -> Self {
+        MergeStrategy {
+            ids: IdCollision::Overwrite,
+            ..Default::default()
+        }
+    }
+
+    pub fn skip() -> Self {
+        MergeStrategy {
+            ids: IdCollision::Skip,
+            ..Default::default()
+        }
+    }
+
+    pub fn keep_local_components(mut self) -> Self {
+        self.components = ComponentConflict::KeepLocal;
+        self
+    }
+}
+
+impl Mosaic {
+    /// Names of components both mosaics define, but differently.
+    fn conflicting_components(&self, other: &Mosaic) -> Vec<S32> {
+        let local = self.component_registry.component_type_map.read().unwrap();
+        let foreign = other.component_registry.component_type_map.read().unwrap();
+        foreign
+            .iter()
+            .filter(|(name, typ)| local.get(name).is_some_and(|l| l != *typ))
+            .map(|(name, _)| *name)
+            .sorted()
+            .collect_vec()
+    }
+}
+
+pub trait MosaicMerge {
+    /// Copies every tile of `other` in, resolving collisions as `strategy` says, and returns
+    /// where each tile ended up; tiles left out are missing from it.
+    fn merge_from(
+        &self,
+        other: &Arc<Mosaic>,
+        strategy: MergeStrategy,
+    ) -> anyhow::Result<HashMap<EntityId, EntityId>>;
+}
+
+impl MosaicMerge for Arc<Mosaic> {
+    fn merge_from(
+        &self,
+        other: &Arc<Mosaic>,
+        strategy: MergeStrategy,
+    ) -> anyhow::Result<HashMap<EntityId, EntityId>> {
+        let conflicts = self.conflicting_components(other);
+        if !conflicts.is_empty() && strategy.components == ComponentConflict::Fail {
+            return format!(
+                "Cannot merge mosaics, components are defined differently: {}",
+                conflicts.iter().join(", ")
+            )
+            .to_error();
+        }
+
+        self.copy_component_types(other)?;
+
+        let tiles = other
+            .get_all()
+            .filter(|t| !conflicts.contains(&t.component))
+            .sorted_by_key(|t| t.id)
+            .collect_vec();
+
+        if strategy.ids == IdCollision::Remap {
+            return Ok(self.copy_tiles(tiles, HashMap::new()));
+        }
+
+        // ids are kept as they are, so a tile can be placed once all of its endpoints exist
+        let mut mapping = HashMap::new();
+        let mut merged = HashSet::new();
+        let mut pending: Vec<Tile> = tiles;
+        loop {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|t| {
+                [t.source_id(), t.target_id()]
+                    .iter()
+                    .all(|e| *e == t.id || merged.contains(e))
+            });
+
+            if ready.is_empty() {
+                break;
+            }
+
+            for tile in ready {
+                if strategy.ids == IdCollision::Overwrite || !self.is_tile_valid(&tile.id) {
+                    self.restore_tile(tile.id, tile.tile_type, tile.component, tile.data());
+                    mapping.insert(tile.id, tile.id);
+                }
+
+                merged.insert(tile.id);
+            }
+
+            pending = waiting;
+        }
+
+        Ok(mapping)
+    }
+}
@@ -1,46 +1,140 @@
 use std::{
-    collections::{HashMap, HashSet},
-    sync::{Arc, Mutex},
+    collections::{HashMap, HashSet, VecDeque},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
     vec::IntoIter,
 };
 
-use anyhow::anyhow;
 use atomic_counter::{AtomicCounter, RelaxedCounter};
 use fstr::FStr;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use ordered_multimap::ListOrderedMultimap;
+pub use uuid::Uuid;
 
 use super::{
-    slice_into_array, ComponentRegistry, ComponentValues, EntityId, Logging, SparseSet, Tile,
-    TileType, ToByteArray, Value, S32,
+    component_grammar::ComponentParser,
+    import::{import_graph, parse_edge_list, parse_graphml},
+    read_header, write_header, AttachedStorage, Blob, BlobId, ChecksumReader, ChecksumWriter,
+    ComponentRegistry, ComponentType, ComponentValues, ComputedFields, Constraint, CrdtState,
+    DateTime, Duration, EntityId, FieldCache, HistoryJournal, HistoryOperation, HistoryStep,
+    ImportOptions, ImportedGraph, LoggedChange, MosaicError, MosaicFormatError, MosaicTransaction,
+    ObserverRegistry, OperationLog, SparseSet, Str, StringPool, Tile, TileIndices, TileRef,
+    TileType, ToByteArray, Value, ADDED_DATA_VERSION, BLOB_TABLE_VERSION, END_OF_TILES,
+    LEGACY_FORMAT_VERSION, S32, STRING_ID_VERSION, STRING_TABLE_VERSION, UUID_VERSION,
 };
 
 type ComponentName = String;
 type ComponentField = S32;
 type DataStorage = HashMap<ComponentName, HashMap<EntityId, HashMap<ComponentField, Value>>>;
 
+/// A monotonically increasing stamp, bumped on every change made to a mosaic.
+pub type Version = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TileChange {
+    Written(EntityId),
+    Deleted(EntityId),
+    Cleared,
+}
+
+/// What deltas are built from: the latest change to each tile, as a delta only needs to know
+/// how a tile ended up, so earlier changes aren't kept.
+#[derive(Debug, Default)]
+pub(crate) struct ChangeLog {
+    /// The version each tile was last changed at, and whether that change deleted it.
+    latest: HashMap<EntityId, (Version, bool)>,
+    /// The version of the last clear, which dropped every change before it.
+    cleared_at: Option<Version>,
+}
+
+impl ChangeLog {
+    pub(crate) fn entries(&self) -> usize {
+        self.latest.len()
+    }
+}
+
+/// Every live mosaic of this process, by `id`. Only weakly held, so that mosaics go away
+/// once nothing else holds them; see `Mosaic::instances`.
 #[allow(clippy::type_complexity)]
-pub static MOSAIC_INSTANCES: Lazy<Arc<Mutex<HashMap<usize, Arc<Mosaic>>>>> =
+pub static MOSAIC_INSTANCES: Lazy<Arc<Mutex<HashMap<usize, Weak<Mosaic>>>>> =
     Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
+/// The `id` of the next mosaic; ids aren't reused, even after their mosaic is gone.
+static NEXT_MOSAIC_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Locks held together are always taken in this order: `tile_registry`, `dependent_ids_map`,
+/// the `*_ids` sets, `indices`, `component_registry`, `strings`, `data_storage`.
 #[derive(Debug)]
 pub struct Mosaic {
     pub id: usize,
+    /// Names this mosaic across processes and files; kept in saves, see `Mosaic::uuid`.
+    pub(crate) uuid: RwLock<Uuid>,
     pub(crate) entity_counter: RelaxedCounter,
     pub component_registry: ComponentRegistry,
-    pub(crate) tile_registry: Mutex<HashMap<EntityId, Tile>>,
-    pub data_storage: Mutex<DataStorage>,
-    pub(crate) dependent_ids_map: Mutex<ListOrderedMultimap<EntityId, EntityId>>,
-    object_ids: Mutex<SparseSet>,
-    arrow_ids: Mutex<SparseSet>,
-    descriptor_ids: Mutex<SparseSet>,
-    extension_ids: Mutex<SparseSet>,
+    pub(crate) tile_registry: RwLock<HashMap<EntityId, Tile>>,
+    pub data_storage: RwLock<DataStorage>,
+    pub(crate) dependent_ids_map: RwLock<ListOrderedMultimap<EntityId, EntityId>>,
+    pub(crate) object_ids: RwLock<SparseSet>,
+    pub(crate) arrow_ids: RwLock<SparseSet>,
+    pub(crate) descriptor_ids: RwLock<SparseSet>,
+    pub(crate) extension_ids: RwLock<SparseSet>,
+    pub(crate) indices: RwLock<TileIndices>,
+    pub(crate) deletions_since_gc: RelaxedCounter,
+    pub(crate) auto_gc_threshold: AtomicUsize,
+    pub(crate) collecting_garbage: AtomicBool,
+    /// Held shared while data is written for tiles that may not be registered yet, and
+    /// exclusively while garbage collection prunes data that belongs to no tile.
+    pub(crate) creating: RwLock<()>,
+    /// Whether tiles and fields come out in a stable order; see `set_deterministic`.
+    pub(crate) deterministic: AtomicBool,
+    pub(crate) history: Mutex<HistoryJournal>,
+    pub(crate) constraints: Mutex<Vec<Constraint>>,
+    /// Fields worked out when read rather than stored; see `MosaicComputedFields`.
+    pub(crate) computed_fields: RwLock<ComputedFields>,
+    /// Components that can't be written to; see `MosaicAccess::guard_component`.
+    pub(crate) guarded_components: Mutex<HashSet<S32>>,
+    /// Every change made, for replaying later; `None` until logging is turned on.
+    pub(crate) operation_log: Mutex<Option<OperationLog>>,
+    pub(crate) version: RelaxedCounter,
+    /// The version the mosaic was last saved or loaded at; see `MosaicStorage::is_dirty`.
+    pub(crate) saved_version: AtomicUsize,
+    /// Bumped whenever tiles come or go without observers hearing about it (restoring tiles,
+    /// clearing), so caches that keep up by observing know to start over.
+    pub(crate) unobserved_changes: RelaxedCounter,
+    pub(crate) change_log: Mutex<ChangeLog>,
+    pub(crate) observers: Mutex<ObserverRegistry>,
+    /// Ids of deleted tiles waiting to be handed out again; `None` when recycling is off.
+    pub(crate) recycled_ids: Mutex<Option<VecDeque<EntityId>>>,
+    /// Hands out the generation of each tile made, to tell stale `TileHandle`s apart.
+    pub(crate) generations: RelaxedCounter,
+    pub(crate) storage: Mutex<Option<AttachedStorage>>,
+    pub(crate) strings: RwLock<StringPool>,
+    /// Replica bookkeeping for conflict-free merges; `None` until CRDT mode is turned on.
+    pub(crate) crdt: Mutex<Option<CrdtState>>,
+    /// Components whose objects and arrows have audit hooks, and whether they are being
+    /// stamped; see `AuditCapability`.
+    pub(crate) audited_components: Mutex<HashMap<S32, bool>>,
 }
 
+impl Drop for Mosaic {
+    fn drop(&mut self) {
+        // not while unwinding from a panic that poisoned the registry
+        if let Ok(mut instances) = MOSAIC_INSTANCES.lock() {
+            instances.remove(&self.id);
+        }
+    }
+}
+
+/// Two mosaics are equal only if they are the same mosaic; to compare what they hold, see
+/// `MosaicComparison::structurally_equals`.
 impl PartialEq for Mosaic {
-    fn eq(&self, _: &Self) -> bool {
-        true
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
     }
 }
 
@@ -49,8 +143,8 @@ impl Eq for Mosaic {}
 impl Mosaic {
     pub fn dot(&self, name: &str) -> String {
         let tiles = {
-            let reg = self.tile_registry.lock().unwrap();
-            reg.values().cloned().collect_vec()
+            let reg = self.tile_registry.read().unwrap();
+            reg.values().cloned().sorted_by_key(|t| t.id).collect_vec()
         };
 
         let horizontal = tiles.len() < 50;
@@ -105,45 +199,433 @@ impl Mosaic {
     }
 
     pub fn new() -> Arc<Mosaic> {
-        let id = { MOSAIC_INSTANCES.lock().unwrap().len() };
+        let id = NEXT_MOSAIC_ID.fetch_add(1, Ordering::Relaxed);
 
         let mosaic = Arc::new(Mosaic {
             id,
+            uuid: RwLock::new(Uuid::new_v4()),
             entity_counter: RelaxedCounter::default(),
             component_registry: ComponentRegistry::default(),
-            tile_registry: Mutex::new(HashMap::default()),
-            dependent_ids_map: Mutex::new(ListOrderedMultimap::default()),
-            data_storage: Mutex::new(HashMap::new()),
-            object_ids: Mutex::new(SparseSet::default()),
-            arrow_ids: Mutex::new(SparseSet::default()),
-            descriptor_ids: Mutex::new(SparseSet::default()),
-            extension_ids: Mutex::new(SparseSet::default()),
+            tile_registry: RwLock::new(HashMap::default()),
+            dependent_ids_map: RwLock::new(ListOrderedMultimap::default()),
+            data_storage: RwLock::new(HashMap::new()),
+            object_ids: RwLock::new(SparseSet::default()),
+            arrow_ids: RwLock::new(SparseSet::default()),
+            descriptor_ids: RwLock::new(SparseSet::default()),
+            extension_ids: RwLock::new(SparseSet::default()),
+            indices: RwLock::new(TileIndices::default()),
+            deletions_since_gc: RelaxedCounter::default(),
+            auto_gc_threshold: AtomicUsize::new(0),
+            collecting_garbage: AtomicBool::new(false),
+            creating: RwLock::new(()),
+            deterministic: AtomicBool::new(false),
+            history: Mutex::new(HistoryJournal::default()),
+            constraints: Mutex::new(vec![]),
+            computed_fields: RwLock::new(ComputedFields::default()),
+            guarded_components: Mutex::new(HashSet::new()),
+            operation_log: Mutex::new(None),
+            version: RelaxedCounter::default(),
+            saved_version: AtomicUsize::new(0),
+            unobserved_changes: RelaxedCounter::default(),
+            change_log: Mutex::new(ChangeLog::default()),
+            observers: Mutex::new(ObserverRegistry::default()),
+            recycled_ids: Mutex::new(None),
+            generations: RelaxedCounter::default(),
+            storage: Mutex::new(None),
+            strings: RwLock::new(StringPool::default()),
+            crdt: Mutex::new(None),
+            audited_components: Mutex::new(HashMap::new()),
         });
 
         mosaic.new_type("void: unit;").unwrap();
 
-        {
-            MOSAIC_INSTANCES
-                .lock()
-                .unwrap()
-                .insert(mosaic.id, Arc::clone(&mosaic));
-        }
+        MOSAIC_INSTANCES
+            .lock()
+            .unwrap()
+            .insert(mosaic.id, Arc::downgrade(&mosaic));
         mosaic
     }
 
+    /// The mosaics of this process still alive and registered, oldest first; one with tiles
+    /// left is only dropped once cleared.
+    pub fn instances() -> Vec<Arc<Mosaic>> {
+        let live = MOSAIC_INSTANCES
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect_vec();
+        // dropped with the registry let go, as dropping the last of a mosaic unregisters it
+        live.into_iter().sorted_by_key(|m| m.id).collect_vec()
+    }
+
+    /// Takes this mosaic out of `instances`, so `find` and the `TileRef`s into it no longer
+    /// reach it. It is otherwise left as it is.
+    pub fn unregister(&self) {
+        MOSAIC_INSTANCES.lock().unwrap().remove(&self.id);
+    }
+
+    /// A random id given to every mosaic and written into its saves; an empty mosaic takes
+    /// the uuid of the save loaded into it.
+    pub fn uuid(&self) -> Uuid {
+        *self.uuid.read().unwrap()
+    }
+
+    /// The live mosaic with `uuid`; if a save was loaded into several, the one made first.
+    pub fn find(uuid: Uuid) -> Option<Arc<Mosaic>> {
+        Mosaic::instances()
+            .into_iter()
+            .find(|mosaic| mosaic.uuid() == uuid)
+    }
+
+    /// Turns on reusing the ids of deleted tiles for new ones, oldest first. This is off by
+    /// default, as anything holding on to ids of deleted tiles could end up pointing to new ones.
+    pub fn set_id_recycling(&self, enabled: bool) {
+        let mut recycled = self.recycled_ids.lock().unwrap();
+        match (enabled, recycled.is_some()) {
+            (true, false) => *recycled = Some(VecDeque::new()),
+            (false, true) => *recycled = None,
+            _ => {}
+        }
+    }
+
+    /// Makes `get_all` and `Tile::data` come out sorted, so output is the same from run to
+    /// run, at the cost of a sort per call.
+    pub fn set_deterministic(&self, enabled: bool) {
+        self.deterministic.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic.load(Ordering::Relaxed)
+    }
+
+    fn recycle_id(&self, id: EntityId) {
+        if let Some(recycled) = self.recycled_ids.lock().unwrap().as_mut() {
+            recycled.push_back(id);
+        }
+    }
+
     fn next_id(&self) -> EntityId {
-        let registry = self.tile_registry.lock().unwrap();
-        let mut id = self.entity_counter.inc();
-        while registry.contains_key(&id) {
-            id = self.entity_counter.inc();
+        self.next_ids(1)[0]
+    }
+
+    /// Hands out `count` unused ids, taking the locks involved only once.
+    pub(crate) fn next_ids(&self, count: usize) -> Vec<EntityId> {
+        let registry = self.tile_registry.read().unwrap();
+        let mut ids = Vec::with_capacity(count);
+        if let Some(recycled) = self.recycled_ids.lock().unwrap().as_mut() {
+            // ids can come back to life through undo or loading, so skip those
+            while ids.len() < count {
+                match recycled.pop_front() {
+                    Some(id) if registry.contains_key(&id) => continue,
+                    Some(id) => ids.push(id),
+                    None => break,
+                }
+            }
+        }
+
+        while ids.len() < count {
+            let id = self.entity_counter.inc();
+            if !registry.contains_key(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Puts a tile with a known id, shape, and data into this mosaic, replacing any tile
+    /// that was previously there. Used when tiles come from outside (loading, replicas).
+    pub(crate) fn restore_tile(
+        self: &Arc<Self>,
+        id: EntityId,
+        tile_type: TileType,
+        component: S32,
+        fields: ComponentValues,
+    ) -> Tile {
+        self.try_restore_tile(id, tile_type, component, fields)
+            .expect("Cannot restore tile, panicking!")
+    }
+
+    /// Like `restore_tile`, but fails if `fields` don't fit `component`.
+    pub(crate) fn try_restore_tile(
+        self: &Arc<Self>,
+        id: EntityId,
+        tile_type: TileType,
+        component: S32,
+        fields: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.unobserved_changes.inc();
+        if let Some(mut existing) = self.get(id) {
+            if existing.tile_type == tile_type && existing.component == component {
+                for (name, value) in fields {
+                    existing.try_set_field(&name.to_string(), value)?;
+                }
+                return Ok(existing);
+            }
+
+            self.delete_tile(id);
+        }
+
+        let tile = Tile::try_new(Arc::clone(self), id, tile_type, component, fields)?;
+        match tile_type {
+            TileType::Object => self.object_ids.write().unwrap().add(id),
+            TileType::Arrow { .. } => self.arrow_ids.write().unwrap().add(id),
+            TileType::Descriptor { .. } => self.descriptor_ids.write().unwrap().add(id),
+            TileType::Extension { .. } => self.extension_ids.write().unwrap().add(id),
+        }
+        Ok(tile)
+    }
+
+    /// Adds freshly made tiles that all hold the same `fields`, taking each lock only once
+    /// rather than once per tile.
+    pub(crate) fn insert_tiles(self: &Arc<Self>, tiles: &[Tile], fields: ComponentValues) {
+        let tiles = tiles
+            .iter()
+            .map(|tile| (tile.clone(), fields.clone()))
+            .collect_vec();
+        self.insert_tiles_with_fields(tiles);
+    }
+
+    /// Adds freshly made tiles, each with fields of its own, taking each lock only once. A tile
+    /// can depend on one that comes before it, e.g. a descriptor on an object made with it.
+    pub(crate) fn insert_tiles_with_fields(self: &Arc<Self>, tiles: Vec<(Tile, ComponentValues)>) {
+        let creating = self.creating.read().unwrap();
+        let tiles = {
+            let mut strings = self.strings.write().unwrap();
+            tiles
+                .into_iter()
+                .map(|(tile, fields)| {
+                    let fields = fields
+                        .into_iter()
+                        .map(|(name, value)| (name, strings.intern_value(value)))
+                        .collect::<HashMap<_, _>>();
+                    (tile, fields)
+                })
+                .collect_vec()
+        };
+
+        {
+            let mut registry = self.tile_registry.write().unwrap();
+            let mut dependents = self.dependent_ids_map.write().unwrap();
+            let mut object_ids = self.object_ids.write().unwrap();
+            let mut arrow_ids = self.arrow_ids.write().unwrap();
+            let mut descriptor_ids = self.descriptor_ids.write().unwrap();
+            let mut extension_ids = self.extension_ids.write().unwrap();
+            let mut indices = self.indices.write().unwrap();
+            let mut storage = self.data_storage.write().unwrap();
+
+            for (tile, fields) in &tiles {
+                match tile.tile_type {
+                    TileType::Object => object_ids.add(tile.id),
+                    TileType::Arrow { source, target } => {
+                        dependents.append(source, tile.id);
+                        dependents.append(target, tile.id);
+                        arrow_ids.add(tile.id);
+                    }
+                    TileType::Descriptor { subject } => {
+                        dependents.append(subject, tile.id);
+                        descriptor_ids.add(tile.id);
+                    }
+                    TileType::Extension { subject } => {
+                        dependents.append(subject, tile.id);
+                        extension_ids.add(tile.id);
+                    }
+                }
+
+                storage
+                    .entry(tile.component.to_string())
+                    .or_default()
+                    .insert(tile.id, fields.clone());
+                registry.insert(tile.id, tile.clone());
+                indices.insert(tile);
+            }
+        }
+        drop(creating);
+
+        for (tile, fields) in tiles {
+            self.record_history(HistoryOperation::Created {
+                id: tile.id,
+                generation: tile.generation,
+                tile_type: tile.tile_type,
+                component: tile.component,
+                fields: fields.into_iter().collect_vec(),
+            });
+        }
+    }
+
+    /// Every tile mutation passes through here, so it feeds the undo journal, the change
+    /// log that deltas are built from, the CRDT replica state, and the subscribed observers.
+    pub(crate) fn record_history(self: &Arc<Self>, operation: HistoryOperation) {
+        match &operation {
+            HistoryOperation::Created { id, .. }
+            | HistoryOperation::FieldChanged { id, .. }
+            | HistoryOperation::Reconnected { id, .. }
+            | HistoryOperation::Retyped { id, .. }
+            | HistoryOperation::DataChanged { id, .. } => self.log_change(TileChange::Written(*id)),
+            HistoryOperation::Deleted { id, .. } => self.log_change(TileChange::Deleted(*id)),
+            HistoryOperation::Redefined { migrated, .. } => {
+                // the new definition goes into the next save even with no tiles holding it
+                self.version.inc();
+                for (id, _, _) in migrated {
+                    self.log_change(TileChange::Written(*id));
+                }
+            }
+        }
+        let mut ids = HashSet::new();
+        {
+            let strings = self.strings.read().unwrap();
+            for value in operation.values() {
+                strings.ids_in(value, &mut ids);
+            }
+        }
+        self.record_crdt(&operation);
+        self.notify_observers(&operation);
+        self.log_operation(LoggedChange::Tile(operation.clone()));
+        let dropped = {
+            let mut history = self.history.lock().unwrap();
+            history.record(operation);
+            std::mem::take(&mut history.dropped)
+        };
+        self.release_strings(ids, dropped);
+    }
+
+    /// Drops `steps` and forgets the pooled strings with the given ids, or held by the
+    /// operations of `steps`, that nothing else holds on to anymore.
+    pub(crate) fn release_strings(&self, mut ids: HashSet<Str>, steps: Vec<HistoryStep>) {
+        let mut strings = self.strings.write().unwrap();
+        for value in steps
+            .iter()
+            .flat_map(|step| &step.operations)
+            .flat_map(|op| op.values())
+        {
+            strings.ids_in(value, &mut ids);
         }
-        id
+        drop(steps);
+        strings.release(ids);
+    }
+
+    /// Whether the mosaic was cleared after `since`, and the ids of the tiles written and
+    /// deleted after it, or after the last clear if there was one.
+    pub(crate) fn changes_since(
+        &self,
+        since: Version,
+    ) -> (bool, HashSet<EntityId>, HashSet<EntityId>) {
+        let mut written = HashSet::new();
+        let mut deleted = HashSet::new();
+
+        let log = self.change_log.lock().unwrap();
+        let cleared = log.cleared_at.is_some_and(|version| version > since);
+        for (id, (version, is_deleted)) in &log.latest {
+            if *version <= since {
+                continue;
+            }
+            match is_deleted {
+                true => deleted.insert(*id),
+                false => written.insert(*id),
+            };
+        }
+
+        (cleared, written, deleted)
+    }
+
+    pub(crate) fn log_change(&self, change: TileChange) {
+        let mut log = self.change_log.lock().unwrap();
+        let version = self.version.inc() + 1;
+        match change {
+            TileChange::Written(id) => {
+                log.latest.insert(id, (version, false));
+            }
+            TileChange::Deleted(id) => {
+                log.latest.insert(id, (version, true));
+            }
+            TileChange::Cleared => {
+                log.latest.clear();
+                log.cleared_at = Some(version);
+            }
+        }
+    }
+
+    pub(crate) fn apply_load_command(
+        self: &Arc<Self>,
+        command: MosaicLoadCommand,
+        offset: EntityId,
+        saved_strings: Option<&HashMap<Str, Arc<str>>>,
+    ) -> anyhow::Result<()> {
+        let read_fields = |component_type: &ComponentType, data| {
+            let fields = Tile::create_fields_from_binary_data(self, component_type, data)?;
+            match saved_strings {
+                Some(table) => fields
+                    .into_iter()
+                    .map(|(name, value)| Ok((name, StringPool::from_saved(value, table)?)))
+                    .collect::<anyhow::Result<HashMap<_, _>>>(),
+                None => Ok(fields),
+            }
+        };
+
+        match command {
+            MosaicLoadCommand::AddType(definition) => {
+                let typename: S32 = ComponentParser::type_name_of(&definition).into();
+
+                if !self.component_registry.has_component_type(&typename) {
+                    self.component_registry
+                        .add_component_types(definition.as_str())?;
+                }
+            }
+            MosaicLoadCommand::CreateTile(id, src, tgt, component, data) => {
+                let shift = |id: EntityId| {
+                    id.checked_add(offset).ok_or_else(|| {
+                        MosaicFormatError::CorruptData(format!("tile id {} is out of range", id))
+                    })
+                };
+                let (id, src, tgt) = (shift(id)?, shift(src)?, shift(tgt)?);
+                let component_type = &self.component_registry.get_component_type(component)?;
+
+                let fields = read_fields(component_type, data)?;
+
+                let tile_type = if id == src && id == tgt {
+                    // ID : ID -> ID
+                    TileType::Object
+                } else if id == src && src != tgt {
+                    // ID : ID -> TGT (descriptor)
+                    TileType::Descriptor { subject: tgt }
+                } else if id == tgt && src != tgt {
+                    // ID : SRC -> ID (extension)
+                    TileType::Extension { subject: src }
+                } else {
+                    TileType::Arrow {
+                        source: src,
+                        target: tgt,
+                    }
+                };
+
+                self.try_restore_tile(id, tile_type, component, fields.into_iter().collect())?;
+            }
+            MosaicLoadCommand::AddString(id, string) => {
+                self.strings.write().unwrap().intern_as(id, &string);
+            }
+            MosaicLoadCommand::AddData(id, component, data) => {
+                let component_type = &self.component_registry.get_component_type(component)?;
+                let fields = read_fields(component_type, data)?;
+                let tile = id
+                    .checked_add(offset)
+                    .and_then(|id| self.get(id))
+                    .ok_or_else(|| {
+                        MosaicFormatError::CorruptData(format!("data added to missing tile {}", id))
+                    })?;
+
+                tile.add_data(&component.to_string(), fields.into_iter().collect())?;
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[derive(Default)]
 pub struct ComponentValuesBuilder {
     values: HashMap<S32, Value>,
+    /// Field names and `s32` values that were too long to set, see `try_ok`.
+    overlong: Vec<String>,
 }
 
 pub fn par<T>(t: T) -> ComponentValues
@@ -158,8 +640,30 @@ pub fn pars() -> ComponentValuesBuilder {
 }
 
 impl ComponentValuesBuilder {
+    /// Like `try_ok`, but panics if a field name or `s32` value was too long to set.
     pub fn ok(self) -> ComponentValues {
-        self.values.into_iter().map(|(k, v)| (k, v)).collect_vec()
+        self.try_ok().expect("Cannot build values, panicking!")
+    }
+
+    /// The values set, or an error naming the first field name or `s32` value that was too
+    /// long to set.
+    pub fn try_ok(self) -> anyhow::Result<ComponentValues> {
+        if let Some(name) = self.overlong.first() {
+            return Err(MosaicError::NameTooLong(name.clone()).into());
+        }
+        Ok(self.values.into_iter().collect_vec())
+    }
+
+    fn insert(mut self, field: &str, value: Value) -> ComponentValuesBuilder {
+        self.note_overlong(field);
+        self.values.insert(S32::truncating(field), value);
+        self
+    }
+
+    fn note_overlong(&mut self, text: &str) {
+        if !S32::fits(text) {
+            self.overlong.push(text.to_string());
+        }
     }
 }
 
@@ -175,98 +679,113 @@ where
 }
 
 impl ComponentValuesBuilderSetter<u8> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: u8) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::U8(value));
-        self
+    fn set(self, field: &str, value: u8) -> ComponentValuesBuilder {
+        self.insert(field, Value::U8(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<u16> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: u16) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::U16(value));
-        self
+    fn set(self, field: &str, value: u16) -> ComponentValuesBuilder {
+        self.insert(field, Value::U16(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<u32> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: u32) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::U32(value));
-        self
+    fn set(self, field: &str, value: u32) -> ComponentValuesBuilder {
+        self.insert(field, Value::U32(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<u64> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: u64) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::U64(value));
-        self
+    fn set(self, field: &str, value: u64) -> ComponentValuesBuilder {
+        self.insert(field, Value::U64(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<i8> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: i8) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::I8(value));
-        self
+    fn set(self, field: &str, value: i8) -> ComponentValuesBuilder {
+        self.insert(field, Value::I8(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<i16> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: i16) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::I16(value));
-        self
+    fn set(self, field: &str, value: i16) -> ComponentValuesBuilder {
+        self.insert(field, Value::I16(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<i32> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: i32) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::I32(value));
-        self
+    fn set(self, field: &str, value: i32) -> ComponentValuesBuilder {
+        self.insert(field, Value::I32(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<i64> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: i64) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::I64(value));
-        self
+    fn set(self, field: &str, value: i64) -> ComponentValuesBuilder {
+        self.insert(field, Value::I64(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<&str> for ComponentValuesBuilder {
     fn set(mut self, field: &str, value: &str) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::S32(value.into()));
-        self
+        self.note_overlong(value);
+        self.insert(field, Value::S32(S32::truncating(value)))
     }
 }
 
 impl ComponentValuesBuilderSetter<String> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: String) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::STR(value));
-        self
+    fn set(self, field: &str, value: String) -> ComponentValuesBuilder {
+        self.insert(field, Value::STR(value.into()))
     }
 }
 
 impl ComponentValuesBuilderSetter<f32> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: f32) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::F32(value));
-        self
+    fn set(self, field: &str, value: f32) -> ComponentValuesBuilder {
+        self.insert(field, Value::F32(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<f64> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: f64) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::F64(value));
-        self
+    fn set(self, field: &str, value: f64) -> ComponentValuesBuilder {
+        self.insert(field, Value::F64(value))
     }
 }
 
 impl ComponentValuesBuilderSetter<bool> for ComponentValuesBuilder {
-    fn set(mut self, field: &str, value: bool) -> ComponentValuesBuilder {
-        self.values.insert(field.into(), Value::BOOL(value));
-        self
+    fn set(self, field: &str, value: bool) -> ComponentValuesBuilder {
+        self.insert(field, Value::BOOL(value))
+    }
+}
+
+impl ComponentValuesBuilderSetter<TileRef> for ComponentValuesBuilder {
+    fn set(self, field: &str, value: TileRef) -> ComponentValuesBuilder {
+        self.insert(field, Value::REF(value))
+    }
+}
+
+impl ComponentValuesBuilderSetter<Blob> for ComponentValuesBuilder {
+    fn set(self, field: &str, value: Blob) -> ComponentValuesBuilder {
+        self.insert(field, Value::BLOB(value))
+    }
+}
+
+impl ComponentValuesBuilderSetter<DateTime> for ComponentValuesBuilder {
+    fn set(self, field: &str, value: DateTime) -> ComponentValuesBuilder {
+        self.insert(field, Value::DATETIME(value))
+    }
+}
+
+impl ComponentValuesBuilderSetter<Duration> for ComponentValuesBuilder {
+    fn set(self, field: &str, value: Duration) -> ComponentValuesBuilder {
+        self.insert(field, Value::DURATION(value))
     }
 }
 
 pub trait MosaicTypelevelCRUD {
     fn new_type(&self, type_def: &str) -> anyhow::Result<()>;
+    /// Like `new_type`, but replaces a type that already exists and migrates its tiles, keeping
+    /// the values of fields that stay the same; it can be undone.
+    fn redefine_type(&self, type_def: &str) -> anyhow::Result<()>;
 }
 
 pub trait MosaicCRUD<Id> {
@@ -280,6 +799,21 @@ pub trait MosaicCRUD<Id> {
         defaults: ComponentValues,
     ) -> Tile;
     fn new_descriptor(&self, subject: &Id, component: &str, defaults: ComponentValues) -> Tile;
+    /// Like `new_arrow`, but returns an error instead of panicking when a constraint is broken.
+    fn try_new_arrow(
+        &self,
+        source: &Id,
+        target: &Id,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile>;
+    /// Like `new_descriptor`, but returns an error instead of panicking when a constraint is broken.
+    fn try_new_descriptor(
+        &self,
+        subject: &Id,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile>;
     fn new_extension(&self, subject: &Id, component: &str, defaults: ComponentValues) -> Tile;
     fn is_tile_valid(&self, i: &Id) -> bool;
     fn delete_tile(&self, tile: Id);
@@ -291,29 +825,60 @@ pub trait MosaicCopy<Id>: MosaicCRUD<Id> {
 
 impl MosaicCopy<EntityId> for Arc<Mosaic> {
     fn copy_from(&self, from: &Self) {
-        let mut mapping = HashMap::new();
-        for foreign_entity in from.get_all() {
-            let comp = foreign_entity.component.to_string();
-            let data = foreign_entity.data();
-
-            let local_entity = match foreign_entity.tile_type {
-                TileType::Object => self.new_object(comp.as_str(), data),
-                TileType::Arrow { source, target } => self.new_arrow(
-                    mapping.get(&source).unwrap(),
-                    mapping.get(&target).unwrap(),
-                    comp.as_str(),
-                    data,
-                ),
-                TileType::Descriptor { subject } => {
-                    self.new_descriptor(mapping.get(&subject).unwrap(), comp.as_str(), data)
-                }
-                TileType::Extension { subject } => {
-                    self.new_extension(mapping.get(&subject).unwrap(), comp.as_str(), data)
-                }
-            };
+        self.copy_tiles(from.get_all().collect_vec(), HashMap::new());
+    }
+}
 
-            mapping.insert(foreign_entity.id, local_entity.id);
-        }
+pub trait MosaicArrowQueries<Id> {
+    /// Arrows going from `source` into `target`, looked up through the endpoints index.
+    fn get_arrows_between(&self, source: &Id, target: &Id) -> IntoIter<Tile>;
+    /// Same as `get_arrows_between`, keeping only arrows of the given component.
+    fn get_arrows_between_with(&self, source: &Id, target: &Id, component: &str) -> IntoIter<Tile>;
+}
+
+impl MosaicArrowQueries<EntityId> for Arc<Mosaic> {
+    fn get_arrows_between(&self, source: &EntityId, target: &EntityId) -> IntoIter<Tile> {
+        let ids = self
+            .indices
+            .read()
+            .unwrap()
+            .arrows_between(*source, *target);
+
+        self.get_tiles(ids)
+    }
+
+    fn get_arrows_between_with(
+        &self,
+        source: &EntityId,
+        target: &EntityId,
+        component: &str,
+    ) -> IntoIter<Tile> {
+        let ids =
+            self.indices
+                .read()
+                .unwrap()
+                .arrows_between_with(*source, *target, component.into());
+
+        self.get_tiles(ids)
+    }
+}
+
+impl MosaicArrowQueries<Tile> for Arc<Mosaic> {
+    fn get_arrows_between(&self, source: &Tile, target: &Tile) -> IntoIter<Tile> {
+        <Arc<Mosaic> as MosaicArrowQueries<EntityId>>::get_arrows_between(
+            self, &source.id, &target.id,
+        )
+    }
+
+    fn get_arrows_between_with(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        component: &str,
+    ) -> IntoIter<Tile> {
+        <Arc<Mosaic> as MosaicArrowQueries<EntityId>>::get_arrows_between_with(
+            self, &source.id, &target.id, component,
+        )
     }
 }
 
@@ -330,10 +895,15 @@ impl TileGetById for Arc<Mosaic> {
     }
 }
 
+/// A step in recreating a saved mosaic, as read by `load_mosaic_commands`.
 #[derive(Debug, Clone)]
-pub(crate) enum MosaicLoadCommand {
+pub enum MosaicLoadCommand {
     AddType(String),
+    /// A string of the string table, which the `str` fields of the tile data that follows
+    /// refer to by its id, written out in decimal.
+    AddString(Str, String),
     CreateTile(EntityId, EntityId, EntityId, S32, Vec<u8>),
+    AddData(EntityId, S32, Vec<u8>),
 }
 
 pub trait MosaicIO {
@@ -342,246 +912,712 @@ pub trait MosaicIO {
     fn load(&self, data: &[u8]) -> anyhow::Result<()>;
     fn get(&self, i: EntityId) -> Option<Tile>;
     fn get_all(&self) -> IntoIter<Tile>;
+    /// The version of the last change made to this mosaic.
+    fn version(&self) -> Version;
+    /// Serializes only what changed after `since`, to be applied with `apply_delta`.
+    fn save_delta(&self, since: Version) -> Vec<u8>;
+    fn apply_delta(&self, data: &[u8]) -> anyhow::Result<()>;
     fn new_object(&self, component: &str, defaults: ComponentValues) -> Tile;
     fn new_specific_object(&self, id: EntityId, component: &str) -> anyhow::Result<Tile>;
+    /// Makes an object for each node of a GraphML document and an arrow for each edge, all
+    /// of them `void`. See `import_graphml_with`.
+    fn import_graphml(&self, text: &str) -> anyhow::Result<ImportedGraph>;
+    /// Makes an object for each node of a GraphML document and an arrow for each edge, filling
+    /// fields from `<data>` by name; nothing is made if any of it can't be read.
+    fn import_graphml_with(
+        &self,
+        text: &str,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph>;
+    /// Makes an arrow for each line of an edge list, such as a CSV file, and an object for each
+    /// node, see `ImportOptions`.
+    fn import_edge_list(
+        &self,
+        reader: &mut dyn Read,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph>;
 }
 
-pub(crate) fn load_mosaic_commands(data: &[u8]) -> anyhow::Result<Vec<MosaicLoadCommand>> {
-    let mut result = vec![];
-    let mut ptr = 0usize;
+/// Parses a saved mosaic into the commands that would recreate it, leaving out type
+/// definitions no tile uses. Fails on malformed data rather than panicking, whatever the bytes.
+pub fn load_mosaic_commands(data: &[u8]) -> anyhow::Result<Vec<MosaicLoadCommand>> {
+    let mut reader = data;
+    let (version, _) = read_header(&mut reader)?;
+    if version == LEGACY_FORMAT_VERSION {
+        reader = data;
+    }
+    let mut reader = ChecksumReader::new(reader);
+
+    let commands = (|| {
+        if version >= UUID_VERSION {
+            read_array::<_, 16>(&mut reader)?;
+        }
 
-    let total = data.len();
+        let mut result = vec![];
+        loop {
+            let len = u16::from_be_bytes(read_array(&mut reader)?);
+            if len == 0 {
+                break;
+            }
 
-    loop {
-        let len = u16::from_be_bytes(slice_into_array(&data[ptr..ptr + 2]));
-        ptr += 2;
-        if len == 0 {
-            break;
-        } else {
-            let s = std::str::from_utf8(&data[ptr..ptr + len as usize]).unwrap();
-            ptr += len as usize;
-            result.push(MosaicLoadCommand::AddType(s.to_owned()));
+            let definition = String::from_utf8(read_bytes(&mut reader, len as usize)?)
+                .map_err(|_| MosaicFormatError::CorruptData("type name is not utf-8".into()))?;
+            result.push(MosaicLoadCommand::AddType(definition));
         }
-    }
 
-    let mut types_used = HashSet::new();
+        // before string ids, strings are written out in full in the tile data too
+        if version >= STRING_ID_VERSION {
+            let table = read_string_table(&mut reader)?;
+            result.extend(
+                table
+                    .into_iter()
+                    .map(|(id, string)| MosaicLoadCommand::AddString(id, string)),
+            );
+        } else if version >= STRING_TABLE_VERSION {
+            read_string_table(&mut reader)?;
+        }
+        if version >= BLOB_TABLE_VERSION {
+            read_blob_table(&mut reader)?;
+        }
+
+        let mut types_used = HashSet::new();
+        loop {
+            let Some(id) = read_tile_id(&mut reader)? else {
+                if version == LEGACY_FORMAT_VERSION {
+                    break;
+                }
+                return Err(MosaicFormatError::CorruptData("tiles do not end".into()).into());
+            };
+            if id == END_OF_TILES {
+                break;
+            }
+
+            let (src, tgt, comp_name, comp_data) = read_tile_record(&mut reader)?;
+            types_used.insert(comp_name.to_string());
+            result.push(MosaicLoadCommand::CreateTile(
+                id, src, tgt, comp_name, comp_data,
+            ));
+        }
+
+        if version >= ADDED_DATA_VERSION {
+            loop {
+                let Some(id) = read_tile_id(&mut reader)? else {
+                    return Err(
+                        MosaicFormatError::CorruptData("added data does not end".into()).into(),
+                    );
+                };
+                if id == END_OF_TILES {
+                    break;
+                }
 
-    loop {
-        if ptr == total {
-            break;
+                let (comp_name, comp_data) = read_added_data_record(&mut reader)?;
+                types_used.insert(comp_name.to_string());
+                result.push(MosaicLoadCommand::AddData(id, comp_name, comp_data));
+            }
         }
 
-        let id = usize::from_be_bytes(slice_into_array(&data[ptr..ptr + 8]));
-        ptr += 8;
-        let src = usize::from_be_bytes(slice_into_array(&data[ptr..ptr + 8]));
-        ptr += 8;
-        let tgt = usize::from_be_bytes(slice_into_array(&data[ptr..ptr + 8]));
-        ptr += 8;
-        let comp_len = usize::from_be_bytes(slice_into_array(&data[ptr..ptr + 8]));
-        ptr += 8;
-        let comp_name = S32(FStr::<32>::from_str_lossy(
-            std::str::from_utf8(&data[ptr..ptr + comp_len]).unwrap(),
-            b'\0',
-        ));
-        ptr += comp_len;
-        let comp_data_len = u32::from_be_bytes(slice_into_array(&data[ptr..ptr + 4]));
-        ptr += 4;
-        let comp_data = data[ptr..ptr + comp_data_len as usize].to_vec();
-        ptr += comp_data_len as usize;
+        anyhow::Ok(
+            result
+                .into_iter()
+                .filter(|command| match command {
+                    MosaicLoadCommand::AddType(t) => {
+                        types_used.contains(ComponentParser::type_name_of(t))
+                    }
+                    _ => true,
+                })
+                .collect_vec(),
+        )
+    })()
+    .map_err(MosaicFormatError::from_read_error)?;
+
+    if version != LEGACY_FORMAT_VERSION {
+        reader.verify()?;
+    }
+    Ok(commands)
+}
 
-        result.push(MosaicLoadCommand::CreateTile(
-            id, src, tgt, comp_name, comp_data,
-        ));
+pub trait MosaicStreamIO {
+    /// Writes the same format as `save`, one tile at a time, without building it in memory.
+    fn save_to<W: Write>(&self, writer: W) -> anyhow::Result<()>;
+    /// Reads the format written by `save`/`save_to`, one tile at a time. What it loads can't
+    /// be undone, nor is it rolled back with a transaction it's called in.
+    fn load_from<R: Read>(&self, reader: R) -> anyhow::Result<()>;
+}
 
-        types_used.insert(comp_name.to_string());
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> anyhow::Result<[u8; N]> {
+    let mut buffer = [0u8; N];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Reads `len` bytes, growing the buffer as they come in rather than trusting a length read
+/// from the data with an allocation up front.
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = vec![];
+    reader.take(len as u64).read_to_end(&mut buffer)?;
+    if buffer.len() < len {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
     }
+    Ok(buffer)
+}
+
+fn read_string_table<R: Read>(reader: &mut R) -> anyhow::Result<Vec<(Str, String)>> {
+    let count = u64::from_be_bytes(read_array(reader)?);
+    (0..count)
+        .map(|_| {
+            let id = Str(u64::from_be_bytes(read_array(reader)?));
+            let len = u64::from_be_bytes(read_array(reader)?);
+            let string = String::from_utf8(read_bytes(reader, len as usize)?)
+                .map_err(|_| MosaicFormatError::CorruptData("string is not utf-8".into()))?;
+            Ok((id, string))
+        })
+        .collect()
+}
 
-    result = result
-        .iter()
-        .flat_map(|command| match command {
-            MosaicLoadCommand::AddType(t) if !types_used.contains(t.split(':').next().unwrap()) => {
-                None
+/// Reads the blobs of a blob table, checking each against its id.
+fn read_blob_table<R: Read>(reader: &mut R) -> anyhow::Result<Vec<Blob>> {
+    let count = u64::from_be_bytes(read_array(reader)?);
+    (0..count)
+        .map(|_| {
+            let id = BlobId(read_array(reader)?);
+            let len = u64::from_be_bytes(read_array(reader)?);
+            let blob = Blob::new(read_bytes(reader, len as usize)?);
+            if blob.id() != id {
+                return Err(MosaicFormatError::CorruptData(format!(
+                    "blob {} does not match its contents",
+                    id
+                ))
+                .into());
             }
-            c => Some(c.clone()),
+            Ok(blob)
         })
-        .collect_vec();
-    Ok(result)
+        .collect()
 }
 
-impl MosaicIO for Arc<Mosaic> {
-    fn save(&self) -> Vec<u8> {
-        let mut result = vec![];
+/// Reads the id that starts a tile record, or `None` if the data ends cleanly before it.
+fn read_tile_id<R: Read>(reader: &mut R) -> anyhow::Result<Option<EntityId>> {
+    let mut buffer = [0u8; 8];
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
 
-        let mut entries = self
-            .tile_registry
-            .lock()
-            .unwrap()
-            .clone()
-            .into_iter()
-            .collect_vec();
+    match filled {
+        0 => Ok(None),
+        8 => Ok(Some(usize::from_be_bytes(buffer))),
+        _ => Err(MosaicFormatError::CorruptData("data ends in the middle of a tile".into()).into()),
+    }
+}
 
-        let used_types = entries
-            .iter()
-            .map(|(_, b)| b.component.to_string())
-            .collect::<HashSet<_>>();
+/// Reads the rest of a tile record after its id: source, target, component, and data.
+fn read_tile_record<R: Read>(reader: &mut R) -> anyhow::Result<(EntityId, EntityId, S32, Vec<u8>)> {
+    let src = usize::from_be_bytes(read_array(reader)?);
+    let tgt = usize::from_be_bytes(read_array(reader)?);
+    let (comp_name, comp_data) = read_added_data_record(reader)?;
+    Ok((src, tgt, comp_name, comp_data))
+}
 
-        println!("USED TYPES: {:?}", used_types);
+/// Reads a component name and its data, which is how tile records end.
+fn read_added_data_record<R: Read>(reader: &mut R) -> anyhow::Result<(S32, Vec<u8>)> {
+    let comp_len = usize::from_be_bytes(read_array(reader)?);
+    if comp_len > 32 {
+        return Err(MosaicFormatError::CorruptData(format!(
+            "component name is {} bytes long",
+            comp_len
+        ))
+        .into());
+    }
 
-        self.component_registry
+    let comp_name = read_bytes(reader, comp_len)?;
+    let comp_name = std::str::from_utf8(&comp_name)
+        .map_err(|_| MosaicFormatError::CorruptData("component name is not utf-8".into()))?;
+    let comp_name = S32(FStr::<32>::from_str_lossy(comp_name, b'\0'));
+    let comp_data_len = u32::from_be_bytes(read_array(reader)?);
+    let comp_data = read_bytes(reader, comp_data_len as usize)?;
+    Ok((comp_name, comp_data))
+}
+
+impl Mosaic {
+    fn write_type_definitions<W: Write>(
+        &self,
+        writer: &mut W,
+        used_types: &HashSet<String>,
+    ) -> anyhow::Result<()> {
+        let definitions = self
+            .component_registry
             .component_definitions
-            .lock()
+            .read()
             .unwrap()
             .clone()
             .into_iter()
-            .filter(|c| used_types.contains(c.split(':').next().unwrap()))
+            .filter(|c| used_types.contains(ComponentParser::type_name_of(c)))
             .sorted()
             .unique()
-            .for_each(|v| {
-                println!("Saving {:?}", v);
-                result.extend((v.len() as u16).to_be_bytes());
-                result.extend(v.as_bytes());
-            });
+            .collect_vec();
 
-        result.extend(0u16.to_be_bytes());
-
-        entries.sort_by(|a, b| a.0.cmp(&b.0));
-
-        entries.into_iter().for_each(|(_, t)| {
-            result.extend(t.id.to_byte_array());
-            result.extend(t.source_id().to_byte_array());
-            result.extend(t.target_id().to_byte_array());
-            let comp = t.component.0.as_str().replace('\0', "");
-            result.extend(comp.len().to_byte_array());
-            result.extend(comp.as_bytes());
-            let data = t.create_binary_data_from_fields(
-                &self
-                    .component_registry
-                    .get_component_type(t.component)
-                    .unwrap(),
-            );
-            result.extend((data.len() as u32).to_byte_array());
-            result.extend(data)
-        });
+        for definition in definitions {
+            writer.write_all(&(definition.len() as u16).to_be_bytes())?;
+            writer.write_all(definition.as_bytes())?;
+        }
+
+        writer.write_all(&0u16.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the pool ids of the strings held by any field, so they survive a reload.
+    fn write_string_table<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        let strings = self.strings.read().unwrap();
+        let mut ids = HashSet::new();
+        for entities in self.data_storage.read().unwrap().values() {
+            for value in entities.values().flat_map(|fields| fields.values()) {
+                strings.ids_in(value, &mut ids);
+            }
+        }
+
+        writer.write_all(&(ids.len() as u64).to_be_bytes())?;
+        for id in ids.into_iter().sorted() {
+            let string = strings.resolve(id).unwrap();
+            writer.write_all(&id.0.to_be_bytes())?;
+            writer.write_all(&(string.len() as u64).to_be_bytes())?;
+            writer.write_all(string.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the blobs held by the fields of the tiles `include` keeps, each once.
+    fn write_blob_table<W: Write>(
+        &self,
+        writer: &mut W,
+        include: impl Fn(EntityId) -> bool,
+    ) -> anyhow::Result<()> {
+        let strings = self.strings.read().unwrap();
+        let mut ids = HashSet::new();
+        for entities in self.data_storage.read().unwrap().values() {
+            let held = entities.iter().filter(|(id, _)| include(**id));
+            for value in held.flat_map(|(_, fields)| fields.values()) {
+                StringPool::blob_ids_in(value, &mut ids);
+            }
+        }
+
+        writer.write_all(&(ids.len() as u64).to_be_bytes())?;
+        for id in ids.into_iter().sorted() {
+            let blob = strings
+                .resolve_blob(id)
+                .ok_or_else(|| anyhow::anyhow!("Cannot find blob {}", id))?;
+            writer.write_all(&id.to_byte_array())?;
+            writer.write_all(&(blob.len() as u64).to_be_bytes())?;
+            writer.write_all(blob.bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a tile with its fields, with strings by their id in the string table if
+    /// `string_ids` is set, in full otherwise.
+    fn write_tile_record<W: Write>(
+        &self,
+        writer: &mut W,
+        t: &Tile,
+        string_ids: bool,
+    ) -> anyhow::Result<()> {
+        writer.write_all(&t.id.to_byte_array())?;
+        writer.write_all(&t.source_id().to_byte_array())?;
+        writer.write_all(&t.target_id().to_byte_array())?;
+        let comp = t.component.0.as_str().replace('\0', "");
+        writer.write_all(&comp.len().to_byte_array())?;
+        writer.write_all(comp.as_bytes())?;
+        let component_type = self.component_registry.get_component_type(t.component)?;
+        let data = match string_ids {
+            true => {
+                let strings = self.strings.read().unwrap();
+                let fields = component_type
+                    .get_fields()
+                    .into_iter()
+                    .map(|f| match component_type.has_self_field() {
+                        true => S32::from("self"),
+                        false => f.name,
+                    })
+                    .map(|name| {
+                        let value = strings.to_saved(t.get(&name.to_string()))?;
+                        Ok((name, value))
+                    })
+                    .collect::<anyhow::Result<HashMap<_, _>>>()?;
+                Tile::create_binary_data(&component_type, |field| {
+                    fields
+                        .get(&S32::from(field))
+                        .cloned()
+                        .unwrap_or(Value::UNIT)
+                })
+            }
+            false => t.create_binary_data_from_fields(&component_type),
+        };
+        writer.write_all(&(data.len() as u32).to_byte_array())?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+
+    fn write_added_data_record<W: Write>(
+        &self,
+        writer: &mut W,
+        id: EntityId,
+        component: S32,
+    ) -> anyhow::Result<()> {
+        let fields = self
+            .data_storage
+            .read()
+            .unwrap()
+            .get(&component.to_string())
+            .and_then(|e| e.get(&id).cloned())
+            .unwrap_or_default();
+        let fields = {
+            let strings = self.strings.read().unwrap();
+            fields
+                .into_iter()
+                .map(|(name, value)| Ok((name, strings.to_saved(value)?)))
+                .collect::<anyhow::Result<HashMap<_, _>>>()?
+        };
+        let data = Tile::create_binary_data(
+            &self.component_registry.get_component_type(component)?,
+            |field| {
+                fields
+                    .get(&S32::from(field))
+                    .cloned()
+                    .unwrap_or(Value::UNIT)
+            },
+        );
+
+        let comp = component.0.as_str().replace('\0', "");
+        writer.write_all(&id.to_byte_array())?;
+        writer.write_all(&comp.len().to_byte_array())?;
+        writer.write_all(comp.as_bytes())?;
+        writer.write_all(&(data.len() as u32).to_byte_array())?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+
+    fn read_type_definitions<R: Read>(
+        self: &Arc<Self>,
+        reader: &mut R,
+        offset: EntityId,
+    ) -> anyhow::Result<()> {
+        loop {
+            let len = u16::from_be_bytes(read_array(reader)?);
+            if len == 0 {
+                return Ok(());
+            }
+
+            let definition = String::from_utf8(read_bytes(reader, len as usize)?)?;
+            self.apply_load_command(MosaicLoadCommand::AddType(definition), offset, None)?;
+        }
+    }
+
+    /// Pools the saved strings under their saved ids, unless those ids are already taken,
+    /// and returns them by their saved ids.
+    fn read_string_table<R: Read>(&self, reader: &mut R) -> anyhow::Result<HashMap<Str, Arc<str>>> {
+        let table = read_string_table(reader)?;
+        let mut strings = self.strings.write().unwrap();
+        Ok(table
+            .into_iter()
+            .map(|(id, string)| (id, strings.intern_as(id, &string).1))
+            .collect())
+    }
+
+    /// Pools the saved blobs, so the tiles read after them find their bytes.
+    fn read_blob_table<R: Read>(&self, reader: &mut R) -> anyhow::Result<()> {
+        let table = read_blob_table(reader)?;
+        let mut strings = self.strings.write().unwrap();
+        for blob in table {
+            strings.intern_blob(blob);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the records of data added with `add_data` up to their end marker.
+    fn read_added_data_records<R: Read>(
+        self: &Arc<Self>,
+        reader: &mut R,
+        offset: EntityId,
+        saved_strings: Option<&HashMap<Str, Arc<str>>>,
+    ) -> anyhow::Result<()> {
+        loop {
+            let Some(id) = read_tile_id(reader)? else {
+                return Err(
+                    MosaicFormatError::CorruptData("added data does not end".into()).into(),
+                );
+            };
+            if id == END_OF_TILES {
+                return Ok(());
+            }
+
+            let (comp_name, comp_data) = read_added_data_record(reader)?;
+            self.apply_load_command(
+                MosaicLoadCommand::AddData(id, comp_name, comp_data),
+                offset,
+                saved_strings,
+            )?;
+        }
+    }
+
+    /// Applies tile records up to the end of the data or the end marker, returning whether it
+    /// was the marker.
+    fn read_tile_records<R: Read>(
+        self: &Arc<Self>,
+        reader: &mut R,
+        offset: EntityId,
+        saved_strings: Option<&HashMap<Str, Arc<str>>>,
+    ) -> anyhow::Result<bool> {
+        while let Some(id) = read_tile_id(reader)? {
+            if id == END_OF_TILES {
+                return Ok(true);
+            }
+
+            let (src, tgt, comp_name, comp_data) = read_tile_record(reader)?;
+            self.apply_load_command(
+                MosaicLoadCommand::CreateTile(id, src, tgt, comp_name, comp_data),
+                offset,
+                saved_strings,
+            )?;
+        }
+
+        Ok(false)
+    }
+
+    /// Applies what follows the cleared flag of a delta written by `save_delta`.
+    fn apply_delta_records(self: &Arc<Self>, reader: &mut &[u8]) -> anyhow::Result<()> {
+        self.read_type_definitions(reader, 0)?;
+        self.read_blob_table(reader)?;
+
+        let deleted = u64::from_be_bytes(read_array(reader)?);
+        for _ in 0..deleted {
+            self.delete_tile(usize::from_be_bytes(read_array(reader)?));
+        }
+
+        self.read_tile_records(reader, 0, None)?;
+        Ok(())
+    }
+}
+
+impl MosaicStreamIO for Arc<Mosaic> {
+    fn save_to<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        // anything changed while saving may or may not make it in, so it counts as unsaved
+        let version = self.version();
+        let mut writer = BufWriter::new(writer);
+        write_header(&mut writer)?;
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(self.uuid().as_bytes())?;
+
+        let (ids, mut used_types) = {
+            let registry = self.tile_registry.read().unwrap();
+            let used_types = registry
+                .values()
+                .map(|t| t.component.to_string())
+                .collect::<HashSet<_>>();
+            (registry.keys().cloned().sorted().collect_vec(), used_types)
+        };
+        let added = {
+            let indices = self.indices.read().unwrap();
+            ids.iter()
+                .flat_map(|id| indices.attached_to(*id).into_iter().map(|c| (*id, c)))
+                .collect_vec()
+        };
+        used_types.extend(added.iter().map(|(_, c)| c.to_string()));
+
+        self.write_type_definitions(&mut writer, &used_types)?;
+        self.write_string_table(&mut writer)?;
+        self.write_blob_table(&mut writer, |_| true)?;
+        for t in ids.into_iter().flat_map(|id| self.get(id)) {
+            self.write_tile_record(&mut writer, &t, true)?;
+        }
+        writer.write_all(&END_OF_TILES.to_byte_array())?;
+        for (id, component) in added {
+            self.write_added_data_record(&mut writer, id, component)?;
+        }
+        writer.write_all(&END_OF_TILES.to_byte_array())?;
+
+        writer.finish()?;
+        self.saved_version.store(version, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn load_from<R: Read>(&self, reader: R) -> anyhow::Result<()> {
+        let mut reader = BufReader::new(reader);
+        let (version, start) = read_header(&mut reader)?;
+        let mut reader = ChecksumReader::new(start.as_slice().chain(reader));
+        // every loaded tile gets an id past those taken, so failing takes back just those
+        let (offset, was_empty) = {
+            let registry = self.tile_registry.read().unwrap();
+            let taken = registry.keys().max().map_or(0, |id| id + 1);
+            (self.entity_counter.get().max(taken), registry.is_empty())
+        };
+        let uuid = match version >= UUID_VERSION {
+            true => read_array(&mut reader)
+                .map(|bytes| Some(Uuid::from_bytes(bytes)))
+                .map_err(MosaicFormatError::from_read_error)?,
+            false => None,
+        };
+
+        // nothing loaded is kept unless the whole payload checks out; the tiles aren't
+        // journaled like in a transaction, which would hold a second copy of all of them
+        self.history.lock().unwrap().loading = true;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            (|| {
+                self.read_type_definitions(&mut reader, offset)?;
+                let strings = match version >= STRING_TABLE_VERSION {
+                    true => self.read_string_table(&mut reader)?,
+                    false => HashMap::new(),
+                };
+                let saved_strings = (version >= STRING_ID_VERSION).then_some(&strings);
+                if version >= BLOB_TABLE_VERSION {
+                    self.read_blob_table(&mut reader)?;
+                }
+                // legacy saves have no end marker, their tiles go on to the end of the data
+                let ended = self.read_tile_records(&mut reader, offset, saved_strings)?;
+                if !ended && version != LEGACY_FORMAT_VERSION {
+                    return Err(MosaicFormatError::CorruptData("tiles do not end".into()).into());
+                }
+                if version >= ADDED_DATA_VERSION {
+                    self.read_added_data_records(&mut reader, offset, saved_strings)?;
+                }
+                anyhow::Ok(())
+            })()
+            .map_err(MosaicFormatError::from_read_error)?;
+            match version {
+                LEGACY_FORMAT_VERSION => Ok(()),
+                _ => reader.verify(),
+            }
+        }))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Loading panicked")));
+
+        if result.is_err() {
+            let loaded = {
+                let registry = self.tile_registry.read().unwrap();
+                registry
+                    .keys()
+                    .filter(|id| **id >= offset)
+                    .cloned()
+                    .sorted()
+                    .collect_vec()
+            };
+            for id in loaded.into_iter().rev() {
+                if self.is_tile_valid(&id) {
+                    self.delete_tile(id);
+                }
+            }
+        }
+        self.history.lock().unwrap().loading = false;
+        result?;
+
+        // loading into a mosaic of its own, the save carries on as the same mosaic
+        if was_empty {
+            self.saved_version.store(self.version(), Ordering::Relaxed);
+            if let Some(uuid) = uuid {
+                *self.uuid.write().unwrap() = uuid;
+            }
+        }
+        Ok(())
+    }
+}
 
+impl MosaicIO for Arc<Mosaic> {
+    fn save(&self) -> Vec<u8> {
+        let mut result = vec![];
+        self.save_to(&mut result)
+            .expect("Cannot save mosaic into memory");
         result
     }
 
     fn clear(&self) {
-        self.tile_registry.lock().unwrap().clear();
-        self.dependent_ids_map.lock().unwrap().clear();
-        self.data_storage.lock().unwrap().clear();
-        self.object_ids.lock().unwrap().clear();
-        self.arrow_ids.lock().unwrap().clear();
-        self.descriptor_ids.lock().unwrap().clear();
-        self.extension_ids.lock().unwrap().clear();
+        self.unobserved_changes.inc();
+        self.tile_registry.write().unwrap().clear();
+        self.dependent_ids_map.write().unwrap().clear();
+        self.data_storage.write().unwrap().clear();
+        self.object_ids.write().unwrap().clear();
+        self.arrow_ids.write().unwrap().clear();
+        self.indices.write().unwrap().clear();
+        self.descriptor_ids.write().unwrap().clear();
+        self.extension_ids.write().unwrap().clear();
         self.entity_counter.reset();
+        if let Some(recycled) = self.recycled_ids.lock().unwrap().as_mut() {
+            recycled.clear();
+        }
         self.component_registry.clear();
+        self.clear_computed_fields();
+        self.history.lock().unwrap().reset();
+        *self.strings.write().unwrap() = StringPool::default();
+        self.observers.lock().unwrap().clear_field_watches();
+        // other replicas see everything cleared away as deleted
+        self.clear_crdt();
+        self.log_change(TileChange::Cleared);
+        self.log_operation(LoggedChange::Cleared);
         self.new_type("void: unit;").unwrap();
     }
 
     fn load(&self, data: &[u8]) -> anyhow::Result<()> {
-        let offset = self.entity_counter.get();
-        let loaded = load_mosaic_commands(data)?;
-
-        for command in loaded.into_iter() {
-            match command {
-                MosaicLoadCommand::AddType(definition) => {
-                    let typename: S32 = definition
-                        .split(':')
-                        .collect_vec()
-                        .first()
-                        .unwrap()
-                        .trim()
-                        .into();
-
-                    if !self.component_registry.has_component_type(&typename) {
-                        self.component_registry
-                            .add_component_types(definition.as_str())
-                            .unwrap();
-                    }
-                }
-                MosaicLoadCommand::CreateTile(id, src, tgt, component, data) => {
-                    let id = id + offset;
-                    let src = src + offset;
-                    let tgt = tgt + offset;
-                    let component_type = &self
-                        .component_registry
-                        .get_component_type(component)
-                        .unwrap();
-
-                    let field_access =
-                        Tile::create_fields_from_binary_data(self, component_type, data);
-
-                    if let Ok(fields) = field_access {
-                        if id == src && id == tgt {
-                            // ID : ID -> ID
-                            let tile = Tile::new(
-                                Arc::clone(self),
-                                id,
-                                TileType::Object,
-                                component,
-                                fields.into_iter().collect(),
-                            );
-                            self.object_ids.lock().unwrap().add(id);
-                            self.tile_registry.lock().unwrap().insert(id, tile.clone());
-                        } else if id == src && src != tgt {
-                            // ID : ID -> TGT (descriptor)
-                            self.dependent_ids_map.lock().unwrap().append(tgt, id);
-
-                            let tile = Tile::new(
-                                Arc::clone(self),
-                                id,
-                                TileType::Descriptor { subject: tgt },
-                                component,
-                                fields.into_iter().collect(),
-                            );
-                            self.descriptor_ids.lock().unwrap().add(id);
-                            self.tile_registry.lock().unwrap().insert(id, tile.clone());
-                        } else if id == tgt && src != tgt {
-                            // ID : SRC -> ID (extension)
-                            self.dependent_ids_map.lock().unwrap().append(src, id);
-
-                            let tile = Tile::new(
-                                Arc::clone(self),
-                                id,
-                                TileType::Extension { subject: src },
-                                component,
-                                fields.into_iter().collect(),
-                            );
-                            self.extension_ids.lock().unwrap().add(id);
-                            self.tile_registry.lock().unwrap().insert(id, tile.clone());
-                        } else {
-                            self.dependent_ids_map.lock().unwrap().append(src, id);
-                            self.dependent_ids_map.lock().unwrap().append(tgt, id);
-
-                            let tile = Tile::new(
-                                Arc::clone(self),
-                                id,
-                                TileType::Arrow {
-                                    source: src,
-                                    target: tgt,
-                                },
-                                component,
-                                fields.into_iter().collect(),
-                            );
-                            self.arrow_ids.lock().unwrap().add(id);
-                            self.tile_registry.lock().unwrap().insert(id, tile.clone());
-                        }
-                    } else {
-                        return Err(field_access.unwrap_err());
-                    }
-                }
-            }
+        self.load_from(data)
+    }
+
+    fn get(&self, i: EntityId) -> Option<Tile> {
+        self.tile_registry.read().unwrap().get(&i).cloned()
+    }
+
+    fn version(&self) -> Version {
+        self.version.get()
+    }
+
+    fn save_delta(&self, since: Version) -> Vec<u8> {
+        let (cleared, written, deleted) = self.changes_since(since);
+        let tiles = written
+            .into_iter()
+            .sorted()
+            .flat_map(|id| self.get(id))
+            .collect_vec();
+        let used_types = tiles
+            .iter()
+            .map(|t| t.component.to_string())
+            .collect::<HashSet<_>>();
+
+        let mut result = vec![cleared as u8];
+        self.write_type_definitions(&mut result, &used_types)
+            .expect("Cannot save mosaic delta into memory");
+        let ids = tiles.iter().map(|t| t.id).collect::<HashSet<_>>();
+        self.write_blob_table(&mut result, |id| ids.contains(&id))
+            .expect("Cannot save mosaic delta into memory");
+        result.extend((deleted.len() as u64).to_be_bytes());
+        for id in deleted.into_iter().sorted() {
+            result.extend(id.to_byte_array());
+        }
+        for t in tiles {
+            self.write_tile_record(&mut result, &t, false)
+                .expect("Cannot save mosaic delta into memory");
         }
 
-        Ok(())
+        result
     }
 
-    fn get(&self, i: EntityId) -> Option<Tile> {
-        self.tile_registry.lock().unwrap().get(&i).cloned()
+    fn apply_delta(&self, data: &[u8]) -> anyhow::Result<()> {
+        let mut reader = data;
+        let [cleared] = read_array(&mut reader)?;
+        if cleared == 1 {
+            // clearing can't be rolled back, so the delta is tried on an empty mosaic first
+            let mut trial = reader;
+            Mosaic::new().apply_delta_records(&mut trial)?;
+            self.clear();
+        }
+
+        // nothing in the delta is kept unless all of it applies
+        self.transaction(|mosaic| mosaic.apply_delta_records(&mut reader))
     }
 
     fn new_object(&self, component: &str, defaults: ComponentValues) -> Tile {
+        self.check_writable(component.into())
+            .expect("Cannot create object, panicking!");
         let id = self.next_id();
         let tile = Tile::new(
             Arc::clone(self),
@@ -590,57 +1626,82 @@ impl MosaicIO for Arc<Mosaic> {
             component.into(),
             defaults,
         );
-        self.object_ids.lock().unwrap().add(id);
+        self.object_ids.write().unwrap().add(id);
         tile
     }
 
     fn new_specific_object(&self, id: EntityId, component: &str) -> anyhow::Result<Tile> {
-        let mut registry = self.tile_registry.lock().unwrap();
+        let mut registry = self.tile_registry.write().unwrap();
         if let std::collections::hash_map::Entry::Vacant(e) = registry.entry(id) {
             let mut tile = Tile {
                 id,
                 mosaic: Arc::clone(self),
                 tile_type: TileType::Object,
                 component: component.into(),
+                generation: self.next_generation(),
+                fields_cache: FieldCache::default(),
             };
-            self.object_ids.lock().unwrap().add(id);
+            self.object_ids.write().unwrap().add(id);
             e.insert(tile.clone());
 
             tile.create_data_fields(par(id.to_string().as_str()))?;
 
             Ok(tile)
         } else {
-            format!(
-                "Cannot create specific object at id {}, it already exists:\n\t{:?}",
-                id,
-                self.get(id)
-            )
-            .to_error()
+            Err(MosaicError::TileExists(id).into())
         }
     }
 
     fn get_all(&self) -> IntoIter<Tile> {
-        self.tile_registry
-            .lock()
+        let mut tiles = self
+            .tile_registry
+            .read()
             .unwrap()
             .values()
             .cloned()
-            .collect_vec()
-            .into_iter()
+            .collect_vec();
+        if self.is_deterministic() {
+            tiles.sort_by_key(|t| t.id);
+        }
+        tiles.into_iter()
+    }
+
+    fn import_graphml(&self, text: &str) -> anyhow::Result<ImportedGraph> {
+        self.import_graphml_with(text, &ImportOptions::default())
+    }
+
+    fn import_graphml_with(
+        &self,
+        text: &str,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph> {
+        let graph = parse_graphml(text)?;
+        self.transaction(|mosaic| import_graph(mosaic, &self.component_registry, graph, options))
+    }
+
+    fn import_edge_list(
+        &self,
+        reader: &mut dyn Read,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph> {
+        let graph = parse_edge_list(reader, options)?;
+        self.transaction(|mosaic| import_graph(mosaic, &self.component_registry, graph, options))
     }
 }
 
 impl MosaicTypelevelCRUD for Arc<Mosaic> {
     fn new_type(&self, type_def: &str) -> anyhow::Result<()> {
         let d = type_def.to_string();
-        let defs = d.chars().filter(|c| *c == ';').count();
+        // array sizes use ';' too, so count the definitions the parser actually finds
+        let defs = ComponentParser::parse_types(type_def).len();
         if defs > 1 {
-            return Err(anyhow!(
-                "Cannot have more than one type definition at once."
-            ));
+            return Err(MosaicError::ParseError(
+                "Cannot have more than one type definition at once.".to_string(),
+            )
+            .into());
         }
 
-        let type_name = d.split(':').collect_vec().first().cloned().unwrap();
+        let type_name = ComponentParser::type_name_of(&d);
         if self
             .component_registry
             .has_component_type(&type_name.into())
@@ -649,18 +1710,117 @@ impl MosaicTypelevelCRUD for Arc<Mosaic> {
         }
 
         let types = self.component_registry.add_component_types(type_def)?;
-        let mut storage = self.data_storage.lock().unwrap();
+        let mut storage = self.data_storage.write().unwrap();
         for typ in types {
             storage.insert(typ.name(), HashMap::new());
         }
 
         Ok(())
     }
+
+    fn redefine_type(&self, type_def: &str) -> anyhow::Result<()> {
+        let type_name = ComponentParser::type_name_of(type_def);
+        if !self
+            .component_registry
+            .component_type_map
+            .read()
+            .unwrap()
+            .contains_key(&type_name.into())
+        {
+            return self.new_type(type_def);
+        }
+
+        self.migrate_type(type_def, None)
+    }
+}
+
+impl Mosaic {
+    /// Redefines a type that exists already, migrating the tiles holding it; or, when undoing
+    /// or replaying a migration, setting them to `values`.
+    pub(crate) fn migrate_type(
+        self: &Arc<Self>,
+        type_def: &str,
+        values: Option<HashMap<EntityId, ComponentValues>>,
+    ) -> anyhow::Result<()> {
+        let type_name = ComponentParser::type_name_of(type_def);
+        let before = self.component_registry.to_definition_string(type_name)?;
+        let (_, new_type) = self.component_registry.redefine_component_type(type_def)?;
+        let name = new_type.name();
+        let defaults = self
+            .component_registry
+            .get_field_defaults(name.as_str().into());
+        let fields = new_type
+            .get_fields()
+            .into_iter()
+            .map(|field| {
+                let key = if new_type.has_self_field() {
+                    "self".into()
+                } else {
+                    field.name
+                };
+                let default = defaults
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_else(|| field.datatype.get_default());
+                (key, field.datatype, default)
+            })
+            .collect_vec();
+
+        let defaults = {
+            let mut strings = self.strings.write().unwrap();
+            fields
+                .iter()
+                .map(|(_, _, default)| strings.intern_value(default.clone()))
+                .collect_vec()
+        };
+
+        let mut migrated = vec![];
+        {
+            let mut storage = self.data_storage.write().unwrap();
+            let mut strings = self.strings.write().unwrap();
+            for (id, entity_fields) in storage.entry(name.clone()).or_default() {
+                let before = entity_fields
+                    .iter()
+                    .map(|(f, v)| (*f, v.clone()))
+                    .collect_vec();
+                let mut given = values
+                    .as_ref()
+                    .and_then(|values| values.get(id))
+                    .map(|values| values.iter().cloned().collect::<HashMap<_, _>>())
+                    .unwrap_or_else(|| entity_fields.clone());
+                *entity_fields = fields
+                    .iter()
+                    .zip(&defaults)
+                    .map(|((key, datatype, _), default)| {
+                        let value = given
+                            .remove(key)
+                            .filter(|value| datatype.accepts(value))
+                            .map(|value| strings.intern_value(value))
+                            .unwrap_or_else(|| default.clone());
+                        (*key, value)
+                    })
+                    .collect();
+                let after = entity_fields
+                    .iter()
+                    .map(|(f, v)| (*f, v.clone()))
+                    .collect_vec();
+                migrated.push((*id, before, after));
+            }
+        }
+
+        self.record_history(HistoryOperation::Redefined {
+            component: name.as_str().into(),
+            before,
+            after: self.component_registry.to_definition_string(&name)?,
+            migrated,
+        });
+        Ok(())
+    }
 }
 
 impl MosaicCRUD<EntityId> for Arc<Mosaic> {
     fn is_tile_valid(&self, i: &EntityId) -> bool {
-        self.tile_registry.lock().unwrap().contains_key(i)
+        self.tile_registry.read().unwrap().contains_key(i)
     }
 
     fn new_arrow(
@@ -670,10 +1830,21 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
         component: &str,
         defaults: ComponentValues,
     ) -> Tile {
-        let id = self.next_id();
-        self.dependent_ids_map.lock().unwrap().append(*source, id);
-        self.dependent_ids_map.lock().unwrap().append(*target, id);
+        self.try_new_arrow(source, target, component, defaults)
+            .expect("Cannot create arrow, panicking!")
+    }
 
+    fn try_new_arrow(
+        &self,
+        source: &EntityId,
+        target: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.check_writable(component.into())?;
+        self.check_new_arrow(*source, *target, component.into())?;
+
+        let id = self.next_id();
         let tile = Tile::new(
             Arc::clone(self),
             id,
@@ -684,8 +1855,8 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
             component.into(),
             defaults,
         );
-        self.arrow_ids.lock().unwrap().add(id);
-        tile
+        self.arrow_ids.write().unwrap().add(id);
+        Ok(tile)
     }
 
     fn new_descriptor(
@@ -694,9 +1865,20 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
         component: &str,
         defaults: ComponentValues,
     ) -> Tile {
-        let id = self.next_id();
-        self.dependent_ids_map.lock().unwrap().append(*subject, id);
+        self.try_new_descriptor(subject, component, defaults)
+            .expect("Cannot create descriptor, panicking!")
+    }
 
+    fn try_new_descriptor(
+        &self,
+        subject: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.check_writable(component.into())?;
+        self.check_new_descriptor(*subject, component.into())?;
+
+        let id = self.next_id();
         let tile = Tile::new(
             Arc::clone(self),
             id,
@@ -704,8 +1886,8 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
             component.into(),
             defaults,
         );
-        self.descriptor_ids.lock().unwrap().add(id);
-        tile
+        self.descriptor_ids.write().unwrap().add(id);
+        Ok(tile)
     }
 
     fn new_extension(
@@ -714,9 +1896,9 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
         component: &str,
         defaults: ComponentValues,
     ) -> Tile {
+        self.check_writable(component.into())
+            .expect("Cannot create extension, panicking!");
         let id = self.next_id();
-        self.dependent_ids_map.lock().unwrap().append(*subject, id);
-
         let tile = Tile::new(
             Arc::clone(self),
             id,
@@ -724,14 +1906,14 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
             component.into(),
             defaults,
         );
-        self.extension_ids.lock().unwrap().add(id);
+        self.extension_ids.write().unwrap().add(id);
         tile
     }
 
     fn delete_tile(&self, id: EntityId) {
         let dependents = self
             .dependent_ids_map
-            .lock()
+            .read()
             .unwrap()
             .get_all(&id)
             .cloned()
@@ -746,26 +1928,62 @@ impl MosaicCRUD<EntityId> for Arc<Mosaic> {
         }
 
         let tile = self.get(id).unwrap();
+        let attached = self.indices.read().unwrap().attached_to(id);
+        let operation = HistoryOperation::Deleted {
+            id,
+            generation: tile.generation,
+            tile_type: tile.tile_type,
+            component: tile.component,
+            fields: tile.data(),
+            attached: attached
+                .iter()
+                .filter_map(|c| Some((*c, tile.get_data(&c.to_string())?)))
+                .collect(),
+        };
+        let mut held = HashSet::new();
+        {
+            let strings = self.strings.read().unwrap();
+            for value in operation.values() {
+                strings.ids_in(value, &mut held);
+            }
+        }
+        self.record_history(operation);
         tile.remove_component_data();
+        for component in attached {
+            if let Some(e) = self
+                .data_storage
+                .write()
+                .unwrap()
+                .get_mut(&component.to_string())
+            {
+                e.remove(&id);
+            }
+        }
 
-        self.dependent_ids_map.lock().unwrap().remove(&id);
-        if let Some(tile) = self.tile_registry.lock().unwrap().get(&id) {
+        self.dependent_ids_map.write().unwrap().remove(&id);
+        if let Some(tile) = self.tile_registry.read().unwrap().get(&id) {
             match tile.tile_type {
-                TileType::Object => self.object_ids.lock().unwrap().remove(id),
-                TileType::Arrow { .. } => self.arrow_ids.lock().unwrap().remove(id),
-                TileType::Descriptor { .. } => self.descriptor_ids.lock().unwrap().remove(id),
-                TileType::Extension { .. } => self.extension_ids.lock().unwrap().remove(id),
+                TileType::Object => self.object_ids.write().unwrap().remove(id),
+                TileType::Arrow { .. } => self.arrow_ids.write().unwrap().remove(id),
+                TileType::Descriptor { .. } => self.descriptor_ids.write().unwrap().remove(id),
+                TileType::Extension { .. } => self.extension_ids.write().unwrap().remove(id),
             }
         }
-        //TODO! REMOVE FROM data_registry ALL component of entity
-        //free id in freelist
-        self.tile_registry.lock().unwrap().remove(&id);
+        self.indices.write().unwrap().remove(&tile);
+        self.tile_registry.write().unwrap().remove(&id);
+        self.recycle_id(id);
+        self.note_deletion();
+        // the fields were still stored while the deletion was recorded
+        drop(tile);
+        self.release_strings(held, vec![]);
     }
 }
 
+/// Tiles are taken as handles, so a tile deleted since is turned away even if its id has been
+/// handed out again.
 impl MosaicCRUD<Tile> for Arc<Mosaic> {
     fn is_tile_valid(&self, i: &Tile) -> bool {
-        <Arc<Mosaic> as MosaicCRUD<EntityId>>::is_tile_valid(self, &i.id)
+        self.generation_of(i.id) == Some(i.generation)
     }
 
     fn new_arrow(
@@ -775,25 +1993,37 @@ impl MosaicCRUD<Tile> for Arc<Mosaic> {
         component: &str,
         defaults: ComponentValues,
     ) -> Tile {
-        <Arc<Mosaic> as MosaicCRUD<EntityId>>::new_arrow(
-            self, &source.id, &target.id, component, defaults,
-        )
+        self.new_arrow(&source.handle(), &target.handle(), component, defaults)
     }
 
     fn new_descriptor(&self, subject: &Tile, component: &str, defaults: ComponentValues) -> Tile {
-        <Arc<Mosaic> as MosaicCRUD<EntityId>>::new_descriptor(
-            self,
-            &subject.id,
-            component,
-            defaults,
-        )
+        self.new_descriptor(&subject.handle(), component, defaults)
+    }
+
+    fn try_new_arrow(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.try_new_arrow(&source.handle(), &target.handle(), component, defaults)
+    }
+
+    fn try_new_descriptor(
+        &self,
+        subject: &Tile,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.try_new_descriptor(&subject.handle(), component, defaults)
     }
 
     fn new_extension(&self, subject: &Tile, component: &str, defaults: ComponentValues) -> Tile {
-        <Arc<Mosaic> as MosaicCRUD<EntityId>>::new_extension(self, &subject.id, component, defaults)
+        self.new_extension(&subject.handle(), component, defaults)
     }
 
     fn delete_tile(&self, tile: Tile) {
-        <Arc<Mosaic> as MosaicCRUD<EntityId>>::delete_tile(self, tile.id);
+        self.delete_tile(tile.handle());
     }
 }
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use super::{
+    ComponentValues, FieldCache, Mosaic, MosaicCRUD, MosaicIO, MosaicTransaction, Tile, TileType,
+    S32,
+};
+
+/// An object and the descriptors it was built with, see `ObjectBuilder`.
+#[derive(Clone, Debug)]
+pub struct BuiltObject {
+    pub object: Tile,
+    /// In the order they were added to the builder.
+    pub descriptors: Vec<Tile>,
+}
+
+impl BuiltObject {
+    /// The first descriptor of `component` the object was built with.
+    pub fn descriptor<'a>(&'a self, component: &'a str) -> Option<&'a Tile> {
+        self.descriptors_of(component).next()
+    }
+
+    pub fn descriptors_of<'a>(&'a self, component: &'a str) -> impl Iterator<Item = &'a Tile> {
+        self.descriptors
+            .iter()
+            .filter(move |d| d.component.is(component))
+    }
+}
+
+/// Makes an object along with its descriptors, all of them or none, e.g.
+/// `mosaic.build_object("Node").with("Position", pars().set("x", 1.0f32).ok()).done()`.
+pub struct ObjectBuilder {
+    mosaic: Arc<Mosaic>,
+    component: S32,
+    fields: ComponentValues,
+    descriptors: Vec<(S32, ComponentValues)>,
+}
+
+impl ObjectBuilder {
+    /// The values of the object's own fields; left out, they take their defaults.
+    pub fn fields(mut self, values: ComponentValues) -> ObjectBuilder {
+        self.fields = values;
+        self
+    }
+
+    /// Adds a `component` descriptor holding `values` on the object.
+    pub fn with(mut self, component: &str, values: ComponentValues) -> ObjectBuilder {
+        self.descriptors.push((component.into(), values));
+        self
+    }
+
+    /// Makes the object and its descriptors, panicking if any of them can't be made.
+    pub fn done(self) -> BuiltObject {
+        self.try_done().expect("Cannot build object, panicking!")
+    }
+
+    /// Makes the object and its descriptors, or none of them if any can't be made.
+    pub fn try_done(self) -> anyhow::Result<BuiltObject> {
+        let mosaic = Arc::clone(&self.mosaic);
+
+        // constraints are checked against the tiles already there, so each descriptor has to
+        // see the object and the descriptors before it
+        if !mosaic.constraints.lock().unwrap().is_empty() {
+            return mosaic.transaction(|mosaic| {
+                let object = mosaic.new_object(&self.component.to_string(), self.fields);
+                let descriptors = self
+                    .descriptors
+                    .into_iter()
+                    .map(|(component, values)| {
+                        mosaic.try_new_descriptor(&object, &component.to_string(), values)
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(BuiltObject {
+                    object,
+                    descriptors,
+                })
+            });
+        }
+
+        let mut parts = vec![];
+        for (component, values) in
+            std::iter::once((self.component, self.fields)).chain(self.descriptors)
+        {
+            let component = mosaic.component_registry.resolve_name(component);
+            mosaic.check_writable(component)?;
+            parts.push((
+                component,
+                Tile::resolve_data_fields(&mosaic, component, values)?,
+            ));
+        }
+
+        let ids = mosaic.next_ids(parts.len());
+        let object_id = ids[0];
+        let tiles = ids
+            .into_iter()
+            .zip(parts)
+            .map(|(id, (component, fields))| {
+                let tile_type = match id == object_id {
+                    true => TileType::Object,
+                    false => TileType::Descriptor { subject: object_id },
+                };
+                let tile = Tile {
+                    id,
+                    mosaic: Arc::clone(&mosaic),
+                    tile_type,
+                    component,
+                    generation: mosaic.next_generation(),
+                    fields_cache: FieldCache::default(),
+                };
+                (tile, fields)
+            })
+            .collect::<Vec<_>>();
+
+        let mut built = tiles.iter().map(|(tile, _)| tile.clone());
+        let object = built.next().unwrap();
+        let descriptors = built.collect();
+        mosaic.insert_tiles_with_fields(tiles);
+        Ok(BuiltObject {
+            object,
+            descriptors,
+        })
+    }
+}
+
+pub trait MosaicObjectBuilder {
+    /// Starts building an object of `component`, see `ObjectBuilder`.
+    fn build_object(&self, component: &str) -> ObjectBuilder;
+}
+
+impl MosaicObjectBuilder for Arc<Mosaic> {
+    fn build_object(&self, component: &str) -> ObjectBuilder {
+        ObjectBuilder {
+            mosaic: Arc::clone(self),
+            component: component.into(),
+            fields: vec![],
+            descriptors: vec![],
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{
+    ComponentValues, EntityId, HistoryOperation, Mosaic, MosaicIO, Tile, TileType, Value, S32,
+};
+
+/// Receives a callback for every change made to a mosaic it is subscribed to.
+/// Callbacks run right after the change, on the thread that made it.
+pub trait MosaicObserver: Send + Sync {
+    fn on_tile_created(&self, _tile: &Tile) {}
+    /// Called while the tile and its data can still be read, right before it is removed.
+    fn on_tile_deleted(&self, _tile: &Tile) {}
+    fn on_field_changed(&self, _tile: &Tile, _field: &str, _before: &Value, _after: &Value) {}
+    /// Called with the arrow as it is now, and the source and target it had before.
+    fn on_arrow_reconnected(&self, _arrow: &Tile, _before: (EntityId, EntityId)) {}
+    /// Called with the tile as it is now, and the type it had before.
+    fn on_tile_retyped(&self, _tile: &Tile, _before: TileType) {}
+    /// Called when data of `component` is added to the tile with `Tile::add_data`, replaced,
+    /// or removed.
+    fn on_data_changed(
+        &self,
+        _tile: &Tile,
+        _component: &str,
+        _before: Option<&ComponentValues>,
+        _after: Option<&ComponentValues>,
+    ) {
+    }
+    /// Called after `component` was redefined, with the tiles whose data was migrated to it.
+    fn on_type_redefined(&self, _component: &str, _migrated: &[Tile]) {}
+}
+
+pub type SubscriptionId = usize;
+
+/// Called with the tile, the value before the change, and the value after it.
+pub type FieldWatch = Arc<dyn Fn(&Tile, &Value, &Value) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct ObserverRegistry {
+    next_id: SubscriptionId,
+    observers: Vec<(SubscriptionId, Arc<dyn MosaicObserver>)>,
+    field_watches: HashMap<(EntityId, S32), Vec<(SubscriptionId, FieldWatch)>>,
+}
+
+impl std::fmt::Debug for ObserverRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "ObserverRegistry({}, {})",
+            self.observers.len(),
+            self.field_watches.values().map(Vec::len).sum::<usize>()
+        ))
+    }
+}
+
+impl ObserverRegistry {
+    fn next_id(&mut self) -> SubscriptionId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Watches belong to a single tile, so they go away together with it.
+    pub(crate) fn clear_field_watches(&mut self) {
+        self.field_watches.clear();
+    }
+}
+
+pub trait MosaicObservable {
+    fn subscribe(&self, observer: Arc<dyn MosaicObserver>) -> SubscriptionId;
+    /// Removes an observer, or a field watch added with `Tile::on_field_change`.
+    fn unsubscribe(&self, subscription: SubscriptionId);
+}
+
+impl MosaicObservable for Arc<Mosaic> {
+    fn subscribe(&self, observer: Arc<dyn MosaicObserver>) -> SubscriptionId {
+        let mut registry = self.observers.lock().unwrap();
+        let id = registry.next_id();
+        registry.observers.push((id, observer));
+        id
+    }
+
+    fn unsubscribe(&self, subscription: SubscriptionId) {
+        let mut registry = self.observers.lock().unwrap();
+        registry.observers.retain(|(id, _)| *id != subscription);
+        registry.field_watches.retain(|_, watches| {
+            watches.retain(|(id, _)| *id != subscription);
+            !watches.is_empty()
+        });
+    }
+}
+
+impl Tile {
+    /// Calls `callback` every time `field` of this tile is set to a different value, until
+    /// the tile is deleted or the returned id is passed to `unsubscribe`.
+    pub fn on_field_change<F>(&self, field: &str, callback: F) -> SubscriptionId
+    where
+        F: Fn(&Tile, &Value, &Value) + Send + Sync + 'static,
+    {
+        let mut registry = self.mosaic.observers.lock().unwrap();
+        let id = registry.next_id();
+        registry
+            .field_watches
+            .entry((self.id, field.into()))
+            .or_default()
+            .push((id, Arc::new(callback)));
+        id
+    }
+}
+
+impl Mosaic {
+    pub(crate) fn notify_observers(self: &Arc<Self>, operation: &HistoryOperation) {
+        let hooks = match operation {
+            HistoryOperation::Created { component, .. } => {
+                self.component_registry.create_hooks(*component)
+            }
+            HistoryOperation::Deleted { component, .. } => {
+                self.component_registry.delete_hooks(*component)
+            }
+            HistoryOperation::FieldChanged { .. }
+            | HistoryOperation::Reconnected { .. }
+            | HistoryOperation::Retyped { .. }
+            | HistoryOperation::DataChanged { .. }
+            | HistoryOperation::Redefined { .. } => vec![],
+        };
+
+        let (observers, watches) = {
+            let mut registry = self.observers.lock().unwrap();
+            let watches = match operation {
+                HistoryOperation::FieldChanged { id, field, .. } => registry
+                    .field_watches
+                    .get(&(*id, *field))
+                    .map(|watches| watches.iter().map(|(_, w)| Arc::clone(w)).collect())
+                    .unwrap_or_default(),
+                HistoryOperation::Deleted { id, .. } => {
+                    registry
+                        .field_watches
+                        .retain(|(watched, _), _| watched != id);
+                    vec![]
+                }
+                HistoryOperation::Created { .. }
+                | HistoryOperation::Reconnected { .. }
+                | HistoryOperation::Retyped { .. }
+                | HistoryOperation::DataChanged { .. }
+                | HistoryOperation::Redefined { .. } => vec![],
+            };
+
+            if registry.observers.is_empty() && watches.is_empty() && hooks.is_empty() {
+                return;
+            }
+
+            let observers = registry
+                .observers
+                .iter()
+                .map(|(_, o)| Arc::clone(o))
+                .collect::<Vec<_>>();
+            (observers, watches)
+        };
+
+        match operation {
+            HistoryOperation::Created { id, .. } => {
+                if let Some(tile) = self.get(*id) {
+                    hooks.iter().for_each(|h| h(&tile));
+                    observers.iter().for_each(|o| o.on_tile_created(&tile));
+                }
+            }
+            HistoryOperation::Deleted { id, .. } => {
+                if let Some(tile) = self.get(*id) {
+                    hooks.iter().for_each(|h| h(&tile));
+                    observers.iter().for_each(|o| o.on_tile_deleted(&tile));
+                }
+            }
+            HistoryOperation::FieldChanged {
+                id,
+                field,
+                before,
+                after,
+            } => {
+                if let Some(tile) = self.get(*id) {
+                    let field = field.to_string();
+                    observers
+                        .iter()
+                        .for_each(|o| o.on_field_changed(&tile, &field, before, after));
+                    watches.iter().for_each(|w| w(&tile, before, after));
+                }
+            }
+            HistoryOperation::Reconnected { id, before, .. } => {
+                if let Some(tile) = self.get(*id) {
+                    observers
+                        .iter()
+                        .for_each(|o| o.on_arrow_reconnected(&tile, *before));
+                }
+            }
+            HistoryOperation::Retyped { id, before, .. } => {
+                if let Some(tile) = self.get(*id) {
+                    observers
+                        .iter()
+                        .for_each(|o| o.on_tile_retyped(&tile, *before));
+                }
+            }
+            HistoryOperation::DataChanged {
+                id,
+                component,
+                before,
+                after,
+            } => {
+                if let Some(tile) = self.get(*id) {
+                    let component = component.to_string();
+                    observers.iter().for_each(|o| {
+                        o.on_data_changed(&tile, &component, before.as_ref(), after.as_ref())
+                    });
+                }
+            }
+            HistoryOperation::Redefined {
+                component,
+                migrated,
+                ..
+            } => {
+                let tiles = migrated
+                    .iter()
+                    .filter_map(|(id, _, _)| self.get(*id))
+                    .collect::<Vec<_>>();
+                let component = component.to_string();
+                observers
+                    .iter()
+                    .for_each(|o| o.on_type_redefined(&component, &tiles));
+            }
+        }
+    }
+}
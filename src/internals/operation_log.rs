@@ -0,0 +1,144 @@
+use std::{collections::HashSet, sync::Arc};
+
+use itertools::Itertools;
+
+use super::{
+    component_grammar::ComponentParser, DateTime, HistoryOperation, Mosaic, MosaicIO,
+    MosaicTypelevelCRUD, S32,
+};
+
+/// Milliseconds since the Unix epoch, read from the same clock as `DateTime::now`.
+pub(crate) fn unix_millis() -> u64 {
+    DateTime::now().unix_millis().max(0) as u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoggedChange {
+    /// A component type came into use, defined as for `new_type`.
+    Type(String),
+    Tile(HistoryOperation),
+    Cleared,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoggedOperation {
+    /// When it happened, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub change: LoggedChange,
+}
+
+/// Every change made while logging was on, in order, so replaying it onto an empty mosaic
+/// rebuilds it, ids and all.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperationLog {
+    pub operations: Vec<LoggedOperation>,
+    /// Types already logged, so each is only logged the first time a tile uses it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    logged_types: HashSet<S32>,
+}
+
+impl OperationLog {
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// A fresh mosaic with the first `count` operations applied to it.
+    pub fn replay_until(&self, count: usize) -> anyhow::Result<Arc<Mosaic>> {
+        let mosaic = Mosaic::new();
+        for operation in self.operations.iter().take(count) {
+            match &operation.change {
+                LoggedChange::Type(definition) => mosaic.new_type(definition)?,
+                LoggedChange::Tile(operation) => mosaic.replay_history_operation(operation),
+                LoggedChange::Cleared => mosaic.clear(),
+            }
+        }
+
+        Ok(mosaic)
+    }
+
+    /// A fresh mosaic with every operation logged up to and including `timestamp` applied.
+    pub fn replay_until_time(&self, timestamp: u64) -> anyhow::Result<Arc<Mosaic>> {
+        let count = self
+            .operations
+            .partition_point(|operation| operation.timestamp <= timestamp);
+        self.replay_until(count)
+    }
+
+    pub fn replay(&self) -> anyhow::Result<Arc<Mosaic>> {
+        self.replay_until(self.operations.len())
+    }
+
+    fn push(&mut self, change: LoggedChange) {
+        match &change {
+            LoggedChange::Cleared => self.logged_types.clear(),
+            LoggedChange::Type(definition) => {
+                self.logged_types
+                    .insert(ComponentParser::type_name_of(definition).into());
+            }
+            LoggedChange::Tile(_) => {}
+        }
+
+        self.operations.push(LoggedOperation {
+            timestamp: unix_millis(),
+            change,
+        });
+    }
+}
+
+impl Mosaic {
+    pub(crate) fn definition_of(&self, component: S32) -> Option<String> {
+        self.component_registry
+            .component_definitions
+            .read()
+            .unwrap()
+            .iter()
+            .find(|d| component.is(ComponentParser::type_name_of(d)))
+            .cloned()
+    }
+
+    /// Logs `change` if the operation log is on, after the type of any tile it makes.
+    pub(crate) fn log_operation(&self, change: LoggedChange) {
+        let component = match &change {
+            LoggedChange::Tile(HistoryOperation::Created { component, .. }) => *component,
+            _ => {
+                if let Some(log) = self.operation_log.lock().unwrap().as_mut() {
+                    log.push(change);
+                }
+                return;
+            }
+        };
+
+        let needs_type = match self.operation_log.lock().unwrap().as_ref() {
+            Some(log) => !log.logged_types.contains(&component),
+            None => return,
+        };
+        let definition = needs_type.then(|| self.definition_of(component)).flatten();
+
+        if let Some(log) = self.operation_log.lock().unwrap().as_mut() {
+            if let Some(definition) = definition {
+                log.push(LoggedChange::Type(definition));
+            }
+            log.push(change);
+        }
+    }
+
+    /// Logs every tile there is as made just now, as if the mosaic was built from nothing.
+    pub(crate) fn log_all_tiles(self: &Arc<Self>) {
+        for tile in self.get_all().sorted_by_key(|t| t.id) {
+            self.log_operation(LoggedChange::Tile(HistoryOperation::Created {
+                id: tile.id,
+                generation: tile.generation,
+                tile_type: tile.tile_type,
+                component: tile.component,
+                fields: tile.data(),
+            }));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use super::{
+    logging::Logging, EntityId, HistoryOperation, Mosaic, MosaicCRUD, MosaicError, MosaicIO, Tile,
+    TileType, S32,
+};
+
+impl Mosaic {
+    /// Points arrow `id` at new endpoints and records the change, without checking whether
+    /// the move is allowed.
+    pub(crate) fn move_arrow(self: &Arc<Self>, id: EntityId, source: EntityId, target: EntityId) {
+        let Some(TileType::Arrow {
+            source: old_source,
+            target: old_target,
+        }) = self.get(id).map(|t| t.tile_type)
+        else {
+            return;
+        };
+
+        self.set_tile_type(id, TileType::Arrow { source, target });
+        self.record_history(HistoryOperation::Reconnected {
+            id,
+            before: (old_source, old_target),
+            after: (source, target),
+        });
+    }
+
+    /// Gives tile `id` a new type and records the change, without checking whether it's
+    /// allowed. Arrows should be moved through `move_arrow`, so observers hear about it.
+    pub(crate) fn retype_tile(self: &Arc<Self>, id: EntityId, after: TileType) {
+        let Some(before) = self.get(id).map(|t| t.tile_type) else {
+            return;
+        };
+
+        self.set_tile_type(id, after);
+        self.record_history(HistoryOperation::Retyped { id, before, after });
+    }
+
+    /// Gives tile `id` a new type, keeping the dependents, the id sets, and the indices in
+    /// step with it.
+    fn set_tile_type(&self, id: EntityId, tile_type: TileType) {
+        let ends = |tile_type: TileType| match tile_type {
+            TileType::Object => vec![],
+            TileType::Arrow { source, target } => vec![source, target],
+            TileType::Descriptor { subject } | TileType::Extension { subject } => vec![subject],
+        };
+
+        let mut registry = self.tile_registry.write().unwrap();
+        let Some(tile) = registry.get_mut(&id) else {
+            return;
+        };
+
+        let before = tile.clone();
+        tile.tile_type = tile_type;
+        let after = tile.clone();
+
+        if ends(before.tile_type) != ends(after.tile_type) {
+            let mut dependents = self.dependent_ids_map.write().unwrap();
+            for end in ends(before.tile_type).into_iter().unique() {
+                let rest = dependents
+                    .remove_all(&end)
+                    .filter(|dependent| *dependent != id)
+                    .collect_vec();
+                for dependent in rest {
+                    dependents.append(end, dependent);
+                }
+            }
+            for end in ends(after.tile_type) {
+                dependents.append(end, id);
+            }
+        }
+
+        if std::mem::discriminant(&before.tile_type) != std::mem::discriminant(&after.tile_type) {
+            for (tile, add) in [(&before, false), (&after, true)] {
+                let mut ids = match tile.tile_type {
+                    TileType::Object => self.object_ids.write().unwrap(),
+                    TileType::Arrow { .. } => self.arrow_ids.write().unwrap(),
+                    TileType::Descriptor { .. } => self.descriptor_ids.write().unwrap(),
+                    TileType::Extension { .. } => self.extension_ids.write().unwrap(),
+                };
+                if add {
+                    ids.add(id);
+                } else {
+                    ids.remove(id);
+                }
+            }
+        }
+
+        // taking the tile out of the indices forgets the components attached to it
+        let mut indices = self.indices.write().unwrap();
+        let attached = indices.attached_to(id);
+        indices.remove(&before);
+        indices.insert(&after);
+        for component in attached {
+            indices.attach(&after, component);
+        }
+    }
+}
+
+/// Changes to the shape of a mosaic that keep the tiles involved, along with their ids, data,
+/// and whatever hangs off of them.
+pub trait MosaicRestructure {
+    /// Makes `arrow` go from `source` into `target` instead. Its endpoints can't be the arrow
+    /// itself, or anything depending on it.
+    fn reconnect(&self, arrow: &Tile, source: &Tile, target: &Tile) -> anyhow::Result<Tile>;
+    /// Turns a descriptor into an extension of the same subject.
+    fn descriptor_to_extension(&self, descriptor: &Tile) -> anyhow::Result<Tile>;
+    /// Turns an extension into a descriptor of the same subject.
+    fn extension_to_descriptor(&self, extension: &Tile) -> anyhow::Result<Tile>;
+    /// Copies `field` of `tile` into a new `component` descriptor on it, under the same name
+    /// or as its `self`.
+    fn reify_field(&self, tile: &Tile, field: &str, component: &str) -> anyhow::Result<Tile>;
+}
+
+impl MosaicRestructure for Arc<Mosaic> {
+    fn reconnect(&self, arrow: &Tile, source: &Tile, target: &Tile) -> anyhow::Result<Tile> {
+        let arrow = self
+            .get(arrow.id)
+            .ok_or(MosaicError::InvalidTile(arrow.id))?;
+        let TileType::Arrow {
+            source: old_source,
+            target: old_target,
+        } = arrow.tile_type
+        else {
+            return format!("Tile {} is not an arrow, it can't be reconnected", arrow.id)
+                .to_error();
+        };
+
+        for end in [source.id, target.id] {
+            if !self.is_tile_valid(&end) {
+                return Err(MosaicError::InvalidTile(end).into());
+            }
+        }
+
+        let depending = self.dependents_closure(&arrow, |_| true);
+        if depending
+            .iter()
+            .any(|t| t.id == source.id || t.id == target.id)
+        {
+            return format!(
+                "Cannot reconnect arrow {} to tiles that depend on it",
+                arrow.id
+            )
+            .to_error();
+        }
+
+        self.check_arrow(
+            source.id,
+            target.id,
+            arrow.component,
+            (source.id != old_source, target.id != old_target),
+        )?;
+
+        self.move_arrow(arrow.id, source.id, target.id);
+
+        Ok(self.get(arrow.id).unwrap())
+    }
+
+    fn descriptor_to_extension(&self, descriptor: &Tile) -> anyhow::Result<Tile> {
+        let tile = self
+            .get(descriptor.id)
+            .ok_or(MosaicError::InvalidTile(descriptor.id))?;
+        if !tile.is_descriptor() {
+            return format!("Tile {} is not a descriptor", tile.id).to_error();
+        }
+
+        let subject = tile.target_id();
+        self.retype_tile(tile.id, TileType::Extension { subject });
+        Ok(self.get(tile.id).unwrap())
+    }
+
+    fn extension_to_descriptor(&self, extension: &Tile) -> anyhow::Result<Tile> {
+        let tile = self
+            .get(extension.id)
+            .ok_or(MosaicError::InvalidTile(extension.id))?;
+        if !tile.is_extension() {
+            return format!("Tile {} is not an extension", tile.id).to_error();
+        }
+
+        let subject = tile.source_id();
+        self.check_new_descriptor(subject, tile.component)?;
+        self.retype_tile(tile.id, TileType::Descriptor { subject });
+        Ok(self.get(tile.id).unwrap())
+    }
+
+    fn reify_field(&self, tile: &Tile, field: &str, component: &str) -> anyhow::Result<Tile> {
+        let tile = self.get(tile.id).ok_or(MosaicError::InvalidTile(tile.id))?;
+        let field: S32 = field.into();
+        let value = tile
+            .data()
+            .into_iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value)
+            .ok_or(MosaicError::UnknownField {
+                component: tile.component,
+                field,
+            })?;
+
+        let component_type = self
+            .component_registry
+            .get_component_type(component.into())?;
+        let name = if component_type.has_self_field() {
+            "self".into()
+        } else if component_type.get_field_names().contains(&field) {
+            field
+        } else {
+            return Err(MosaicError::UnknownField {
+                component: component.into(),
+                field,
+            }
+            .into());
+        };
+
+        self.try_new_descriptor(&tile.id, component, vec![(name, value)])
+    }
+}
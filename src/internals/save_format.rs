@@ -0,0 +1,177 @@
+use std::{
+    fmt::Display,
+    io::{ErrorKind, Read, Write},
+};
+
+use super::EntityId;
+
+/// Every saved mosaic starts with these bytes, followed by the format version.
+pub const MOSAIC_MAGIC: [u8; 4] = *b"MOSA";
+pub const MOSAIC_FORMAT_VERSION: u16 = 6;
+/// The version of saves from before the header, which start right at the type definitions,
+/// end after the last tile, and have no checksum.
+pub const LEGACY_FORMAT_VERSION: u16 = 0;
+/// Versions before this one have no string table between the type definitions and the tiles.
+pub(crate) const STRING_TABLE_VERSION: u16 = 2;
+/// Versions before this one end right after the tiles, without data added through `add_data`.
+pub(crate) const ADDED_DATA_VERSION: u16 = 3;
+/// Versions before this one go straight to the type definitions, without the mosaic's uuid.
+pub(crate) const UUID_VERSION: u16 = 4;
+/// Versions before this one have no blob table after the string table.
+pub(crate) const BLOB_TABLE_VERSION: u16 = 5;
+/// Versions before this one write strings out in full in the tile data as well as in the
+/// string table; from this one on, fields hold the id of their string in the table.
+pub(crate) const STRING_ID_VERSION: u16 = 6;
+
+/// Written in place of a tile id to mark the end of the tile records; no tile ever gets it.
+pub(crate) const END_OF_TILES: EntityId = EntityId::MAX;
+
+/// Reasons a saved mosaic can be rejected while loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MosaicFormatError {
+    MissingHeader,
+    UnsupportedVersion(u16),
+    CorruptData(String),
+}
+
+impl Display for MosaicFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MosaicFormatError::MissingHeader => f.write_str("Data is not a saved mosaic"),
+            MosaicFormatError::UnsupportedVersion(v) => f.write_fmt(format_args!(
+                "Mosaic format version {} is not supported, expected {}",
+                v, MOSAIC_FORMAT_VERSION
+            )),
+            MosaicFormatError::CorruptData(reason) => {
+                f.write_fmt(format_args!("Mosaic data is corrupt: {}", reason))
+            }
+        }
+    }
+}
+
+impl std::error::Error for MosaicFormatError {}
+
+impl MosaicFormatError {
+    /// Truncated input surfaces as an unexpected end of file from the reader; this turns
+    /// that into `CorruptData` and passes every other error through.
+    pub(crate) fn from_read_error(error: anyhow::Error) -> anyhow::Error {
+        match error.downcast_ref::<std::io::Error>() {
+            Some(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                MosaicFormatError::CorruptData("data ends unexpectedly".to_string()).into()
+            }
+            _ => error,
+        }
+    }
+}
+
+pub(crate) fn write_header<W: Write>(writer: &mut W) -> anyhow::Result<()> {
+    writer.write_all(&MOSAIC_MAGIC)?;
+    writer.write_all(&MOSAIC_FORMAT_VERSION.to_be_bytes())?;
+    Ok(())
+}
+
+/// Reads the header, returning the format version and, for legacy saves without one, the
+/// bytes read looking for it.
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> anyhow::Result<(u16, Vec<u8>)> {
+    let mut magic = vec![];
+    reader
+        .by_ref()
+        .take(MOSAIC_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(|_| MosaicFormatError::MissingHeader)?;
+    if magic.is_empty() {
+        return Err(MosaicFormatError::MissingHeader.into());
+    }
+    if magic != MOSAIC_MAGIC {
+        return Ok((LEGACY_FORMAT_VERSION, magic));
+    }
+
+    let mut version = [0u8; 2];
+    reader
+        .read_exact(&mut version)
+        .map_err(|_| MosaicFormatError::MissingHeader)?;
+    match u16::from_be_bytes(version) {
+        v @ 1..=MOSAIC_FORMAT_VERSION => Ok((v, vec![])),
+        v => Err(MosaicFormatError::UnsupportedVersion(v).into()),
+    }
+}
+
+/// The format version `data`, a saved mosaic, is written in, without reading the rest of it.
+pub fn format_version(data: &[u8]) -> anyhow::Result<u16> {
+    read_header(&mut &data[..]).map(|(version, _)| version)
+}
+
+/// Passes writes through while keeping a CRC32 of everything written.
+pub(crate) struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Appends the checksum of everything written so far, outside of the checksum itself.
+    pub(crate) fn finish(mut self) -> anyhow::Result<W> {
+        let checksum = self.hasher.clone().finalize();
+        self.inner.write_all(&checksum.to_be_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Passes reads through while keeping a CRC32 of everything read.
+pub(crate) struct ChecksumReader<R: Read> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        ChecksumReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Reads the stored checksum that follows the data and compares it to what was read.
+    pub(crate) fn verify(mut self) -> anyhow::Result<()> {
+        let expected = self.hasher.clone().finalize();
+        let mut stored = [0u8; 4];
+        self.inner
+            .read_exact(&mut stored)
+            .map_err(|_| MosaicFormatError::CorruptData("checksum is missing".to_string()))?;
+
+        if u32::from_be_bytes(stored) != expected {
+            return Err(
+                MosaicFormatError::CorruptData("checksum does not match".to_string()).into(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    component_grammar::ComponentParser, ComponentValues, EntityId, Mosaic, MosaicCRUD, MosaicIO,
+    TileType, S32,
+};
+
+/// A tile as serde sees it: where it sits, and its field values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedTile {
+    pub id: EntityId,
+    pub tile_type: TileType,
+    pub component: S32,
+    pub fields: ComponentValues,
+}
+
+/// A whole mosaic in a form any serde format can write, as an alternative to the binary
+/// format of `save`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SerializedMosaic {
+    /// Component type definitions, written the same way as for `new_type`.
+    pub components: Vec<String>,
+    /// Tiles in id order, so endpoints always come before the tiles attached to them.
+    pub tiles: Vec<SerializedTile>,
+}
+
+impl SerializedMosaic {
+    /// Makes a fresh mosaic holding these tiles, with their ids kept.
+    pub fn to_mosaic(&self) -> anyhow::Result<Arc<Mosaic>> {
+        let mosaic = Mosaic::new();
+        mosaic.load_serialized(self)?;
+        Ok(mosaic)
+    }
+}
+
+pub trait MosaicSerialization {
+    fn to_serialized(&self) -> SerializedMosaic;
+    /// Adds the components and tiles of `serialized` under their ids, or nothing if a tile
+    /// has an unknown component or endpoint.
+    fn load_serialized(&self, serialized: &SerializedMosaic) -> anyhow::Result<()>;
+}
+
+impl MosaicSerialization for Arc<Mosaic> {
+    fn to_serialized(&self) -> SerializedMosaic {
+        SerializedMosaic {
+            components: self
+                .component_registry
+                .component_definitions
+                .read()
+                .unwrap()
+                .clone(),
+            tiles: self
+                .get_all()
+                .sorted_by_key(|t| t.id)
+                .map(|t| SerializedTile {
+                    id: t.id,
+                    tile_type: t.tile_type,
+                    component: t.component,
+                    fields: t.data(),
+                })
+                .collect_vec(),
+        }
+    }
+
+    fn load_serialized(&self, serialized: &SerializedMosaic) -> anyhow::Result<()> {
+        let registry = &self.component_registry;
+        let mut known: HashSet<S32> = HashSet::new();
+        for definition in &serialized.components {
+            let typename: S32 = ComponentParser::type_name_of(definition).into();
+            if !registry.has_component_type(&typename) {
+                let parsed = ComponentParser::parse_all_with_defaults(definition)?;
+                known.extend(parsed.into_iter().map(|(t, _)| S32::from(t.name())));
+            }
+        }
+
+        let ids: HashSet<EntityId> = serialized.tiles.iter().map(|t| t.id).collect();
+        for tile in &serialized.tiles {
+            if !known.contains(&tile.component) && !registry.has_component_type(&tile.component) {
+                return Err(anyhow!(
+                    "Tile {} is a {}, which is not a known component",
+                    tile.id,
+                    tile.component
+                ));
+            }
+
+            let endpoints = match tile.tile_type {
+                TileType::Object => vec![],
+                TileType::Arrow { source, target } => vec![source, target],
+                TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                    vec![subject]
+                }
+            };
+            if let Some(missing) = endpoints
+                .into_iter()
+                .find(|e| !ids.contains(e) && !self.is_tile_valid(e))
+            {
+                return Err(anyhow!(
+                    "Tile {} hangs off of missing tile {}",
+                    tile.id,
+                    missing
+                ));
+            }
+        }
+
+        for definition in &serialized.components {
+            let typename: S32 = ComponentParser::type_name_of(definition).into();
+            if !registry.has_component_type(&typename) {
+                registry.add_component_types(definition)?;
+            }
+        }
+
+        for tile in serialized.tiles.iter().sorted_by_key(|t| t.id) {
+            self.restore_tile(tile.id, tile.tile_type, tile.component, tile.fields.clone());
+        }
+
+        Ok(())
+    }
+}
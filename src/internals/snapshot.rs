@@ -0,0 +1,160 @@
+use std::{collections::HashSet, sync::Arc};
+
+use atomic_counter::AtomicCounter;
+use itertools::Itertools;
+
+use super::{
+    component_grammar::ComponentParser, EntityId, Mosaic, MosaicIO, TileType, Value, Version, S32,
+};
+
+struct SnapshotTile {
+    id: EntityId,
+    tile_type: TileType,
+    component: S32,
+    fields: Vec<(S32, Value)>,
+}
+
+struct SnapshotData {
+    version: Version,
+    next_id: usize,
+    definitions: Vec<String>,
+    /// In id order, so tiles mostly come after their endpoints.
+    tiles: Vec<SnapshotTile>,
+}
+
+/// The state of a mosaic at one point in time, copied out of memory rather than saved, and
+/// shared when cloned.
+#[derive(Clone)]
+pub struct MosaicSnapshot {
+    data: Arc<SnapshotData>,
+}
+
+impl MosaicSnapshot {
+    /// The version of the mosaic when the snapshot was taken.
+    pub fn version(&self) -> Version {
+        self.data.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.tiles.is_empty()
+    }
+}
+
+impl std::fmt::Debug for MosaicSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "MosaicSnapshot(version {}, {} tiles)",
+            self.data.version,
+            self.data.tiles.len()
+        ))
+    }
+}
+
+impl Mosaic {
+    /// Makes a new mosaic holding what `snapshot` holds, under the same ids, to be changed
+    /// without touching the mosaic the snapshot came from.
+    pub fn fork_from(snapshot: &MosaicSnapshot) -> Arc<Mosaic> {
+        let fork = Mosaic::new();
+        fork.fill_from_snapshot(snapshot);
+        fork
+    }
+
+    fn fill_from_snapshot(self: &Arc<Self>, snapshot: &MosaicSnapshot) {
+        for definition in &snapshot.data.definitions {
+            let typename: S32 = ComponentParser::type_name_of(definition).into();
+            if !self.component_registry.has_component_type(&typename) {
+                // these definitions were accepted once already
+                self.component_registry
+                    .add_component_types(definition)
+                    .unwrap();
+            }
+        }
+
+        let mut placed = HashSet::new();
+        let mut pending = snapshot.data.tiles.iter().collect_vec();
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|tile| {
+                let endpoints = match tile.tile_type {
+                    TileType::Object => vec![],
+                    TileType::Arrow { source, target } => vec![source, target],
+                    TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                        vec![subject]
+                    }
+                };
+                endpoints
+                    .iter()
+                    .all(|e| *e == tile.id || placed.contains(e))
+            });
+            if ready.is_empty() {
+                break;
+            }
+
+            for tile in ready {
+                self.restore_tile(tile.id, tile.tile_type, tile.component, tile.fields.clone());
+                placed.insert(tile.id);
+            }
+            pending = waiting;
+        }
+
+        // ids handed out before the snapshot stay used, even those of tiles deleted since
+        let taken = self.entity_counter.get();
+        if snapshot.data.next_id > taken {
+            self.entity_counter.add(snapshot.data.next_id - taken);
+        }
+    }
+}
+
+pub trait MosaicSnapshots {
+    fn snapshot(&self) -> MosaicSnapshot;
+    /// Throws away everything in this mosaic, history included, and puts the contents of
+    /// `snapshot` in its place; this is how a fork that worked out gets promoted.
+    fn restore_snapshot(&self, snapshot: &MosaicSnapshot);
+}
+
+impl MosaicSnapshots for Arc<Mosaic> {
+    fn snapshot(&self) -> MosaicSnapshot {
+        let definitions = self
+            .component_registry
+            .component_definitions
+            .read()
+            .unwrap()
+            .clone();
+
+        let tiles = {
+            let registry = self.tile_registry.read().unwrap();
+            let storage = self.data_storage.read().unwrap();
+            registry
+                .values()
+                .sorted_by_key(|t| t.id)
+                .map(|t| SnapshotTile {
+                    id: t.id,
+                    tile_type: t.tile_type,
+                    component: t.component,
+                    fields: storage
+                        .get(&t.component.to_string())
+                        .and_then(|entities| entities.get(&t.id))
+                        .map(|fields| fields.iter().map(|(f, v)| (*f, v.clone())).collect_vec())
+                        .unwrap_or_default(),
+                })
+                .collect_vec()
+        };
+
+        MosaicSnapshot {
+            data: Arc::new(SnapshotData {
+                version: self.version(),
+                next_id: self.entity_counter.get(),
+                definitions,
+                tiles,
+            }),
+        }
+    }
+
+    fn restore_snapshot(&self, snapshot: &MosaicSnapshot) {
+        self.clear();
+        self.fill_from_snapshot(snapshot);
+    }
+}
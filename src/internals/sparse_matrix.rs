@@ -474,7 +474,8 @@ impl BidirectionalMatrix {
             .iter()
             .flatten()
             .filter(|t| *t == &tgt)
-            .collect::<Vec<_>>().is_empty()
+            .collect::<Vec<_>>()
+            .is_empty()
     }
 
     pub fn reach_backward_until(&self, src: EntityId, tgt: EntityId) -> bool {
@@ -483,7 +484,8 @@ impl BidirectionalMatrix {
             .iter()
             .flatten()
             .filter(|t| *t == &tgt)
-            .collect::<Vec<_>>().is_empty()
+            .collect::<Vec<_>>()
+            .is_empty()
     }
 
     pub fn are_reachable(&self, src: EntityId, tgt: EntityId) -> bool {
@@ -500,7 +502,7 @@ impl BidirectionalMatrix {
 }
 
 /* /////////////////////////////////////////////////////////////////////////////////// */
-/// Unit Tests
+// Unit Tests
 /* /////////////////////////////////////////////////////////////////////////////////// */
 
 #[cfg(test)]
@@ -123,6 +123,21 @@ impl SparseSet {
     pub fn clear(&mut self) {
         self.order_max = 0;
     }
+
+    /// Forgets the elements removed so far and gives back unused capacity. Returns how many
+    /// removed elements were still kept track of.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        let stale = self.index_array.len() - self.len();
+        self.order_array.truncate(self.order_max);
+        self.order_array.shrink_to_fit();
+        self.index_array = self
+            .order_array
+            .iter()
+            .enumerate()
+            .map(|(dense, sparse)| (*sparse, dense + 1))
+            .collect();
+        stale
+    }
 }
 
 impl<'a> IntoIterator for &'a SparseSet {
@@ -135,7 +150,7 @@ impl<'a> IntoIterator for &'a SparseSet {
 }
 
 /* /////////////////////////////////////////////////////////////////////////////////// */
-/// Unit Tests
+// Unit Tests
 /* /////////////////////////////////////////////////////////////////////////////////// */
 
 #[cfg(test)]
@@ -221,4 +236,25 @@ mod sparse_set_testing {
         assert_eq!(*s.index_array.get(&8).unwrap(), 0);
         assert_eq!(*s.index_array.get(&9).unwrap(), 2);
     }
+
+    #[test]
+    fn test_sparse_set_shrink_to_fit() {
+        let mut s = SparseSet::new();
+        s.add(7);
+        s.add(8);
+        s.add(6);
+        s.remove(7);
+        s.remove(6);
+        assert_eq!(3, s.index_array.len());
+
+        assert_eq!(2, s.shrink_to_fit());
+        assert_eq!(s.order_array, [8]);
+        assert_eq!(1, s.index_array.len());
+        assert!(s.is_member(8));
+        assert!(!s.is_member(7));
+
+        s.add(7);
+        assert_eq!(s.elements(), &vec![8, 7]);
+        assert_eq!(0, s.shrink_to_fit());
+    }
 }
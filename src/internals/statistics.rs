@@ -0,0 +1,153 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use super::{EntityId, Mosaic, TileType, ToByteArray, S32};
+
+/// How much a mosaic holds, and how much bookkeeping it keeps for it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MosaicStats {
+    pub objects: usize,
+    pub arrows: usize,
+    pub descriptors: usize,
+    pub extensions: usize,
+    /// How many tiles hold each component, as their own or added with `Tile::add_data`.
+    pub components: BTreeMap<S32, usize>,
+    /// Field values kept in the data storage, one per tile and component.
+    pub data_entries: usize,
+    /// What the field values take up when encoded, roughly what saving them costs.
+    pub data_bytes: usize,
+    pub dependent_entries: usize,
+    pub index_entries: usize,
+    /// Tiles whose latest change is kept for building deltas; see `MosaicIO::save_delta`.
+    pub change_log_entries: usize,
+    pub strings: usize,
+}
+
+impl MosaicStats {
+    pub fn tiles(&self) -> usize {
+        self.objects + self.arrows + self.descriptors + self.extensions
+    }
+}
+
+/// Bookkeeping that outlived the tiles it was kept for. Any of it is a bug, or the leftovers
+/// of a crash; `collect_garbage` clears all of it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Component data kept for ids with no tile, as (component, id).
+    pub orphaned_data: Vec<(S32, EntityId)>,
+    /// Dependents map entries where either end has no tile, as (owner, dependent).
+    pub dangling_dependents: Vec<(EntityId, EntityId)>,
+    /// Arrows, descriptors, and extensions whose endpoints are gone.
+    pub orphaned_tiles: Vec<EntityId>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned_data.is_empty()
+            && self.dangling_dependents.is_empty()
+            && self.orphaned_tiles.is_empty()
+    }
+}
+
+pub trait MosaicStatistics {
+    fn stats(&self) -> MosaicStats;
+    /// Looks through the data storage and the dependents map for entries left behind by
+    /// tiles that are gone. This walks everything, so it's meant for checks, not hot paths.
+    fn check_leaks(&self) -> LeakReport;
+}
+
+impl MosaicStatistics for Arc<Mosaic> {
+    fn stats(&self) -> MosaicStats {
+        let mut stats = MosaicStats::default();
+        for tile in self.tile_registry.read().unwrap().values() {
+            match tile.tile_type {
+                TileType::Object => stats.objects += 1,
+                TileType::Arrow { .. } => stats.arrows += 1,
+                TileType::Descriptor { .. } => stats.descriptors += 1,
+                TileType::Extension { .. } => stats.extensions += 1,
+            }
+        }
+
+        stats.dependent_entries = self.dependent_ids_map.read().unwrap().values_len();
+        stats.index_entries = self.indices.read().unwrap().len();
+        stats.change_log_entries = self.change_log.lock().unwrap().entries();
+        stats.strings = self.strings.read().unwrap().len();
+
+        for (component, entities) in self.data_storage.read().unwrap().iter() {
+            if !entities.is_empty() {
+                stats
+                    .components
+                    .insert(component.as_str().into(), entities.len());
+            }
+            stats.data_entries += entities.len();
+            stats.data_bytes += entities
+                .values()
+                .flat_map(|fields| fields.values())
+                .map(|value| value.to_byte_array().len())
+                .sum::<usize>();
+        }
+
+        stats
+    }
+
+    fn check_leaks(&self) -> LeakReport {
+        let tiles = self
+            .tile_registry
+            .read()
+            .unwrap()
+            .values()
+            .map(|t| (t.id, t.tile_type))
+            .collect_vec();
+        let alive = tiles.iter().map(|(id, _)| *id).collect::<HashSet<_>>();
+
+        let dangling_dependents = self
+            .dependent_ids_map
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(owner, dependent)| !alive.contains(owner) || !alive.contains(dependent))
+            .map(|(owner, dependent)| (*owner, *dependent))
+            .sorted()
+            .collect_vec();
+
+        let orphaned_data = self
+            .data_storage
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(component, entities)| {
+                entities
+                    .keys()
+                    .filter(|id| !alive.contains(id))
+                    .map(|id| (S32::from(component.as_str()), *id))
+                    .collect_vec()
+            })
+            .sorted()
+            .collect_vec();
+
+        let orphaned_tiles = tiles
+            .into_iter()
+            .filter(|(_, tile_type)| match *tile_type {
+                TileType::Object => false,
+                TileType::Arrow { source, target } => {
+                    !alive.contains(&source) || !alive.contains(&target)
+                }
+                TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                    !alive.contains(&subject)
+                }
+            })
+            .map(|(id, _)| id)
+            .sorted()
+            .collect_vec();
+
+        LeakReport {
+            orphaned_data,
+            dangling_dependents,
+            orphaned_tiles,
+        }
+    }
+}
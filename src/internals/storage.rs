@@ -0,0 +1,243 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+
+use uuid::Uuid;
+
+use super::{EntityId, Mosaic, MosaicIO, MosaicStreamIO, Version};
+
+/// Somewhere a mosaic can be persisted to, in the same format `save` produces.
+pub trait StorageBackend: std::fmt::Debug + Send + Sync {
+    /// A reader over the stored mosaic, or `None` if nothing has been stored yet.
+    fn reader(&self) -> anyhow::Result<Option<Box<dyn Read + '_>>>;
+    /// Replaces whatever is stored with `data`.
+    fn store(&self, data: &[u8]) -> anyhow::Result<()>;
+}
+
+/// Keeps the mosaic in a shared in-memory buffer; clones of it see the same buffer.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        MemoryStorage {
+            buffer: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.buffer.lock().unwrap().clone()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn reader(&self) -> anyhow::Result<Option<Box<dyn Read + '_>>> {
+        let data = self.contents();
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Box::new(Cursor::new(data))))
+    }
+
+    fn store(&self, data: &[u8]) -> anyhow::Result<()> {
+        *self.buffer.lock().unwrap() = data.to_vec();
+        Ok(())
+    }
+}
+
+/// Writes `data` to a new file next to `path` and renames it over `path`, so a crash or a
+/// failed write leaves the old contents whole, and readers of the old file keep seeing them.
+pub fn write_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Cannot store to {}, it names no file", path.display()))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        Uuid::new_v4().simple()
+    ));
+
+    let written = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+
+    // the rename itself is only durable once the directory is synced
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn has_contents(path: &Path) -> anyhow::Result<bool> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len() > 0),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads and writes the mosaic through a regular file, which is created on first store.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn reader(&self) -> anyhow::Result<Option<Box<dyn Read + '_>>> {
+        if !has_contents(&self.path)? {
+            return Ok(None);
+        }
+
+        Ok(Some(Box::new(File::open(&self.path)?)))
+    }
+
+    fn store(&self, data: &[u8]) -> anyhow::Result<()> {
+        write_file(&self.path, data)
+    }
+}
+
+/// Like `FileStorage`, but reads through a memory map; loading still copies every tile.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct MmapStorage {
+    path: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MmapStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        MmapStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBackend for MmapStorage {
+    fn reader(&self) -> anyhow::Result<Option<Box<dyn Read + '_>>> {
+        if !has_contents(&self.path)? {
+            return Ok(None);
+        }
+
+        let file = File::open(&self.path)?;
+        // SAFETY: `store` renames a new file over the path, so it never changes a mapped file.
+        // Another process truncating or rewriting the file in place while it is mapped makes
+        // reading it fault, which nothing here can prevent; this backend is only for files that
+        // nothing else writes to in place.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Some(Box::new(Cursor::new(map))))
+    }
+
+    fn store(&self, data: &[u8]) -> anyhow::Result<()> {
+        write_file(&self.path, data)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct AttachedStorage {
+    backend: Box<dyn StorageBackend>,
+    flushed: Version,
+}
+
+/// Persists a mosaic to a `StorageBackend`, loading all of it on attach and writing all of it
+/// back on `flush`.
+pub trait MosaicStorage {
+    /// Loads what `backend` holds and keeps it as where `flush` writes back to.
+    fn attach_storage<B: StorageBackend + 'static>(&self, backend: B) -> anyhow::Result<()>;
+    fn detach_storage(&self);
+    /// True if the mosaic changed since it was last saved, or loaded into while empty.
+    fn is_dirty(&self) -> bool;
+    /// The sorted ids of the tiles made, changed or deleted since then, as for `is_dirty`.
+    fn changed_since_save(&self) -> Vec<EntityId>;
+    /// Writes the mosaic back to its backend if it is dirty; returns whether anything was written.
+    fn flush(&self) -> anyhow::Result<bool>;
+}
+
+impl MosaicStorage for Arc<Mosaic> {
+    fn attach_storage<B: StorageBackend + 'static>(&self, backend: B) -> anyhow::Result<()> {
+        if let Some(reader) = backend.reader()? {
+            self.load_from(reader)?;
+        }
+
+        *self.storage.lock().unwrap() = Some(AttachedStorage {
+            backend: Box::new(backend),
+            flushed: self.version(),
+        });
+        Ok(())
+    }
+
+    fn detach_storage(&self) {
+        *self.storage.lock().unwrap() = None;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.saved_version.load(Ordering::Relaxed) != self.version()
+    }
+
+    fn changed_since_save(&self) -> Vec<EntityId> {
+        let (_, written, deleted) = self.changes_since(self.saved_version.load(Ordering::Relaxed));
+        written.into_iter().chain(deleted).sorted().collect_vec()
+    }
+
+    fn flush(&self) -> anyhow::Result<bool> {
+        let mut storage = self.storage.lock().unwrap();
+        let Some(attached) = storage.as_mut() else {
+            return Ok(false);
+        };
+
+        let version = self.version();
+        if attached.flushed == version {
+            return Ok(false);
+        }
+
+        let mut data = vec![];
+        self.save_to(&mut data)?;
+        attached.backend.store(&data)?;
+        attached.flushed = version;
+        Ok(true)
+    }
+}
+
+impl Mosaic {
+    /// Creates a new mosaic backed by `backend`, loading anything it already holds.
+    pub fn open<B: StorageBackend + 'static>(backend: B) -> anyhow::Result<Arc<Mosaic>> {
+        let mosaic = Mosaic::new();
+        mosaic.attach_storage(backend)?;
+        Ok(mosaic)
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use super::{Blob, BlobId, Mosaic, MosaicFormatError, Str, Value};
+
+/// A single copy of every string and blob in the fields of a mosaic, under an id that stays
+/// the same while it's in use.
+#[derive(Default, Debug)]
+pub struct StringPool {
+    ids: HashMap<Arc<str>, Str>,
+    strings: HashMap<Str, Arc<str>>,
+    next_id: u64,
+    blobs: HashMap<BlobId, Blob>,
+}
+
+impl StringPool {
+    pub fn intern(&mut self, s: &str) -> (Str, Arc<str>) {
+        if let Some((string, id)) = self.ids.get_key_value(s) {
+            return (*id, Arc::clone(string));
+        }
+
+        let id = Str(self.next_id);
+        self.next_id += 1;
+        self.insert(id, s.into())
+    }
+
+    /// Interns `s` under `id` if neither of them is taken yet, which is what keeps ids the
+    /// same across saving and loading; falls back to `intern` otherwise.
+    pub(crate) fn intern_as(&mut self, id: Str, s: &str) -> (Str, Arc<str>) {
+        if self.ids.contains_key(s) || self.strings.contains_key(&id) {
+            return self.intern(s);
+        }
+
+        self.next_id = self.next_id.max(id.0 + 1);
+        self.insert(id, s.into())
+    }
+
+    fn insert(&mut self, id: Str, string: Arc<str>) -> (Str, Arc<str>) {
+        self.ids.insert(Arc::clone(&string), id);
+        self.strings.insert(id, Arc::clone(&string));
+        (id, string)
+    }
+
+    pub fn id_of(&self, s: &str) -> Option<Str> {
+        self.ids.get(s).cloned()
+    }
+
+    pub fn resolve(&self, id: Str) -> Option<Arc<str>> {
+        self.strings.get(&id).cloned()
+    }
+
+    /// Swaps `blob` for the pooled blob with the same contents, pooling it if there is none.
+    pub fn intern_blob(&mut self, blob: Blob) -> Blob {
+        if blob.id() == BlobId::EMPTY {
+            return Blob::empty();
+        }
+        self.blobs.entry(blob.id()).or_insert(blob).clone()
+    }
+
+    pub fn resolve_blob(&self, id: BlobId) -> Option<Blob> {
+        match id == BlobId::EMPTY {
+            true => Some(Blob::empty()),
+            false => self.blobs.get(&id).cloned(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Swaps every string inside `value` for its pooled copy.
+    pub(crate) fn intern_value(&mut self, value: Value) -> Value {
+        match value {
+            Value::STR(s) => Value::STR(self.intern(&s).1),
+            Value::BLOB(blob) => Value::BLOB(self.intern_blob(blob)),
+            Value::SUM(tag, inner) => Value::SUM(tag, Box::new(self.intern_value(*inner))),
+            Value::ARR(values) => {
+                Value::ARR(values.into_iter().map(|v| self.intern_value(v)).collect())
+            }
+            Value::LIST(values) => {
+                Value::LIST(values.into_iter().map(|v| self.intern_value(v)).collect())
+            }
+            value => value,
+        }
+    }
+
+    /// The ids of every pooled string inside `value`.
+    pub(crate) fn ids_in(&self, value: &Value, ids: &mut HashSet<Str>) {
+        match value {
+            Value::STR(s) => ids.extend(self.id_of(s)),
+            Value::SUM(_, inner) => self.ids_in(inner, ids),
+            Value::ARR(values) | Value::LIST(values) => {
+                values.iter().for_each(|v| self.ids_in(v, ids))
+            }
+            _ => {}
+        }
+    }
+
+    /// Fills in the bytes of the blobs inside `value` read from saved data, which only know
+    /// their id; fails if one of them isn't pooled.
+    pub(crate) fn resolve_blobs(&self, value: Value) -> anyhow::Result<Value> {
+        Ok(match value {
+            Value::BLOB(blob) => Value::BLOB(
+                self.resolve_blob(blob.id())
+                    .ok_or_else(|| anyhow::anyhow!("blob {} is missing", blob.id()))?,
+            ),
+            Value::SUM(tag, inner) => Value::SUM(tag, Box::new(self.resolve_blobs(*inner)?)),
+            Value::ARR(values) => Value::ARR(
+                values
+                    .into_iter()
+                    .map(|v| self.resolve_blobs(v))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Value::LIST(values) => Value::LIST(
+                values
+                    .into_iter()
+                    .map(|v| self.resolve_blobs(v))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            value => value,
+        })
+    }
+
+    /// The ids of every blob inside `value`, but the empty one.
+    pub(crate) fn blob_ids_in(value: &Value, ids: &mut HashSet<BlobId>) {
+        match value {
+            Value::BLOB(blob) if blob.id() != BlobId::EMPTY => {
+                ids.insert(blob.id());
+            }
+            Value::SUM(_, inner) => Self::blob_ids_in(inner, ids),
+            Value::ARR(values) | Value::LIST(values) => {
+                values.iter().for_each(|v| Self::blob_ids_in(v, ids))
+            }
+            _ => {}
+        }
+    }
+
+    /// Forgets strings that nothing outside the pool holds on to anymore, be it a field or
+    /// the undo history, and returns how many there were. Their ids are never reused.
+    pub(crate) fn prune(&mut self) -> usize {
+        let ids = self.strings.keys().copied().collect::<Vec<_>>();
+        self.release(ids)
+    }
+
+    /// Like `prune`, for just the strings with the given ids; called with the strings of
+    /// values as they are dropped, so overwritten strings don't wait for garbage collection.
+    pub(crate) fn release(&mut self, ids: impl IntoIterator<Item = Str>) -> usize {
+        let unused = ids
+            .into_iter()
+            .filter(|id| {
+                self.strings
+                    .get(id)
+                    .is_some_and(|s| Arc::strong_count(s) == 2)
+            })
+            .collect::<HashSet<_>>();
+
+        for id in &unused {
+            if let Some(s) = self.strings.remove(id) {
+                self.ids.remove(&s);
+            }
+        }
+
+        unused.len()
+    }
+
+    /// Swaps every string inside `value` for its id, written out in decimal, which is how
+    /// saves refer to the strings of their string table.
+    pub(crate) fn to_saved(&self, value: Value) -> anyhow::Result<Value> {
+        Ok(match value {
+            Value::STR(s) => {
+                let id = self
+                    .id_of(&s)
+                    .ok_or_else(|| anyhow::anyhow!("string {:?} is not pooled", s))?;
+                Value::STR(id.0.to_string().into())
+            }
+            Value::SUM(tag, inner) => Value::SUM(tag, Box::new(self.to_saved(*inner)?)),
+            Value::ARR(values) => Value::ARR(
+                values
+                    .into_iter()
+                    .map(|v| self.to_saved(v))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Value::LIST(values) => Value::LIST(
+                values
+                    .into_iter()
+                    .map(|v| self.to_saved(v))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            value => value,
+        })
+    }
+
+    /// Undoes `to_saved`, looking the ids up in the string table read from a save.
+    pub(crate) fn from_saved(
+        value: Value,
+        table: &HashMap<Str, Arc<str>>,
+    ) -> anyhow::Result<Value> {
+        Ok(match value {
+            Value::STR(s) => {
+                let string = s
+                    .parse()
+                    .ok()
+                    .and_then(|id| table.get(&Str(id)))
+                    .ok_or_else(|| {
+                        MosaicFormatError::CorruptData(format!("string {} is missing", s))
+                    })?;
+                Value::STR(Arc::clone(string))
+            }
+            Value::SUM(tag, inner) => Value::SUM(tag, Box::new(Self::from_saved(*inner, table)?)),
+            Value::ARR(values) => Value::ARR(
+                values
+                    .into_iter()
+                    .map(|v| Self::from_saved(v, table))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Value::LIST(values) => Value::LIST(
+                values
+                    .into_iter()
+                    .map(|v| Self::from_saved(v, table))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            value => value,
+        })
+    }
+
+    /// Like `prune`, for blobs.
+    pub(crate) fn prune_blobs(&mut self) -> usize {
+        let before = self.blobs.len();
+        self.blobs
+            .retain(|_, blob| Arc::strong_count(blob.shared_bytes()) > 1);
+        before - self.blobs.len()
+    }
+}
+
+pub trait MosaicStrings {
+    /// The id of `s` in the string pool, if any field holds it.
+    fn string_id(&self, s: &str) -> Option<Str>;
+    fn resolve_string(&self, id: Str) -> Option<String>;
+}
+
+impl MosaicStrings for Arc<Mosaic> {
+    fn string_id(&self, s: &str) -> Option<Str> {
+        self.strings.read().unwrap().id_of(s)
+    }
+
+    fn resolve_string(&self, id: Str) -> Option<String> {
+        self.strings
+            .read()
+            .unwrap()
+            .resolve(id)
+            .map(|s| s.to_string())
+    }
+}
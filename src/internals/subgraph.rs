@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use super::{
+    component_grammar::ComponentParser, EntityId, Mosaic, MosaicCRUD, MosaicIO, Tile, TileType, S32,
+};
+
+impl Mosaic {
+    /// Registers every component type `from` knows about that this mosaic doesn't.
+    pub(crate) fn copy_component_types(&self, from: &Mosaic) -> anyhow::Result<()> {
+        let definitions = from
+            .component_registry
+            .component_definitions
+            .read()
+            .unwrap()
+            .clone();
+
+        for definition in definitions {
+            let typename: S32 = ComponentParser::type_name_of(&definition).into();
+            if !self.component_registry.has_component_type(&typename) {
+                self.component_registry
+                    .add_component_types(definition.as_str())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies `tiles` in, endpoints first, returning the old to new ids; tiles whose endpoints
+    /// are neither in `mapping` nor among `tiles` are left out.
+    pub(crate) fn copy_tiles(
+        self: &Arc<Self>,
+        tiles: Vec<Tile>,
+        mut mapping: HashMap<EntityId, EntityId>,
+    ) -> HashMap<EntityId, EntityId> {
+        let mut pending = tiles.into_iter().sorted_by_key(|t| t.id).collect_vec();
+
+        loop {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|t| {
+                [t.source_id(), t.target_id()]
+                    .iter()
+                    .all(|e| *e == t.id || mapping.contains_key(e))
+            });
+
+            if ready.is_empty() {
+                break;
+            }
+
+            for tile in ready {
+                let component = tile.component.to_string();
+                let data = tile.data();
+                let copy = match tile.tile_type {
+                    TileType::Object => self.new_object(&component, data),
+                    TileType::Arrow { source, target } => {
+                        self.new_arrow(&mapping[&source], &mapping[&target], &component, data)
+                    }
+                    TileType::Descriptor { subject } => {
+                        self.new_descriptor(&mapping[&subject], &component, data)
+                    }
+                    TileType::Extension { subject } => {
+                        self.new_extension(&mapping[&subject], &component, data)
+                    }
+                };
+
+                mapping.insert(tile.id, copy.id);
+            }
+
+            pending = waiting;
+        }
+
+        mapping
+    }
+
+    /// `tile` and every tile depending on it, through dependents `follow` accepts, in the
+    /// order they're found.
+    pub(crate) fn dependents_closure<F>(&self, tile: &Tile, follow: F) -> Vec<Tile>
+    where
+        F: Fn(&Tile) -> bool,
+    {
+        let mut seen = HashSet::from([tile.id]);
+        let mut found = vec![tile.clone()];
+        let mut next = 0;
+        while next < found.len() {
+            let dependents = self
+                .dependent_ids_map
+                .read()
+                .unwrap()
+                .get_all(&found[next].id)
+                .cloned()
+                .collect_vec();
+
+            let dependents = {
+                let registry = self.tile_registry.read().unwrap();
+                dependents
+                    .into_iter()
+                    .flat_map(|id| registry.get(&id).cloned())
+                    .collect_vec()
+            };
+            for dependent in dependents {
+                if follow(&dependent) && seen.insert(dependent.id) {
+                    found.push(dependent);
+                }
+            }
+            next += 1;
+        }
+
+        found
+    }
+
+    /// Copies `tiles` next to themselves: endpoints that aren't among them stay as they are.
+    /// Returns the old-to-new mapping of the copies.
+    fn copy_in_place(self: &Arc<Self>, tiles: Vec<Tile>) -> HashMap<EntityId, EntityId> {
+        let copied = tiles.iter().map(|t| t.id).collect::<HashSet<_>>();
+        let outside = tiles
+            .iter()
+            .flat_map(|t| [t.source_id(), t.target_id()])
+            .filter(|e| !copied.contains(e))
+            .map(|e| (e, e))
+            .collect::<HashMap<_, _>>();
+
+        let mut mapping = self.copy_tiles(tiles, outside);
+        mapping.retain(|old, _| copied.contains(old));
+        mapping
+    }
+}
+
+impl Tile {
+    /// Copies this tile onto a new id, along with its descriptors and extensions, and
+    /// theirs. An arrow copy connects the same tiles. Returns the old-to-new id mapping.
+    pub fn duplicate(&self) -> HashMap<EntityId, EntityId> {
+        let tiles = self
+            .mosaic
+            .dependents_closure(self, |t| t.is_descriptor() || t.is_extension());
+        self.mosaic.copy_in_place(tiles)
+    }
+
+    /// Like `duplicate`, but also copies the arrows going in and out, and everything depending
+    /// on those in turn. Arrows to tiles that aren't copied connect to the originals.
+    pub fn deep_copy(&self) -> HashMap<EntityId, EntityId> {
+        let tiles = self.mosaic.dependents_closure(self, |_| true);
+        self.mosaic.copy_in_place(tiles)
+    }
+}
+
+pub trait MosaicSubgraph {
+    /// Copies `tiles` into a fresh mosaic, along with every arrow, descriptor, and extension
+    /// whose endpoints all end up inside the selection. Component types come along too.
+    fn extract_subgraph(&self, tiles: &[Tile]) -> Arc<Mosaic>;
+}
+
+impl MosaicSubgraph for Arc<Mosaic> {
+    fn extract_subgraph(&self, tiles: &[Tile]) -> Arc<Mosaic> {
+        let mut selected = tiles
+            .iter()
+            .filter(|t| self.is_tile_valid(&t.id))
+            .map(|t| t.id)
+            .collect::<HashSet<_>>();
+
+        // dependents can be endpoints of other dependents, so grow the selection until it settles
+        let others = self.get_all().filter(|t| !t.is_object()).collect_vec();
+        loop {
+            let before = selected.len();
+            for tile in &others {
+                let endpoints = [tile.source_id(), tile.target_id()];
+                if endpoints
+                    .iter()
+                    .all(|e| *e == tile.id || selected.contains(e))
+                {
+                    selected.insert(tile.id);
+                }
+            }
+
+            if selected.len() == before {
+                break;
+            }
+        }
+
+        let subgraph = Mosaic::new();
+        subgraph
+            .copy_component_types(self)
+            .expect("Cannot copy component types, panicking!");
+        subgraph.copy_tiles(
+            selected
+                .into_iter()
+                .flat_map(|id| self.get(id))
+                .collect_vec(),
+            HashMap::new(),
+        );
+
+        subgraph
+    }
+}
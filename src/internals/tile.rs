@@ -1,18 +1,24 @@
-use std::{collections::HashMap, sync::Arc, vec::IntoIter};
+use std::{
+    collections::HashMap,
+    ops::Index,
+    sync::{Arc, OnceLock},
+    vec::IntoIter,
+};
 
 use anyhow::anyhow;
 use itertools::Itertools;
-use log::debug;
 
 use crate::internals::{ComponentField, ToByteArray};
 
 use super::{
-    Bytesize, ComponentType, ComponentValues, Datatype, EntityId, Mosaic, MosaicCRUD, MosaicIO,
+    Blob, BlobId, Bytesize, ComponentRegistry, ComponentType, ComponentValues, Datatype, DateTime,
+    Duration, EntityId, HistoryOperation, Mosaic, MosaicCRUD, MosaicError, MosaicIO, Str, TileRef,
     Value, S32,
 };
 use crate::internals::byte_utilities::FromByteArray;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileType {
     Object,
     Arrow { source: EntityId, target: EntityId },
@@ -20,20 +26,72 @@ pub enum TileType {
     Extension { subject: EntityId },
 }
 
+/// A field value read through `tile[...]`, kept so the reference can outlive the read.
+struct KeptValue {
+    field: S32,
+    value: Value,
+    next: OnceLock<Box<KeptValue>>,
+}
+
+/// Copies of field values handed out by indexing a tile, at most one per field, dropped when
+/// the tile is written through.
+#[derive(Default)]
+pub(crate) struct FieldCache {
+    kept: OnceLock<Box<KeptValue>>,
+}
+
+impl Clone for FieldCache {
+    fn clone(&self) -> Self {
+        FieldCache::default()
+    }
+}
+
+impl FieldCache {
+    /// The copy of `field` kept before, or else the one `read` gives, kept from now on.
+    fn keep(&self, field: S32, read: impl FnOnce() -> Value) -> &Value {
+        let mut slot = &self.kept;
+        let mut read = Some(read);
+        loop {
+            let kept = slot.get_or_init(|| {
+                Box::new(KeptValue {
+                    field,
+                    value: read.take().unwrap()(),
+                    next: OnceLock::new(),
+                })
+            });
+            if kept.field == field {
+                return &kept.value;
+            }
+            slot = &kept.next;
+        }
+    }
+
+    fn forget(&mut self) {
+        self.kept.take();
+    }
+}
+
 #[derive(Clone)]
 pub struct Tile {
     pub id: EntityId,
     pub mosaic: Arc<Mosaic>,
     pub tile_type: TileType,
     pub component: S32,
+    pub(crate) generation: u64,
+    pub(crate) fields_cache: FieldCache,
 }
 
 impl Tile {
     pub fn data(&self) -> Vec<(S32, Value)> {
-        let storage = self.mosaic.data_storage.lock().unwrap();
+        let storage = self.mosaic.data_storage.read().unwrap();
         if let Some(e) = storage.get(&self.component.to_string()) {
             if let Some(h) = e.get(&self.id) {
-                h.clone().iter().map(|(a, b)| (*a, b.clone())).collect_vec()
+                let fields = h.iter().map(|(a, b)| (*a, b.clone()));
+                if self.mosaic.is_deterministic() {
+                    fields.sorted_by_key(|(a, _)| *a).collect_vec()
+                } else {
+                    fields.collect_vec()
+                }
             } else {
                 vec![]
             }
@@ -47,51 +105,262 @@ impl Tile {
     }
 
     pub fn get(&self, index: &str) -> Value {
+        let mut is_sum = false;
         if let Some(ct) = self
             .mosaic
             .component_registry
             .component_type_map
-            .lock()
+            .read()
             .unwrap()
             .get(&self.component)
         {
+            is_sum = ct.is_sum();
             if let Some(field) = ct.get_field(index.into()) {
-                if field.datatype == Datatype::UNIT {
+                if field.datatype == Datatype::UNIT && !is_sum {
                     return Value::UNIT;
                 }
             }
         }
 
-        let storage = self.mosaic.data_storage.lock().unwrap();
-        if let Some(e) = storage.get(&self.component.to_string()) {
-            if let Some(h) = e.get(&self.id) {
-                if h.contains_key(&index.into()) {
-                    h.get(&index.into()).unwrap().clone()
-                } else {
-                    panic!(
-                        "Cannot find component {:?} in id {}",
-                        self.component.to_string(),
-                        self.id
-                    );
-                }
-            } else {
-                panic!("There is no entity with this id: {}", self.id);
-                Value::UNIT
+        // variants of a sum read as the value they hold, but only while they're active
+        if is_sum && index != "self" {
+            let (tag, value) = self.get("self").as_sum();
+            if tag.is(index) {
+                return value;
             }
+
+            panic!(
+                "Variant {} is not active in component {:?} of id {}, {} is",
+                index,
+                self.component.to_string(),
+                self.id,
+                tag
+            );
+        }
+
+        let stored = {
+            let storage = self.mosaic.data_storage.read().unwrap();
+            let Some(e) = storage.get(&self.component.to_string()) else {
+                panic!("There is no component with name: {}", self.component);
+            };
+            let Some(h) = e.get(&self.id) else {
+                panic!("There is no entity with this id: {}", self.id);
+            };
+            h.get(&index.into()).cloned()
+        };
+
+        // computed fields are read with the storage let go, as they read other fields
+        stored
+            .or_else(|| {
+                self.mosaic
+                    .computed_value(self, self.component, index.into())
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "Cannot find component {:?} in id {}",
+                    self.component.to_string(),
+                    self.id
+                )
+            })
+    }
+
+    /// The fields of this tile and their values, in the order its component declares them.
+    pub fn fields(&self) -> IntoIter<(S32, Value)> {
+        let Ok(component_type) = self
+            .mosaic
+            .component_registry
+            .get_component_type(self.component)
+        else {
+            return vec![].into_iter();
+        };
+
+        let names = if component_type.has_self_field() {
+            vec!["self".into()]
         } else {
-            panic!("There is no component with name: {}", self.component);
-            Value::UNIT
+            component_type
+                .get_fields()
+                .into_iter()
+                .map(|f| f.name)
+                .collect_vec()
+        };
+
+        let data: HashMap<S32, Value> = self.data().into_iter().collect();
+        names
+            .into_iter()
+            .filter_map(|name| data.get(&name).map(|value| (name, value.clone())))
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// The string pool id of a `str` field, so it can be compared without comparing text.
+    pub fn get_str_id(&self, index: &str) -> Option<Str> {
+        match self.get(index) {
+            Value::STR(s) => self.mosaic.strings.read().unwrap().id_of(&s),
+            _ => None,
+        }
+    }
+
+    /// The active variant of a sum component and the value it holds, `None` for other components.
+    pub fn variant(&self) -> Option<(S32, Value)> {
+        let component_type = self
+            .mosaic
+            .component_registry
+            .get_component_type(self.component)
+            .ok()?;
+
+        if component_type.is_sum() {
+            Some(self.get("self").as_sum())
+        } else {
+            None
+        }
+    }
+
+    /// Switches a sum component over to the variant `tag`, holding `value`.
+    pub fn set_variant(&mut self, tag: &str, value: Value) -> anyhow::Result<()> {
+        let component_type = self
+            .mosaic
+            .component_registry
+            .get_component_type(self.component)?;
+
+        let value = Value::sum(tag, value);
+        match component_type.get_fields().first() {
+            Some(field) if component_type.is_sum() && field.datatype.accepts(&value) => {
+                self.set_field("self", value);
+                Ok(())
+            }
+            Some(_) if component_type.is_sum() => Err(anyhow!(
+                "Sum {} has no variant {} holding {:?}",
+                component_type.name(),
+                tag,
+                value.as_sum().1
+            )),
+            _ => Err(anyhow!("Component {} is not a sum", component_type.name())),
         }
     }
 
     pub fn remove_component_data(&self) {
-        let mut storage = self.mosaic.data_storage.lock().unwrap();
+        let mut storage = self.mosaic.data_storage.write().unwrap();
         if let Some(e) = storage.get_mut(&self.component.to_string()) {
             let _ = e.remove(&self.id);
         }
     }
+
+    /// Stores data of another component on this tile, next to that of its own component,
+    /// replacing whatever was added for `component` before.
+    pub fn add_data(&self, component: &str, values: ComponentValues) -> anyhow::Result<()> {
+        let name: S32 = component.into();
+        if !self.mosaic.is_tile_valid(&self.id) {
+            return Err(anyhow!("Tile {} does not exist", self.id));
+        }
+        if name == self.component {
+            return Err(anyhow!("Tile {} is already a {}", self.id, component));
+        }
+        self.mosaic.check_writable(name)?;
+
+        let values = Tile::resolve_data_fields(&self.mosaic, name, values)?;
+        let values = {
+            let mut strings = self.mosaic.strings.write().unwrap();
+            values
+                .into_iter()
+                .map(|(field, value)| (field, strings.intern_value(value)))
+                .collect()
+        };
+
+        let before = self.get_data(component);
+        self.mosaic.indices.write().unwrap().attach(self, name);
+        self.mosaic
+            .data_storage
+            .write()
+            .unwrap()
+            .entry(component.to_string())
+            .or_default()
+            .insert(self.id, values);
+        self.mosaic.record_history(HistoryOperation::DataChanged {
+            id: self.id,
+            component: name,
+            before,
+            after: self.get_data(component),
+        });
+        Ok(())
+    }
+
+    /// Removes data added with `add_data`; returns whether there was any.
+    pub fn remove_data(&self, component: &str) -> bool {
+        let name: S32 = component.into();
+        if !self
+            .mosaic
+            .indices
+            .read()
+            .unwrap()
+            .attached_to(self.id)
+            .contains(&name)
+        {
+            return false;
+        }
+
+        let before = self.get_data(component);
+        self.mosaic.indices.write().unwrap().detach(self, name);
+        if let Some(e) = self.mosaic.data_storage.write().unwrap().get_mut(component) {
+            e.remove(&self.id);
+        }
+        self.mosaic.record_history(HistoryOperation::DataChanged {
+            id: self.id,
+            component: name,
+            before,
+            after: None,
+        });
+        true
+    }
+
+    /// The fields of `component`, whether it is the tile's own or was added with `add_data`.
+    /// For a type the tile's component extends, these are the fields it got from that type.
+    pub fn get_data(&self, component: &str) -> Option<ComponentValues> {
+        let name: S32 = component.into();
+        let registry = &self.mosaic.component_registry;
+        let components = self.components();
+        let bucket = if components.contains(&name) {
+            name
+        } else {
+            *components.iter().find(|c| registry.is_kind_of(**c, name))?
+        };
+        let inherited = (bucket != name)
+            .then(|| registry.get_component_type(name).ok())
+            .flatten()
+            .map(|t| t.get_fields().iter().map(|f| f.name).collect_vec());
+
+        let storage = self.mosaic.data_storage.read().unwrap();
+        storage
+            .get(&bucket.to_string())
+            .and_then(|e| e.get(&self.id))
+            .map(|fields| {
+                fields
+                    .iter()
+                    .filter(|(f, _)| inherited.as_ref().is_none_or(|names| names.contains(f)))
+                    .map(|(f, v)| (*f, v.clone()))
+                    .collect_vec()
+            })
+    }
+
+    /// Whether the tile has `component`, or one extending it, as its own or through `add_data`.
+    pub fn has_component(&self, component: &str) -> bool {
+        let name: S32 = component.into();
+        self.components()
+            .into_iter()
+            .any(|c| c == name || self.mosaic.component_registry.is_kind_of(c, name))
+    }
+
+    /// The tile's own component, followed by the ones added with `add_data`.
+    pub fn components(&self) -> Vec<S32> {
+        let mut components = vec![self.component];
+        components.extend(self.mosaic.indices.read().unwrap().attached_to(self.id));
+        components
+    }
 }
 
+/// The most elements taking no space a list or array read from binary data can hold, as
+/// nothing in the data itself bounds how many of them there are.
+const MAX_EMPTY_ELEMENTS: usize = 1 << 16;
+
 impl IntoIterator for Tile {
     type Item = Tile;
 
@@ -102,6 +371,16 @@ impl IntoIterator for Tile {
     }
 }
 
+/// Reads a field like `get`, but keeps the value read until something is written through
+/// this tile, so use `get` to see changes made elsewhere.
+impl Index<&str> for Tile {
+    type Output = Value;
+
+    fn index(&self, index: &str) -> &Value {
+        self.fields_cache.keep(index.into(), || self.get(index))
+    }
+}
+
 impl std::fmt::Display for Tile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mark = match self.tile_type {
@@ -175,6 +454,28 @@ impl std::fmt::Debug for Tile {
                             )
                         }
                         Datatype::COMP(_) => "".to_string(),
+                        Datatype::ARR(..) => {
+                            format!("{}: {:?}", f.name, tile.get(f_name.as_str()).as_arr())
+                        }
+                        Datatype::LIST(_) => {
+                            format!("{}: {:?}", f.name, tile.get(f_name.as_str()).as_list())
+                        }
+                        Datatype::REF => {
+                            format!("{}: {}", f.name, tile.get(f_name.as_str()).as_tile_ref())
+                        }
+                        Datatype::BLOB => {
+                            format!("{}: {}", f.name, tile.get(f_name.as_str()).as_blob())
+                        }
+                        Datatype::DATETIME => {
+                            format!("{}: {}", f.name, tile.get(f_name.as_str()).as_datetime())
+                        }
+                        Datatype::DURATION => {
+                            format!("{}: {}", f.name, tile.get(f_name.as_str()).as_duration())
+                        }
+                        Datatype::SUM(_) => {
+                            let (tag, value) = tile.get(f_name.as_str()).as_sum();
+                            format!("{}: {}({:?})", f.name, tag, value)
+                        }
                     }
                 })
                 .join(", ")
@@ -215,7 +516,7 @@ impl Eq for Tile {}
 
 impl PartialOrd for Tile {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.id.cmp(&other.id))
+        Some(self.cmp(other))
     }
 }
 
@@ -233,28 +534,69 @@ impl std::hash::Hash for Tile {
 
 impl Tile {
     pub(crate) fn set_field(&mut self, index: &str, value: Value) {
-        let mut storage = self.mosaic.data_storage.lock().unwrap();
-        if let Some(entities_by_component) = storage.get_mut(&self.component.to_string()) {
+        self.try_set_field(index, value)
+            .expect("Cannot write field, panicking!");
+    }
+
+    pub(crate) fn try_set_field(&mut self, index: &str, value: Value) -> anyhow::Result<()> {
+        // a name cut short could be that of another field, which would be overwritten
+        S32::new(index)?;
+        self.mosaic.check_writable(self.component)?;
+        self.fields_cache.forget();
+        let value = self.mosaic.strings.write().unwrap().intern_value(value);
+        let previous = {
+            let mut storage = self.mosaic.data_storage.write().unwrap();
+            // types registered directly through the component registry (e.g. when loading)
+            // don't have a storage bucket yet, so we make one on first write
+            let entities_by_component = storage.entry(self.component.to_string()).or_default();
             if let Some(entity_by_field) = entities_by_component.get_mut(&self.id) {
-                entity_by_field.insert(index.into(), value);
+                entity_by_field.insert(index.into(), value.clone())
             } else {
                 let mut hm = HashMap::new();
-                hm.insert(index.into(), value);
+                hm.insert(index.into(), value.clone());
                 entities_by_component.insert(self.id, hm);
+                None
             }
+        };
+
+        // fresh fields are part of creating the tile, only overwrites are changes
+        if let Some(before) = previous.filter(|before| *before != value) {
+            self.mosaic.record_history(HistoryOperation::FieldChanged {
+                id: self.id,
+                field: index.into(),
+                before,
+                after: value,
+            });
         }
+        Ok(())
     }
 
     pub(crate) fn create_data_fields(&mut self, defaults: ComponentValues) -> anyhow::Result<()> {
+        for (name, value) in Tile::resolve_data_fields(&self.mosaic, self.component, defaults)? {
+            self.try_set_field(&name.to_string(), value)?;
+        }
+
+        Ok(())
+    }
+
+    /// The values a new tile of `component` starts with, given the ones passed in for it.
+    pub(crate) fn resolve_data_fields(
+        mosaic: &Mosaic,
+        component: S32,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<ComponentValues> {
         let mut defaults = defaults.into_iter().collect::<HashMap<_, _>>();
 
-        let component_type = self
-            .mosaic
-            .component_registry
-            .get_component_type(self.component)?;
+        let component_type = mosaic.component_registry.get_component_type(component)?;
+
+        // fields declared with a default, or as optional, can be left out
+        let nothing_given = defaults.is_empty();
+        for (field_name, value) in mosaic.component_registry.get_field_defaults(component) {
+            defaults.entry(field_name).or_insert(value);
+        }
 
-        if defaults.is_empty() {
-            if component_type.is_alias() {
+        if nothing_given {
+            if component_type.has_self_field() {
                 defaults.insert(
                     "self".into(),
                     component_type
@@ -266,48 +608,51 @@ impl Tile {
                 );
             } else {
                 for field in component_type.get_fields() {
-                    defaults.insert(field.name, field.datatype.get_default());
+                    defaults
+                        .entry(field.name)
+                        .or_insert_with(|| field.datatype.get_default());
                 }
             }
         }
 
+        let mut fields = vec![];
         for (field_name, datatype) in component_type
             .get_fields()
             .iter()
             .map(|field| (field.name, field.datatype.to_owned()))
         {
-            let name = if component_type.is_alias() {
+            let name = if component_type.has_self_field() {
                 "self".into()
             } else {
                 field_name
             };
 
             if let Some(default_field) = defaults.get(&name) {
-                if datatype == default_field.get_datatype() {
+                if datatype.accepts(default_field) {
                     let value = defaults
                         .get(&name)
                         .cloned()
                         .unwrap_or(datatype.get_default());
 
-                    self.set_field(&name.to_string(), value);
+                    fields.push((name, value));
                 } else {
-                    return Err(anyhow!(
-                        "Expected type for field {} is {:?}, but found type {:?}",
-                        name,
-                        datatype,
-                        default_field.get_datatype()
-                    ));
+                    return Err(MosaicError::FieldTypeMismatch {
+                        field: name,
+                        expected: datatype,
+                        found: default_field.get_datatype(),
+                    }
+                    .into());
                 }
             } else {
-                return Err(anyhow!(
-                    "Missing field {} in type {}",
-                    name,
-                    component_type.name()
-                ));
+                return Err(MosaicError::MissingField {
+                    component: component_type.name().as_str().into(),
+                    field: name,
+                }
+                .into());
             }
         }
 
-        Ok(())
+        Ok(fields)
     }
 
     pub(crate) fn create_fields_from_binary_data(
@@ -319,7 +664,7 @@ impl Tile {
             .get_fields()
             .into_iter()
             .map(|f| {
-                if component.is_alias() {
+                if component.has_self_field() {
                     ("self".into(), f.datatype)
                 } else {
                     (f.name, f.datatype)
@@ -328,35 +673,25 @@ impl Tile {
             .try_fold(
                 (0usize, HashMap::<S32, Value>::new()),
                 |(ptr, mut old), (name, datatype)| {
-                    let size = datatype.bytesize(&mosaic.component_registry, &data);
-                    if data.len() >= ptr + size {
+                    // variable-length datatypes read their length from where they start
+                    let size = datatype.bytesize(&mosaic.component_registry, &data[ptr..]);
+                    if size <= data.len() - ptr {
                         let comp_data = &data[ptr..ptr + size];
-
-                        let value = match datatype {
-                            Datatype::UNIT => Value::UNIT,
-                            Datatype::I8 => Value::I8(i8::from_byte_array(comp_data)),
-                            Datatype::I16 => Value::I16(i16::from_byte_array(comp_data)),
-                            Datatype::I32 => Value::I32(i32::from_byte_array(comp_data)),
-                            Datatype::I64 => Value::I64(i64::from_byte_array(comp_data)),
-                            Datatype::U8 => Value::U8(u8::from_byte_array(comp_data)),
-                            Datatype::U16 => Value::U16(u16::from_byte_array(comp_data)),
-                            Datatype::U32 => Value::U32(u32::from_byte_array(comp_data)),
-                            Datatype::U64 => Value::U64(u64::from_byte_array(comp_data)),
-                            Datatype::F32 => Value::F32(f32::from_byte_array(comp_data)),
-                            Datatype::F64 => Value::F64(f64::from_byte_array(comp_data)),
-                            Datatype::S32 => Value::S32(S32::from_byte_array(comp_data)),
-                            Datatype::STR => Value::STR(String::from_byte_array(comp_data)),
-                            Datatype::BOOL => Value::BOOL(bool::from_byte_array(comp_data)),
-                            Datatype::COMP(_) => panic!("Unreachable"),
-                        };
+                        let value = Self::value_from_binary_data(
+                            &mosaic.component_registry,
+                            &datatype,
+                            comp_data,
+                        )?;
+                        let value = mosaic.strings.read().unwrap().resolve_blobs(value)?;
 
                         old.insert(name, value);
                         Ok((ptr + size, old))
                     } else {
-                        Err(anyhow!(
-                            "Wrong data layout in component {:?} with field {} -- maybe it changed recently?",
-                            component.name(), name,
-                        ))
+                        Err(MosaicError::DataLayout {
+                            component: component.name().as_str().into(),
+                            field: name,
+                        }
+                        .into())
                     }
                 },
             );
@@ -364,15 +699,130 @@ impl Tile {
         result.map(|(_, fields)| fields)
     }
 
+    fn value_from_binary_data(
+        registry: &ComponentRegistry,
+        datatype: &Datatype,
+        data: &[u8],
+    ) -> anyhow::Result<Value> {
+        let value = match datatype {
+            Datatype::UNIT => Value::UNIT,
+            Datatype::I8 => Value::I8(i8::from_byte_array(data)),
+            Datatype::I16 => Value::I16(i16::from_byte_array(data)),
+            Datatype::I32 => Value::I32(i32::from_byte_array(data)),
+            Datatype::I64 => Value::I64(i64::from_byte_array(data)),
+            Datatype::U8 => Value::U8(u8::from_byte_array(data)),
+            Datatype::U16 => Value::U16(u16::from_byte_array(data)),
+            Datatype::U32 => Value::U32(u32::from_byte_array(data)),
+            Datatype::U64 => Value::U64(u64::from_byte_array(data)),
+            Datatype::F32 => Value::F32(f32::from_byte_array(data)),
+            Datatype::F64 => Value::F64(f64::from_byte_array(data)),
+            Datatype::S32 if std::str::from_utf8(data).is_err() => {
+                return Err(anyhow!("s32 data is not utf-8"));
+            }
+            Datatype::STR if std::str::from_utf8(&data[8..]).is_err() => {
+                return Err(anyhow!("str data is not utf-8"));
+            }
+            Datatype::S32 => Value::S32(S32::from_byte_array(data)),
+            Datatype::STR => Value::STR(String::from_byte_array(data).into()),
+            Datatype::BOOL => Value::BOOL(bool::from_byte_array(data)),
+            Datatype::REF => Value::REF(TileRef::from_byte_array(data)),
+            Datatype::BLOB => Value::BLOB(Blob::unresolved(BlobId::from_byte_array(data))),
+            Datatype::DATETIME => Value::DATETIME(DateTime::from_byte_array(data)),
+            Datatype::DURATION => Value::DURATION(Duration::from_byte_array(data)),
+            Datatype::COMP(_) => panic!("Unreachable"),
+            Datatype::SUM(_) if std::str::from_utf8(&data[0..32]).is_err() => {
+                return Err(anyhow!("Sum variant tag is not utf-8"));
+            }
+            Datatype::SUM(variants) => {
+                let tag = S32::from_byte_array(&data[0..32]);
+                let variant = variants
+                    .iter()
+                    .find(|v| v.name == tag)
+                    .ok_or_else(|| anyhow!("Unknown variant {} in sum data", tag))?;
+                Value::SUM(
+                    tag,
+                    Box::new(Self::value_from_binary_data(
+                        registry,
+                        &variant.datatype,
+                        &data[32..],
+                    )?),
+                )
+            }
+            Datatype::ARR(element, size) => Value::ARR(Self::values_from_binary_data(
+                registry, element, data, *size,
+            )?),
+            Datatype::LIST(element) => {
+                let count = u64::from_byte_array(&data[0..8]) as usize;
+                Value::LIST(Self::values_from_binary_data(
+                    registry,
+                    element,
+                    &data[8..],
+                    count,
+                )?)
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn values_from_binary_data(
+        registry: &ComponentRegistry,
+        element: &Datatype,
+        data: &[u8],
+        count: usize,
+    ) -> anyhow::Result<Vec<Value>> {
+        // checked up front, so a count read from bad data can't make us allocate for it
+        let fits = match element.bytesize(registry, &[]) {
+            0 => count <= MAX_EMPTY_ELEMENTS,
+            smallest => count <= data.len() / smallest,
+        };
+        if !fits {
+            return Err(anyhow!(
+                "Not enough data for {} {:?} elements",
+                count,
+                element
+            ));
+        }
+
+        let mut ptr = 0usize;
+        let mut values = vec![];
+        for _ in 0..count {
+            let size = element.bytesize(registry, &data[ptr..]);
+            if size > data.len() - ptr {
+                return Err(anyhow!(
+                    "Not enough data for {} {:?} elements",
+                    count,
+                    element
+                ));
+            }
+
+            values.push(Self::value_from_binary_data(
+                registry,
+                element,
+                &data[ptr..ptr + size],
+            )?);
+            ptr += size;
+        }
+
+        Ok(values)
+    }
+
     pub(crate) fn create_binary_data_from_fields(&self, component: &ComponentType) -> Vec<u8> {
+        Tile::create_binary_data(component, |field| self.get(field))
+    }
+
+    pub(crate) fn create_binary_data<F>(component: &ComponentType, get: F) -> Vec<u8>
+    where
+        F: Fn(&str) -> Value,
+    {
         component
             .get_fields()
             .into_iter()
             .map(|f| {
-                if component.is_alias() {
-                    ("self".into(), self.get("self"))
+                if component.has_self_field() {
+                    ("self".into(), get("self"))
                 } else {
-                    (f.name, self.get(&f.name.to_string()))
+                    (f.name, get(&f.name.to_string()))
                 }
             })
             .fold(vec![], |old: Vec<u8>, (_, value)| {
@@ -393,8 +843,15 @@ impl Tile {
                     Value::F32(x) => x.to_byte_array(),
                     Value::F64(x) => x.to_byte_array(),
                     Value::S32(x) => x.to_byte_array(),
-                    Value::STR(x) => x.to_byte_array(),
+                    Value::STR(x) => x.to_string().to_byte_array(),
                     Value::BOOL(x) => x.to_byte_array(),
+                    Value::REF(x) => x.to_byte_array(),
+                    Value::BLOB(x) => x.id().to_byte_array(),
+                    Value::DATETIME(x) => x.to_byte_array(),
+                    Value::DURATION(x) => x.to_byte_array(),
+                    nested @ (Value::SUM(..) | Value::ARR(_) | Value::LIST(_)) => {
+                        nested.to_byte_array()
+                    }
                 };
                 temp.extend(value_bytes);
                 temp
@@ -414,22 +871,70 @@ impl Tile {
         component: S32,
         fields: ComponentValues,
     ) -> Tile {
+        Tile::try_new(mosaic, id, tile_type, component, fields)
+            .expect("Cannot create data fields, panicking!")
+    }
+
+    /// Like `new`, but fails without adding anything if `fields` don't fit `component`.
+    pub(crate) fn try_new(
+        mosaic: Arc<Mosaic>,
+        id: EntityId,
+        tile_type: TileType,
+        component: S32,
+        fields: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        let component = mosaic.component_registry.resolve_name(component);
+        let fields = Tile::resolve_data_fields(&mosaic, component, fields)?;
+        if !fields.is_empty() {
+            mosaic.check_writable(component)?;
+        }
         let mut tile = Tile {
             id,
             mosaic: Arc::clone(&mosaic),
             tile_type,
             component,
+            generation: mosaic.next_generation(),
+            fields_cache: FieldCache::default(),
         };
 
-        tile.create_data_fields(fields)
-            .expect("Cannot create data fields, panicking!");
+        // garbage collection would take anything written before the tile is registered as
+        // belonging to no tile
+        let creating = mosaic.creating.read().unwrap();
+        match tile_type {
+            TileType::Object => {}
+            TileType::Arrow { source, target } => {
+                let mut dependents = mosaic.dependent_ids_map.write().unwrap();
+                dependents.append(source, id);
+                dependents.append(target, id);
+            }
+            TileType::Descriptor { subject } | TileType::Extension { subject } => {
+                mosaic
+                    .dependent_ids_map
+                    .write()
+                    .unwrap()
+                    .append(subject, id);
+            }
+        }
+        for (name, value) in fields {
+            tile.set_field(&name.to_string(), value);
+        }
 
         mosaic
             .tile_registry
-            .lock()
+            .write()
             .unwrap()
             .insert(id, tile.clone());
-        tile
+        mosaic.indices.write().unwrap().insert(&tile);
+        drop(creating);
+
+        mosaic.record_history(HistoryOperation::Created {
+            id,
+            generation: tile.generation,
+            tile_type,
+            component,
+            fields: tile.data(),
+        });
+        Ok(tile)
     }
 
     pub fn source(&self) -> Tile {
@@ -1,84 +1,122 @@
-use super::{Tile, ToByteArray, Value, S32};
+use super::{Blob, DateTime, Duration, Tile, TileRef, ToByteArray, Value, S32};
 
 pub trait TileFieldSetter<T: ToByteArray> {
-    fn set(&mut self, index: &str, value: T);
+    /// Like `try_set`, but panics if the field can't be written.
+    fn set(&mut self, index: &str, value: T) {
+        self.try_set(index, value)
+            .expect("Cannot write field, panicking!")
+    }
+
+    /// Writes a field, failing if its name is too long to be one, or the tile is read-only.
+    fn try_set(&mut self, index: &str, value: T) -> anyhow::Result<()>;
 }
 
 impl TileFieldSetter<i8> for Tile {
-    fn set(&mut self, index: &str, value: i8) {
-        self.set_field(index, Value::I8(value))
+    fn try_set(&mut self, index: &str, value: i8) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::I8(value))
     }
 }
 
 impl TileFieldSetter<i16> for Tile {
-    fn set(&mut self, index: &str, value: i16) {
-        self.set_field(index, Value::I16(value))
+    fn try_set(&mut self, index: &str, value: i16) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::I16(value))
     }
 }
 
 impl TileFieldSetter<i32> for Tile {
-    fn set(&mut self, index: &str, value: i32) {
-        self.set_field(index, Value::I32(value))
+    fn try_set(&mut self, index: &str, value: i32) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::I32(value))
     }
 }
 
 impl TileFieldSetter<i64> for Tile {
-    fn set(&mut self, index: &str, value: i64) {
-        self.set_field(index, Value::I64(value))
+    fn try_set(&mut self, index: &str, value: i64) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::I64(value))
     }
 }
 
 impl TileFieldSetter<u8> for Tile {
-    fn set(&mut self, index: &str, value: u8) {
-        self.set_field(index, Value::U8(value))
+    fn try_set(&mut self, index: &str, value: u8) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::U8(value))
     }
 }
 
 impl TileFieldSetter<u16> for Tile {
-    fn set(&mut self, index: &str, value: u16) {
-        self.set_field(index, Value::U16(value))
+    fn try_set(&mut self, index: &str, value: u16) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::U16(value))
     }
 }
 
 impl TileFieldSetter<u32> for Tile {
-    fn set(&mut self, index: &str, value: u32) {
-        self.set_field(index, Value::U32(value))
+    fn try_set(&mut self, index: &str, value: u32) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::U32(value))
     }
 }
 
 impl TileFieldSetter<u64> for Tile {
-    fn set(&mut self, index: &str, value: u64) {
-        self.set_field(index, Value::U64(value))
+    fn try_set(&mut self, index: &str, value: u64) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::U64(value))
     }
 }
 
 impl TileFieldSetter<f32> for Tile {
-    fn set(&mut self, index: &str, value: f32) {
-        self.set_field(index, Value::F32(value))
+    fn try_set(&mut self, index: &str, value: f32) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::F32(value))
     }
 }
 
 impl TileFieldSetter<f64> for Tile {
-    fn set(&mut self, index: &str, value: f64) {
-        self.set_field(index, Value::F64(value))
+    fn try_set(&mut self, index: &str, value: f64) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::F64(value))
     }
 }
 
 impl TileFieldSetter<S32> for Tile {
-    fn set(&mut self, index: &str, value: S32) {
-        self.set_field(index, Value::S32(value))
+    fn try_set(&mut self, index: &str, value: S32) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::S32(value))
     }
 }
 
 impl TileFieldSetter<String> for Tile {
-    fn set(&mut self, index: &str, value: String) {
-        self.set_field(index, Value::STR(value))
+    fn try_set(&mut self, index: &str, value: String) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::STR(value.into()))
     }
 }
 
 impl TileFieldSetter<bool> for Tile {
-    fn set(&mut self, index: &str, value: bool) {
-        self.set_field(index, Value::BOOL(value))
+    fn try_set(&mut self, index: &str, value: bool) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::BOOL(value))
+    }
+}
+
+impl TileFieldSetter<TileRef> for Tile {
+    fn try_set(&mut self, index: &str, value: TileRef) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::REF(value))
+    }
+}
+
+impl TileFieldSetter<Blob> for Tile {
+    fn try_set(&mut self, index: &str, value: Blob) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::BLOB(value))
+    }
+}
+
+impl TileFieldSetter<DateTime> for Tile {
+    fn try_set(&mut self, index: &str, value: DateTime) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::DATETIME(value))
+    }
+}
+
+impl TileFieldSetter<Duration> for Tile {
+    fn try_set(&mut self, index: &str, value: Duration) -> anyhow::Result<()> {
+        self.try_set_field(index, Value::DURATION(value))
+    }
+}
+
+/// Sets a field to an already built value, e.g. an array, a list, or a sum variant.
+impl TileFieldSetter<Value> for Tile {
+    fn try_set(&mut self, index: &str, value: Value) -> anyhow::Result<()> {
+        self.try_set_field(index, value)
     }
 }
 
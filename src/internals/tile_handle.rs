@@ -0,0 +1,139 @@
+use atomic_counter::AtomicCounter;
+use std::sync::Arc;
+
+use super::{ComponentValues, EntityId, Mosaic, MosaicCRUD, MosaicError, MosaicIO, Tile};
+
+/// A tile id together with its generation, which goes stale once the tile is gone rather
+/// than pointing to whatever tile gets the id next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileHandle {
+    pub index: EntityId,
+    pub generation: u64,
+}
+
+impl Mosaic {
+    pub(crate) fn next_generation(&self) -> u64 {
+        self.generations.inc() as u64
+    }
+
+    /// Gives the tile under `id` back the generation it had, for undoing its deletion.
+    pub(crate) fn set_generation(&self, id: EntityId, generation: u64) {
+        if let Some(tile) = self.tile_registry.write().unwrap().get_mut(&id) {
+            tile.generation = generation;
+        }
+    }
+
+    /// The generation of the tile currently under `id`, if there is one.
+    pub(crate) fn generation_of(&self, id: EntityId) -> Option<u64> {
+        self.tile_registry
+            .read()
+            .unwrap()
+            .get(&id)
+            .map(|tile| tile.generation)
+    }
+}
+
+impl Tile {
+    /// A handle to this tile, which goes stale once it is deleted, like the tile itself.
+    pub fn handle(&self) -> TileHandle {
+        TileHandle {
+            index: self.id,
+            generation: self.generation,
+        }
+    }
+}
+
+pub trait MosaicHandles {
+    /// A handle to the tile currently under `id`, if there is one.
+    fn handle_of(&self, id: EntityId) -> Option<TileHandle>;
+    /// Like `get`, but `None` once the tile the handle was taken for is gone, even if its id
+    /// has been handed out again since.
+    fn get_by_handle(&self, handle: &TileHandle) -> Option<Tile>;
+}
+
+impl MosaicHandles for Arc<Mosaic> {
+    fn handle_of(&self, id: EntityId) -> Option<TileHandle> {
+        self.get(id).map(|tile| tile.handle())
+    }
+
+    fn get_by_handle(&self, handle: &TileHandle) -> Option<Tile> {
+        self.get(handle.index)
+            .filter(|tile| tile.generation == handle.generation)
+    }
+}
+
+fn live(mosaic: &Arc<Mosaic>, handle: &TileHandle) -> anyhow::Result<EntityId> {
+    match mosaic.get_by_handle(handle) {
+        Some(tile) => Ok(tile.id),
+        None => Err(MosaicError::InvalidTile(handle.index).into()),
+    }
+}
+
+impl MosaicCRUD<TileHandle> for Arc<Mosaic> {
+    fn is_tile_valid(&self, i: &TileHandle) -> bool {
+        self.get_by_handle(i).is_some()
+    }
+
+    fn new_arrow(
+        &self,
+        source: &TileHandle,
+        target: &TileHandle,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        self.try_new_arrow(source, target, component, defaults)
+            .expect("Cannot create arrow, panicking!")
+    }
+
+    fn new_descriptor(
+        &self,
+        subject: &TileHandle,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        self.try_new_descriptor(subject, component, defaults)
+            .expect("Cannot create descriptor, panicking!")
+    }
+
+    fn try_new_arrow(
+        &self,
+        source: &TileHandle,
+        target: &TileHandle,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        let (source, target) = (live(self, source)?, live(self, target)?);
+        <Arc<Mosaic> as MosaicCRUD<EntityId>>::try_new_arrow(
+            self, &source, &target, component, defaults,
+        )
+    }
+
+    fn try_new_descriptor(
+        &self,
+        subject: &TileHandle,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        let subject = live(self, subject)?;
+        <Arc<Mosaic> as MosaicCRUD<EntityId>>::try_new_descriptor(
+            self, &subject, component, defaults,
+        )
+    }
+
+    fn new_extension(
+        &self,
+        subject: &TileHandle,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        let subject = live(self, subject).expect("Cannot create extension, panicking!");
+        <Arc<Mosaic> as MosaicCRUD<EntityId>>::new_extension(self, &subject, component, defaults)
+    }
+
+    /// Does nothing for stale handles, so a newer tile under the same id is left alone.
+    fn delete_tile(&self, tile: TileHandle) {
+        if let Ok(id) = live(self, &tile) {
+            <Arc<Mosaic> as MosaicCRUD<EntityId>>::delete_tile(self, id);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+    sync::Arc,
+    vec::IntoIter,
+};
+
+use itertools::Itertools;
+
+use super::{EntityId, Mosaic, Tile, TileGetById, S32};
+
+type IdSet = BTreeSet<EntityId>;
+
+/// Secondary lookups kept next to the tile registry. Objects are only indexed by component;
+/// every other tile is also indexed by whichever of its endpoints is not the tile itself.
+#[derive(Debug, Default)]
+pub(crate) struct TileIndices {
+    by_component: HashMap<S32, IdSet>,
+    by_source: HashMap<EntityId, IdSet>,
+    by_target: HashMap<EntityId, IdSet>,
+    by_source_component: HashMap<(EntityId, S32), IdSet>,
+    by_target_component: HashMap<(EntityId, S32), IdSet>,
+    arrows_by_endpoints: HashMap<(EntityId, EntityId), IdSet>,
+    arrows_by_endpoints_component: HashMap<(EntityId, EntityId, S32), IdSet>,
+    /// Components added to a tile with `Tile::add_data`, besides the one it was made with.
+    attached: HashMap<EntityId, BTreeSet<S32>>,
+}
+
+fn insert_into<K: Hash + Eq>(index: &mut HashMap<K, IdSet>, key: K, id: EntityId) {
+    index.entry(key).or_default().insert(id);
+}
+
+fn remove_from<K: Hash + Eq>(index: &mut HashMap<K, IdSet>, key: K, id: EntityId) {
+    if let Some(ids) = index.get_mut(&key) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+fn ids_in<K: Hash + Eq>(index: &HashMap<K, IdSet>, key: &K) -> Vec<EntityId> {
+    index
+        .get(key)
+        .map(|ids| ids.iter().copied().collect_vec())
+        .unwrap_or_default()
+}
+
+impl TileIndices {
+    pub(crate) fn insert(&mut self, tile: &Tile) {
+        let (id, component) = (tile.id, tile.component);
+        let (source, target) = (tile.source_id(), tile.target_id());
+
+        insert_into(&mut self.by_component, component, id);
+        if source != id {
+            insert_into(&mut self.by_source, source, id);
+            insert_into(&mut self.by_source_component, (source, component), id);
+        }
+        if target != id {
+            insert_into(&mut self.by_target, target, id);
+            insert_into(&mut self.by_target_component, (target, component), id);
+        }
+        if tile.is_arrow() {
+            insert_into(&mut self.arrows_by_endpoints, (source, target), id);
+            insert_into(
+                &mut self.arrows_by_endpoints_component,
+                (source, target, component),
+                id,
+            );
+        }
+    }
+
+    pub(crate) fn remove(&mut self, tile: &Tile) {
+        for component in self.attached_to(tile.id) {
+            self.detach(tile, component);
+        }
+
+        let (id, component) = (tile.id, tile.component);
+        let (source, target) = (tile.source_id(), tile.target_id());
+
+        remove_from(&mut self.by_component, component, id);
+        if source != id {
+            remove_from(&mut self.by_source, source, id);
+            remove_from(&mut self.by_source_component, (source, component), id);
+        }
+        if target != id {
+            remove_from(&mut self.by_target, target, id);
+            remove_from(&mut self.by_target_component, (target, component), id);
+        }
+        if tile.is_arrow() {
+            remove_from(&mut self.arrows_by_endpoints, (source, target), id);
+            remove_from(
+                &mut self.arrows_by_endpoints_component,
+                (source, target, component),
+                id,
+            );
+        }
+    }
+
+    /// Indexes `tile` under `component` as well, as if it had been made with it.
+    pub(crate) fn attach(&mut self, tile: &Tile, component: S32) {
+        if !self.attached.entry(tile.id).or_default().insert(component) {
+            return;
+        }
+
+        let (id, source, target) = (tile.id, tile.source_id(), tile.target_id());
+        insert_into(&mut self.by_component, component, id);
+        if source != id {
+            insert_into(&mut self.by_source_component, (source, component), id);
+        }
+        if target != id {
+            insert_into(&mut self.by_target_component, (target, component), id);
+        }
+        if tile.is_arrow() {
+            insert_into(
+                &mut self.arrows_by_endpoints_component,
+                (source, target, component),
+                id,
+            );
+        }
+    }
+
+    pub(crate) fn detach(&mut self, tile: &Tile, component: S32) {
+        let id = tile.id;
+        match self.attached.get_mut(&id) {
+            Some(components) => {
+                if !components.remove(&component) {
+                    return;
+                }
+                if components.is_empty() {
+                    self.attached.remove(&id);
+                }
+            }
+            None => return,
+        }
+
+        let (source, target) = (tile.source_id(), tile.target_id());
+        remove_from(&mut self.by_component, component, id);
+        if source != id {
+            remove_from(&mut self.by_source_component, (source, component), id);
+        }
+        if target != id {
+            remove_from(&mut self.by_target_component, (target, component), id);
+        }
+        if tile.is_arrow() {
+            remove_from(
+                &mut self.arrows_by_endpoints_component,
+                (source, target, component),
+                id,
+            );
+        }
+    }
+
+    pub(crate) fn attached_to(&self, id: EntityId) -> Vec<S32> {
+        self.attached
+            .get(&id)
+            .map(|components| components.iter().copied().collect_vec())
+            .unwrap_or_default()
+    }
+
+    /// How many ids are filed across all the indices.
+    pub(crate) fn len(&self) -> usize {
+        fn count<K>(index: &HashMap<K, IdSet>) -> usize {
+            index.values().map(BTreeSet::len).sum()
+        }
+
+        count(&self.by_component)
+            + count(&self.by_source)
+            + count(&self.by_target)
+            + count(&self.by_source_component)
+            + count(&self.by_target_component)
+            + count(&self.arrows_by_endpoints)
+            + count(&self.arrows_by_endpoints_component)
+            + self.attached.values().map(BTreeSet::len).sum::<usize>()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = TileIndices::default();
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.by_component.shrink_to_fit();
+        self.by_source.shrink_to_fit();
+        self.by_target.shrink_to_fit();
+        self.by_source_component.shrink_to_fit();
+        self.by_target_component.shrink_to_fit();
+        self.arrows_by_endpoints.shrink_to_fit();
+        self.arrows_by_endpoints_component.shrink_to_fit();
+        self.attached.shrink_to_fit();
+    }
+
+    pub(crate) fn with_component(&self, component: S32) -> Vec<EntityId> {
+        ids_in(&self.by_component, &component)
+    }
+
+    pub(crate) fn with_source(&self, source: EntityId) -> Vec<EntityId> {
+        ids_in(&self.by_source, &source)
+    }
+
+    pub(crate) fn with_target(&self, target: EntityId) -> Vec<EntityId> {
+        ids_in(&self.by_target, &target)
+    }
+
+    pub(crate) fn with_source_and_component(
+        &self,
+        source: EntityId,
+        component: S32,
+    ) -> Vec<EntityId> {
+        ids_in(&self.by_source_component, &(source, component))
+    }
+
+    pub(crate) fn with_target_and_component(
+        &self,
+        target: EntityId,
+        component: S32,
+    ) -> Vec<EntityId> {
+        ids_in(&self.by_target_component, &(target, component))
+    }
+
+    pub(crate) fn arrows_between(&self, source: EntityId, target: EntityId) -> Vec<EntityId> {
+        ids_in(&self.arrows_by_endpoints, &(source, target))
+    }
+
+    pub(crate) fn arrows_between_with(
+        &self,
+        source: EntityId,
+        target: EntityId,
+        component: S32,
+    ) -> Vec<EntityId> {
+        ids_in(
+            &self.arrows_by_endpoints_component,
+            &(source, target, component),
+        )
+    }
+}
+
+/// Index-backed lookups; all of them return tiles in id order.
+pub trait MosaicIndices {
+    fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile>;
+    /// Arrows leaving `source` and extensions of `source`.
+    fn get_tiles_from(&self, source: EntityId) -> IntoIter<Tile>;
+    /// Arrows entering `target` and descriptors of `target`.
+    fn get_tiles_into(&self, target: EntityId) -> IntoIter<Tile>;
+    fn get_tiles_from_with(&self, source: EntityId, component: &str) -> IntoIter<Tile>;
+    fn get_tiles_into_with(&self, target: EntityId, component: &str) -> IntoIter<Tile>;
+}
+
+impl MosaicIndices for Arc<Mosaic> {
+    fn get_tiles_with_component(&self, component: &str) -> IntoIter<Tile> {
+        let kinds = self.component_registry.kinds_of(component.into());
+        let indices = self.indices.read().unwrap();
+        let ids = kinds
+            .into_iter()
+            .flat_map(|kind| indices.with_component(kind))
+            .sorted()
+            .collect_vec();
+        drop(indices);
+        self.get_tiles(ids)
+    }
+
+    fn get_tiles_from(&self, source: EntityId) -> IntoIter<Tile> {
+        let ids = self.indices.read().unwrap().with_source(source);
+        self.get_tiles(ids)
+    }
+
+    fn get_tiles_into(&self, target: EntityId) -> IntoIter<Tile> {
+        let ids = self.indices.read().unwrap().with_target(target);
+        self.get_tiles(ids)
+    }
+
+    fn get_tiles_from_with(&self, source: EntityId, component: &str) -> IntoIter<Tile> {
+        let kinds = self.component_registry.kinds_of(component.into());
+        let indices = self.indices.read().unwrap();
+        let ids = kinds
+            .into_iter()
+            .flat_map(|kind| indices.with_source_and_component(source, kind))
+            .sorted()
+            .collect_vec();
+        drop(indices);
+        self.get_tiles(ids)
+    }
+
+    fn get_tiles_into_with(&self, target: EntityId, component: &str) -> IntoIter<Tile> {
+        let kinds = self.component_registry.kinds_of(component.into());
+        let indices = self.indices.read().unwrap();
+        let ids = kinds
+            .into_iter()
+            .flat_map(|kind| indices.with_target_and_component(target, kind))
+            .sorted()
+            .collect_vec();
+        drop(indices);
+        self.get_tiles(ids)
+    }
+}
@@ -0,0 +1,125 @@
+use std::{fmt::Display, sync::Arc};
+
+use itertools::Itertools;
+
+use super::{EntityId, FromByteArray, Mosaic, MosaicIO, Tile, ToByteArray, Uuid, Value, S32};
+
+/// A link to a tile that can live in another mosaic, found by the mosaic's uuid whenever it
+/// is followed, so it keeps nothing alive.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileRef {
+    pub mosaic: Uuid,
+    pub id: EntityId,
+}
+
+impl TileRef {
+    /// Points nowhere; `ref` fields start out like this.
+    pub const NONE: TileRef = TileRef {
+        mosaic: Uuid::nil(),
+        id: usize::MAX,
+    };
+
+    pub fn to(tile: &Tile) -> TileRef {
+        TileRef {
+            mosaic: tile.mosaic.uuid(),
+            id: tile.id,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == TileRef::NONE
+    }
+
+    /// The tile this points to, if both it and its mosaic are still around.
+    pub fn resolve(&self) -> Option<Tile> {
+        Mosaic::find(self.mosaic)?.get(self.id)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.resolve().is_some()
+    }
+}
+
+impl Default for TileRef {
+    fn default() -> Self {
+        TileRef::NONE
+    }
+}
+
+impl Display for TileRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_none() {
+            f.write_str("none")
+        } else {
+            f.write_fmt(format_args!("{}#{}", self.mosaic, self.id))
+        }
+    }
+}
+
+impl ToByteArray for TileRef {
+    fn to_byte_array(&self) -> Vec<u8> {
+        let mut v = self.mosaic.as_bytes().to_vec();
+        v.extend((self.id as u64).to_byte_array());
+        v
+    }
+}
+
+impl FromByteArray for TileRef {
+    fn from_byte_array(data: &[u8]) -> Self {
+        TileRef {
+            mosaic: Uuid::from_slice(&data[0..16]).unwrap(),
+            id: u64::from_byte_array(&data[16..24]) as usize,
+        }
+    }
+}
+
+impl Tile {
+    pub fn to_ref(&self) -> TileRef {
+        TileRef::to(self)
+    }
+
+    /// Follows the `ref` field `index` to the tile it points to, which may be in another
+    /// mosaic; `None` if it points nowhere or the tile is gone.
+    pub fn follow(&self, index: &str) -> Option<Tile> {
+        self.get(index).as_tile_ref().resolve()
+    }
+}
+
+fn refs_in(value: &Value, refs: &mut Vec<TileRef>) {
+    match value {
+        Value::REF(r) => refs.push(*r),
+        Value::SUM(_, inner) => refs_in(inner, refs),
+        Value::ARR(values) | Value::LIST(values) => values.iter().for_each(|v| refs_in(v, refs)),
+        _ => {}
+    }
+}
+
+pub trait MosaicReferences {
+    /// The `ref` fields in this mosaic that point to tiles which are gone, or to mosaics that
+    /// are, as (tile, field). References that were never set don't count.
+    fn dangling_refs(&self) -> Vec<(EntityId, S32)>;
+}
+
+impl MosaicReferences for Arc<Mosaic> {
+    fn dangling_refs(&self) -> Vec<(EntityId, S32)> {
+        // collected first, as following a reference can lock this mosaic again
+        let mut fields = vec![];
+        for entities in self.data_storage.read().unwrap().values() {
+            for (id, values) in entities {
+                for (field, value) in values {
+                    let mut refs = vec![];
+                    refs_in(value, &mut refs);
+                    fields.push((*id, *field, refs));
+                }
+            }
+        }
+
+        fields
+            .into_iter()
+            .filter(|(_, _, refs)| refs.iter().any(|r| !r.is_none() && !r.is_valid()))
+            .map(|(id, field, _)| (id, field))
+            .sorted()
+            .collect_vec()
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::HashSet,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+
+use super::{HistoryStep, Mosaic};
+
+pub trait MosaicTransaction {
+    /// Runs `body`, putting back every change it made if it returns an error or panics.
+    fn transaction<T, F>(&self, body: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&Arc<Mosaic>) -> anyhow::Result<T>;
+}
+
+impl MosaicTransaction for Arc<Mosaic> {
+    fn transaction<T, F>(&self, body: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&Arc<Mosaic>) -> anyhow::Result<T>,
+    {
+        self.history.lock().unwrap().transactions.push(vec![]);
+
+        let result = match catch_unwind(AssertUnwindSafe(|| body(self))) {
+            Ok(result) => result,
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_default();
+                Err(anyhow!("Transaction panicked: {}", message))
+            }
+        };
+
+        let operations = {
+            let mut history = self.history.lock().unwrap();
+            let operations = history.transactions.pop().unwrap_or_default();
+            if result.is_err() {
+                history.replaying = true;
+            }
+            operations
+        };
+
+        if result.is_err() {
+            operations
+                .iter()
+                .rev()
+                .for_each(|operation| self.revert_history_operation(operation));
+            self.history.lock().unwrap().replaying = false;
+            let step = HistoryStep {
+                name: "transaction".to_string(),
+                operations,
+            };
+            self.release_strings(HashSet::new(), vec![step]);
+            return result;
+        }
+
+        let dropped = {
+            let mut history = self.history.lock().unwrap();
+            if let Some(outer) = history.transactions.last_mut() {
+                outer.extend(operations);
+            } else if history.is_recording() && !operations.is_empty() {
+                let redone = std::mem::take(&mut history.redo_stack);
+                history.dropped.extend(redone);
+                if let Some(step) = history.open_step.as_mut() {
+                    step.operations.extend(operations);
+                } else {
+                    history.push_step(HistoryStep {
+                        name: "transaction".to_string(),
+                        operations,
+                    });
+                }
+            } else {
+                history.dropped.push(HistoryStep {
+                    name: "transaction".to_string(),
+                    operations,
+                });
+            }
+            std::mem::take(&mut history.dropped)
+        };
+        self.release_strings(HashSet::new(), dropped);
+
+        result
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use super::{
+    Blob, ComponentValues, DateTime, Duration, Logging, Mosaic, MosaicIO, MosaicTypelevelCRUD,
+    Tile, TileRef, Value, S32,
+};
+
+pub type TypedResult<T> = anyhow::Result<T>;
+
+/// A Rust type that can be stored in a single component field.
+pub trait ComponentFieldType: Sized {
+    const DATATYPE: &'static str;
+    fn to_value(&self) -> Value;
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_component_field_type {
+    ($ty:ty, $datatype:literal, $variant:ident) => {
+        impl ComponentFieldType for $ty {
+            const DATATYPE: &'static str = $datatype;
+
+            fn to_value(&self) -> Value {
+                Value::$variant(self.clone())
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(v.clone()),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_component_field_type!(i8, "i8", I8);
+impl_component_field_type!(i16, "i16", I16);
+impl_component_field_type!(i32, "i32", I32);
+impl_component_field_type!(i64, "i64", I64);
+impl_component_field_type!(u8, "u8", U8);
+impl_component_field_type!(u16, "u16", U16);
+impl_component_field_type!(u32, "u32", U32);
+impl_component_field_type!(u64, "u64", U64);
+impl_component_field_type!(f32, "f32", F32);
+impl_component_field_type!(f64, "f64", F64);
+impl_component_field_type!(bool, "bool", BOOL);
+impl_component_field_type!(S32, "s32", S32);
+impl_component_field_type!(TileRef, "ref", REF);
+impl_component_field_type!(Blob, "blob", BLOB);
+impl_component_field_type!(DateTime, "datetime", DATETIME);
+impl_component_field_type!(Duration, "duration", DURATION);
+
+impl ComponentFieldType for String {
+    const DATATYPE: &'static str = "str";
+
+    fn to_value(&self) -> Value {
+        Value::STR(self.as_str().into())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::STR(v) => Some(v.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// A Rust struct mapped onto a component definition, usually through `mosaic_component!`.
+pub trait TypedComponent: Sized {
+    fn name() -> &'static str;
+    fn definition() -> String;
+    fn to_values(&self) -> ComponentValues;
+    fn from_values(values: &ComponentValues) -> TypedResult<Self>;
+}
+
+/// Looks up a single field in a list of component values, checking its type on the way.
+pub fn typed_field<T: ComponentFieldType>(
+    values: &ComponentValues,
+    component: &str,
+    field: &str,
+) -> TypedResult<T> {
+    let name: S32 = field.into();
+    let value = values
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| v)
+        .ok_or_else(|| anyhow!("Component {} has no field {}", component, field))?;
+
+    T::from_value(value).ok_or_else(|| {
+        anyhow!(
+            "Field {}.{} holds {:?}, expected {}",
+            component,
+            field,
+            value,
+            T::DATATYPE
+        )
+    })
+}
+
+/// Declares a struct and maps it onto a product component with the same name and fields:
+///
+/// ```
+/// mosaic::mosaic_component! {
+///     pub struct Position { pub x: f32, pub y: f32 }
+/// }
+/// ```
+#[macro_export]
+macro_rules! mosaic_component {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident { $($fvis:vis $field:ident : $ty:ty),* $(,)? }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($fvis $field: $ty),*
+        }
+
+        impl $crate::internals::TypedComponent for $name {
+            fn name() -> &'static str {
+                stringify!($name)
+            }
+
+            fn definition() -> String {
+                let fields: Vec<String> = vec![$(format!(
+                    "{}: {}",
+                    stringify!($field),
+                    <$ty as $crate::internals::ComponentFieldType>::DATATYPE
+                )),*];
+                format!("{}: {{ {} }};", stringify!($name), fields.join(", "))
+            }
+
+            fn to_values(&self) -> $crate::internals::ComponentValues {
+                vec![$((
+                    stringify!($field).into(),
+                    $crate::internals::ComponentFieldType::to_value(&self.$field),
+                )),*]
+            }
+
+            fn from_values(
+                values: &$crate::internals::ComponentValues,
+            ) -> $crate::internals::TypedResult<Self> {
+                Ok($name {
+                    $($field: $crate::internals::typed_field(
+                        values,
+                        stringify!($name),
+                        stringify!($field),
+                    )?),*
+                })
+            }
+        }
+    };
+}
+
+impl Tile {
+    pub fn get_typed<T: TypedComponent>(&self) -> anyhow::Result<T> {
+        if self.component != T::name().into() {
+            return format!(
+                "Cannot read tile {} as {}, it is a {}",
+                self.id,
+                T::name(),
+                self.component
+            )
+            .to_error();
+        }
+
+        T::from_values(&self.data())
+    }
+
+    pub fn set_typed<T: TypedComponent>(&mut self, value: &T) -> anyhow::Result<()> {
+        if self.component != T::name().into() {
+            return format!(
+                "Cannot write {} into tile {}, it is a {}",
+                T::name(),
+                self.id,
+                self.component
+            )
+            .to_error();
+        }
+
+        for (field, value) in value.to_values() {
+            self.set_field(&field.to_string(), value);
+        }
+
+        Ok(())
+    }
+}
+
+pub trait MosaicTypedComponents {
+    fn register_component<T: TypedComponent>(&self) -> anyhow::Result<()>;
+    fn new_typed_object<T: TypedComponent>(&self, value: &T) -> anyhow::Result<Tile>;
+}
+
+impl MosaicTypedComponents for Arc<Mosaic> {
+    fn register_component<T: TypedComponent>(&self) -> anyhow::Result<()> {
+        self.new_type(&T::definition())
+    }
+
+    fn new_typed_object<T: TypedComponent>(&self, value: &T) -> anyhow::Result<Tile> {
+        self.register_component::<T>()?;
+        Ok(self.new_object(T::name(), value.to_values()))
+    }
+}
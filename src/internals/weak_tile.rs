@@ -0,0 +1,53 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::{Arc, Weak},
+};
+
+use super::{EntityId, Mosaic, MosaicIO, Tile};
+
+/// A tile that doesn't keep its mosaic alive, upgraded back into a `Tile` while both are
+/// still there.
+#[derive(Debug, Clone)]
+pub struct WeakTile {
+    pub id: EntityId,
+    mosaic: Weak<Mosaic>,
+}
+
+impl WeakTile {
+    /// The tile, if its mosaic is still alive and it hasn't been deleted since.
+    pub fn upgrade(&self) -> Option<Tile> {
+        self.mosaic.upgrade()?.get(self.id)
+    }
+
+    pub fn mosaic(&self) -> Option<Arc<Mosaic>> {
+        self.mosaic.upgrade()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.upgrade().is_some()
+    }
+}
+
+impl PartialEq for WeakTile {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && Weak::ptr_eq(&self.mosaic, &other.mosaic)
+    }
+}
+
+impl Eq for WeakTile {}
+
+impl Hash for WeakTile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.mosaic.as_ptr().hash(state);
+    }
+}
+
+impl Tile {
+    pub fn downgrade(&self) -> WeakTile {
+        WeakTile {
+            id: self.id,
+            mosaic: Arc::downgrade(&self.mosaic),
+        }
+    }
+}
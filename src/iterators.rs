@@ -1,5 +1,15 @@
 pub mod component_selectors;
+pub mod lazy;
+pub mod match_query;
+pub mod pagination;
+// wasm has no threads for rayon to run on
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
+pub mod query;
+pub mod query_builder;
 pub mod tile_deletion;
 pub mod tile_filters;
 pub mod tile_getters;
+pub mod tile_sorting;
 mod unit_tests;
+pub mod weak_tiles;
@@ -4,11 +4,14 @@ use itertools::Itertools;
 
 use crate::internals::Tile;
 
+/// Tiles count as having a component when it is their own or was added with `Tile::add_data`.
 pub trait ComponentSelectors: Iterator {
     fn include_components(self, components: &[String]) -> IntoIter<Self::Item>;
     fn include_component(self, component: &str) -> IntoIter<Self::Item>;
     fn exclude_components(self, components: &[String]) -> IntoIter<Self::Item>;
     fn exclude_component(self, component: &str) -> IntoIter<Self::Item>;
+    /// Same as `include_components`, for names at hand as string slices.
+    fn with_component_in(self, components: &[&str]) -> IntoIter<Self::Item>;
 }
 
 impl<I> ComponentSelectors for I
@@ -16,10 +19,7 @@ where
     I: Iterator<Item = Tile>,
 {
     fn include_components(self, components: &[String]) -> IntoIter<Self::Item> {
-        let binding = components.iter().map(|c| c.as_str().into()).collect_vec();
-        let components = binding.as_slice();
-
-        self.filter(|t| components.contains(&t.component))
+        self.filter(|t| components.iter().any(|c| t.has_component(c)))
             .collect_vec()
             .into_iter()
     }
@@ -29,10 +29,7 @@ where
     }
 
     fn exclude_components(self, components: &[String]) -> IntoIter<Self::Item> {
-        let binding = components.iter().map(|c| c.as_str().into()).collect_vec();
-        let components = binding.as_slice();
-
-        self.filter(|t| !components.contains(&t.component))
+        self.filter(|t| !components.iter().any(|c| t.has_component(c)))
             .collect_vec()
             .into_iter()
     }
@@ -40,4 +37,10 @@ where
     fn exclude_component(self, component: &str) -> IntoIter<Self::Item> {
         self.exclude_components(&[component.to_string()])
     }
+
+    fn with_component_in(self, components: &[&str]) -> IntoIter<Self::Item> {
+        self.filter(|t| components.iter().any(|c| t.has_component(c)))
+            .collect_vec()
+            .into_iter()
+    }
 }
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use crate::internals::{EntityId, MosaicIO, Tile, Value};
+
+/// A chain of tile steps run one tile at a time as it is pulled from, unlike `TileGetters`
+/// and friends, which collect after every step.
+pub struct TileIterator<I> {
+    inner: I,
+}
+
+impl<I> Iterator for TileIterator<I>
+where
+    I: Iterator<Item = Tile>,
+{
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Tile> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+pub trait LazyTiles: Iterator<Item = Tile> + Sized {
+    /// Starts a lazy chain from these tiles.
+    fn lazy(self) -> TileIterator<Self>;
+}
+
+impl<I> LazyTiles for I
+where
+    I: Iterator<Item = Tile>,
+{
+    fn lazy(self) -> TileIterator<Self> {
+        TileIterator { inner: self }
+    }
+}
+
+fn dependents_of(tile: &Tile) -> Vec<Tile> {
+    let registry = tile.mosaic.tile_registry.read().unwrap();
+    tile.mosaic
+        .dependent_ids_map
+        .read()
+        .unwrap()
+        .get_all(&tile.id)
+        .filter_map(|id| registry.get(id))
+        .cloned()
+        .collect_vec()
+}
+
+/// Arrows among the dependents of `tile` whose `end` is the tile, once each.
+fn arrows_at(tile: &Tile, end: fn(&Tile) -> EntityId) -> Vec<Tile> {
+    dependents_of(tile)
+        .into_iter()
+        .filter(|t| t.is_arrow() && end(t) == tile.id)
+        .unique()
+        .collect_vec()
+}
+
+impl<I> TileIterator<I>
+where
+    I: Iterator<Item = Tile>,
+{
+    pub fn get_dependents(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        TileIterator {
+            inner: self.inner.flat_map(|tile| dependents_of(&tile)),
+        }
+    }
+
+    pub fn get_objects(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.get_dependents().filter_objects()
+    }
+
+    pub fn get_arrows(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.get_dependents().filter_arrows()
+    }
+
+    pub fn get_loops(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.get_dependents().filter_loops()
+    }
+
+    pub fn get_descriptors(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.get_dependents().filter_descriptors()
+    }
+
+    pub fn get_extensions(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.get_dependents().filter_extensions()
+    }
+
+    pub fn get_sources(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        TileIterator {
+            inner: self.inner.filter_map(|t| t.mosaic.get(t.source_id())),
+        }
+    }
+
+    pub fn get_targets(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        TileIterator {
+            inner: self.inner.filter_map(|t| t.mosaic.get(t.target_id())),
+        }
+    }
+
+    pub fn get_arrows_into(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        TileIterator {
+            inner: self
+                .inner
+                .flat_map(|tile| arrows_at(&tile, Tile::target_id)),
+        }
+    }
+
+    pub fn get_arrows_from(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        TileIterator {
+            inner: self
+                .inner
+                .flat_map(|tile| arrows_at(&tile, Tile::source_id)),
+        }
+    }
+
+    pub fn filter_objects(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let inner = self.inner.filter(|t| t.is_object());
+        TileIterator { inner }
+    }
+
+    pub fn filter_arrows(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let inner = self.inner.filter(|t| t.is_arrow());
+        TileIterator { inner }
+    }
+
+    pub fn filter_loops(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let inner = self.inner.filter(|t| t.is_loop());
+        TileIterator { inner }
+    }
+
+    pub fn filter_descriptors(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let inner = self.inner.filter(|t| t.is_descriptor());
+        TileIterator { inner }
+    }
+
+    pub fn filter_extensions(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let inner = self.inner.filter(|t| t.is_extension());
+        TileIterator { inner }
+    }
+
+    /// Keeps tiles that have any of `components`, their own or added with `Tile::add_data`.
+    pub fn include_components(
+        self,
+        components: &[&str],
+    ) -> TileIterator<impl Iterator<Item = Tile>> {
+        let components = components.iter().map(|c| c.to_string()).collect_vec();
+        let inner = self
+            .inner
+            .filter(move |t| components.iter().any(|c| t.has_component(c)));
+        TileIterator { inner }
+    }
+
+    pub fn include_component(self, component: &str) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.include_components(&[component])
+    }
+
+    pub fn exclude_components(
+        self,
+        components: &[&str],
+    ) -> TileIterator<impl Iterator<Item = Tile>> {
+        let components = components.iter().map(|c| c.to_string()).collect_vec();
+        let inner = self
+            .inner
+            .filter(move |t| !components.iter().any(|c| t.has_component(c)));
+        TileIterator { inner }
+    }
+
+    pub fn exclude_component(self, component: &str) -> TileIterator<impl Iterator<Item = Tile>> {
+        self.exclude_components(&[component])
+    }
+
+    /// Same as `TileFilters::filter_field`.
+    pub fn filter_field<P>(
+        self,
+        field: &str,
+        predicate: P,
+    ) -> TileIterator<impl Iterator<Item = Tile>>
+    where
+        P: Fn(&Value) -> bool,
+    {
+        let field = field.to_string();
+        let inner = self.inner.filter(move |tile| {
+            tile.data()
+                .iter()
+                .find(|(name, _)| name.is(&field))
+                .is_some_and(|(_, value)| predicate(value))
+        });
+        TileIterator { inner }
+    }
+
+    /// Drops tiles already given out earlier in the chain.
+    pub fn unique(self) -> TileIterator<impl Iterator<Item = Tile>> {
+        let mut seen = HashSet::new();
+        let inner = self.inner.filter(move |t| seen.insert(t.id));
+        TileIterator { inner }
+    }
+
+    /// These tiles followed by those of `other`, each tile once.
+    pub fn union<J>(self, other: J) -> TileIterator<impl Iterator<Item = Tile>>
+    where
+        J: IntoIterator<Item = Tile>,
+    {
+        let inner = self.inner.chain(other);
+        TileIterator { inner }.unique()
+    }
+}
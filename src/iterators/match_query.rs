@@ -0,0 +1,305 @@
+use std::{collections::HashMap, sync::Arc, vec::IntoIter};
+
+use itertools::Itertools;
+use pest::iterators::Pair;
+use pest_derive::*;
+
+use crate::internals::{Logging, Mosaic, MosaicIO, MosaicIndices, Tile};
+use crate::pest::Parser;
+
+use super::query::{has_component, matches_condition, QueryCondition, QueryLiteral};
+
+#[derive(Parser)]
+#[grammar = "iterators/match_grammar.pest"]
+struct MatchParser;
+
+#[derive(Debug, Clone, PartialEq)]
+struct MatchNode {
+    variable: String,
+    label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchDirection {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MatchEdge {
+    node: MatchNode,
+    direction: MatchDirection,
+}
+
+/// One chain of the MATCH clause: a starting node followed by `(edge, node)` hops.
+#[derive(Debug, Clone, PartialEq)]
+struct MatchPattern {
+    start: MatchNode,
+    hops: Vec<(MatchEdge, MatchNode)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MatchQuery {
+    patterns: Vec<MatchPattern>,
+    conditions: Vec<(String, QueryCondition)>,
+    returns: Vec<String>,
+}
+
+type Bindings = HashMap<String, Tile>;
+
+impl MatchParser {
+    fn parse_query(text: &str) -> anyhow::Result<MatchQuery> {
+        let parsed = MatchParser::parse(Rule::query, text)?.next().unwrap();
+        let mut query = MatchQuery {
+            patterns: vec![],
+            conditions: vec![],
+            returns: vec![],
+        };
+        let mut anonymous = 0;
+
+        for pair in parsed.into_inner() {
+            match pair.as_rule() {
+                Rule::pattern => query
+                    .patterns
+                    .push(Self::parse_pattern(pair, &mut anonymous)),
+                Rule::where_clause => {
+                    for condition in pair.into_inner() {
+                        query.conditions.push(Self::parse_condition(condition)?);
+                    }
+                }
+                Rule::return_clause => {
+                    query.returns = pair.into_inner().map(|v| v.as_str().to_string()).collect();
+                }
+                _ => {}
+            }
+        }
+
+        let known = query
+            .patterns
+            .iter()
+            .flat_map(|p| {
+                std::iter::once(&p.start)
+                    .chain(p.hops.iter().flat_map(|(e, n)| [&e.node, n]))
+                    .map(|n| n.variable.as_str())
+            })
+            .collect_vec();
+        let unknown = query
+            .returns
+            .iter()
+            .chain(query.conditions.iter().map(|(v, _)| v))
+            .find(|v| !known.contains(&v.as_str()));
+        if let Some(variable) = unknown {
+            return format!("Variable {} is not bound in the MATCH clause", variable).to_error();
+        }
+
+        Ok(query)
+    }
+
+    fn parse_node(pair: Pair<'_, Rule>, anonymous: &mut usize) -> MatchNode {
+        let mut node = MatchNode {
+            variable: String::new(),
+            label: None,
+        };
+
+        for sub in pair.into_inner() {
+            match sub.as_rule() {
+                Rule::variable => node.variable = sub.as_str().to_string(),
+                Rule::label => node.label = sub.into_inner().next().map(|l| l.as_str().to_string()),
+                _ => {}
+            }
+        }
+
+        // unnamed nodes still need a slot in the bindings, under a name no query can spell
+        if node.variable.is_empty() {
+            node.variable = format!("#{}", anonymous);
+            *anonymous += 1;
+        }
+
+        node
+    }
+
+    fn parse_pattern(pair: Pair<'_, Rule>, anonymous: &mut usize) -> MatchPattern {
+        let mut subs = pair.into_inner();
+        let start = Self::parse_node(subs.next().unwrap(), anonymous);
+        let hops = subs
+            .tuples()
+            .map(|(edge, node)| {
+                let edge = edge.into_inner().next().unwrap();
+                let direction = match edge.as_rule() {
+                    Rule::incoming => MatchDirection::Incoming,
+                    _ => MatchDirection::Outgoing,
+                };
+
+                (
+                    MatchEdge {
+                        node: Self::parse_node(edge, anonymous),
+                        direction,
+                    },
+                    Self::parse_node(node, anonymous),
+                )
+            })
+            .collect_vec();
+
+        MatchPattern { start, hops }
+    }
+
+    fn parse_condition(pair: Pair<'_, Rule>) -> anyhow::Result<(String, QueryCondition)> {
+        let mut subs = pair.into_inner();
+        let variable = subs.next().unwrap().as_str().to_string();
+        let path = subs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|p| p.as_str())
+            .collect_vec();
+        let operator = subs.next().unwrap().as_str().to_string();
+        let literal = subs.next().unwrap();
+
+        let literal = match literal.as_rule() {
+            Rule::string => QueryLiteral::Text(literal.as_str().to_string()),
+            Rule::number => QueryLiteral::Number(literal.as_str().parse()?),
+            Rule::boolean => QueryLiteral::Bool(literal.as_str().eq_ignore_ascii_case("true")),
+            e => {
+                return format!("Expected a literal in query condition, {:?} found.", e).to_error()
+            }
+        };
+
+        let (component, field) = match path.as_slice() {
+            [component, field] => (Some(component.to_string()), field.to_string()),
+            [field] => (None, field.to_string()),
+            _ => unreachable!(),
+        };
+
+        Ok((
+            variable,
+            QueryCondition {
+                component,
+                field,
+                operator,
+                literal,
+            },
+        ))
+    }
+}
+
+fn fits(node: &MatchNode, tile: &Tile, bindings: &Bindings) -> bool {
+    match bindings.get(&node.variable) {
+        Some(bound) => bound.id == tile.id,
+        None => node.label.as_ref().is_none_or(|l| has_component(tile, l)),
+    }
+}
+
+fn bind(node: &MatchNode, tile: &Tile, bindings: &Bindings) -> Option<Bindings> {
+    if !fits(node, tile, bindings) {
+        return None;
+    }
+
+    let mut bindings = bindings.clone();
+    bindings.insert(node.variable.clone(), tile.clone());
+    Some(bindings)
+}
+
+impl Mosaic {
+    fn match_hops(
+        self: &Arc<Self>,
+        current: &Tile,
+        hops: &[(MatchEdge, MatchNode)],
+        bindings: Bindings,
+    ) -> Vec<Bindings> {
+        let Some(((edge, node), rest)) = hops.split_first() else {
+            return vec![bindings];
+        };
+
+        let arrows = match edge.direction {
+            MatchDirection::Outgoing => self.get_tiles_from(current.id),
+            MatchDirection::Incoming => self.get_tiles_into(current.id),
+        };
+
+        arrows
+            .filter(|a| a.is_arrow())
+            .filter_map(|arrow| {
+                let next = match edge.direction {
+                    MatchDirection::Outgoing => self.get(arrow.target_id())?,
+                    MatchDirection::Incoming => self.get(arrow.source_id())?,
+                };
+                let bindings = bind(&edge.node, &arrow, &bindings)?;
+                let bindings = bind(node, &next, &bindings)?;
+                Some(self.match_hops(&next, rest, bindings))
+            })
+            .flatten()
+            .collect_vec()
+    }
+
+    fn match_patterns(
+        self: &Arc<Self>,
+        patterns: &[MatchPattern],
+        bindings: Bindings,
+    ) -> Vec<Bindings> {
+        let Some((pattern, rest)) = patterns.split_first() else {
+            return vec![bindings];
+        };
+
+        let starts = match bindings.get(&pattern.start.variable) {
+            Some(bound) => vec![bound.clone()],
+            None => self.get_all().sorted_by_key(|t| t.id).collect_vec(),
+        };
+
+        starts
+            .iter()
+            .filter_map(|start| bind(&pattern.start, start, &bindings).map(|b| (start, b)))
+            .flat_map(|(start, b)| self.match_hops(start, &pattern.hops, b))
+            .flat_map(|b| self.match_patterns(rest, b))
+            .collect_vec()
+    }
+}
+
+/// The rows produced by a MATCH query, one column per variable in its RETURN clause.
+#[derive(Debug, Clone)]
+pub struct MatchResults {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Tile>>,
+}
+
+impl MatchResults {
+    /// All tiles bound to `variable`, row by row; empty if it isn't a returned column.
+    pub fn column(&self, variable: &str) -> IntoIter<Tile> {
+        match self.columns.iter().position(|c| c == variable) {
+            Some(index) => self
+                .rows
+                .iter()
+                .map(|row| row[index].clone())
+                .collect_vec()
+                .into_iter(),
+            None => vec![].into_iter(),
+        }
+    }
+}
+
+pub trait MosaicMatchQuery {
+    /// Runs a graph pattern query such as
+    /// `MATCH (a:Position)-[r:Arrow]->(b) WHERE a.x > 10 RETURN b`.
+    fn query(&self, query: &str) -> anyhow::Result<MatchResults>;
+}
+
+impl MosaicMatchQuery for Arc<Mosaic> {
+    fn query(&self, query: &str) -> anyhow::Result<MatchResults> {
+        let query = MatchParser::parse_query(query)?;
+
+        let rows = self
+            .match_patterns(&query.patterns, Bindings::new())
+            .into_iter()
+            .filter(|b| {
+                query
+                    .conditions
+                    .iter()
+                    .all(|(v, c)| matches_condition(&b[v], c))
+            })
+            .map(|b| query.returns.iter().map(|v| b[v].clone()).collect_vec())
+            .collect_vec();
+
+        Ok(MatchResults {
+            columns: query.returns,
+            rows,
+        })
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+
+use crate::internals::{EntityId, Tile};
+
+/// Where a page of tiles in id order ended, written out as a short token. It holds the last
+/// id, so it stays good as tiles come and go.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Cursor(EntityId);
+
+impl Cursor {
+    /// The cursor after `tile`.
+    pub fn after(tile: &Tile) -> Cursor {
+        Cursor(tile.id)
+    }
+
+    /// Whether `tile` comes after this cursor.
+    pub fn precedes(&self, tile: &Tile) -> bool {
+        tile.id > self.0
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:x}", self.0))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EntityId::from_str_radix(s.trim(), 16)
+            .map(Cursor)
+            .map_err(|_| anyhow!("{} is not a cursor", s))
+    }
+}
+
+/// A page of tiles, and the cursor to the next one if there are more.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub tiles: Vec<Tile>,
+    pub next: Option<Cursor>,
+}
+
+impl Page {
+    pub fn is_last(&self) -> bool {
+        self.next.is_none()
+    }
+}
+
+/// Paging through tiles that come in order of id, pulling no more of them than a page takes.
+pub trait TilePagination: Iterator<Item = Tile> + Sized {
+    fn offset(self, n: usize) -> std::iter::Skip<Self> {
+        self.skip(n)
+    }
+
+    fn limit(self, n: usize) -> std::iter::Take<Self> {
+        self.take(n)
+    }
+
+    /// The tiles after `cursor`, or all of them for `None`.
+    fn after(self, cursor: Option<Cursor>) -> impl Iterator<Item = Tile> {
+        self.skip_while(move |t| cursor.is_some_and(|c| !c.precedes(t)))
+    }
+
+    /// The first `size` tiles after `cursor`; `Page::next` picks up where it ended.
+    fn page(self, cursor: Option<Cursor>, size: usize) -> Page {
+        let mut tiles = self.after(cursor).take(size + 1).collect::<Vec<_>>();
+        let next = match tiles.len() > size {
+            true => {
+                tiles.truncate(size);
+                tiles.last().map(Cursor::after)
+            }
+            false => None,
+        };
+        Page { tiles, next }
+    }
+}
+
+impl<I> TilePagination for I where I: Iterator<Item = Tile> {}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use rayon::{prelude::*, vec::IntoIter};
+
+use crate::internals::{Mosaic, MosaicIO, MosaicIndices, Tile};
+
+/// Parallel counterparts of `get_all` and the tile iterator traits, running on rayon's
+/// global thread pool. Results keep the order of the input wherever the input is ordered.
+pub trait MosaicParallel {
+    fn par_get_all(&self) -> IntoIter<Tile>;
+}
+
+impl MosaicParallel for Arc<Mosaic> {
+    fn par_get_all(&self) -> IntoIter<Tile> {
+        self.get_all().collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+pub trait ParTileFilters: ParallelIterator {
+    fn par_filter_arrows(self) -> IntoIter<Self::Item>;
+    fn par_filter_descriptors(self) -> IntoIter<Self::Item>;
+    fn par_filter_extensions(self) -> IntoIter<Self::Item>;
+    fn par_filter_loops(self) -> IntoIter<Self::Item>;
+    fn par_filter_objects(self) -> IntoIter<Self::Item>;
+    fn par_include_component(self, component: &str) -> IntoIter<Self::Item>;
+    fn par_exclude_component(self, component: &str) -> IntoIter<Self::Item>;
+}
+
+impl<I> ParTileFilters for I
+where
+    I: ParallelIterator<Item = Tile>,
+{
+    fn par_filter_arrows(self) -> IntoIter<Tile> {
+        self.filter(|tile| tile.is_arrow())
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_filter_descriptors(self) -> IntoIter<Tile> {
+        self.filter(|tile| tile.is_descriptor())
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_filter_extensions(self) -> IntoIter<Tile> {
+        self.filter(|tile| tile.is_extension())
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_filter_loops(self) -> IntoIter<Tile> {
+        self.filter(|tile| tile.is_loop())
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_filter_objects(self) -> IntoIter<Tile> {
+        self.filter(|tile| tile.is_object())
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_include_component(self, component: &str) -> IntoIter<Tile> {
+        self.filter(|tile| tile.has_component(component))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_exclude_component(self, component: &str) -> IntoIter<Tile> {
+        self.filter(|tile| !tile.has_component(component))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+}
+
+pub trait ParTileGetters: ParallelIterator {
+    fn par_get_sources(self) -> IntoIter<Self::Item>;
+    fn par_get_targets(self) -> IntoIter<Self::Item>;
+    fn par_get_arrows_from(self) -> IntoIter<Self::Item>;
+    fn par_get_arrows_into(self) -> IntoIter<Self::Item>;
+}
+
+impl<I> ParTileGetters for I
+where
+    I: ParallelIterator<Item = Tile>,
+{
+    fn par_get_sources(self) -> IntoIter<Tile> {
+        self.flat_map_iter(|t| t.mosaic.get(t.source_id()))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_get_targets(self) -> IntoIter<Tile> {
+        self.flat_map_iter(|t| t.mosaic.get(t.target_id()))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_get_arrows_from(self) -> IntoIter<Tile> {
+        self.flat_map_iter(|t| t.mosaic.get_tiles_from(t.id).filter(|a| a.is_arrow()))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    fn par_get_arrows_into(self) -> IntoIter<Tile> {
+        self.flat_map_iter(|t| t.mosaic.get_tiles_into(t.id).filter(|a| a.is_arrow()))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+}
@@ -0,0 +1,327 @@
+use std::{cmp::Ordering, sync::Arc, vec::IntoIter};
+
+use itertools::Itertools;
+use pest::iterators::Pair;
+use pest_derive::*;
+
+use crate::internals::{
+    DateTime, Duration, EntityId, Logging, Mosaic, MosaicError, MosaicIO, Tile, Value, S32,
+};
+use crate::pest::Parser;
+
+use super::{
+    lazy::LazyTiles,
+    pagination::{Cursor, TilePagination},
+};
+
+#[derive(Parser)]
+#[grammar = "iterators/query_grammar.pest"]
+struct QueryParser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuerySelection {
+    Tiles,
+    Objects,
+    Arrows,
+    Descriptors,
+    Extensions,
+    Loops,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryTraversal {
+    ArrowsInto,
+    ArrowsFrom,
+    DependentsOf,
+    DescriptorsOf,
+    ExtensionsOf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum QueryLiteral {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct QueryCondition {
+    pub(crate) component: Option<String>,
+    pub(crate) field: String,
+    pub(crate) operator: String,
+    pub(crate) literal: QueryLiteral,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Query {
+    selection: QuerySelection,
+    components: Vec<String>,
+    conditions: Vec<QueryCondition>,
+    traversal: Option<(QueryTraversal, EntityId)>,
+    after: Option<Cursor>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Query {
+    fn is_paged(&self) -> bool {
+        self.after.is_some() || self.offset > 0 || self.limit.is_some()
+    }
+}
+
+impl QueryParser {
+    fn parse_query(text: &str) -> anyhow::Result<Query> {
+        let parsed = QueryParser::parse(Rule::query, text)?.next().unwrap();
+        let mut query = Query {
+            selection: QuerySelection::Tiles,
+            components: vec![],
+            conditions: vec![],
+            traversal: None,
+            after: None,
+            offset: 0,
+            limit: None,
+        };
+
+        for pair in parsed.into_inner() {
+            match pair.as_rule() {
+                Rule::selection => {
+                    query.selection = match pair.as_str().to_lowercase().as_str() {
+                        "objects" => QuerySelection::Objects,
+                        "arrows" => QuerySelection::Arrows,
+                        "descriptors" => QuerySelection::Descriptors,
+                        "extensions" => QuerySelection::Extensions,
+                        "loops" => QuerySelection::Loops,
+                        _ => QuerySelection::Tiles,
+                    }
+                }
+                Rule::with_clause => {
+                    query.components = pair
+                        .into_inner()
+                        .map(|c| c.as_str().to_string())
+                        .collect_vec();
+                }
+                Rule::where_clause => {
+                    for condition in pair.into_inner() {
+                        query.conditions.push(Self::parse_condition(condition)?);
+                    }
+                }
+                Rule::traversal => query.traversal = Some(Self::parse_traversal(pair)?),
+                Rule::after_clause => {
+                    query.after = Some(pair.into_inner().next().unwrap().as_str().parse()?)
+                }
+                Rule::limit_clause => {
+                    query.limit = Some(pair.into_inner().next().unwrap().as_str().parse()?)
+                }
+                Rule::offset_clause => {
+                    query.offset = pair.into_inner().next().unwrap().as_str().parse()?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(query)
+    }
+
+    fn parse_condition(pair: Pair<'_, Rule>) -> anyhow::Result<QueryCondition> {
+        let mut subs = pair.into_inner();
+        let path = subs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|p| p.as_str())
+            .collect_vec();
+        let operator = subs.next().unwrap().as_str().to_string();
+        let literal = subs.next().unwrap();
+
+        let literal = match literal.as_rule() {
+            Rule::string => QueryLiteral::Text(literal.as_str().to_string()),
+            Rule::number => QueryLiteral::Number(literal.as_str().parse()?),
+            Rule::boolean => QueryLiteral::Bool(literal.as_str().eq_ignore_ascii_case("true")),
+            e => {
+                return format!("Expected a literal in query condition, {:?} found.", e).to_error()
+            }
+        };
+
+        let (component, field) = match path.as_slice() {
+            [component, field] => (Some(component.to_string()), field.to_string()),
+            [field] => (None, field.to_string()),
+            _ => unreachable!(),
+        };
+
+        Ok(QueryCondition {
+            component,
+            field,
+            operator,
+            literal,
+        })
+    }
+
+    fn parse_traversal(pair: Pair<'_, Rule>) -> anyhow::Result<(QueryTraversal, EntityId)> {
+        let mut subs = pair.into_inner();
+        let relation = subs
+            .next()
+            .unwrap()
+            .as_str()
+            .split_whitespace()
+            .map(|w| w.to_lowercase())
+            .join(" ");
+        let id = subs.next().unwrap().as_str().parse()?;
+
+        let traversal = match relation.as_str() {
+            "arrows into" => QueryTraversal::ArrowsInto,
+            "arrows from" => QueryTraversal::ArrowsFrom,
+            "dependents of" => QueryTraversal::DependentsOf,
+            "descriptors of" => QueryTraversal::DescriptorsOf,
+            _ => QueryTraversal::ExtensionsOf,
+        };
+
+        Ok((traversal, id))
+    }
+}
+
+fn compare(value: &Value, literal: &QueryLiteral) -> Option<Ordering> {
+    match (value, literal) {
+        (Value::S32(s), QueryLiteral::Text(t)) => Some(s.to_string().as_str().cmp(t.as_str())),
+        (Value::STR(s), QueryLiteral::Text(t)) => Some((**s).cmp(t.as_str())),
+        (Value::BOOL(b), QueryLiteral::Bool(l)) => Some(b.cmp(l)),
+        (Value::DATETIME(d), QueryLiteral::Text(t)) => {
+            t.parse::<DateTime>().ok().map(|literal| d.cmp(&literal))
+        }
+        (Value::DURATION(d), QueryLiteral::Text(t)) => {
+            t.parse::<Duration>().ok().map(|literal| d.cmp(&literal))
+        }
+        (value, QueryLiteral::Number(n)) => {
+            let v = match value {
+                Value::I8(v) => *v as f64,
+                Value::I16(v) => *v as f64,
+                Value::I32(v) => *v as f64,
+                Value::I64(v) => *v as f64,
+                Value::U8(v) => *v as f64,
+                Value::U16(v) => *v as f64,
+                Value::U32(v) => *v as f64,
+                Value::U64(v) => *v as f64,
+                Value::F32(v) => *v as f64,
+                Value::F64(v) => *v,
+                _ => return None,
+            };
+            v.partial_cmp(n)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn has_component(tile: &Tile, component: &str) -> bool {
+    tile.has_component(component)
+        || tile
+            .iter()
+            .lazy()
+            .get_dependents()
+            .include_component(component)
+            .next()
+            .is_some()
+}
+
+pub(crate) fn matches_condition(tile: &Tile, condition: &QueryCondition) -> bool {
+    let holder = match &condition.component {
+        None => Some((tile.clone(), tile.component)),
+        Some(c) if tile.has_component(c) => Some((tile.clone(), S32::from(c.as_str()))),
+        Some(c) => tile
+            .iter()
+            .lazy()
+            .get_dependents()
+            .include_component(c)
+            .next()
+            .map(|t| (t, S32::from(c.as_str()))),
+    };
+
+    let value = holder.and_then(|(holder, component)| {
+        let fields = match &condition.component {
+            None => Some(holder.data()),
+            Some(c) => holder.get_data(c),
+        };
+        fields
+            .and_then(|fields| {
+                fields
+                    .into_iter()
+                    .find(|(name, _)| name.to_string() == condition.field)
+                    .map(|(_, v)| v)
+            })
+            .or_else(|| {
+                let field = S32::from(condition.field.as_str());
+                holder.mosaic.computed_value(&holder, component, field)
+            })
+    });
+
+    let ordering = value.and_then(|v| compare(&v, &condition.literal));
+    match (condition.operator.as_str(), ordering) {
+        (_, None) => false,
+        ("=", Some(o)) => o == Ordering::Equal,
+        ("!=", Some(o)) => o != Ordering::Equal,
+        ("<", Some(o)) => o == Ordering::Less,
+        ("<=", Some(o)) => o != Ordering::Greater,
+        (">", Some(o)) => o == Ordering::Greater,
+        (">=", Some(o)) => o != Ordering::Less,
+        _ => false,
+    }
+}
+
+pub trait MosaicQuery {
+    /// Runs a textual query, e.g. `SELECT tiles WITH Label WHERE Label.self = "foo"`, optionally
+    /// with a traversal like `ARROWS INTO #42`, and `AFTER`, `LIMIT` and `OFFSET` at the end.
+    fn query_str(&self, query: &str) -> anyhow::Result<IntoIter<Tile>>;
+}
+
+impl MosaicQuery for Arc<Mosaic> {
+    fn query_str(&self, query: &str) -> anyhow::Result<IntoIter<Tile>> {
+        let query = QueryParser::parse_query(query)?;
+
+        let candidates: Box<dyn Iterator<Item = Tile>> = match query.traversal {
+            None => Box::new(self.get_all().sorted_by_key(|t| t.id)),
+            Some((traversal, id)) => {
+                let Some(tile) = self.get(id) else {
+                    return Err(MosaicError::InvalidTile(id).into());
+                };
+
+                let start = tile.iter().lazy();
+                match traversal {
+                    QueryTraversal::ArrowsInto => Box::new(start.get_arrows_into()),
+                    QueryTraversal::ArrowsFrom => Box::new(start.get_arrows_from()),
+                    QueryTraversal::DependentsOf => Box::new(start.get_dependents()),
+                    QueryTraversal::DescriptorsOf => Box::new(start.get_descriptors()),
+                    QueryTraversal::ExtensionsOf => Box::new(start.get_extensions()),
+                }
+            }
+        };
+
+        let candidates = candidates.lazy();
+        let selected: Box<dyn Iterator<Item = Tile>> = match query.selection {
+            QuerySelection::Tiles => Box::new(candidates),
+            QuerySelection::Objects => Box::new(candidates.filter_objects()),
+            QuerySelection::Arrows => Box::new(candidates.filter_arrows()),
+            QuerySelection::Descriptors => Box::new(candidates.filter_descriptors()),
+            QuerySelection::Extensions => Box::new(candidates.filter_extensions()),
+            QuerySelection::Loops => Box::new(candidates.filter_loops()),
+        };
+
+        let found = selected
+            .filter(|t| query.components.iter().all(|c| has_component(t, c)))
+            .filter(|t| query.conditions.iter().all(|c| matches_condition(t, c)))
+            .unique_by(|t| t.id);
+        if !query.is_paged() {
+            return Ok(found.collect_vec().into_iter());
+        }
+
+        // without a traversal the tiles already come in order of id, and only as many as the
+        // page takes are looked at
+        let found: Box<dyn Iterator<Item = Tile> + '_> = match query.traversal {
+            None => Box::new(found),
+            Some(_) => Box::new(found.sorted_by_key(|t| t.id)),
+        };
+        Ok(found
+            .after(query.after)
+            .offset(query.offset)
+            .limit(query.limit.unwrap_or(usize::MAX))
+            .collect_vec()
+            .into_iter())
+    }
+}
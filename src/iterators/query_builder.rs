@@ -0,0 +1,185 @@
+use std::{collections::BTreeSet, sync::Arc, vec::IntoIter};
+
+use itertools::Itertools;
+
+use crate::internals::{EntityId, Mosaic, MosaicIO, Tile, TileGetById, S32};
+
+use super::pagination::{Cursor, Page, TilePagination};
+
+type TileFilter = Box<dyn Fn(&Tile) -> bool>;
+
+/// A fluent query over the tiles of a mosaic. Source, target, and component constraints are
+/// answered from the mosaic's indices first; the remaining conditions filter what is left.
+pub struct QueryBuilder {
+    mosaic: Arc<Mosaic>,
+    source: Option<EntityId>,
+    target: Option<EntityId>,
+    components: Vec<S32>,
+    excluded_components: Vec<S32>,
+    filters: Vec<TileFilter>,
+    after: Option<Cursor>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+pub trait MosaicQueryBuilder {
+    fn build_query(&self) -> QueryBuilder;
+}
+
+impl MosaicQueryBuilder for Arc<Mosaic> {
+    fn build_query(&self) -> QueryBuilder {
+        QueryBuilder {
+            mosaic: Arc::clone(self),
+            source: None,
+            target: None,
+            components: vec![],
+            excluded_components: vec![],
+            filters: vec![],
+            after: None,
+            offset: 0,
+            limit: None,
+        }
+    }
+}
+
+impl QueryBuilder {
+    /// Keeps tiles that start at `source`: arrows going out of it and its extensions.
+    pub fn with_source(mut self, source: EntityId) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Keeps tiles that end at `target`: arrows coming into it and its descriptors.
+    pub fn with_target(mut self, target: EntityId) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_endpoints(self, source: EntityId, target: EntityId) -> Self {
+        self.with_source(source).with_target(target)
+    }
+
+    /// Keeps tiles of the given component or of types extending it, including tiles it was
+    /// added to with `Tile::add_data`; calling it again widens the query to either one.
+    pub fn with_component(mut self, component: &str) -> Self {
+        let kinds = self.mosaic.component_registry.kinds_of(component.into());
+        self.components.extend(kinds);
+        self
+    }
+
+    pub fn without_component(mut self, component: &str) -> Self {
+        let kinds = self.mosaic.component_registry.kinds_of(component.into());
+        self.excluded_components.extend(kinds);
+        self
+    }
+
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Tile) -> bool + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Skips the first `n` tiles found. Paged queries find tiles in order of id.
+    pub fn offset(mut self, n: usize) -> Self {
+        self.offset = n;
+        self
+    }
+
+    /// Stops after `n` tiles are found, without running the filters on the rest.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Keeps the tiles after `cursor`, as handed out by `page`.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    fn is_paged(&self) -> bool {
+        self.after.is_some() || self.offset > 0 || self.limit.is_some()
+    }
+
+    fn candidates(&self) -> Vec<Tile> {
+        let ids = {
+            let indices = self.mosaic.indices.read().unwrap();
+            let per_component = |lookup: &dyn Fn(S32) -> Vec<EntityId>| {
+                self.components
+                    .iter()
+                    .flat_map(|c| lookup(*c))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect_vec()
+            };
+
+            match (self.source, self.target) {
+                (Some(source), Some(target)) => indices.arrows_between(source, target),
+                (Some(source), None) if !self.components.is_empty() => {
+                    per_component(&|c| indices.with_source_and_component(source, c))
+                }
+                (Some(source), None) => indices.with_source(source),
+                (None, Some(target)) if !self.components.is_empty() => {
+                    per_component(&|c| indices.with_target_and_component(target, c))
+                }
+                (None, Some(target)) => indices.with_target(target),
+                (None, None) if !self.components.is_empty() => {
+                    per_component(&|c| indices.with_component(c))
+                }
+                (None, None) => return self.mosaic.get_all().sorted_by_key(|t| t.id).collect_vec(),
+            }
+        };
+
+        self.mosaic.get_tiles(ids).collect_vec()
+    }
+
+    fn matches(&self, tile: &Tile) -> bool {
+        let components = tile.components();
+        self.source
+            .is_none_or(|s| tile.id != s && tile.source_id() == s)
+            && self
+                .target
+                .is_none_or(|t| tile.id != t && tile.target_id() == t)
+            && (self.components.is_empty()
+                || self.components.iter().any(|c| components.contains(c)))
+            && !self
+                .excluded_components
+                .iter()
+                .any(|c| components.contains(c))
+            && self.filters.iter().all(|f| f(tile))
+    }
+
+    pub fn execute(self) -> IntoIter<Tile> {
+        let mut candidates = self.candidates();
+        if self.is_paged() {
+            candidates.sort_by_key(|t| t.id);
+        }
+
+        candidates
+            .into_iter()
+            .after(self.after)
+            .filter(|t| self.matches(t))
+            .offset(self.offset)
+            .limit(self.limit.unwrap_or(usize::MAX))
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// The first `size` tiles found, after the cursor if one is set, and the cursor to the
+    /// next page if there is one.
+    pub fn page(self, size: usize) -> Page {
+        self.limit(size.saturating_add(1))
+            .execute()
+            .page(None, size)
+    }
+
+    pub fn first(self) -> Option<Tile> {
+        self.execute().next()
+    }
+
+    pub fn count(self) -> usize {
+        self.execute().len()
+    }
+}
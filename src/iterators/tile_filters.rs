@@ -2,7 +2,7 @@ use std::vec::IntoIter;
 
 use itertools::Itertools;
 
-use crate::internals::Tile;
+use crate::internals::{Tile, Value};
 
 pub trait TileFilters: Iterator {
     fn filter_arrows(self) -> IntoIter<Self::Item>;
@@ -10,6 +10,11 @@ pub trait TileFilters: Iterator {
     fn filter_extensions(self) -> IntoIter<Self::Item>;
     fn filter_loops(self) -> IntoIter<Self::Item>;
     fn filter_objects(self) -> IntoIter<Self::Item>;
+    /// Keeps tiles whose `field` holds a value `predicate` accepts; tiles without that field
+    /// are dropped rather than handed to `predicate`.
+    fn filter_field<P>(self, field: &str, predicate: P) -> IntoIter<Self::Item>
+    where
+        P: Fn(&Value) -> bool;
 }
 
 impl<I> TileFilters for I
@@ -43,4 +48,18 @@ where
             .collect_vec()
             .into_iter()
     }
+
+    fn filter_field<P>(self, field: &str, predicate: P) -> IntoIter<Self::Item>
+    where
+        P: Fn(&Value) -> bool,
+    {
+        self.filter(|tile| {
+            tile.data()
+                .iter()
+                .find(|(name, _)| name.is(field))
+                .is_some_and(|(_, value)| predicate(value))
+        })
+        .collect_vec()
+        .into_iter()
+    }
 }
@@ -1,9 +1,9 @@
 use itertools::Itertools;
 use std::vec::IntoIter;
 
-use crate::internals::{MosaicIO, Tile};
+use crate::internals::Tile;
 
-use super::tile_filters::TileFilters;
+use super::lazy::LazyTiles;
 
 pub trait TileGetters: Iterator {
     fn get_dependents(self) -> IntoIter<Self::Item>;
@@ -23,92 +23,42 @@ where
     I: Iterator<Item = Tile>,
 {
     fn get_dependents(self) -> IntoIter<Tile> {
-        self.into_iter()
-            .flat_map(|tile| {
-                let tile_storage = tile.mosaic.tile_registry.lock().unwrap();
-
-                tile.mosaic
-                    .dependent_ids_map
-                    .lock()
-                    .unwrap()
-                    .get_all(&tile.id)
-                    .filter_map(|id| tile_storage.get(id))
-                    .cloned()
-                    .collect_vec()
-            })
-            .collect_vec()
-            .into_iter()
+        self.lazy().get_dependents().collect_vec().into_iter()
     }
 
     fn get_objects(self) -> IntoIter<Self::Item> {
-        self.get_dependents().filter_objects()
+        self.lazy().get_objects().collect_vec().into_iter()
     }
 
     fn get_arrows(self) -> IntoIter<Self::Item> {
-        self.get_dependents().filter_arrows()
+        self.lazy().get_arrows().collect_vec().into_iter()
     }
 
     fn get_loops(self) -> IntoIter<Self::Item> {
-        self.get_dependents().filter_loops()
+        self.lazy().get_loops().collect_vec().into_iter()
     }
 
     fn get_descriptors(self) -> IntoIter<Self::Item> {
-        self.get_dependents().filter_descriptors()
+        self.lazy().get_descriptors().collect_vec().into_iter()
     }
 
     fn get_extensions(self) -> IntoIter<Self::Item> {
-        self.get_dependents().filter_extensions()
+        self.lazy().get_extensions().collect_vec().into_iter()
     }
 
     fn get_sources(self) -> IntoIter<Self::Item> {
-        self.flat_map(|t| t.mosaic.get(t.source_id()))
-            .collect_vec()
-            .into_iter()
+        self.lazy().get_sources().collect_vec().into_iter()
     }
 
     fn get_targets(self) -> IntoIter<Self::Item> {
-        self.flat_map(|t| t.mosaic.get(t.target_id()))
-            .collect_vec()
-            .into_iter()
+        self.lazy().get_targets().collect_vec().into_iter()
     }
 
     fn get_arrows_into(self) -> IntoIter<Self::Item> {
-        self.into_iter()
-            .flat_map(|tile| {
-                let tile_storage = tile.mosaic.tile_registry.lock().unwrap();
-                let id = tile.id;
-                tile.mosaic
-                    .dependent_ids_map
-                    .lock()
-                    .unwrap()
-                    .get_all(&id)
-                    .filter_map(|id| tile_storage.get(id))
-                    .filter(|tile| tile.is_arrow() && tile.target_id() == id)
-                    .cloned()
-                    .unique()
-                    .collect_vec()
-            })
-            .collect_vec()
-            .into_iter()
+        self.lazy().get_arrows_into().collect_vec().into_iter()
     }
 
     fn get_arrows_from(self) -> IntoIter<Self::Item> {
-        self.into_iter()
-            .flat_map(|tile| {
-                let tile_storage = tile.mosaic.tile_registry.lock().unwrap();
-                let id = tile.id;
-                tile.mosaic
-                    .dependent_ids_map
-                    .lock()
-                    .unwrap()
-                    .get_all(&id)
-                    .filter_map(|id| tile_storage.get(id))
-                    .filter(|tile| tile.is_arrow() && tile.source_id() == id)
-                    .cloned()
-                    .unique()
-                    .collect_vec()
-            })
-            .collect_vec()
-            .into_iter()
+        self.lazy().get_arrows_from().collect_vec().into_iter()
     }
 }
@@ -0,0 +1,95 @@
+use std::{cmp::Ordering, collections::BTreeMap, vec::IntoIter};
+
+use itertools::Itertools;
+
+use crate::internals::{EntityId, Tile, Value, S32};
+
+/// Sorting and grouping that ends a chain of tile iterators. Grouped tiles keep the order
+/// they came in.
+pub trait TileSorting: Iterator {
+    /// Sorts tiles by the value of `field`, keeping ties in order and tiles without it last.
+    fn sort_by_field(self, field: &str) -> IntoIter<Self::Item>;
+    fn group_by_component(self) -> BTreeMap<S32, Vec<Self::Item>>;
+    /// Groups tiles by `target_id`, which for objects and extensions is the tile itself.
+    fn group_by_target(self) -> BTreeMap<EntityId, Vec<Self::Item>>;
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::I8(v) => Some(*v as f64),
+        Value::I16(v) => Some(*v as f64),
+        Value::I32(v) => Some(*v as f64),
+        Value::I64(v) => Some(*v as f64),
+        Value::U8(v) => Some(*v as f64),
+        Value::U16(v) => Some(*v as f64),
+        Value::U32(v) => Some(*v as f64),
+        Value::U64(v) => Some(*v as f64),
+        Value::F32(v) => Some(*v as f64),
+        Value::F64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::BOOL(a), Value::BOOL(b)) => a.cmp(b),
+        (Value::DATETIME(a), Value::DATETIME(b)) => a.cmp(b),
+        (Value::DURATION(a), Value::DURATION(b)) => a.cmp(b),
+        (Value::S32(_) | Value::STR(_), Value::S32(_) | Value::STR(_)) => {
+            text_of(a).cmp(&text_of(b))
+        }
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => Ordering::Equal,
+        },
+    }
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::S32(s) => s.to_string(),
+        Value::STR(s) => s.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn field_of(tile: &Tile, field: &str) -> Option<Value> {
+    tile.data()
+        .into_iter()
+        .find(|(name, _)| name.is(field))
+        .map(|(_, value)| value)
+}
+
+impl<I> TileSorting for I
+where
+    I: Iterator<Item = Tile>,
+{
+    fn sort_by_field(self, field: &str) -> IntoIter<Self::Item> {
+        self.map(|tile| (field_of(&tile, field), tile))
+            .sorted_by(|(a, _), (b, _)| match (a, b) {
+                (Some(a), Some(b)) => compare_values(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+            .map(|(_, tile)| tile)
+            .collect_vec()
+            .into_iter()
+    }
+
+    fn group_by_component(self) -> BTreeMap<S32, Vec<Self::Item>> {
+        let mut groups: BTreeMap<S32, Vec<Tile>> = BTreeMap::new();
+        for tile in self {
+            groups.entry(tile.component).or_default().push(tile);
+        }
+        groups
+    }
+
+    fn group_by_target(self) -> BTreeMap<EntityId, Vec<Self::Item>> {
+        let mut groups: BTreeMap<EntityId, Vec<Tile>> = BTreeMap::new();
+        for tile in self {
+            groups.entry(tile.target_id()).or_default().push(tile);
+        }
+        groups
+    }
+}
@@ -3,10 +3,22 @@ mod test_iterators {
     use itertools::Itertools;
 
     use crate::{
-        internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD},
+        internals::{
+            par, pars, void, ComponentValuesBuilderSetter, Mosaic, MosaicCRUD, MosaicIO,
+            MosaicTypelevelCRUD, S32,
+        },
         iterators::{
-            component_selectors::ComponentSelectors, tile_filters::TileFilters,
+            component_selectors::ComponentSelectors,
+            lazy::LazyTiles,
+            match_query::MosaicMatchQuery,
+            pagination::{Cursor, TilePagination},
+            parallel::{MosaicParallel, ParTileFilters, ParTileGetters},
+            query::MosaicQuery,
+            query_builder::MosaicQueryBuilder,
+            tile_filters::TileFilters,
             tile_getters::TileGetters,
+            tile_sorting::TileSorting,
+            weak_tiles::{DowngradeTiles, UpgradeTiles},
         },
     };
 
@@ -160,4 +172,421 @@ mod test_iterators {
         assert_eq!(Some(tgt3), p.next());
         assert_eq!(None, p.next());
     }
+
+    #[test]
+    fn test_field_filters() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Road: { weight: f32 };").unwrap();
+        mosaic.new_type("Rail: { weight: f32 };").unwrap();
+        mosaic.new_type("Path: unit;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let road = mosaic.new_arrow(&a, &b, "Road", pars().set("weight", 2.0f32).ok());
+        let _closed = mosaic.new_arrow(&a, &b, "Road", pars().set("weight", 0.0f32).ok());
+        let rail = mosaic.new_arrow(&a, &b, "Rail", pars().set("weight", 1.5f32).ok());
+        let path = mosaic.new_arrow(&a, &b, "Path", void());
+
+        let open = a
+            .iter()
+            .get_arrows_from()
+            .filter_field("weight", |w| w.as_f32() > 0.0)
+            .collect_vec();
+        assert_eq!(vec![road.clone(), rail.clone()], open);
+
+        let ways = a
+            .iter()
+            .get_arrows_from()
+            .with_component_in(&["Rail", "Path"])
+            .collect_vec();
+        assert_eq!(vec![rail, path], ways);
+
+        let heavy = mosaic
+            .get_all()
+            .with_component_in(&["Road", "Rail"])
+            .filter_field("weight", |w| w.as_f32() > 1.5)
+            .collect_vec();
+        assert_eq!(vec![road], heavy);
+    }
+
+    #[test]
+    fn test_sorting_and_grouping() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("City: { name: str, size: u32 };").unwrap();
+        mosaic.new_type("Road: unit;").unwrap();
+        let city = |name: &str, size: u32| {
+            mosaic.new_object(
+                "City",
+                pars().set("name", name.to_string()).set("size", size).ok(),
+            )
+        };
+        let a = city("Bree", 300);
+        let b = city("Annuminas", 5000);
+        let c = city("Archet", 300);
+        let hobbit = mosaic.new_object("void", void());
+        let a_b = mosaic.new_arrow(&a, &b, "Road", void());
+        let c_b = mosaic.new_arrow(&c, &b, "Road", void());
+        let a_c = mosaic.new_arrow(&a, &c, "void", void());
+
+        let tiles = vec![a.clone(), b.clone(), hobbit.clone(), c.clone()];
+        assert_eq!(
+            vec![b.id, c.id, a.id, hobbit.id],
+            tiles
+                .clone()
+                .into_iter()
+                .sort_by_field("name")
+                .map(|t| t.id)
+                .collect_vec()
+        );
+        assert_eq!(
+            vec![a.id, c.id, b.id, hobbit.id],
+            tiles
+                .into_iter()
+                .sort_by_field("size")
+                .map(|t| t.id)
+                .collect_vec()
+        );
+
+        let by_component = vec![a.clone(), a_b.clone(), hobbit, c_b.clone(), a_c.clone()]
+            .into_iter()
+            .group_by_component();
+        assert_eq!(3, by_component.len());
+        assert_eq!(
+            vec![a_b.clone(), c_b.clone()],
+            by_component[&S32::from("Road")]
+        );
+
+        let by_target = vec![a_b, c_b, a_c].into_iter().group_by_target();
+        assert_eq!(vec![&b.id, &c.id], by_target.keys().collect_vec());
+        assert_eq!(2, by_target[&b.id].len());
+    }
+
+    #[test]
+    fn test_lazy_tile_iterator() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Arr1: unit;").unwrap();
+        mosaic.new_type("Arr2: unit;").unwrap();
+        let src = mosaic.new_object("void", void());
+        let src2 = mosaic.new_object("void", void());
+        let tgt1 = mosaic.new_object("void", void());
+        let tgt2 = mosaic.new_object("void", void());
+        let _a1 = mosaic.new_arrow(&src, &tgt1, "Arr1", void());
+        let _a2 = mosaic.new_arrow(&src, &tgt2, "Arr2", void());
+        let _a3 = mosaic.new_arrow(&src2, &tgt2, "Arr2", void());
+        let _a4 = mosaic.new_arrow(&src2, &src, "Arr1", void());
+
+        let eager = mosaic
+            .get_all()
+            .filter_objects()
+            .get_arrows_from()
+            .include_component("Arr2")
+            .get_targets()
+            .sorted()
+            .collect_vec();
+        let lazy = mosaic
+            .get_all()
+            .lazy()
+            .filter_objects()
+            .get_arrows_from()
+            .include_component("Arr2")
+            .get_targets()
+            .sorted()
+            .collect_vec();
+        assert_eq!(vec![tgt2.clone(), tgt2.clone()], eager);
+        assert_eq!(eager, lazy);
+
+        let targets = src.iter().lazy().get_arrows_from().get_targets();
+        let union = targets
+            .union(src2.iter().lazy().get_arrows_from().get_targets())
+            .collect_vec();
+        assert_eq!(vec![tgt1, tgt2.clone(), src.clone()], union);
+
+        assert_eq!(
+            Some(tgt2),
+            mosaic
+                .get_all()
+                .sorted()
+                .lazy()
+                .get_arrows_from()
+                .exclude_component("Arr1")
+                .get_targets()
+                .next()
+        );
+    }
+
+    #[test]
+    fn test_query_with_components_and_conditions() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Position: { x: i32, y: i32 };").unwrap();
+        mosaic.new_type("Label: s32;").unwrap();
+
+        let a = mosaic.new_object("Position", void());
+        mosaic.new_descriptor(&a, "Label", par("foo"));
+        let b = mosaic.new_object("Position", void());
+        mosaic.new_descriptor(&b, "Label", par("bar"));
+        mosaic.new_object("Position", void());
+
+        let labelled = mosaic
+            .query_str("SELECT objects WITH Position, Label")
+            .unwrap()
+            .collect_vec();
+        assert_eq!(vec![a.clone(), b.clone()], labelled);
+
+        let foo = mosaic
+            .query_str("select objects with Position where Label.self = \"foo\" and x >= 0")
+            .unwrap()
+            .collect_vec();
+        assert_eq!(vec![a], foo);
+    }
+
+    #[test]
+    fn test_query_with_traversal() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Parent: unit;").unwrap();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let ba = mosaic.new_arrow(&b, &a, "Parent", void());
+        let _ca = mosaic.new_arrow(&c, &a, "void", void());
+
+        let query = format!("SELECT arrows WITH Parent ARROWS INTO #{}", a.id);
+        let parents = mosaic.query_str(&query).unwrap().collect_vec();
+        assert_eq!(vec![ba], parents);
+
+        assert!(mosaic.query_str("SELECT nothing").is_err());
+        assert!(mosaic.query_str("SELECT tiles ARROWS INTO #9999").is_err());
+    }
+
+    #[test]
+    fn test_query_builder() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Edge: u32;").unwrap();
+        mosaic.new_type("Label: s32;").unwrap();
+
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("void", void());
+        let a_b = mosaic.new_arrow(&a, &b, "Edge", par(1u32));
+        let a_c = mosaic.new_arrow(&a, &c, "Edge", par(5u32));
+        let a_b_void = mosaic.new_arrow(&a, &b, "void", void());
+        let label = mosaic.new_descriptor(&b, "Label", par("b"));
+
+        let ids = |q: crate::iterators::query_builder::QueryBuilder| {
+            q.execute().map(|t| t.id).collect_vec()
+        };
+
+        assert_eq!(
+            vec![a_b.id, a_c.id, a_b_void.id],
+            ids(mosaic.build_query().with_source(a.id))
+        );
+        assert_eq!(
+            vec![a_b.id, a_b_void.id, label.id],
+            ids(mosaic.build_query().with_target(b.id))
+        );
+        assert_eq!(
+            vec![a_b.id],
+            ids(mosaic
+                .build_query()
+                .with_endpoints(a.id, b.id)
+                .with_component("Edge"))
+        );
+        assert_eq!(
+            vec![a_b.id, a_c.id],
+            ids(mosaic.build_query().with_component("Edge"))
+        );
+        assert_eq!(
+            vec![a_c.id],
+            ids(mosaic
+                .build_query()
+                .with_component("Edge")
+                .with_filter(|t| t.get("self").as_u32() > 2))
+        );
+        assert_eq!(
+            vec![a_b.id, a_c.id, label.id],
+            ids(mosaic.build_query().without_component("void"))
+        );
+
+        mosaic.delete_tile(a_b.clone());
+        assert_eq!(1, mosaic.build_query().with_component("Edge").count());
+    }
+
+    #[test]
+    fn test_pagination() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Weight: i32;").unwrap();
+        let tiles = (0..10i32)
+            .map(|i| mosaic.new_object("Weight", par(i)))
+            .collect_vec();
+        let ids = |tiles: &[crate::internals::Tile]| tiles.iter().map(|t| t.id).collect_vec();
+
+        let first = mosaic.build_query().with_component("Weight").page(4);
+        assert_eq!(ids(&tiles[0..4]), ids(&first.tiles));
+        let token = first.next.unwrap().to_string();
+        let cursor: Cursor = token.parse().unwrap();
+        assert!("not a cursor".parse::<Cursor>().is_err());
+
+        // the cursor holds its place when tiles before it go away
+        mosaic.delete_tile(tiles[1].id);
+        let second = mosaic
+            .build_query()
+            .with_component("Weight")
+            .after(cursor)
+            .page(4);
+        assert_eq!(ids(&tiles[4..8]), ids(&second.tiles));
+        let last = mosaic
+            .build_query()
+            .with_component("Weight")
+            .after(second.next.unwrap())
+            .page(4);
+        assert_eq!(ids(&tiles[8..10]), ids(&last.tiles));
+        assert!(last.is_last());
+
+        let odd = mosaic
+            .build_query()
+            .with_filter(|t| t.get("self").as_i32() % 2 == 1)
+            .offset(1)
+            .limit(2)
+            .execute()
+            .collect_vec();
+        assert_eq!(vec![tiles[5].id, tiles[7].id], ids(&odd));
+
+        let found = mosaic
+            .query_str(&format!(
+                "SELECT objects WITH Weight AFTER {} LIMIT 3",
+                token
+            ))
+            .unwrap()
+            .collect_vec();
+        assert_eq!(ids(&tiles[4..7]), ids(&found));
+        let found = mosaic
+            .query_str("SELECT objects LIMIT 2 OFFSET 1")
+            .unwrap()
+            .collect_vec();
+        assert_eq!(vec![tiles[2].id, tiles[3].id], ids(&found));
+        assert!(mosaic.query_str("SELECT objects OFFSET 1 LIMIT 2").is_err());
+
+        let page = mosaic
+            .get_all()
+            .sorted_by_key(|t| t.id)
+            .lazy()
+            .page(Some(cursor), 2);
+        assert_eq!(ids(&tiles[4..6]), ids(&page.tiles));
+        assert_eq!(
+            ids(&tiles[2..4]),
+            ids(&tiles.clone().into_iter().offset(2).limit(2).collect_vec())
+        );
+    }
+
+    #[test]
+    fn test_parallel_iterators() {
+        use rayon::prelude::*;
+
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Edge: void;").unwrap();
+        let objects = (0..64)
+            .map(|_| mosaic.new_object("void", void()))
+            .collect_vec();
+        objects.iter().tuple_windows().for_each(|(a, b)| {
+            mosaic.new_arrow(a, b, "Edge", void());
+        });
+
+        assert_eq!(64 + 63, mosaic.par_get_all().count());
+        assert_eq!(
+            mosaic.get_all().filter_objects().count(),
+            mosaic.par_get_all().par_filter_objects().count()
+        );
+        assert_eq!(
+            63,
+            mosaic.par_get_all().par_include_component("Edge").count()
+        );
+
+        let targets = mosaic
+            .par_get_all()
+            .par_filter_objects()
+            .par_get_arrows_from()
+            .par_get_targets()
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            objects.iter().skip(1).map(|t| t.id).sorted().collect_vec(),
+            targets.into_iter().sorted().collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_match_query() {
+        let mosaic = Mosaic::new();
+        mosaic.new_type("Position: { x: f32, y: f32 };").unwrap();
+        mosaic.new_type("Arrow: void;").unwrap();
+
+        let a = mosaic.new_object("Position", pars().set("x", 20.0f32).set("y", 0.0f32).ok());
+        let b = mosaic.new_object("void", void());
+        let c = mosaic.new_object("Position", pars().set("x", 5.0f32).set("y", 0.0f32).ok());
+        let d = mosaic.new_object("void", void());
+        let a_b = mosaic.new_arrow(&a, &b, "Arrow", void());
+        mosaic.new_arrow(&c, &d, "Arrow", void());
+        mosaic.new_arrow(&a, &d, "void", void());
+
+        let results = mosaic
+            .query("MATCH (a:Position)-[r:Arrow]->(b) WHERE a.x > 10 RETURN b, r")
+            .unwrap();
+        assert_eq!(vec!["b".to_string(), "r".to_string()], results.columns);
+        assert_eq!(
+            vec![vec![b.id, a_b.id]],
+            results
+                .rows
+                .iter()
+                .map(|row| row.iter().map(|t| t.id).collect_vec())
+                .collect_vec()
+        );
+
+        let sources = mosaic
+            .query("match (x)<-[:Arrow]-(y:Position) return y")
+            .unwrap();
+        assert_eq!(
+            vec![a.id, c.id],
+            sources.column("y").map(|t| t.id).sorted().collect_vec()
+        );
+
+        let shared = mosaic
+            .query("MATCH (p)-->(b), (p)-->(d) WHERE b.Position.x = 1 RETURN p")
+            .unwrap();
+        assert!(shared.rows.is_empty());
+
+        assert!(mosaic.query("MATCH (a) RETURN z").is_err());
+        assert!(mosaic.query("MATCH a RETURN a").is_err());
+    }
+
+    #[test]
+    fn test_weak_tiles() {
+        let mosaic = Mosaic::new();
+        let a = mosaic.new_object("void", void());
+        let b = mosaic.new_object("void", void());
+        let arrow = mosaic.new_arrow(&a, &b, "void", void());
+
+        let weak = mosaic.get_all().downgrade().collect_vec();
+        assert_eq!(3, weak.len());
+        assert_eq!(
+            a.downgrade(),
+            weak.iter().find(|w| w.id == a.id).cloned().unwrap()
+        );
+        assert_eq!(
+            vec![arrow.id],
+            weak.iter()
+                .filter(|w| w.id == a.id)
+                .cloned()
+                .upgrade()
+                .get_arrows()
+                .map(|t| t.id)
+                .collect_vec()
+        );
+
+        mosaic.delete_tile(arrow.id);
+        assert_eq!(2, weak.iter().cloned().upgrade().count());
+
+        // a weak tile doesn't keep the mosaic alive
+        drop((a, b, arrow));
+        mosaic.clear();
+        drop(mosaic);
+        assert!(weak.iter().all(|w| !w.is_alive() && w.mosaic().is_none()));
+    }
 }
@@ -0,0 +1,30 @@
+use crate::internals::{Tile, WeakTile};
+
+pub trait DowngradeTiles: Iterator<Item = Tile> + Sized {
+    /// Turns the tiles into `WeakTile`s, which don't keep their mosaic alive.
+    fn downgrade(self) -> impl Iterator<Item = WeakTile>;
+}
+
+impl<I> DowngradeTiles for I
+where
+    I: Iterator<Item = Tile>,
+{
+    fn downgrade(self) -> impl Iterator<Item = WeakTile> {
+        self.map(|tile| tile.downgrade())
+    }
+}
+
+pub trait UpgradeTiles: Iterator<Item = WeakTile> + Sized {
+    /// Pins the tiles back into `Tile`s, leaving out those that are gone, so the other
+    /// iterator adapters can carry on from them.
+    fn upgrade(self) -> impl Iterator<Item = Tile>;
+}
+
+impl<I> UpgradeTiles for I
+where
+    I: Iterator<Item = WeakTile>,
+{
+    fn upgrade(self) -> impl Iterator<Item = Tile> {
+        self.filter_map(|tile| tile.upgrade())
+    }
+}
@@ -1,6 +1,18 @@
 extern crate pest;
 extern crate pest_derive;
 
+#[cfg(feature = "bevy")]
+pub mod bevy_bridge;
 pub mod capabilities;
 pub mod internals;
 pub mod iterators;
+#[cfg(feature = "ffi")]
+pub mod mosaic_ffi;
+#[cfg(feature = "json")]
+pub mod mosaic_json;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mosaic_server;
+#[cfg(feature = "wasm")]
+pub mod mosaic_wasm;
+pub mod testing;
+pub mod workspace;
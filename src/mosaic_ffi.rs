@@ -0,0 +1,511 @@
+//! A C ABI, built with the `ffi` feature; `include/mosaic.h` is made from it by cbindgen.
+//!
+//! Mosaics are `MosaicHandle`s and tiles their ids. Pointers handed in must be null or valid,
+//! and strings nul-terminated UTF-8. Failures return `false`, `MOSAIC_NO_TILE` or null with a
+//! message for `mosaic_last_error`; what is handed out goes back through `mosaic_free_*`.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+    vec::IntoIter,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::{
+    internals::{
+        void, EntityId, Mosaic, MosaicCRUD, MosaicIO, MosaicIndices, MosaicTypelevelCRUD, Tile,
+        Value, S32,
+    },
+    iterators::tile_getters::TileGetters,
+};
+
+mod unit_tests;
+
+/// Returned in place of a tile id when no tile could be made or found.
+pub const MOSAIC_NO_TILE: u64 = u64::MAX;
+
+pub const MOSAIC_OBJECT: i32 = 0;
+pub const MOSAIC_ARROW: i32 = 1;
+pub const MOSAIC_DESCRIPTOR: i32 = 2;
+pub const MOSAIC_EXTENSION: i32 = 3;
+
+pub struct MosaicHandle(Arc<Mosaic>);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Runs `body`, turning errors and panics into `fallback` so neither crosses the boundary.
+fn guard<T>(fallback: T, body: impl FnOnce() -> anyhow::Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            fallback
+        }
+        Err(_) => {
+            set_last_error("Mosaic panicked".to_string());
+            fallback
+        }
+    }
+}
+
+unsafe fn mosaic<'a>(handle: *const MosaicHandle) -> anyhow::Result<&'a Arc<Mosaic>> {
+    handle
+        .as_ref()
+        .map(|h| &h.0)
+        .ok_or_else(|| anyhow!("The mosaic handle is null"))
+}
+
+unsafe fn text<'a>(string: *const c_char) -> anyhow::Result<&'a str> {
+    if string.is_null() {
+        return Err(anyhow!("Expected a string, got null"));
+    }
+
+    Ok(CStr::from_ptr(string).to_str()?)
+}
+
+fn tile(mosaic: &Arc<Mosaic>, id: u64) -> anyhow::Result<Tile> {
+    mosaic
+        .get(id as EntityId)
+        .ok_or_else(|| anyhow!("There is no tile {}", id))
+}
+
+fn component(mosaic: &Arc<Mosaic>, name: &str) -> anyhow::Result<()> {
+    match mosaic.component_registry.has_component_type(&name.into()) {
+        true => Ok(()),
+        false => Err(anyhow!("There is no component named {}", name)),
+    }
+}
+
+fn field(tile: &Tile, name: &str) -> anyhow::Result<Value> {
+    let name: S32 = name.into();
+    tile.data()
+        .into_iter()
+        .find(|(f, _)| *f == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| anyhow!("Tile {} has no field {}", tile.id, name))
+}
+
+/// Writes `value` into the field named `name`, after `convert` fits it to the field's datatype.
+fn set_field(
+    tile: &mut Tile,
+    name: &str,
+    convert: impl FnOnce(&Value) -> Option<Value>,
+) -> anyhow::Result<bool> {
+    let current = field(tile, name)?;
+    let value = convert(&current).ok_or_else(|| {
+        anyhow!(
+            "Field {} of tile {} is {:?}, which cannot take that value",
+            name,
+            tile.id,
+            current.get_datatype()
+        )
+    })?;
+    tile.try_set_field(name, value)?;
+    Ok(true)
+}
+
+fn hand_out_string(string: String) -> *mut c_char {
+    CString::new(string.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+/// Where to write how many elements are handed out; without it they could never be freed.
+unsafe fn length<'a>(len: *mut usize) -> anyhow::Result<&'a mut usize> {
+    len.as_mut()
+        .ok_or_else(|| anyhow!("Expected somewhere to write the length, got null"))
+}
+
+unsafe fn hand_out_ids(tiles: IntoIter<Tile>, len: *mut usize) -> anyhow::Result<*mut u64> {
+    let len = length(len)?;
+    let ids = tiles.map(|t| t.id as u64).collect_vec().into_boxed_slice();
+    *len = ids.len();
+    Ok(Box::into_raw(ids) as *mut u64)
+}
+
+/// The message of the last call on this thread that failed, or null. It stays valid until
+/// the next call that fails on this thread.
+#[no_mangle]
+pub extern "C" fn mosaic_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+#[no_mangle]
+pub extern "C" fn mosaic_new() -> *mut MosaicHandle {
+    Box::into_raw(Box::new(MosaicHandle(Mosaic::new())))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_free(handle: *mut MosaicHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_free_bytes(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_free_ids(ids: *mut u64, len: usize) {
+    if !ids.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ids, len)));
+    }
+}
+
+/// Adds component types, written the same way as for `new_type`.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_new_type(
+    handle: *const MosaicHandle,
+    definition: *const c_char,
+) -> bool {
+    guard(false, || {
+        mosaic(handle)?.new_type(text(definition)?)?;
+        Ok(true)
+    })
+}
+
+/// Makes an object with every field set to its default.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_new_object(
+    handle: *const MosaicHandle,
+    component_name: *const c_char,
+) -> u64 {
+    guard(MOSAIC_NO_TILE, || {
+        let mosaic = mosaic(handle)?;
+        let name = text(component_name)?;
+        component(mosaic, name)?;
+        Ok(mosaic.new_object(name, void()).id as u64)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_new_arrow(
+    handle: *const MosaicHandle,
+    source: u64,
+    target: u64,
+    component_name: *const c_char,
+) -> u64 {
+    guard(MOSAIC_NO_TILE, || {
+        let mosaic = mosaic(handle)?;
+        let name = text(component_name)?;
+        component(mosaic, name)?;
+        let (source, target) = (tile(mosaic, source)?, tile(mosaic, target)?);
+        Ok(mosaic.try_new_arrow(&source, &target, name, void())?.id as u64)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_new_descriptor(
+    handle: *const MosaicHandle,
+    subject: u64,
+    component_name: *const c_char,
+) -> u64 {
+    guard(MOSAIC_NO_TILE, || {
+        let mosaic = mosaic(handle)?;
+        let name = text(component_name)?;
+        component(mosaic, name)?;
+        let subject = tile(mosaic, subject)?;
+        Ok(mosaic.try_new_descriptor(&subject, name, void())?.id as u64)
+    })
+}
+
+/// Deletes the tile along with everything attached to it.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_delete_tile(handle: *const MosaicHandle, id: u64) -> bool {
+    guard(false, || {
+        let mosaic = mosaic(handle)?;
+        mosaic.delete_tile(tile(mosaic, id)?.id);
+        Ok(true)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_is_tile_valid(handle: *const MosaicHandle, id: u64) -> bool {
+    guard(false, || {
+        Ok(mosaic(handle)?.is_tile_valid(&(id as EntityId)))
+    })
+}
+
+/// One of `MOSAIC_OBJECT`, `MOSAIC_ARROW`, `MOSAIC_DESCRIPTOR`, or `MOSAIC_EXTENSION`; -1 if
+/// there is no such tile.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_tile_kind(handle: *const MosaicHandle, id: u64) -> i32 {
+    guard(-1, || {
+        let tile = tile(mosaic(handle)?, id)?;
+        Ok(if tile.is_object() {
+            MOSAIC_OBJECT
+        } else if tile.is_arrow() {
+            MOSAIC_ARROW
+        } else if tile.is_descriptor() {
+            MOSAIC_DESCRIPTOR
+        } else {
+            MOSAIC_EXTENSION
+        })
+    })
+}
+
+/// The source of an arrow, or the subject of a descriptor or extension; objects are their
+/// own source.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_tile_source(handle: *const MosaicHandle, id: u64) -> u64 {
+    guard(MOSAIC_NO_TILE, || {
+        Ok(tile(mosaic(handle)?, id)?.source_id() as u64)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_tile_target(handle: *const MosaicHandle, id: u64) -> u64 {
+    guard(MOSAIC_NO_TILE, || {
+        Ok(tile(mosaic(handle)?, id)?.target_id() as u64)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_tile_component(
+    handle: *const MosaicHandle,
+    id: u64,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let tile = tile(mosaic(handle)?, id)?;
+        Ok(hand_out_string(tile.component.to_string()))
+    })
+}
+
+/// Reads an integer or bool field into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_int(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+    out: *mut i64,
+) -> bool {
+    guard(false, || {
+        let tile = tile(mosaic(handle)?, id)?;
+        let name = text(field_name)?;
+        let value = match field(&tile, name)? {
+            Value::I8(v) => v as i64,
+            Value::I16(v) => v as i64,
+            Value::I32(v) => v as i64,
+            Value::I64(v) => v,
+            Value::U8(v) => v as i64,
+            Value::U16(v) => v as i64,
+            Value::U32(v) => v as i64,
+            Value::U64(v) => i64::try_from(v)?,
+            Value::BOOL(v) => v as i64,
+            other => return Err(anyhow!("Field {} is {:?}", name, other.get_datatype())),
+        };
+        *out.as_mut()
+            .ok_or_else(|| anyhow!("Nowhere to write {}", name))? = value;
+        Ok(true)
+    })
+}
+
+/// Reads a float field into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_float(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+    out: *mut f64,
+) -> bool {
+    guard(false, || {
+        let tile = tile(mosaic(handle)?, id)?;
+        let name = text(field_name)?;
+        let value = match field(&tile, name)? {
+            Value::F32(v) => v as f64,
+            Value::F64(v) => v,
+            other => return Err(anyhow!("Field {} is {:?}", name, other.get_datatype())),
+        };
+        *out.as_mut()
+            .ok_or_else(|| anyhow!("Nowhere to write {}", name))? = value;
+        Ok(true)
+    })
+}
+
+/// Reads an `s32` or `str` field into a new string.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_string(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let tile = tile(mosaic(handle)?, id)?;
+        let name = text(field_name)?;
+        match field(&tile, name)? {
+            Value::S32(v) => Ok(hand_out_string(v.to_string())),
+            Value::STR(v) => Ok(hand_out_string(v.to_string())),
+            other => Err(anyhow!("Field {} is {:?}", name, other.get_datatype())),
+        }
+    })
+}
+
+/// Writes an integer or bool field; fails if `value` doesn't fit.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_set_int(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+    value: i64,
+) -> bool {
+    guard(false, || {
+        let mut tile = tile(mosaic(handle)?, id)?;
+        set_field(&mut tile, text(field_name)?, |current| match current {
+            Value::I8(_) => i8::try_from(value).ok().map(Value::I8),
+            Value::I16(_) => i16::try_from(value).ok().map(Value::I16),
+            Value::I32(_) => i32::try_from(value).ok().map(Value::I32),
+            Value::I64(_) => Some(Value::I64(value)),
+            Value::U8(_) => u8::try_from(value).ok().map(Value::U8),
+            Value::U16(_) => u16::try_from(value).ok().map(Value::U16),
+            Value::U32(_) => u32::try_from(value).ok().map(Value::U32),
+            Value::U64(_) => u64::try_from(value).ok().map(Value::U64),
+            Value::BOOL(_) => Some(Value::BOOL(value != 0)),
+            _ => None,
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_set_float(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+    value: f64,
+) -> bool {
+    guard(false, || {
+        let mut tile = tile(mosaic(handle)?, id)?;
+        set_field(&mut tile, text(field_name)?, |current| match current {
+            Value::F32(_) => Some(Value::F32(value as f32)),
+            Value::F64(_) => Some(Value::F64(value)),
+            _ => None,
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_set_string(
+    handle: *const MosaicHandle,
+    id: u64,
+    field_name: *const c_char,
+    value: *const c_char,
+) -> bool {
+    guard(false, || {
+        let mut tile = tile(mosaic(handle)?, id)?;
+        let value = text(value)?;
+        set_field(&mut tile, text(field_name)?, |current| match current {
+            Value::S32(_) => Some(Value::S32(value.into())),
+            Value::STR(_) => Some(Value::STR(value.into())),
+            _ => None,
+        })
+    })
+}
+
+/// Every tile, by id; the number of ids goes into `len`.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_all(handle: *const MosaicHandle, len: *mut usize) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let tiles = mosaic(handle)?.get_all().sorted_by_key(|t| t.id);
+        hand_out_ids(tiles, len)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_tiles_with_component(
+    handle: *const MosaicHandle,
+    component_name: *const c_char,
+    len: *mut usize,
+) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let tiles = mosaic(handle)?.get_tiles_with_component(text(component_name)?);
+        hand_out_ids(tiles, len)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_arrows_from(
+    handle: *const MosaicHandle,
+    id: u64,
+    len: *mut usize,
+) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let tiles = tile(mosaic(handle)?, id)?.iter().get_arrows_from();
+        hand_out_ids(tiles, len)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_arrows_into(
+    handle: *const MosaicHandle,
+    id: u64,
+    len: *mut usize,
+) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let tiles = tile(mosaic(handle)?, id)?.iter().get_arrows_into();
+        hand_out_ids(tiles, len)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_get_descriptors(
+    handle: *const MosaicHandle,
+    id: u64,
+    len: *mut usize,
+) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let tiles = tile(mosaic(handle)?, id)?.iter().get_descriptors();
+        hand_out_ids(tiles, len)
+    })
+}
+
+/// Saves the whole mosaic; the number of bytes goes into `len`.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_save(handle: *const MosaicHandle, len: *mut usize) -> *mut u8 {
+    guard(ptr::null_mut(), || {
+        let mosaic = mosaic(handle)?;
+        let len = length(len)?;
+        let bytes = mosaic.save().into_boxed_slice();
+        *len = bytes.len();
+        Ok(Box::into_raw(bytes) as *mut u8)
+    })
+}
+
+/// Loads what `mosaic_save` gave out, next to the tiles already there.
+#[no_mangle]
+pub unsafe extern "C" fn mosaic_load(
+    handle: *const MosaicHandle,
+    bytes: *const u8,
+    len: usize,
+) -> bool {
+    guard(false, || {
+        if bytes.is_null() {
+            return Err(anyhow!("Expected bytes to load, got null"));
+        }
+
+        mosaic(handle)?.load(std::slice::from_raw_parts(bytes, len))?;
+        Ok(true)
+    })
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod mosaic_ffi_tests {
+    use std::ffi::{CStr, CString};
+
+    use crate::mosaic_ffi::*;
+
+    fn c(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    #[test]
+    fn test_ffi_roundtrip() {
+        unsafe {
+            let mosaic = mosaic_new();
+            assert!(mosaic_new_type(
+                mosaic,
+                c("Position: { x: i32, name: str };").as_ptr()
+            ));
+            assert!(mosaic_new_type(mosaic, c("Link: f64;").as_ptr()));
+
+            let a = mosaic_new_object(mosaic, c("Position").as_ptr());
+            let b = mosaic_new_object(mosaic, c("Position").as_ptr());
+            let link = mosaic_new_arrow(mosaic, a, b, c("Link").as_ptr());
+            assert_eq!(MOSAIC_ARROW, mosaic_tile_kind(mosaic, link));
+            assert_eq!(a, mosaic_tile_source(mosaic, link));
+            assert_eq!(b, mosaic_tile_target(mosaic, link));
+
+            assert!(mosaic_set_int(mosaic, a, c("x").as_ptr(), 42));
+            assert!(mosaic_set_string(
+                mosaic,
+                a,
+                c("name").as_ptr(),
+                c("a").as_ptr()
+            ));
+            assert!(mosaic_set_float(mosaic, link, c("self").as_ptr(), 0.5));
+
+            let mut x = 0;
+            assert!(mosaic_get_int(mosaic, a, c("x").as_ptr(), &mut x));
+            assert_eq!(42, x);
+            let name = mosaic_get_string(mosaic, a, c("name").as_ptr());
+            assert_eq!("a", CStr::from_ptr(name).to_str().unwrap());
+            mosaic_free_string(name);
+
+            let mut len = 0;
+            let arrows = mosaic_get_arrows_from(mosaic, a, &mut len);
+            assert_eq!(&[link], std::slice::from_raw_parts(arrows, len));
+            mosaic_free_ids(arrows, len);
+
+            let bytes = mosaic_save(mosaic, &mut len);
+            let copy = mosaic_new();
+            assert!(mosaic_load(copy, bytes, len));
+            mosaic_free_bytes(bytes, len);
+            let mut weight = 0.0;
+            assert!(mosaic_get_float(
+                copy,
+                link,
+                c("self").as_ptr(),
+                &mut weight
+            ));
+            assert_eq!(0.5, weight);
+
+            mosaic_free(copy);
+            mosaic_free(mosaic);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        unsafe {
+            let mosaic = mosaic_new();
+            assert!(mosaic_new_type(mosaic, c("Small: u8;").as_ptr()));
+            assert_eq!(
+                MOSAIC_NO_TILE,
+                mosaic_new_object(mosaic, c("Missing").as_ptr())
+            );
+            let error = CStr::from_ptr(mosaic_last_error()).to_str().unwrap();
+            assert!(error.contains("Missing"));
+
+            let tile = mosaic_new_object(mosaic, c("Small").as_ptr());
+            assert!(!mosaic_set_int(mosaic, tile, c("self").as_ptr(), 300));
+            assert!(!mosaic_set_float(mosaic, tile, c("self").as_ptr(), 1.0));
+            assert!(mosaic_get_string(mosaic, tile, c("self").as_ptr()).is_null());
+            assert_eq!(-1, mosaic_tile_kind(mosaic, 1000));
+            assert!(!mosaic_new_type(std::ptr::null(), c("Foo: u8;").as_ptr()));
+            // lengths have to go somewhere, or what's handed out could never be freed
+            assert!(mosaic_get_all(mosaic, std::ptr::null_mut()).is_null());
+            assert!(mosaic_save(mosaic, std::ptr::null_mut()).is_null());
+
+            assert!(mosaic_delete_tile(mosaic, tile));
+            assert!(!mosaic_is_tile_valid(mosaic, tile));
+            mosaic_free(mosaic);
+        }
+    }
+
+    #[test]
+    fn test_ffi_header_declares_every_function() {
+        let header = include_str!("../../include/mosaic.h");
+        let source = include_str!("../mosaic_ffi.rs");
+        for line in source.lines().filter(|l| l.contains("extern \"C\" fn ")) {
+            let name = line.split("fn ").nth(1).unwrap().split('(').next().unwrap();
+            assert!(
+                header.contains(&format!(" *{}(", name)) || header.contains(&format!(" {}(", name)),
+                "include/mosaic.h is missing {}, regenerate it with cbindgen",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use serde_json::{json, Map, Value as Json};
+
+use crate::internals::{
+    ComponentValues, Datatype, EntityId, Mosaic, MosaicError, MosaicIO, Tile, TileRef, TileType,
+    Uuid, Value, S32,
+};
+
+/// The datatypes of the fields of `component`, by the names values are given under.
+fn field_types(mosaic: &Arc<Mosaic>, component: S32) -> anyhow::Result<HashMap<String, Datatype>> {
+    let component_type = mosaic.component_registry.get_component_type(component)?;
+    let has_self_field = component_type.has_self_field();
+    Ok(component_type
+        .get_fields()
+        .into_iter()
+        .map(|field| match has_self_field {
+            true => ("self".to_string(), field.datatype),
+            false => (field.name.to_string(), field.datatype),
+        })
+        .collect())
+}
+
+/// Reads `fields`, a JSON object of field values by name, as values of the fields of
+/// `component`. Missing fields are left out, to take their defaults.
+pub fn fields_from_json(
+    mosaic: &Arc<Mosaic>,
+    component: S32,
+    fields: &Json,
+) -> anyhow::Result<ComponentValues> {
+    let types = field_types(mosaic, component)?;
+    let fields = match fields {
+        Json::Null => return Ok(vec![]),
+        Json::Object(fields) => fields,
+        _ => return Err(anyhow!("Fields of {} have to be an object", component)),
+    };
+
+    fields
+        .iter()
+        .map(|(name, value)| {
+            let datatype = types.get(name).ok_or_else(|| MosaicError::UnknownField {
+                component,
+                field: S32::truncating(name),
+            })?;
+            Ok((S32::new(name)?, value_from_json(datatype, value)?))
+        })
+        .collect()
+}
+
+/// Numbers, text and booleans as they are; datetimes and durations as text, sums as
+/// `{"variant": value}`, refs as `{"mosaic", "id"}` and blobs as `{"blob", "len"}`.
+pub fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::UNIT => Json::Null,
+        Value::I8(v) => json!(v),
+        Value::I16(v) => json!(v),
+        Value::I32(v) => json!(v),
+        Value::I64(v) => json!(v),
+        Value::U8(v) => json!(v),
+        Value::U16(v) => json!(v),
+        Value::U32(v) => json!(v),
+        Value::U64(v) => json!(v),
+        Value::F32(v) => json!(v),
+        Value::F64(v) => json!(v),
+        Value::S32(v) => json!(v.to_string()),
+        Value::STR(v) => json!(v.to_string()),
+        Value::BOOL(v) => json!(v),
+        Value::REF(r) => json!({ "mosaic": r.mosaic.to_string(), "id": r.id }),
+        Value::BLOB(blob) => json!({ "blob": blob.id().to_string(), "len": blob.len() }),
+        Value::DATETIME(v) => json!(v.to_string()),
+        Value::DURATION(v) => json!(v.to_string()),
+        Value::SUM(variant, inner) => {
+            let mut sum = Map::new();
+            sum.insert(variant.to_string(), value_to_json(inner));
+            Json::Object(sum)
+        }
+        Value::ARR(values) | Value::LIST(values) => {
+            Json::Array(values.iter().map(value_to_json).collect())
+        }
+    }
+}
+
+/// Reads a value of `datatype` written as `value_to_json` writes it. Blobs and nested
+/// components can't be read back.
+pub fn value_from_json(datatype: &Datatype, json: &Json) -> anyhow::Result<Value> {
+    let mismatch = || anyhow!("{} doesn't fit datatype {:?}", json, datatype);
+    let int = || json.as_i64().ok_or_else(mismatch);
+    let uint = || json.as_u64().ok_or_else(mismatch);
+    let float = || json.as_f64().ok_or_else(mismatch);
+    let text = || json.as_str().ok_or_else(mismatch);
+    let elements = || json.as_array().ok_or_else(mismatch);
+
+    let value = match datatype {
+        Datatype::UNIT => Value::UNIT,
+        Datatype::I8 => Value::I8(int()?.try_into().map_err(|_| mismatch())?),
+        Datatype::I16 => Value::I16(int()?.try_into().map_err(|_| mismatch())?),
+        Datatype::I32 => Value::I32(int()?.try_into().map_err(|_| mismatch())?),
+        Datatype::I64 => Value::I64(int()?),
+        Datatype::U8 => Value::U8(uint()?.try_into().map_err(|_| mismatch())?),
+        Datatype::U16 => Value::U16(uint()?.try_into().map_err(|_| mismatch())?),
+        Datatype::U32 => Value::U32(uint()?.try_into().map_err(|_| mismatch())?),
+        Datatype::U64 => Value::U64(uint()?),
+        Datatype::F32 => Value::F32(float()? as f32),
+        Datatype::F64 => Value::F64(float()?),
+        Datatype::S32 => Value::S32(S32::new(text()?)?),
+        Datatype::STR => Value::STR(text()?.into()),
+        Datatype::BOOL => Value::BOOL(json.as_bool().ok_or_else(mismatch)?),
+        Datatype::DATETIME | Datatype::DURATION => {
+            Value::parse_temporal(datatype, text()?).unwrap()?
+        }
+        Datatype::REF => {
+            let mosaic = json["mosaic"].as_str().ok_or_else(mismatch)?;
+            let id = json["id"].as_u64().ok_or_else(mismatch)?;
+            Value::REF(TileRef {
+                mosaic: Uuid::parse_str(mosaic)?,
+                id: id as EntityId,
+            })
+        }
+        Datatype::ARR(element, size) => {
+            let values = elements()?;
+            if values.len() != *size {
+                return Err(mismatch());
+            }
+            Value::ARR(
+                values
+                    .iter()
+                    .map(|v| value_from_json(element, v))
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        }
+        Datatype::LIST(element) => Value::LIST(
+            elements()?
+                .iter()
+                .map(|v| value_from_json(element, v))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Datatype::SUM(variants) => {
+            let (name, inner) = json
+                .as_object()
+                .filter(|sum| sum.len() == 1)
+                .and_then(|sum| sum.iter().next())
+                .ok_or_else(mismatch)?;
+            let variant = variants
+                .iter()
+                .find(|v| v.name.is(name))
+                .ok_or_else(mismatch)?;
+            Value::SUM(
+                variant.name,
+                Box::new(value_from_json(&variant.datatype, inner)?),
+            )
+        }
+        Datatype::COMP(_) | Datatype::BLOB => {
+            return Err(anyhow!(
+                "Fields of datatype {:?} can't be read from json",
+                datatype
+            ))
+        }
+    };
+
+    Ok(value)
+}
+
+/// `{"id", "kind", "component", "fields"}`, with the `source` and `target` of arrows and the
+/// `subject` of descriptors and extensions.
+pub fn tile_to_json(tile: &Tile) -> Json {
+    let fields = tile
+        .data()
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value_to_json(&value)))
+        .collect::<Map<_, _>>();
+    let mut record = json!({
+        "id": tile.id,
+        "component": tile.component.to_string(),
+        "fields": fields,
+    });
+
+    let kind = match tile.tile_type {
+        TileType::Object => "object",
+        TileType::Arrow { source, target } => {
+            record["source"] = json!(source);
+            record["target"] = json!(target);
+            "arrow"
+        }
+        TileType::Descriptor { subject } => {
+            record["subject"] = json!(subject);
+            "descriptor"
+        }
+        TileType::Extension { subject } => {
+            record["subject"] = json!(subject);
+            "extension"
+        }
+    };
+    record["kind"] = json!(kind);
+    record
+}
+
+/// The whole of `mosaic`: `{"uuid", "types", "tiles"}`, with the type definitions and the
+/// tiles in order of id.
+pub fn mosaic_to_json(mosaic: &Arc<Mosaic>) -> Json {
+    let types = mosaic
+        .component_registry
+        .component_definitions
+        .read()
+        .unwrap()
+        .clone();
+    let tiles = mosaic
+        .get_all()
+        .sorted_by_key(|t| t.id)
+        .map(|t| tile_to_json(&t))
+        .collect_vec();
+    json!({
+        "uuid": mosaic.uuid().to_string(),
+        "types": types,
+        "tiles": tiles,
+    })
+}
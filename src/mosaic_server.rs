@@ -0,0 +1,12 @@
+#[cfg(feature = "server")]
+pub mod http;
+pub mod protocol;
+pub mod remote_mosaic;
+pub mod server;
+
+mod unit_tests;
+
+#[cfg(feature = "server")]
+pub use http::*;
+pub use remote_mosaic::*;
+pub use server::*;
@@ -0,0 +1,598 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use log::{error, info};
+use serde_json::{json, Value as Json};
+
+use crate::{
+    internals::{
+        EntityId, Mosaic, MosaicCRUD, MosaicError, MosaicIO, MosaicObservable, MosaicObserver,
+        MosaicTypelevelCRUD, Tile, TileFieldSetter, TileType, Value,
+    },
+    iterators::{pagination::Page, query::MosaicQuery, query_builder::MosaicQueryBuilder},
+    mosaic_json::{fields_from_json, tile_to_json, value_to_json},
+};
+
+/// How often a quiet event stream is written to, so clients that went away are noticed.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The longest request line or header line taken.
+const MAX_LINE_SIZE: usize = 8 * 1024;
+
+/// Shares a mosaic over HTTP with JSON bodies, on `/types`, `/tiles`, `/query`, `/save`,
+/// `/load` and `/events`; the limits on clients are set with `HttpOptions`.
+pub struct HttpServer {
+    mosaic: Arc<Mosaic>,
+    listener: TcpListener,
+    options: Arc<HttpOptions>,
+    connections: Arc<AtomicUsize>,
+}
+
+/// The limits an `HttpServer` puts on its clients.
+#[derive(Clone, Debug)]
+pub struct HttpOptions {
+    /// How long a client may take to send its request or to take in a response.
+    pub timeout: Duration,
+    /// How many clients are served at once; the ones past it are answered with 503.
+    pub max_connections: usize,
+    /// The largest request body taken, answered with 413 past it.
+    pub max_body_size: usize,
+    /// The largest save `POST /load` takes.
+    pub max_load_size: usize,
+    /// How many changes may wait for an `/events` client before it is dropped as too slow.
+    pub max_pending_events: usize,
+    /// The token every request must bring as `Authorization: Bearer <token>`, answered with
+    /// 401 otherwise. `POST /load` replaces the whole mosaic, so without a token it is refused.
+    pub token: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            timeout: Duration::from_secs(30),
+            max_connections: 64,
+            max_body_size: 1024 * 1024,
+            max_load_size: 64 * 1024 * 1024,
+            max_pending_events: 1024,
+            token: None,
+        }
+    }
+}
+
+/// Counts a client as served until dropped.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl HttpServer {
+    pub fn bind<A: ToSocketAddrs>(mosaic: &Arc<Mosaic>, addr: A) -> anyhow::Result<HttpServer> {
+        Self::bind_with(mosaic, addr, HttpOptions::default())
+    }
+
+    pub fn bind_with<A: ToSocketAddrs>(
+        mosaic: &Arc<Mosaic>,
+        addr: A,
+        options: HttpOptions,
+    ) -> anyhow::Result<HttpServer> {
+        Ok(HttpServer {
+            mosaic: Arc::clone(mosaic),
+            listener: TcpListener::bind(addr)?,
+            options: Arc::new(options),
+            connections: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections forever, serving each client on its own thread, up to
+    /// `max_connections` of them at once.
+    pub fn serve(self) {
+        for stream in self.listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Failed to accept http client: {}", e);
+                    continue;
+                }
+            };
+
+            let timeout = Some(self.options.timeout);
+            if let Err(e) = stream
+                .set_read_timeout(timeout)
+                .and_then(|_| stream.set_write_timeout(timeout))
+            {
+                error!("Failed to set up http client: {}", e);
+                continue;
+            }
+
+            if self.connections.fetch_add(1, Ordering::SeqCst) >= self.options.max_connections {
+                self.connections.fetch_sub(1, Ordering::SeqCst);
+                let busy = HttpResponse::error(503, "Too many clients, try again later");
+                if let Err(e) = busy.write_to(&mut stream) {
+                    error!("Failed to turn away http client: {}", e);
+                }
+                continue;
+            }
+
+            let connection = Connection(Arc::clone(&self.connections));
+            let mosaic = Arc::clone(&self.mosaic);
+            let options = Arc::clone(&self.options);
+            std::thread::spawn(move || {
+                serve_client(mosaic, &options, &mut stream);
+                // freed before the client sees the connection close
+                drop(connection);
+            });
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        std::thread::spawn(move || self.serve())
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    params: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn json(&self) -> anyhow::Result<Json> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    fn text(&self) -> anyhow::Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn json(status: u16, body: Json) -> HttpResponse {
+        HttpResponse {
+            status,
+            content_type: "application/json",
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    fn bytes(body: Vec<u8>) -> HttpResponse {
+        HttpResponse {
+            status: 200,
+            content_type: "application/octet-stream",
+            body,
+        }
+    }
+
+    fn done() -> HttpResponse {
+        HttpResponse {
+            status: 204,
+            content_type: "text/plain",
+            body: vec![],
+        }
+    }
+
+    fn error(status: u16, message: &str) -> HttpResponse {
+        HttpResponse::json(status, json!({ "error": message }))
+    }
+
+    fn not_found() -> HttpResponse {
+        HttpResponse::error(404, "Not found")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A request turned away with `status` before its body is read.
+#[derive(Debug)]
+struct Refused(u16, String);
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.1)
+    }
+}
+
+impl std::error::Error for Refused {}
+
+/// Compares tokens without stopping at the first difference, so timing tells nothing of them.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Reads a line of at most `MAX_LINE_SIZE` bytes, returning how many were read.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> anyhow::Result<usize> {
+    let read = reader.by_ref().take(MAX_LINE_SIZE as u64).read_line(line)?;
+    if read == MAX_LINE_SIZE && !line.ends_with('\n') {
+        return Err(anyhow!(
+            "Request line or header longer than {} bytes",
+            MAX_LINE_SIZE
+        ));
+    }
+    Ok(read)
+}
+
+fn read_request<R: BufRead>(
+    reader: &mut R,
+    options: &HttpOptions,
+) -> anyhow::Result<Option<HttpRequest>> {
+    let mut line = String::new();
+    if read_line(reader, &mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(anyhow!("Malformed request line {}", line.trim()));
+    };
+
+    let mut length = 0;
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        if read_line(reader, &mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse()?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    if let Some(token) = &options.token {
+        let given = authorization
+            .as_deref()
+            .and_then(|a| a.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| same_token(given.trim(), token)) {
+            return Err(Refused(401, "Missing or wrong bearer token".to_string()).into());
+        }
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path);
+    let loading = method.eq_ignore_ascii_case("POST") && path.trim_matches('/') == "load";
+    if loading && options.token.is_none() {
+        return Err(Refused(403, "Loading needs the server to have a token".to_string()).into());
+    }
+
+    let most = match loading {
+        true => options.max_load_size,
+        false => options.max_body_size,
+    };
+    if length > most {
+        return Err(Refused(
+            413,
+            format!(
+                "Cannot take a body of {} bytes, the most is {}",
+                length, most
+            ),
+        )
+        .into());
+    }
+
+    // grown as the body comes in, rather than allocated up front for what the client claims
+    let mut body = vec![];
+    reader.by_ref().take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length {
+        return Err(anyhow!(
+            "Request body ended after {} of {} bytes",
+            body.len(),
+            length
+        ));
+    }
+
+    let params = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (name, value) = p.split_once('=').unwrap_or((p, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+
+    Ok(Some(HttpRequest {
+        method: method.to_uppercase(),
+        path,
+        params,
+        body,
+    }))
+}
+
+fn serve_client(mosaic: Arc<Mosaic>, options: &HttpOptions, stream: &mut TcpStream) {
+    info!("Http client connected: {:?}", stream.peer_addr());
+
+    let request = match stream.try_clone() {
+        Ok(reader) => read_request(&mut BufReader::new(reader), options),
+        Err(e) => Err(e.into()),
+    };
+
+    let response = match request {
+        Ok(None) => return,
+        Ok(Some(request)) if request.method == "GET" && request.path == "/events" => {
+            return stream_events(&mosaic, options, stream)
+        }
+        Ok(Some(request)) => catch_unwind(AssertUnwindSafe(|| handle_request(&mosaic, &request)))
+            .unwrap_or_else(|_| Ok(HttpResponse::error(500, "Request panicked on the server")))
+            .unwrap_or_else(|e| HttpResponse::error(400, &e.to_string())),
+        Err(e) => match e.downcast_ref::<Refused>() {
+            Some(Refused(status, message)) => HttpResponse::error(*status, message),
+            None => HttpResponse::error(400, &e.to_string()),
+        },
+    };
+
+    if let Err(e) = response.write_to(stream) {
+        error!("Failed to respond to http client: {}", e);
+    }
+}
+
+fn handle_request(mosaic: &Arc<Mosaic>, request: &HttpRequest) -> anyhow::Result<HttpResponse> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect_vec();
+
+    let response = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["types"]) => HttpResponse::json(
+            200,
+            json!(mosaic
+                .component_registry
+                .component_definitions
+                .read()
+                .unwrap()
+                .clone()),
+        ),
+        ("POST", ["types"]) => {
+            mosaic.new_type(request.text()?)?;
+            HttpResponse::done()
+        }
+        ("GET", ["tiles"]) => {
+            let mut query = mosaic.build_query();
+            if let Some(component) = request.params.get("component") {
+                query = query.with_component(component);
+            }
+            if let Some(cursor) = request.params.get("after") {
+                query = query.after(cursor.parse()?);
+            }
+            let page = match request.params.get("limit") {
+                Some(limit) => query.page(limit.parse()?),
+                None => Page {
+                    tiles: query.execute().collect_vec(),
+                    next: None,
+                },
+            };
+            HttpResponse::json(
+                200,
+                json!({
+                    "tiles": page.tiles.iter().map(tile_to_json).collect_vec(),
+                    "next": page.next.map(|cursor| cursor.to_string()),
+                }),
+            )
+        }
+        ("POST", ["tiles"]) => {
+            HttpResponse::json(201, tile_to_json(&create_tile(mosaic, &request.json()?)?))
+        }
+        ("GET", ["tiles", id]) => match mosaic.get(id.parse()?) {
+            Some(tile) => HttpResponse::json(200, tile_to_json(&tile)),
+            None => HttpResponse::not_found(),
+        },
+        ("PATCH", ["tiles", id]) => match mosaic.get(id.parse()?) {
+            Some(mut tile) => {
+                let fields = fields_from_json(mosaic, tile.component, &request.json()?)?;
+                for (name, value) in fields {
+                    tile.try_set(&name.to_string(), value)?;
+                }
+                HttpResponse::json(200, tile_to_json(&tile))
+            }
+            None => HttpResponse::not_found(),
+        },
+        ("DELETE", ["tiles", id]) => {
+            let id: EntityId = id.parse()?;
+            match mosaic.is_tile_valid(&id) {
+                true => {
+                    mosaic.delete_tile(id);
+                    HttpResponse::done()
+                }
+                false => HttpResponse::not_found(),
+            }
+        }
+        ("GET", ["query"]) => {
+            let query = request
+                .params
+                .get("q")
+                .ok_or_else(|| anyhow!("Missing query parameter q"))?;
+            let tiles = mosaic
+                .query_str(query)?
+                .map(|t| tile_to_json(&t))
+                .collect_vec();
+            HttpResponse::json(200, json!({ "tiles": tiles }))
+        }
+        ("GET", ["save"]) => HttpResponse::bytes(mosaic.save()),
+        ("POST", ["load"]) => {
+            mosaic.load(&request.body)?;
+            HttpResponse::done()
+        }
+        _ => HttpResponse::not_found(),
+    };
+
+    Ok(response)
+}
+
+fn create_tile(mosaic: &Arc<Mosaic>, body: &Json) -> anyhow::Result<Tile> {
+    let component = body["component"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing component"))?;
+    let fields = fields_from_json(mosaic, component.into(), &body["fields"])?;
+    let end = |key: &str| -> anyhow::Result<EntityId> {
+        let id = body[key]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Missing {}", key))? as EntityId;
+        match mosaic.is_tile_valid(&id) {
+            true => Ok(id),
+            false => Err(MosaicError::InvalidTile(id).into()),
+        }
+    };
+
+    match body["kind"].as_str().unwrap_or("object") {
+        "object" => Ok(mosaic.new_object(component, fields)),
+        "arrow" => mosaic.try_new_arrow(&end("source")?, &end("target")?, component, fields),
+        "descriptor" => mosaic.try_new_descriptor(&end("subject")?, component, fields),
+        "extension" => Ok(mosaic.new_extension(&end("subject")?, component, fields)),
+        kind => Err(anyhow!("There are no {} tiles", kind)),
+    }
+}
+
+/// Hands the changes to a mosaic to the connection streaming them, see `stream_events`.
+struct EventStream {
+    events: Mutex<SyncSender<Json>>,
+    lagging: AtomicBool,
+}
+
+impl EventStream {
+    fn send(&self, event: Json) {
+        // never blocks the change being made; a client that can't keep up is dropped instead,
+        // and one that went away already unsubscribes once it notices
+        if let Err(TrySendError::Full(_)) = self.events.lock().unwrap().try_send(event) {
+            self.lagging.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl MosaicObserver for EventStream {
+    fn on_tile_created(&self, tile: &Tile) {
+        self.send(json!({ "event": "created", "tile": tile_to_json(tile) }));
+    }
+
+    fn on_tile_deleted(&self, tile: &Tile) {
+        self.send(json!({ "event": "deleted", "id": tile.id }));
+    }
+
+    fn on_field_changed(&self, tile: &Tile, field: &str, _before: &Value, after: &Value) {
+        self.send(json!({
+            "event": "field_changed",
+            "id": tile.id,
+            "field": field,
+            "value": value_to_json(after),
+        }));
+    }
+
+    fn on_arrow_reconnected(&self, arrow: &Tile, _before: (EntityId, EntityId)) {
+        self.send(json!({ "event": "reconnected", "tile": tile_to_json(arrow) }));
+    }
+
+    fn on_tile_retyped(&self, tile: &Tile, _before: TileType) {
+        self.send(json!({ "event": "retyped", "tile": tile_to_json(tile) }));
+    }
+}
+
+/// Writes each change to the mosaic to `stream` as a server-sent event, until the client
+/// goes away or falls `max_pending_events` behind.
+fn stream_events(mosaic: &Arc<Mosaic>, options: &HttpOptions, stream: &mut TcpStream) {
+    let (sender, receiver) = mpsc::sync_channel(options.max_pending_events);
+    let events = Arc::new(EventStream {
+        events: Mutex::new(sender),
+        lagging: AtomicBool::new(false),
+    });
+    let subscription = mosaic.subscribe(Arc::clone(&events) as Arc<dyn MosaicObserver>);
+
+    let head =
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n";
+    let mut written = stream.write_all(head.as_bytes());
+    while written.is_ok() {
+        if events.lagging.load(Ordering::SeqCst) {
+            info!("Dropping http client that fell behind on events");
+            break;
+        }
+        written = match receiver.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => write!(stream, "data: {}\n\n", event),
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keep-alive\n\n"),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        .and_then(|_| stream.flush());
+    }
+
+    mosaic.unsubscribe(subscription);
+}
@@ -0,0 +1,467 @@
+use std::io::{Read, Write};
+
+use anyhow::anyhow;
+
+use crate::internals::{
+    Blob, ComponentValues, DateTime, Duration, EntityId, FromByteArray, Logging, TileRef, TileType,
+    ToByteArray, Value, Version, S32,
+};
+
+/// The largest payload a frame may carry. Saves and deltas go in a single frame, so this is
+/// also the largest mosaic that can be moved over the wire.
+pub const MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+/// How deep sums, arrays and lists may be nested inside one another in a payload.
+pub const MAX_VALUE_DEPTH: usize = 64;
+
+/// Every message is sent as a frame: a big-endian `u32` length followed by the payload,
+/// where the first byte of the payload is the opcode of the request or response.
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> anyhow::Result<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(anyhow!(
+            "Cannot send a frame of {} bytes, the most is {}",
+            payload.len(),
+            MAX_FRAME_SIZE
+        ));
+    }
+
+    writer.write_all(&u32::try_from(payload.len())?.to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a frame, failing on one longer than `MAX_FRAME_SIZE`. The payload is read as it
+/// arrives rather than allocated up front, so a peer can't reserve memory it doesn't send.
+pub fn read_frame<R: Read>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow!(
+            "Frame of {} bytes is over the most of {}",
+            len,
+            MAX_FRAME_SIZE
+        ));
+    }
+
+    let mut payload = vec![];
+    reader.take(len as u64).read_to_end(&mut payload)?;
+    if payload.len() < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(payload)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    NewType(String),
+    GetTypes,
+    NewObject(S32, ComponentValues),
+    NewSpecificObject(EntityId, S32),
+    NewArrow(EntityId, EntityId, S32, ComponentValues),
+    NewDescriptor(EntityId, S32, ComponentValues),
+    NewExtension(EntityId, S32, ComponentValues),
+    DeleteTile(EntityId),
+    IsTileValid(EntityId),
+    Get(EntityId),
+    GetAll,
+    Query(S32),
+    Save,
+    Load(Vec<u8>),
+    Clear,
+    Version,
+    SaveDelta(Version),
+    ApplyDelta(Vec<u8>),
+    RedefineType(String),
+}
+
+/// A tile as it travels over the wire: its identity, shape, and the binary layout of its data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileRecord {
+    pub id: EntityId,
+    pub tile_type: TileType,
+    pub component: S32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Done,
+    Bool(bool),
+    Bytes(Vec<u8>),
+    Types(Vec<String>),
+    Tiles(Vec<TileRecord>),
+    Error(String),
+    Number(usize),
+}
+
+#[derive(Default)]
+struct PayloadWriter {
+    data: Vec<u8>,
+}
+
+impl PayloadWriter {
+    fn opcode(mut self, op: u8) -> Self {
+        self.data.push(op);
+        self
+    }
+
+    fn id(mut self, id: EntityId) -> Self {
+        self.data.extend(id.to_byte_array());
+        self
+    }
+
+    fn name(mut self, name: &S32) -> Self {
+        self.data.extend(name.to_byte_array());
+        self
+    }
+
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.data.extend((bytes.len() as u64).to_byte_array());
+        self.data.extend(bytes);
+        self
+    }
+
+    fn string(self, s: &str) -> Self {
+        self.bytes(s.as_bytes())
+    }
+
+    fn values(mut self, values: &ComponentValues) -> Self {
+        self.data.extend((values.len() as u64).to_byte_array());
+        for (name, value) in values {
+            self = self.name(name).value(value);
+        }
+        self
+    }
+
+    fn value(mut self, value: &Value) -> Self {
+        let tag: u8 = match value {
+            Value::UNIT => 0,
+            Value::I8(_) => 1,
+            Value::I16(_) => 2,
+            Value::I32(_) => 3,
+            Value::I64(_) => 4,
+            Value::U8(_) => 5,
+            Value::U16(_) => 6,
+            Value::U32(_) => 7,
+            Value::U64(_) => 8,
+            Value::F32(_) => 9,
+            Value::F64(_) => 10,
+            Value::S32(_) => 11,
+            Value::STR(_) => 12,
+            Value::BOOL(_) => 13,
+            Value::REF(_) => 17,
+            Value::DATETIME(_) => 19,
+            Value::DURATION(_) => 20,
+            // the bytes go along in full, the other side may not have the blob
+            Value::BLOB(blob) => {
+                self.data.push(18);
+                return self.bytes(blob.bytes());
+            }
+            Value::SUM(variant, inner) => {
+                self.data.push(14);
+                return self.name(variant).value(inner);
+            }
+            Value::ARR(values) => return self.elements(15, values),
+            Value::LIST(values) => return self.elements(16, values),
+        };
+        self.data.push(tag);
+        self.data.extend(value.to_byte_array());
+        self
+    }
+
+    fn elements(mut self, tag: u8, values: &[Value]) -> Self {
+        self.data.push(tag);
+        self.data.extend((values.len() as u64).to_byte_array());
+        values.iter().fold(self, |payload, v| payload.value(v))
+    }
+
+    fn tile_type(self, tile_type: &TileType) -> Self {
+        match tile_type {
+            TileType::Object => self.opcode(0),
+            TileType::Arrow { source, target } => self.opcode(1).id(*source).id(*target),
+            TileType::Descriptor { subject } => self.opcode(2).id(*subject),
+            TileType::Extension { subject } => self.opcode(3).id(*subject),
+        }
+    }
+
+    fn record(self, record: &TileRecord) -> Self {
+        self.id(record.id)
+            .tile_type(&record.tile_type)
+            .name(&record.component)
+            .bytes(&record.data)
+    }
+
+    fn done(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+struct PayloadReader<'a> {
+    data: &'a [u8],
+    ptr: usize,
+    depth: usize,
+}
+
+impl<'a> PayloadReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        PayloadReader {
+            data,
+            ptr: 0,
+            depth: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        // lengths come from the peer, so `ptr + len` may not fit
+        if len > self.data.len() - self.ptr {
+            return format!(
+                "Unexpected end of payload: needed {} bytes at offset {}, but only {} remain",
+                len,
+                self.ptr,
+                self.data.len() - self.ptr
+            )
+            .to_error();
+        }
+
+        let slice = &self.data[self.ptr..self.ptr + len];
+        self.ptr += len;
+        Ok(slice)
+    }
+
+    fn opcode(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn id(&mut self) -> anyhow::Result<EntityId> {
+        Ok(usize::from_byte_array(self.take(8)?))
+    }
+
+    fn len(&mut self) -> anyhow::Result<usize> {
+        Ok(u64::from_byte_array(self.take(8)?) as usize)
+    }
+
+    fn name(&mut self) -> anyhow::Result<S32> {
+        Ok(S32::from_byte_array(self.take(32)?))
+    }
+
+    fn bytes(&mut self) -> anyhow::Result<Vec<u8>> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.bytes()?)?)
+    }
+
+    fn values(&mut self) -> anyhow::Result<ComponentValues> {
+        let count = self.len()?;
+        let mut values = vec![];
+        for _ in 0..count {
+            let name = self.name()?;
+            let value = self.value()?;
+            values.push((name, value));
+        }
+        Ok(values)
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        if self.depth == MAX_VALUE_DEPTH {
+            return format!("Values nested over {} deep", MAX_VALUE_DEPTH).to_error();
+        }
+
+        self.depth += 1;
+        let value = self.value_at_depth();
+        self.depth -= 1;
+        value
+    }
+
+    fn value_at_depth(&mut self) -> anyhow::Result<Value> {
+        let value = match self.opcode()? {
+            0 => Value::UNIT,
+            1 => Value::I8(i8::from_byte_array(self.take(1)?)),
+            2 => Value::I16(i16::from_byte_array(self.take(2)?)),
+            3 => Value::I32(i32::from_byte_array(self.take(4)?)),
+            4 => Value::I64(i64::from_byte_array(self.take(8)?)),
+            5 => Value::U8(u8::from_byte_array(self.take(1)?)),
+            6 => Value::U16(u16::from_byte_array(self.take(2)?)),
+            7 => Value::U32(u32::from_byte_array(self.take(4)?)),
+            8 => Value::U64(u64::from_byte_array(self.take(8)?)),
+            9 => Value::F32(f32::from_byte_array(self.take(4)?)),
+            10 => Value::F64(f64::from_byte_array(self.take(8)?)),
+            11 => Value::S32(self.name()?),
+            12 => Value::STR(self.string()?.into()),
+            13 => Value::BOOL(bool::from_byte_array(self.take(1)?)),
+            14 => Value::SUM(self.name()?, Box::new(self.value()?)),
+            15 => Value::ARR(self.elements()?),
+            16 => Value::LIST(self.elements()?),
+            17 => Value::REF(TileRef::from_byte_array(self.take(24)?)),
+            18 => Value::BLOB(Blob::new(self.bytes()?)),
+            19 => Value::DATETIME(DateTime::from_byte_array(self.take(8)?)),
+            20 => Value::DURATION(Duration::from_byte_array(self.take(8)?)),
+            tag => return format!("Unknown value tag {}", tag).to_error(),
+        };
+        Ok(value)
+    }
+
+    fn elements(&mut self) -> anyhow::Result<Vec<Value>> {
+        let count = self.len()?;
+        (0..count).map(|_| self.value()).collect()
+    }
+
+    fn tile_type(&mut self) -> anyhow::Result<TileType> {
+        match self.opcode()? {
+            0 => Ok(TileType::Object),
+            1 => Ok(TileType::Arrow {
+                source: self.id()?,
+                target: self.id()?,
+            }),
+            2 => Ok(TileType::Descriptor {
+                subject: self.id()?,
+            }),
+            3 => Ok(TileType::Extension {
+                subject: self.id()?,
+            }),
+            tag => format!("Unknown tile type tag {}", tag).to_error(),
+        }
+    }
+
+    fn record(&mut self) -> anyhow::Result<TileRecord> {
+        Ok(TileRecord {
+            id: self.id()?,
+            tile_type: self.tile_type()?,
+            component: self.name()?,
+            data: self.bytes()?,
+        })
+    }
+
+    fn finish<T>(self, t: T) -> anyhow::Result<T> {
+        if self.ptr == self.data.len() {
+            Ok(t)
+        } else {
+            Err(anyhow!(
+                "Trailing {} bytes found in payload",
+                self.data.len() - self.ptr
+            ))
+        }
+    }
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        let w = PayloadWriter::default();
+        match self {
+            Request::NewType(def) => w.opcode(0).string(def),
+            Request::GetTypes => w.opcode(1),
+            Request::NewObject(comp, values) => w.opcode(2).name(comp).values(values),
+            Request::NewSpecificObject(id, comp) => w.opcode(3).id(*id).name(comp),
+            Request::NewArrow(src, tgt, comp, values) => {
+                w.opcode(4).id(*src).id(*tgt).name(comp).values(values)
+            }
+            Request::NewDescriptor(subject, comp, values) => {
+                w.opcode(5).id(*subject).name(comp).values(values)
+            }
+            Request::NewExtension(subject, comp, values) => {
+                w.opcode(6).id(*subject).name(comp).values(values)
+            }
+            Request::DeleteTile(id) => w.opcode(7).id(*id),
+            Request::IsTileValid(id) => w.opcode(8).id(*id),
+            Request::Get(id) => w.opcode(9).id(*id),
+            Request::GetAll => w.opcode(10),
+            Request::Query(comp) => w.opcode(11).name(comp),
+            Request::Save => w.opcode(12),
+            Request::Load(data) => w.opcode(13).bytes(data),
+            Request::Clear => w.opcode(14),
+            Request::Version => w.opcode(15),
+            Request::SaveDelta(since) => w.opcode(16).id(*since),
+            Request::ApplyDelta(data) => w.opcode(17).bytes(data),
+            Request::RedefineType(def) => w.opcode(18).string(def),
+        }
+        .done()
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Request> {
+        let mut r = PayloadReader::new(data);
+        let request = match r.opcode()? {
+            0 => Request::NewType(r.string()?),
+            1 => Request::GetTypes,
+            2 => Request::NewObject(r.name()?, r.values()?),
+            3 => Request::NewSpecificObject(r.id()?, r.name()?),
+            4 => Request::NewArrow(r.id()?, r.id()?, r.name()?, r.values()?),
+            5 => Request::NewDescriptor(r.id()?, r.name()?, r.values()?),
+            6 => Request::NewExtension(r.id()?, r.name()?, r.values()?),
+            7 => Request::DeleteTile(r.id()?),
+            8 => Request::IsTileValid(r.id()?),
+            9 => Request::Get(r.id()?),
+            10 => Request::GetAll,
+            11 => Request::Query(r.name()?),
+            12 => Request::Save,
+            13 => Request::Load(r.bytes()?),
+            14 => Request::Clear,
+            15 => Request::Version,
+            16 => Request::SaveDelta(r.id()?),
+            17 => Request::ApplyDelta(r.bytes()?),
+            18 => Request::RedefineType(r.string()?),
+            op => return format!("Unknown request opcode {}", op).to_error(),
+        };
+        r.finish(request)
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        let w = PayloadWriter::default();
+        match self {
+            Response::Done => w.opcode(0),
+            Response::Bool(b) => w.opcode(1).opcode(*b as u8),
+            Response::Bytes(data) => w.opcode(2).bytes(data),
+            Response::Types(defs) => {
+                let mut w = w.opcode(3).id(defs.len());
+                for def in defs {
+                    w = w.string(def);
+                }
+                w
+            }
+            Response::Tiles(records) => {
+                let mut w = w.opcode(4).id(records.len());
+                for record in records {
+                    w = w.record(record);
+                }
+                w
+            }
+            Response::Error(message) => w.opcode(5).string(message),
+            Response::Number(n) => w.opcode(6).id(*n),
+        }
+        .done()
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Response> {
+        let mut r = PayloadReader::new(data);
+        let response = match r.opcode()? {
+            0 => Response::Done,
+            1 => Response::Bool(r.opcode()? == 1),
+            2 => Response::Bytes(r.bytes()?),
+            3 => {
+                let count = r.len()?;
+                let mut defs = vec![];
+                for _ in 0..count {
+                    defs.push(r.string()?);
+                }
+                Response::Types(defs)
+            }
+            4 => {
+                let count = r.len()?;
+                let mut records = vec![];
+                for _ in 0..count {
+                    records.push(r.record()?);
+                }
+                Response::Tiles(records)
+            }
+            5 => Response::Error(r.string()?),
+            6 => Response::Number(r.id()?),
+            op => return format!("Unknown response opcode {}", op).to_error(),
+        };
+        r.finish(response)
+    }
+}
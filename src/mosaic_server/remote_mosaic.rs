@@ -0,0 +1,400 @@
+use std::{
+    io::Read,
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    vec::IntoIter,
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::internals::{
+    import::{import_graph, parse_edge_list, parse_graphml},
+    ComponentValues, EntityId, ImportOptions, ImportedGraph, Logging, Mosaic, MosaicCRUD, MosaicIO,
+    MosaicTypelevelCRUD, Tile, Version,
+};
+
+use super::protocol::{read_frame, write_frame, Request, Response, TileRecord};
+
+/// A client for a `MosaicServer`, used like a local mosaic. Tiles it returns live in a local
+/// replica, so changes have to go through the `RemoteMosaic`; `try_` methods don't panic.
+pub struct RemoteMosaic {
+    /// `None` once a request failed halfway, after which the stream can't be trusted.
+    stream: Mutex<Option<TcpStream>>,
+    pub replica: Arc<Mosaic>,
+}
+
+impl RemoteMosaic {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> anyhow::Result<RemoteMosaic> {
+        Ok(RemoteMosaic {
+            stream: Mutex::new(Some(TcpStream::connect(addr)?)),
+            replica: Mosaic::new(),
+        })
+    }
+
+    /// Whether the connection is still usable; it isn't after any failure to reach the server.
+    pub fn is_connected(&self) -> bool {
+        self.stream.lock().unwrap().is_some()
+    }
+
+    fn request(&self, request: Request) -> anyhow::Result<Response> {
+        let mut guard = self.stream.lock().unwrap();
+        let Some(stream) = guard.as_mut() else {
+            return "The connection to the mosaic server was lost".to_error();
+        };
+
+        let response = write_frame(stream, &request.encode())
+            .and_then(|_| read_frame(stream))
+            .and_then(|frame| Response::decode(&frame));
+        match response {
+            Ok(Response::Error(message)) => message.to_error(),
+            Ok(response) => Ok(response),
+            Err(e) => {
+                // whatever was left half written or half read would be taken for the next answer
+                *guard = None;
+                Err(e)
+            }
+        }
+    }
+
+    fn sync_types(&self) -> anyhow::Result<()> {
+        if let Response::Types(definitions) = self.request(Request::GetTypes)? {
+            for definition in definitions {
+                self.replica.new_type(&definition)?;
+            }
+            Ok(())
+        } else {
+            "Expected type definitions from the mosaic server".to_error()
+        }
+    }
+
+    fn materialize(&self, record: TileRecord) -> anyhow::Result<Tile> {
+        if !self
+            .replica
+            .component_registry
+            .has_component_type(&record.component)
+        {
+            self.sync_types()?;
+        }
+
+        let component_type = self
+            .replica
+            .component_registry
+            .get_component_type(record.component)?;
+        let fields =
+            Tile::create_fields_from_binary_data(&self.replica, &component_type, record.data)?;
+
+        Ok(self.replica.restore_tile(
+            record.id,
+            record.tile_type,
+            record.component,
+            fields.into_iter().collect_vec(),
+        ))
+    }
+
+    fn request_tiles(&self, request: Request) -> anyhow::Result<Vec<Tile>> {
+        match self.request(request)? {
+            Response::Tiles(records) => records
+                .into_iter()
+                .map(|record| self.materialize(record))
+                .collect(),
+            response => Err(anyhow!("Expected tiles, found {:?}", response)),
+        }
+    }
+
+    fn request_tile(&self, request: Request) -> anyhow::Result<Tile> {
+        self.request_tiles(request)?
+            .first()
+            .cloned()
+            .ok_or(anyhow!("Remote tile creation returned no tile"))
+    }
+
+    fn request_bytes(&self, request: Request) -> anyhow::Result<Vec<u8>> {
+        match self.request(request)? {
+            Response::Bytes(data) => Ok(data),
+            response => Err(anyhow!("Expected bytes, found {:?}", response)),
+        }
+    }
+
+    /// Returns all the tiles on the server carrying the given component.
+    pub fn query(&self, component: &str) -> IntoIter<Tile> {
+        self.request_tiles(Request::Query(component.into()))
+            .unwrap_or_default()
+            .into_iter()
+    }
+
+    pub fn try_clear(&self) -> anyhow::Result<()> {
+        self.request(Request::Clear)?;
+        self.replica.clear();
+        Ok(())
+    }
+
+    pub fn try_save(&self) -> anyhow::Result<Vec<u8>> {
+        self.request_bytes(Request::Save)
+    }
+
+    pub fn try_version(&self) -> anyhow::Result<Version> {
+        match self.request(Request::Version)? {
+            Response::Number(version) => Ok(version),
+            response => Err(anyhow!("Expected a version, found {:?}", response)),
+        }
+    }
+
+    pub fn try_save_delta(&self, since: Version) -> anyhow::Result<Vec<u8>> {
+        self.request_bytes(Request::SaveDelta(since))
+    }
+
+    pub fn try_new_object(
+        &self,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.request_tile(Request::NewObject(component.into(), defaults))
+    }
+
+    pub fn try_new_extension(
+        &self,
+        subject: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.request_tile(Request::NewExtension(*subject, component.into(), defaults))
+    }
+
+    pub fn try_delete_tile(&self, tile: EntityId) -> anyhow::Result<()> {
+        self.request(Request::DeleteTile(tile))?;
+        self.replica.delete_tile(tile);
+        Ok(())
+    }
+}
+
+impl MosaicTypelevelCRUD for RemoteMosaic {
+    fn new_type(&self, type_def: &str) -> anyhow::Result<()> {
+        self.request(Request::NewType(type_def.to_string()))?;
+        self.replica.new_type(type_def)
+    }
+
+    fn redefine_type(&self, type_def: &str) -> anyhow::Result<()> {
+        self.request(Request::RedefineType(type_def.to_string()))?;
+        self.replica.redefine_type(type_def)
+    }
+}
+
+impl MosaicIO for RemoteMosaic {
+    fn clear(&self) {
+        self.try_clear()
+            .expect("Cannot clear remote mosaic, panicking!");
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.try_save()
+            .expect("Cannot save remote mosaic, panicking!")
+    }
+
+    fn load(&self, data: &[u8]) -> anyhow::Result<()> {
+        self.request(Request::Load(data.to_vec()))?;
+        Ok(())
+    }
+
+    fn get(&self, i: EntityId) -> Option<Tile> {
+        let tile = self
+            .request_tiles(Request::Get(i))
+            .ok()
+            .and_then(|tiles| tiles.first().cloned());
+
+        if tile.is_none() {
+            self.replica.delete_tile(i);
+        }
+
+        tile
+    }
+
+    fn get_all(&self) -> IntoIter<Tile> {
+        self.request_tiles(Request::GetAll)
+            .unwrap_or_default()
+            .into_iter()
+    }
+
+    fn version(&self) -> Version {
+        self.try_version()
+            .expect("Cannot get remote mosaic version, panicking!")
+    }
+
+    fn save_delta(&self, since: Version) -> Vec<u8> {
+        self.try_save_delta(since)
+            .expect("Cannot save remote mosaic delta, panicking!")
+    }
+
+    fn apply_delta(&self, data: &[u8]) -> anyhow::Result<()> {
+        self.request(Request::ApplyDelta(data.to_vec()))?;
+        Ok(())
+    }
+
+    fn new_object(&self, component: &str, defaults: ComponentValues) -> Tile {
+        self.try_new_object(component, defaults)
+            .expect("Cannot create remote object, panicking!")
+    }
+
+    fn new_specific_object(&self, id: EntityId, component: &str) -> anyhow::Result<Tile> {
+        self.request_tile(Request::NewSpecificObject(id, component.into()))
+    }
+
+    fn import_graphml(&self, text: &str) -> anyhow::Result<ImportedGraph> {
+        self.import_graphml_with(text, &ImportOptions::default())
+    }
+
+    /// Field values are read against the types known to the replica. Remote changes can't be
+    /// rolled back, so the tiles made before a failure are kept.
+    fn import_graphml_with(
+        &self,
+        text: &str,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph> {
+        let graph = parse_graphml(text)?;
+        import_graph(self, &self.replica.component_registry, graph, options)
+    }
+
+    fn import_edge_list(
+        &self,
+        reader: &mut dyn Read,
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedGraph> {
+        let graph = parse_edge_list(reader, options)?;
+        import_graph(self, &self.replica.component_registry, graph, options)
+    }
+}
+
+impl MosaicCRUD<EntityId> for RemoteMosaic {
+    fn new_arrow(
+        &self,
+        source: &EntityId,
+        target: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        self.try_new_arrow(source, target, component, defaults)
+            .expect("Cannot create remote arrow, panicking!")
+    }
+
+    fn new_descriptor(
+        &self,
+        subject: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        self.try_new_descriptor(subject, component, defaults)
+            .expect("Cannot create remote descriptor, panicking!")
+    }
+
+    fn new_extension(
+        &self,
+        subject: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        self.try_new_extension(subject, component, defaults)
+            .expect("Cannot create remote extension, panicking!")
+    }
+
+    fn try_new_arrow(
+        &self,
+        source: &EntityId,
+        target: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.request_tile(Request::NewArrow(
+            *source,
+            *target,
+            component.into(),
+            defaults,
+        ))
+    }
+
+    fn try_new_descriptor(
+        &self,
+        subject: &EntityId,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        self.request_tile(Request::NewDescriptor(*subject, component.into(), defaults))
+    }
+
+    fn is_tile_valid(&self, i: &EntityId) -> bool {
+        matches!(
+            self.request(Request::IsTileValid(*i)),
+            Ok(Response::Bool(true))
+        )
+    }
+
+    fn delete_tile(&self, tile: EntityId) {
+        self.try_delete_tile(tile)
+            .expect("Cannot delete remote tile, panicking!");
+    }
+}
+
+impl MosaicCRUD<Tile> for RemoteMosaic {
+    fn new_arrow(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> Tile {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::new_arrow(
+            self, &source.id, &target.id, component, defaults,
+        )
+    }
+
+    fn new_descriptor(&self, subject: &Tile, component: &str, defaults: ComponentValues) -> Tile {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::new_descriptor(
+            self,
+            &subject.id,
+            component,
+            defaults,
+        )
+    }
+
+    fn new_extension(&self, subject: &Tile, component: &str, defaults: ComponentValues) -> Tile {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::new_extension(
+            self,
+            &subject.id,
+            component,
+            defaults,
+        )
+    }
+
+    fn try_new_arrow(
+        &self,
+        source: &Tile,
+        target: &Tile,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::try_new_arrow(
+            self, &source.id, &target.id, component, defaults,
+        )
+    }
+
+    fn try_new_descriptor(
+        &self,
+        subject: &Tile,
+        component: &str,
+        defaults: ComponentValues,
+    ) -> anyhow::Result<Tile> {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::try_new_descriptor(
+            self,
+            &subject.id,
+            component,
+            defaults,
+        )
+    }
+
+    fn is_tile_valid(&self, i: &Tile) -> bool {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::is_tile_valid(self, &i.id)
+    }
+
+    fn delete_tile(&self, tile: Tile) {
+        <RemoteMosaic as MosaicCRUD<EntityId>>::delete_tile(self, tile.id);
+    }
+}
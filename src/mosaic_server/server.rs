@@ -0,0 +1,178 @@
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use itertools::Itertools;
+use log::{error, info};
+
+use crate::{
+    internals::{Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, Tile},
+    iterators::component_selectors::ComponentSelectors,
+};
+
+use super::protocol::{read_frame, write_frame, Request, Response, TileRecord};
+
+/// A server that shares a single mosaic with any number of remote clients over TCP.
+pub struct MosaicServer {
+    mosaic: Arc<Mosaic>,
+    listener: TcpListener,
+}
+
+impl MosaicServer {
+    pub fn bind<A: ToSocketAddrs>(mosaic: &Arc<Mosaic>, addr: A) -> anyhow::Result<MosaicServer> {
+        Ok(MosaicServer {
+            mosaic: Arc::clone(mosaic),
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections forever, serving each client on its own thread.
+    pub fn serve(self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let mosaic = Arc::clone(&self.mosaic);
+                    std::thread::spawn(move || serve_client(mosaic, stream));
+                }
+                Err(e) => error!("Failed to accept mosaic client: {}", e),
+            }
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        std::thread::spawn(move || self.serve())
+    }
+}
+
+fn serve_client(mosaic: Arc<Mosaic>, mut stream: TcpStream) {
+    info!("Mosaic client connected: {:?}", stream.peer_addr());
+
+    while let Ok(frame) = read_frame(&mut stream) {
+        let response = match Request::decode(&frame) {
+            Ok(request) => catch_unwind(AssertUnwindSafe(|| handle_request(&mosaic, request)))
+                .unwrap_or_else(|_| Response::Error("Request panicked on the server".to_string())),
+            Err(e) => Response::Error(e.to_string()),
+        };
+
+        if let Err(e) = write_frame(&mut stream, &response.encode()) {
+            error!("Failed to respond to mosaic client: {}", e);
+            break;
+        }
+    }
+}
+
+pub(crate) fn to_record(mosaic: &Arc<Mosaic>, tile: &Tile) -> TileRecord {
+    TileRecord {
+        id: tile.id,
+        tile_type: tile.tile_type,
+        component: tile.component,
+        data: tile.create_binary_data_from_fields(
+            &mosaic
+                .component_registry
+                .get_component_type(tile.component)
+                .unwrap(),
+        ),
+    }
+}
+
+fn to_records<I: Iterator<Item = Tile>>(mosaic: &Arc<Mosaic>, tiles: I) -> Response {
+    Response::Tiles(tiles.map(|t| to_record(mosaic, &t)).collect_vec())
+}
+
+pub(crate) fn handle_request(mosaic: &Arc<Mosaic>, request: Request) -> Response {
+    let component = |c: &crate::internals::S32| c.to_string();
+
+    match request {
+        Request::NewType(def) => match mosaic.new_type(&def) {
+            Ok(()) => Response::Done,
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::RedefineType(def) => match mosaic.redefine_type(&def) {
+            Ok(()) => Response::Done,
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::GetTypes => Response::Types(
+            mosaic
+                .component_registry
+                .component_definitions
+                .read()
+                .unwrap()
+                .clone(),
+        ),
+        Request::NewObject(comp, values) => to_records(
+            mosaic,
+            mosaic.new_object(&component(&comp), values).into_iter(),
+        ),
+        Request::NewSpecificObject(id, comp) => {
+            match mosaic.new_specific_object(id, &component(&comp)) {
+                Ok(tile) => to_records(mosaic, tile.into_iter()),
+                Err(e) => Response::Error(e.to_string()),
+            }
+        }
+        Request::NewArrow(src, tgt, comp, values) => {
+            if !mosaic.is_tile_valid(&src) || !mosaic.is_tile_valid(&tgt) {
+                Response::Error(format!("Cannot create arrow between {} and {}", src, tgt))
+            } else {
+                match mosaic.try_new_arrow(&src, &tgt, &component(&comp), values) {
+                    Ok(tile) => to_records(mosaic, tile.into_iter()),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+        }
+        Request::NewDescriptor(subject, comp, values) => {
+            if !mosaic.is_tile_valid(&subject) {
+                Response::Error(format!("Cannot create descriptor on {}", subject))
+            } else {
+                match mosaic.try_new_descriptor(&subject, &component(&comp), values) {
+                    Ok(tile) => to_records(mosaic, tile.into_iter()),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+        }
+        Request::NewExtension(subject, comp, values) => {
+            if !mosaic.is_tile_valid(&subject) {
+                Response::Error(format!("Cannot create extension on {}", subject))
+            } else {
+                to_records(
+                    mosaic,
+                    mosaic
+                        .new_extension(&subject, &component(&comp), values)
+                        .into_iter(),
+                )
+            }
+        }
+        Request::DeleteTile(id) => {
+            mosaic.delete_tile(id);
+            Response::Done
+        }
+        Request::IsTileValid(id) => Response::Bool(mosaic.is_tile_valid(&id)),
+        Request::Get(id) => to_records(mosaic, mosaic.get(id).into_iter()),
+        Request::GetAll => to_records(mosaic, mosaic.get_all()),
+        Request::Query(comp) => to_records(
+            mosaic,
+            mosaic.get_all().include_component(&component(&comp)),
+        ),
+        Request::Save => Response::Bytes(mosaic.save()),
+        Request::Load(data) => match mosaic.load(&data) {
+            Ok(()) => Response::Done,
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::Clear => {
+            mosaic.clear();
+            Response::Done
+        }
+        Request::Version => Response::Number(mosaic.version()),
+        Request::SaveDelta(since) => Response::Bytes(mosaic.save_delta(since)),
+        Request::ApplyDelta(data) => match mosaic.apply_delta(&data) {
+            Ok(()) => Response::Done,
+            Err(e) => Response::Error(e.to_string()),
+        },
+    }
+}
@@ -0,0 +1,376 @@
+#[cfg(test)]
+mod mosaic_server_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        internals::{par, void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, TileType},
+        mosaic_server::{
+            protocol::{read_frame, write_frame, Request, Response, MAX_FRAME_SIZE},
+            MosaicServer, RemoteMosaic,
+        },
+    };
+
+    fn start_server() -> (std::sync::Arc<Mosaic>, RemoteMosaic) {
+        let mosaic = Mosaic::new();
+        let server = MosaicServer::bind(&mosaic, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        (mosaic, RemoteMosaic::connect(addr).unwrap())
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let request = Request::NewArrow(1, 2, "Foo".into(), par(7i32));
+        assert_eq!(request, Request::decode(&request.encode()).unwrap());
+
+        let response = Response::Types(vec!["Foo: i32;".to_string()]);
+        assert_eq!(response, Response::decode(&response.encode()).unwrap());
+    }
+
+    #[test]
+    fn test_truncated_request_is_an_error() {
+        let request = Request::NewObject("Foo".into(), par(7i32)).encode();
+        assert!(Request::decode(&request[0..request.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_hostile_payloads_are_errors() {
+        // a length that overflows the read offset
+        let mut request = vec![13u8];
+        request.extend(u64::MAX.to_be_bytes());
+        assert!(Request::decode(&request).is_err());
+
+        // lists nested far deeper than any component type goes
+        let mut request = Request::NewObject("Foo".into(), vec![]).encode();
+        request.truncate(request.len() - 8);
+        request.extend(1u64.to_be_bytes());
+        request.extend([0u8; 32]);
+        for _ in 0..100_000 {
+            request.push(16);
+            request.extend(1u64.to_be_bytes());
+        }
+        request.push(0);
+        assert!(Request::decode(&request).is_err());
+    }
+
+    #[test]
+    fn test_frame_limits() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, b"abc").unwrap();
+        assert_eq!(b"abc".to_vec(), read_frame(&mut &buffer[..]).unwrap());
+
+        // a frame may say it's longer than it is, or longer than any frame can be
+        assert!(read_frame(&mut &buffer[..buffer.len() - 1]).is_err());
+        let oversized = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut &oversized[..]).is_err());
+        assert!(read_frame(&mut &u32::MAX.to_be_bytes()[..]).is_err());
+        assert!(write_frame(&mut vec![], &vec![0u8; MAX_FRAME_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn test_remote_crud() {
+        let (local, remote) = start_server();
+        remote.new_type("Foo: i32;").unwrap();
+
+        let a = remote.new_object("Foo", par(7i32));
+        let b = remote.new_object("void", void());
+        let ab = remote.new_arrow(&a, &b, "void", void());
+
+        assert!(local.is_tile_valid(&a.id));
+        assert_eq!(7, local.get(a.id).unwrap().get("self").as_i32());
+        assert_eq!(7, a.get("self").as_i32());
+        assert_eq!(
+            TileType::Arrow {
+                source: a.id,
+                target: b.id
+            },
+            ab.tile_type
+        );
+
+        assert_eq!(3, remote.get_all().len());
+        assert_eq!(vec![a.id], remote.query("Foo").map(|t| t.id).collect_vec());
+
+        remote.delete_tile(a.id);
+        assert!(!local.is_tile_valid(&ab.id));
+        assert!(!remote.is_tile_valid(&a.id));
+        assert!(remote.get(ab.id).is_none());
+    }
+
+    #[test]
+    fn test_remote_save_and_errors() {
+        let (local, remote) = start_server();
+        local.new_type("Bar: u32;").unwrap();
+        let o = local.new_object("Bar", par(3u32));
+
+        assert_eq!(local.save(), remote.save());
+        assert_eq!(3, remote.get(o.id).unwrap().get("self").as_u32());
+        assert!(remote.new_type("A: unit; B: unit;").is_err());
+        assert!(remote.new_specific_object(o.id, "Bar").is_err());
+    }
+
+    #[test]
+    fn test_remote_connection_dies_on_bad_answers() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_frame(&mut stream).unwrap();
+            write_frame(&mut stream, &[99]).unwrap();
+            stream
+        });
+
+        let remote = RemoteMosaic::connect(addr).unwrap();
+        assert!(remote.try_version().is_err());
+        assert!(!remote.is_connected());
+        let _stream = server.join().unwrap();
+        assert!(remote.try_save().is_err());
+        assert!(remote.try_new_object("void", void()).is_err());
+    }
+
+    #[test]
+    fn test_remote_deltas() {
+        let (local, remote) = start_server();
+        let checkpoint = remote.version();
+        let o = local.new_object("void", void());
+        assert!(remote.version() > checkpoint);
+
+        let replica = Mosaic::new();
+        replica.apply_delta(&remote.save_delta(checkpoint)).unwrap();
+        assert!(replica.is_tile_valid(&o.id));
+    }
+
+    /// Sends one request to an `HttpServer`, and returns the status and body of the response.
+    #[cfg(feature = "server")]
+    fn http(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        http_raw(
+            addr,
+            format!(
+                "{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                body.len(),
+                body
+            ),
+        )
+    }
+
+    /// Sends `request` as it is to an `HttpServer`, and returns the status and body of the
+    /// response.
+    #[cfg(feature = "server")]
+    fn http_raw(addr: std::net::SocketAddr, request: impl AsRef<[u8]>) -> (u16, serde_json::Value) {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_ref()).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = serde_json::from_str(body).unwrap_or(serde_json::Value::Null);
+        (status, body)
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_http_server() {
+        use std::io::{BufRead, BufReader, Write};
+
+        use crate::mosaic_server::{HttpOptions, HttpServer};
+
+        let local = Mosaic::new();
+        let server = HttpServer::bind(&local, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+
+        assert_eq!(
+            204,
+            http(
+                addr,
+                "POST",
+                "/types",
+                "Node: { weight: i32, at: datetime };"
+            )
+            .0
+        );
+        assert_eq!(204, http(addr, "POST", "/types", "Label: s32;").0);
+        assert_eq!(400, http(addr, "POST", "/types", "Broken: {").0);
+
+        // lengths are checked before anything is read or allocated for the body
+        let (status, error) = http_raw(
+            addr,
+            format!(
+                "POST /types HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                HttpOptions::default().max_body_size + 1
+            ),
+        );
+        assert_eq!(413, status);
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .contains("Cannot take a body"));
+        let short = "POST /types HTTP/1.1\r\nContent-Length: 100\r\n\r\nShort: i32;";
+        assert_eq!(400, http_raw(addr, short).0);
+        assert!(!local.component_registry.has_component_type(&"Short".into()));
+
+        let (status, a) = http(
+            addr,
+            "POST",
+            "/tiles",
+            r#"{"component": "Node", "fields": {"weight": 3, "at": "2024-05-01T10:00:00Z"}}"#,
+        );
+        assert_eq!(201, status);
+        let a_id = a["id"].as_u64().unwrap() as usize;
+        assert_eq!(3, local.get(a_id).unwrap().get("weight").as_i32());
+        assert_eq!("2024-05-01T10:00:00Z", a["fields"]["at"]);
+
+        let (status, label) = http(
+            addr,
+            "POST",
+            "/tiles",
+            &format!(
+                r#"{{"kind": "descriptor", "subject": {}, "component": "Label", "fields": {{"self": "a"}}}}"#,
+                a_id
+            ),
+        );
+        assert_eq!(201, status);
+        assert_eq!(a_id as u64, label["subject"]);
+        assert_eq!(
+            400,
+            http(
+                addr,
+                "POST",
+                "/tiles",
+                r#"{"component": "Node", "fields": {"weight": "heavy"}}"#
+            )
+            .0
+        );
+
+        let (status, patched) = http(
+            addr,
+            "PATCH",
+            &format!("/tiles/{}", a_id),
+            r#"{"weight": 5}"#,
+        );
+        assert_eq!(200, status);
+        assert_eq!(5, patched["fields"]["weight"]);
+
+        let b = local.new_object("Node", void());
+        let (_, page) = http(addr, "GET", "/tiles?component=Node&limit=1", "");
+        assert_eq!(a_id as u64, page["tiles"][0]["id"]);
+        let next = page["next"].as_str().unwrap().to_string();
+        let (_, page) = http(
+            addr,
+            "GET",
+            &format!("/tiles?component=Node&limit=1&after={}", next),
+            "",
+        );
+        assert_eq!(b.id as u64, page["tiles"][0]["id"]);
+        assert!(page["next"].is_null());
+
+        let (_, found) = http(
+            addr,
+            "GET",
+            "/query?q=SELECT+objects+WHERE+weight+%3E+4",
+            "",
+        );
+        assert_eq!(
+            vec![a_id as u64],
+            found["tiles"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| t["id"].as_u64().unwrap())
+                .collect_vec()
+        );
+
+        // subscribers hear about changes as they happen
+        let mut events = std::net::TcpStream::connect(addr).unwrap();
+        write!(events, "GET /events HTTP/1.1\r\n\r\n").unwrap();
+        let mut events = BufReader::new(events);
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+        }
+        // the stream is subscribed once its head is written, so changes made from now on are sent
+        assert_eq!(204, http(addr, "DELETE", &format!("/tiles/{}", b.id), "").0);
+        line.clear();
+        events.read_line(&mut line).unwrap();
+        let event: serde_json::Value =
+            serde_json::from_str(line.trim().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!("deleted", event["event"]);
+        assert_eq!(b.id as u64, event["id"]);
+
+        assert_eq!(404, http(addr, "GET", &format!("/tiles/{}", b.id), "").0);
+        assert_eq!(404, http(addr, "GET", "/nowhere", "").0);
+
+        // without a token, nobody may replace the whole mosaic
+        assert_eq!(403, http(addr, "POST", "/load", "").0);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_http_server_limits() {
+        use std::time::Duration;
+
+        use crate::mosaic_server::{HttpOptions, HttpServer};
+
+        let local = Mosaic::new();
+        let server = HttpServer::bind_with(
+            &local,
+            "127.0.0.1:0",
+            HttpOptions {
+                timeout: Duration::from_millis(300),
+                max_connections: 1,
+                token: Some("secret".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+
+        let authorized = |method: &str, path: &str, body: &[u8]| {
+            let head = format!(
+                "{} {} HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\n\r\n",
+                method,
+                path,
+                body.len()
+            );
+            http_raw(addr, [head.as_bytes(), body].concat()).0
+        };
+
+        assert_eq!(401, http(addr, "GET", "/types", "").0);
+        assert_eq!(
+            401,
+            http_raw(
+                addr,
+                "GET /types HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n"
+            )
+            .0
+        );
+        assert_eq!(200, authorized("GET", "/types", b""));
+
+        let saved = Mosaic::new();
+        saved.new_type("Foo: u8;").unwrap();
+        saved.new_object("Foo", par(7u8));
+        assert_eq!(204, authorized("POST", "/load", &saved.save()));
+        assert_eq!(7, local.get_all().next().unwrap().get("self").as_u8());
+
+        // a client that sends nothing holds the only connection until it times out
+        let idle = std::net::TcpStream::connect(addr).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        // turned away as soon as it connects, so the request needn't even be sent
+        assert_eq!(503, http_raw(addr, "").0);
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(200, authorized("GET", "/types", b""));
+        drop(idle);
+    }
+}
@@ -0,0 +1,286 @@
+//! JavaScript bindings through `wasm-bindgen`, with tiles as ids and values as plain JS
+//! values.
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use js_sys::{Array, Date, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    internals::{
+        void, DateTime, Duration, EntityId, Mosaic, MosaicCRUD, MosaicError, MosaicIO,
+        MosaicIndices, MosaicTypelevelCRUD, Tile, Value, S32,
+    },
+    iterators::{query::MosaicQuery, tile_getters::TileGetters},
+};
+
+fn js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+fn ids<I: Iterator<Item = Tile>>(tiles: I) -> Vec<usize> {
+    tiles.map(|t| t.id).collect_vec()
+}
+
+fn to_js(value: Value) -> JsValue {
+    match value {
+        Value::UNIT => JsValue::UNDEFINED,
+        Value::I8(v) => v.into(),
+        Value::I16(v) => v.into(),
+        Value::I32(v) => v.into(),
+        Value::I64(v) => v.into(),
+        Value::U8(v) => v.into(),
+        Value::U16(v) => v.into(),
+        Value::U32(v) => v.into(),
+        Value::U64(v) => v.into(),
+        Value::F32(v) => v.into(),
+        Value::F64(v) => v.into(),
+        Value::S32(v) => v.to_string().into(),
+        Value::STR(v) => JsValue::from_str(&v),
+        Value::BOOL(v) => v.into(),
+        Value::SUM(tag, inner) => Array::of2(&tag.to_string().into(), &to_js(*inner)).into(),
+        Value::ARR(values) | Value::LIST(values) => {
+            values.into_iter().map(to_js).collect::<Array>().into()
+        }
+        Value::REF(r) => r.to_string().into(),
+        Value::BLOB(blob) => Uint8Array::from(blob.bytes()).into(),
+        Value::DATETIME(v) => Date::new(&(v.unix_millis() as f64).into()).into(),
+        Value::DURATION(v) => (v.as_micros() as f64 / 1000.0).into(),
+    }
+}
+
+/// A whole number from JS, if it fits into `T`.
+fn integer<T: TryFrom<i64>>(value: &JsValue) -> Option<T> {
+    let number = match value.as_f64() {
+        Some(n) if n.fract() == 0.0 => n as i64,
+        _ => i64::try_from(value.clone()).ok()?,
+    };
+    T::try_from(number).ok()
+}
+
+/// Fits a JS value to the datatype of the `current` value of a field.
+fn from_js(current: &Value, value: &JsValue) -> Option<Value> {
+    match current {
+        Value::I8(_) => integer(value).map(Value::I8),
+        Value::I16(_) => integer(value).map(Value::I16),
+        Value::I32(_) => integer(value).map(Value::I32),
+        Value::I64(_) => integer(value).map(Value::I64),
+        Value::U8(_) => integer(value).map(Value::U8),
+        Value::U16(_) => integer(value).map(Value::U16),
+        Value::U32(_) => integer(value).map(Value::U32),
+        Value::U64(_) => integer(value).map(Value::U64),
+        Value::F32(_) => value.as_f64().map(|v| Value::F32(v as f32)),
+        Value::F64(_) => value.as_f64().map(Value::F64),
+        Value::S32(_) => value.as_string().map(|v| Value::S32(v.as_str().into())),
+        Value::STR(_) => value.as_string().map(|v| Value::STR(v.into())),
+        Value::BOOL(_) => value.as_bool().map(Value::BOOL),
+        Value::DATETIME(_) => match value.dyn_ref::<Date>() {
+            Some(date) => Some(Value::DATETIME(DateTime::from_unix_millis(
+                date.get_time() as i64
+            ))),
+            None => value
+                .as_string()
+                .and_then(|v| v.parse().ok())
+                .map(Value::DATETIME),
+        },
+        Value::DURATION(_) => value
+            .as_f64()
+            .map(|millis| Value::DURATION(Duration::from_micros((millis * 1000.0) as i64))),
+        _ => None,
+    }
+}
+
+#[wasm_bindgen(js_name = Mosaic)]
+pub struct WasmMosaic {
+    mosaic: Arc<Mosaic>,
+}
+
+impl WasmMosaic {
+    fn tile(&self, id: EntityId) -> Result<Tile, JsError> {
+        self.mosaic
+            .get(id)
+            .ok_or_else(|| js_error(MosaicError::InvalidTile(id).into()))
+    }
+
+    fn component(&self, name: &str) -> Result<(), JsError> {
+        match self
+            .mosaic
+            .component_registry
+            .has_component_type(&name.into())
+        {
+            true => Ok(()),
+            false => Err(js_error(MosaicError::UnknownComponent(name.into()).into())),
+        }
+    }
+
+    fn field(&self, tile: &Tile, name: &str) -> Result<Value, JsError> {
+        let name: S32 = name.into();
+        tile.data()
+            .into_iter()
+            .find(|(f, _)| *f == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                js_error(
+                    MosaicError::UnknownField {
+                        component: tile.component,
+                        field: name,
+                    }
+                    .into(),
+                )
+            })
+    }
+}
+
+#[wasm_bindgen(js_class = Mosaic)]
+impl WasmMosaic {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> WasmMosaic {
+        WasmMosaic {
+            mosaic: Mosaic::new(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = newType)]
+    pub fn new_type(&self, definition: &str) -> Result<(), JsError> {
+        self.mosaic.new_type(definition).map_err(js_error)
+    }
+
+    /// Makes an object with every field set to its default.
+    #[wasm_bindgen(js_name = newObject)]
+    pub fn new_object(&self, component: &str) -> Result<usize, JsError> {
+        self.component(component)?;
+        Ok(self.mosaic.new_object(component, void()).id)
+    }
+
+    #[wasm_bindgen(js_name = newArrow)]
+    pub fn new_arrow(
+        &self,
+        source: usize,
+        target: usize,
+        component: &str,
+    ) -> Result<usize, JsError> {
+        self.component(component)?;
+        let (source, target) = (self.tile(source)?, self.tile(target)?);
+        self.mosaic
+            .try_new_arrow(&source, &target, component, void())
+            .map(|t| t.id)
+            .map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = newDescriptor)]
+    pub fn new_descriptor(&self, subject: usize, component: &str) -> Result<usize, JsError> {
+        self.component(component)?;
+        let subject = self.tile(subject)?;
+        self.mosaic
+            .try_new_descriptor(&subject, component, void())
+            .map(|t| t.id)
+            .map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = newExtension)]
+    pub fn new_extension(&self, subject: usize, component: &str) -> Result<usize, JsError> {
+        self.component(component)?;
+        let subject = self.tile(subject)?;
+        Ok(self.mosaic.new_extension(&subject, component, void()).id)
+    }
+
+    #[wasm_bindgen(js_name = deleteTile)]
+    pub fn delete_tile(&self, id: usize) {
+        self.mosaic.delete_tile(id);
+    }
+
+    #[wasm_bindgen(js_name = isTileValid)]
+    pub fn is_tile_valid(&self, id: usize) -> bool {
+        self.mosaic.is_tile_valid(&id)
+    }
+
+    /// `"object"`, `"arrow"`, `"descriptor"`, or `"extension"`.
+    pub fn kind(&self, id: usize) -> Result<String, JsError> {
+        let tile = self.tile(id)?;
+        let kind = if tile.is_object() {
+            "object"
+        } else if tile.is_arrow() {
+            "arrow"
+        } else if tile.is_descriptor() {
+            "descriptor"
+        } else {
+            "extension"
+        };
+        Ok(kind.to_string())
+    }
+
+    pub fn source(&self, id: usize) -> Result<usize, JsError> {
+        Ok(self.tile(id)?.source_id())
+    }
+
+    pub fn target(&self, id: usize) -> Result<usize, JsError> {
+        Ok(self.tile(id)?.target_id())
+    }
+
+    #[wasm_bindgen(js_name = componentOf)]
+    pub fn component_of(&self, id: usize) -> Result<String, JsError> {
+        Ok(self.tile(id)?.component.to_string())
+    }
+
+    pub fn get(&self, id: usize, field: &str) -> Result<JsValue, JsError> {
+        let tile = self.tile(id)?;
+        Ok(to_js(self.field(&tile, field)?))
+    }
+
+    /// Writes a field, failing if `value` doesn't fit its datatype.
+    pub fn set(&self, id: usize, field: &str, value: JsValue) -> Result<(), JsError> {
+        let mut tile = self.tile(id)?;
+        let current = self.field(&tile, field)?;
+        let value = from_js(&current, &value).ok_or_else(|| {
+            js_error(anyhow!(
+                "Field {} of tile {} is {:?}, which cannot take that value",
+                field,
+                id,
+                current.get_datatype()
+            ))
+        })?;
+        tile.set_field(field, value);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = getAll)]
+    pub fn get_all(&self) -> Vec<usize> {
+        ids(self.mosaic.get_all().sorted_by_key(|t| t.id))
+    }
+
+    #[wasm_bindgen(js_name = tilesWithComponent)]
+    pub fn tiles_with_component(&self, component: &str) -> Vec<usize> {
+        ids(self.mosaic.get_tiles_with_component(component))
+    }
+
+    #[wasm_bindgen(js_name = arrowsFrom)]
+    pub fn arrows_from(&self, id: usize) -> Result<Vec<usize>, JsError> {
+        Ok(ids(self.tile(id)?.iter().get_arrows_from()))
+    }
+
+    #[wasm_bindgen(js_name = arrowsInto)]
+    pub fn arrows_into(&self, id: usize) -> Result<Vec<usize>, JsError> {
+        Ok(ids(self.tile(id)?.iter().get_arrows_into()))
+    }
+
+    pub fn descriptors(&self, id: usize) -> Result<Vec<usize>, JsError> {
+        Ok(ids(self.tile(id)?.iter().get_descriptors()))
+    }
+
+    /// Runs a textual query, as `query_str` does, returning the ids of the matching tiles.
+    pub fn query(&self, query: &str) -> Result<Vec<usize>, JsError> {
+        self.mosaic.query_str(query).map(ids).map_err(js_error)
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        self.mosaic.save()
+    }
+
+    /// Loads what `save` gave out, next to the tiles already there.
+    pub fn load(&self, data: &[u8]) -> Result<(), JsError> {
+        self.mosaic.load(data).map_err(js_error)
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use crate::internals::{void, Mosaic, MosaicCRUD, MosaicIO, MosaicTypelevelCRUD, Tile};
+
+/// A small pseudo-random generator (SplitMix64), so fixtures built from the same seed come
+/// out the same on every platform and run. Not fit for anything but tests.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `[0, bound)`; `bound` can't be `0`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// `true` with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+/// Builds mosaics shaped like well-known graphs, each in a new mosaic, with objects for nodes
+/// and arrows for edges.
+pub struct GraphGenerator {
+    rng: SeededRng,
+    definitions: Vec<String>,
+    node_component: String,
+    edge_component: String,
+}
+
+impl GraphGenerator {
+    pub fn new(seed: u64) -> Self {
+        GraphGenerator {
+            rng: SeededRng::new(seed),
+            definitions: vec![],
+            node_component: "void".to_string(),
+            edge_component: "void".to_string(),
+        }
+    }
+
+    /// Makes nodes of `node` and edges of `edge`, after adding the types in `definitions`.
+    pub fn with_components(mut self, definitions: &[&str], node: &str, edge: &str) -> Self {
+        self.definitions = definitions.iter().map(|d| d.to_string()).collect();
+        self.node_component = node.to_string();
+        self.edge_component = edge.to_string();
+        self
+    }
+
+    pub fn rng(&mut self) -> &mut SeededRng {
+        &mut self.rng
+    }
+
+    fn with_nodes(&self, count: usize) -> (Arc<Mosaic>, Vec<Tile>) {
+        let mosaic = Mosaic::new();
+        for definition in &self.definitions {
+            mosaic.new_type(definition).unwrap();
+        }
+        let nodes = (0..count)
+            .map(|_| mosaic.new_object(&self.node_component, void()))
+            .collect();
+        (mosaic, nodes)
+    }
+
+    fn connect(&self, mosaic: &Arc<Mosaic>, source: &Tile, target: &Tile) {
+        mosaic.new_arrow(source, target, &self.edge_component, void());
+    }
+
+    /// Erdős–Rényi: an edge goes from each node to each other node with `probability`.
+    pub fn erdos_renyi(&mut self, nodes: usize, probability: f64) -> Arc<Mosaic> {
+        let (mosaic, tiles) = self.with_nodes(nodes);
+        for source in &tiles {
+            for target in &tiles {
+                if source != target && self.rng.chance(probability) {
+                    self.connect(&mosaic, source, target);
+                }
+            }
+        }
+        mosaic
+    }
+
+    /// Barabási–Albert: each node links to `edges_per_node` earlier ones, preferring those
+    /// with more edges.
+    pub fn barabasi_albert(&mut self, nodes: usize, edges_per_node: usize) -> Arc<Mosaic> {
+        let (mosaic, tiles) = self.with_nodes(nodes);
+        // every node once for each edge it has, so a uniform pick favours the busy ones
+        let mut ends: Vec<usize> = (0..edges_per_node.min(nodes)).collect();
+        for source in edges_per_node..nodes {
+            let mut targets = vec![];
+            while targets.len() < edges_per_node {
+                let target = ends[self.rng.below(ends.len())];
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+
+            for target in targets {
+                self.connect(&mosaic, &tiles[source], &tiles[target]);
+                ends.extend([source, target]);
+            }
+        }
+        mosaic
+    }
+
+    /// A `width` by `height` grid, each node linked to the one right of it and the one
+    /// below it; nodes are made row by row.
+    pub fn grid(&mut self, width: usize, height: usize) -> Arc<Mosaic> {
+        let (mosaic, tiles) = self.with_nodes(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                let node = &tiles[row * width + column];
+                if column + 1 < width {
+                    self.connect(&mosaic, node, &tiles[row * width + column + 1]);
+                }
+                if row + 1 < height {
+                    self.connect(&mosaic, node, &tiles[(row + 1) * width + column]);
+                }
+            }
+        }
+        mosaic
+    }
+
+    /// A random directed acyclic graph: an edge goes from each node to each node made after
+    /// it with `probability`, so the order the nodes were made in is a topological order.
+    pub fn dag(&mut self, nodes: usize, probability: f64) -> Arc<Mosaic> {
+        let (mosaic, tiles) = self.with_nodes(nodes);
+        for (i, source) in tiles.iter().enumerate() {
+            for target in &tiles[i + 1..] {
+                if self.rng.chance(probability) {
+                    self.connect(&mosaic, source, target);
+                }
+            }
+        }
+        mosaic
+    }
+}
+
+#[cfg(test)]
+mod testing_tests {
+    use itertools::Itertools;
+
+    use crate::{
+        capabilities::TraversalCapability,
+        internals::{MosaicIO, MosaicIndices, Tile},
+    };
+
+    use super::{GraphGenerator, SeededRng};
+
+    fn edges(tiles: impl Iterator<Item = Tile>) -> Vec<(usize, usize)> {
+        tiles
+            .filter(|t| t.is_arrow())
+            .map(|t| (t.source_id(), t.target_id()))
+            .sorted()
+            .collect_vec()
+    }
+
+    #[test]
+    fn test_seeded_rng_is_reproducible() {
+        let mut a = SeededRng::new(7);
+        let mut b = SeededRng::new(7);
+        assert_eq!(
+            (0..10).map(|_| a.next_u64()).collect_vec(),
+            (0..10).map(|_| b.next_u64()).collect_vec()
+        );
+        assert!((0..100).all(|_| a.below(5) < 5));
+        assert!((0..100)
+            .map(|_| a.next_f64())
+            .all(|x| (0.0..1.0).contains(&x)));
+
+        let first = GraphGenerator::new(3).erdos_renyi(20, 0.2);
+        let second = GraphGenerator::new(3).erdos_renyi(20, 0.2);
+        assert_eq!(edges(first.get_all()), edges(second.get_all()));
+    }
+
+    #[test]
+    fn test_graph_generators() {
+        let mut generator =
+            GraphGenerator::new(11).with_components(&["Node: i32;", "Edge: f32;"], "Node", "Edge");
+
+        let grid = generator.grid(4, 3);
+        assert_eq!(12, grid.get_tiles_with_component("Node").count());
+        assert_eq!(3 * 3 + 4 * 2, grid.get_tiles_with_component("Edge").count());
+
+        let ba = generator.barabasi_albert(50, 2);
+        let edges = edges(ba.get_all());
+        assert_eq!(48 * 2, edges.len());
+        assert!(edges.iter().all(|(s, t)| s > t));
+        assert_eq!(edges.len(), edges.iter().unique().count());
+
+        let dag = generator.dag(30, 0.3);
+        assert!(dag
+            .strongly_connected_components()
+            .iter()
+            .all(|c| c.len() == 1));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::{
+    internals::{Mosaic, MosaicIO, MosaicStorage, StorageBackend, Tile, TileRef, Uuid},
+    iterators::query::MosaicQuery,
+};
+
+/// Several mosaics open side by side under names, like the documents of an editor;
+/// `save_all` writes each back to its storage backend.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    mosaics: RwLock<BTreeMap<String, Arc<Mosaic>>>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `mosaic` under `name`, which must not be taken yet.
+    pub fn insert(&self, name: &str, mosaic: Arc<Mosaic>) -> anyhow::Result<Arc<Mosaic>> {
+        let mut mosaics = self.mosaics.write().unwrap();
+        if mosaics.contains_key(name) {
+            return Err(anyhow!("There is already a mosaic named {}", name));
+        }
+
+        mosaics.insert(name.to_string(), Arc::clone(&mosaic));
+        Ok(mosaic)
+    }
+
+    /// Makes an empty mosaic under `name`.
+    pub fn create(&self, name: &str) -> anyhow::Result<Arc<Mosaic>> {
+        self.insert(name, Mosaic::new())
+    }
+
+    /// Opens the mosaic held by `backend` under `name`, see `Mosaic::open`.
+    pub fn open<B: StorageBackend + 'static>(
+        &self,
+        name: &str,
+        backend: B,
+    ) -> anyhow::Result<Arc<Mosaic>> {
+        if self.contains(name) {
+            return Err(anyhow!("There is already a mosaic named {}", name));
+        }
+        self.insert(name, Mosaic::open(backend)?)
+    }
+
+    /// Takes the mosaic named `name` out of the workspace, writing it back to its storage
+    /// first if it has unsaved changes. If that fails, the mosaic stays open.
+    pub fn close(&self, name: &str) -> anyhow::Result<Option<Arc<Mosaic>>> {
+        let mut mosaics = self.mosaics.write().unwrap();
+        if let Some(mosaic) = mosaics.get(name) {
+            mosaic.flush()?;
+        }
+        Ok(mosaics.remove(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.mosaics.read().unwrap().contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Mosaic>> {
+        self.mosaics.read().unwrap().get(name).cloned()
+    }
+
+    /// The name and mosaic of the open mosaic with `uuid`.
+    pub fn find(&self, uuid: Uuid) -> Option<(String, Arc<Mosaic>)> {
+        self.mosaics
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, mosaic)| mosaic.uuid() == uuid)
+            .map(|(name, mosaic)| (name.clone(), Arc::clone(mosaic)))
+    }
+
+    /// The name of `mosaic` in this workspace.
+    pub fn name_of(&self, mosaic: &Arc<Mosaic>) -> Option<String> {
+        self.mosaics
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, m)| Arc::ptr_eq(m, mosaic))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// The names of the open mosaics, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.mosaics.read().unwrap().keys().cloned().collect_vec()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mosaics.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mosaics.read().unwrap().is_empty()
+    }
+
+    /// The tile `reference` points to, if its mosaic is open in this workspace.
+    pub fn resolve(&self, reference: TileRef) -> Option<Tile> {
+        self.find(reference.mosaic)?.1.get(reference.id)
+    }
+
+    /// Runs `query` on each open mosaic, by name, and gathers what it finds. The tiles keep
+    /// their mosaic, see `name_of` to tell which one that is.
+    pub fn query<F, I>(&self, query: F) -> Vec<Tile>
+    where
+        F: Fn(&Arc<Mosaic>) -> I,
+        I: IntoIterator<Item = Tile>,
+    {
+        // the mosaics are let go before querying, so the query can open and close others
+        let mosaics = self.mosaics.read().unwrap().values().cloned().collect_vec();
+        mosaics.iter().flat_map(query).collect_vec()
+    }
+
+    /// Runs a textual query, as for `MosaicQuery::query_str`, on each open mosaic.
+    pub fn query_str(&self, query: &str) -> anyhow::Result<Vec<Tile>> {
+        let mosaics = self.mosaics.read().unwrap().values().cloned().collect_vec();
+        let mut found = vec![];
+        for mosaic in mosaics {
+            found.extend(mosaic.query_str(query)?);
+        }
+        Ok(found)
+    }
+
+    /// Whether any open mosaic has changes it hasn't been saved with.
+    pub fn is_dirty(&self) -> bool {
+        self.mosaics.read().unwrap().values().any(|m| m.is_dirty())
+    }
+
+    /// Writes every mosaic with unsaved changes back to its storage, carrying on past those
+    /// that fail, and returns the names of those written. Mosaics without storage are skipped.
+    pub fn save_all(&self) -> anyhow::Result<Vec<String>> {
+        let mosaics = self.mosaics.read().unwrap().clone();
+        let mut saved = vec![];
+        let mut failed = vec![];
+        for (name, mosaic) in mosaics {
+            match mosaic.flush() {
+                Ok(true) => saved.push(name),
+                Ok(false) => {}
+                Err(e) => failed.push(format!("{}: {}", name, e)),
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(saved)
+        } else {
+            Err(anyhow!("Cannot save {}", failed.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod workspace_tests {
+    use crate::internals::{void, MemoryStorage, MosaicCRUD, MosaicIO, MosaicIndices};
+
+    use super::Workspace;
+
+    #[test]
+    fn test_workspace() {
+        let workspace = Workspace::new();
+        let storage = MemoryStorage::new();
+        let notes = workspace.open("notes", storage.clone()).unwrap();
+        let scratch = workspace.create("scratch").unwrap();
+        assert!(workspace.create("notes").is_err());
+        assert!(workspace.open("notes", MemoryStorage::new()).is_err());
+        assert_eq!(vec!["notes", "scratch"], workspace.names());
+
+        let a = notes.new_object("void", void());
+        let b = scratch.new_object("void", void());
+        assert_eq!(
+            Some("notes".to_string()),
+            workspace.find(notes.uuid()).map(|(name, _)| name)
+        );
+        assert_eq!(Some(b.id), workspace.resolve(b.to_ref()).map(|t| t.id));
+        assert_eq!(
+            Some("scratch".to_string()),
+            workspace.name_of(&workspace.resolve(b.to_ref()).unwrap().mosaic)
+        );
+
+        let everything = workspace.query(|m| m.get_tiles_with_component("void"));
+        assert_eq!(2, everything.len());
+        assert_eq!(2, workspace.query_str("SELECT objects").unwrap().len());
+
+        assert!(workspace.is_dirty());
+        assert_eq!(vec!["notes"], workspace.save_all().unwrap());
+        assert_eq!(notes.save(), storage.contents());
+
+        notes.new_arrow(&a, &a, "void", void());
+        let closed = workspace.close("notes").unwrap().unwrap();
+        assert_eq!(closed.save(), storage.contents());
+        assert!(workspace.get("notes").is_none());
+        assert!(workspace.resolve(a.to_ref()).is_none());
+        assert_eq!(1, workspace.len());
+    }
+}